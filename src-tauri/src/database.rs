@@ -1,8 +1,13 @@
-use rusqlite::{params, Connection, Result};
+use crate::i18n;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OpenFlags, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::hash::BuildHasher;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use chrono::Datelike;
 use csv::ReaderBuilder;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -41,6 +46,15 @@ pub struct Category {
     pub is_default: bool,
 }
 
+/// One entry of a batch category edit: renames `old_name` to `new_name` and
+/// sets its type.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategoryEdit {
+    pub old_name: String,
+    pub new_name: String,
+    pub category_type: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CategoryBalance {
     pub name: String,
@@ -60,6 +74,23 @@ pub struct Transaction {
     pub account_id: i64,
     pub transfer_id: i64,
     pub transfer_account_id: i64,
+    pub needs_review: bool,
+    pub payment_method: Option<String>,
+    pub notes: Option<String>,
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkDeleteResult {
+    pub id: i64,
+    pub deleted: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashedTransaction {
+    pub transaction: Transaction,
+    pub deleted_at: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -70,6 +101,28 @@ pub struct NewTransaction {
     pub container_id: i64,
     pub account_id: i64,
     pub date: Option<String>,
+    pub idempotency_key: Option<String>,
+    pub payment_method: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewTransfer {
+    pub container_id: i64,
+    pub from_account_id: i64,
+    pub to_account_id: i64,
+    pub amount: i64,
+    pub description: Option<String>,
+    pub date: Option<String>,
+    pub idempotency_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PaymentMethodTotal {
+    pub payment_method: Option<String>,
+    pub income: i64,
+    pub expense: i64,
+    pub transaction_count: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -107,1333 +160,10845 @@ pub struct ReportsCsvExport {
     pub transactions: String,
 }
 
-pub struct Database {
-    conn: Mutex<Connection>,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SakEmkmExport {
+    pub balance_sheet: String,
+    pub profit_loss: String,
+    pub notes: String,
 }
 
-impl Database {
-    const DEFAULT_EQUITY_ACCOUNTS: [&'static str; 6] = [
-        "Modal Saham",
-        "Tambahan Modal Disetor",
-        "Laba Ditahan",
-        "Laba Tahun Berjalan",
-        "Pendapatan Komprehensif Lainnya",
-        "Ekuitas Lainnya",
-    ];
-    const DEFAULT_FALLBACK_CATEGORY: &'static str = "Beban Usaha Lainnya";
-    const DEFAULT_CATEGORIES: [(&'static str, &'static str); 8] = [
-        ("Biaya Gaji", "expense"),
-        ("Beban Transportasi", "expense"),
-        ("Beban Penyusutan dan Amortisasi", "expense"),
-        ("Beban Sewa", "expense"),
-        ("Beban Umum dan Administrasi", "expense"),
-        ("Beban Pemasaran atau Promosi", "expense"),
-        ("Penjualan", "income"),
-        ("Beban Usaha Lainnya", "expense"),
-    ];
-    const LEGACY_CATEGORY_RENAMES: [(&'static str, &'static str, &'static str); 8] = [
-        ("Food & Dining", "Biaya Gaji", "expense"),
-        ("Transportation", "Beban Transportasi", "expense"),
-        ("Shopping", "Beban Penyusutan dan Amortisasi", "expense"),
-        ("Entertainment", "Beban Sewa", "expense"),
-        ("Bills & Utilities", "Beban Umum dan Administrasi", "expense"),
-        ("Healthcare", "Beban Pemasaran atau Promosi", "expense"),
-        ("Income", "Penjualan", "income"),
-        ("Other", "Beban Usaha Lainnya", "expense"),
-    ];
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppState {
+    pub last_container_id: i64,
+    pub locale: String,
+    pub onboarding_completed: bool,
+    pub readonly_queries_enabled: bool,
+}
 
-    pub fn new(db_path: PathBuf) -> Result<Self> {
-        let conn = Connection::open(db_path)?;
-        
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS containers (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL UNIQUE,
-                created_at TEXT NOT NULL,
-                is_default INTEGER NOT NULL DEFAULT 0
-            )",
-            [],
-        )?;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Voucher {
+    pub id: i64,
+    pub container_id: i64,
+    pub code: String,
+    pub amount: i64,
+    pub remaining: i64,
+    pub liability_account_id: i64,
+    pub issued_at: String,
+    pub expires_at: Option<String>,
+}
 
-        let container_count: i64 = conn.query_row("SELECT COUNT(*) FROM containers", [], |row| row.get(0))?;
-        if container_count == 0 {
-            let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-            conn.execute(
-                "INSERT INTO containers (name, created_at, is_default) VALUES (?1, ?2, 1)",
-                ["Personal", &now],
-            )?;
-        }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransferSuggestion {
+    pub outgoing: Transaction,
+    pub incoming: Transaction,
+}
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS transactions (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                amount INTEGER NOT NULL,
-                description TEXT NOT NULL,
-                category TEXT NOT NULL,
-                date TEXT NOT NULL,
-                container_id INTEGER NOT NULL DEFAULT 1,
-                account_id INTEGER,
-                transfer_id INTEGER,
-                transfer_account_id INTEGER,
-                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Fund {
+    pub id: i64,
+    pub container_id: i64,
+    pub name: String,
+    pub grantor: String,
+    pub created_at: String,
+    pub received: i64,
+    pub spent: i64,
+    pub remaining: i64,
+}
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS accounts (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL,
-                account_type TEXT NOT NULL,
-                opening_balance INTEGER NOT NULL DEFAULT 0,
-                container_id INTEGER NOT NULL,
-                created_at TEXT NOT NULL,
-                UNIQUE(name, container_id),
-                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Envelope {
+    pub id: i64,
+    pub account_id: i64,
+    pub name: String,
+    pub balance: i64,
+    pub created_at: String,
+}
 
-        let has_container_id: Result<i64, _> = conn.query_row(
-            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='container_id'",
-            [],
-            |row| row.get(0)
-        );
-        
-        if let Ok(0) = has_container_id {
-            conn.execute(
-                "ALTER TABLE transactions ADD COLUMN container_id INTEGER NOT NULL DEFAULT 1",
-                [],
-            )?;
-        }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnvelopeReconciliation {
+    pub account_id: i64,
+    pub account_balance: i64,
+    pub envelope_total: i64,
+    pub difference: i64,
+    pub is_balanced: bool,
+    pub envelopes: Vec<Envelope>,
+}
 
-        let has_account_id: Result<i64, _> = conn.query_row(
-            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='account_id'",
-            [],
-            |row| row.get(0)
-        );
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: i64,
+    pub container_id: i64,
+    pub name: String,
+    pub transaction_count: i64,
+}
 
-        if let Ok(0) = has_account_id {
-            conn.execute(
-                "ALTER TABLE transactions ADD COLUMN account_id INTEGER",
-                [],
-            )?;
-        }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FundReport {
+    pub fund: Fund,
+    pub spending: Vec<ProfitLossLine>,
+    pub transactions: Vec<Transaction>,
+}
 
-        let has_transfer_id: Result<i64, _> = conn.query_row(
-            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='transfer_id'",
-            [],
-            |row| row.get(0),
-        );
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ZakatReport {
+    pub period_start: String,
+    pub period_end: String,
+    pub rate_bps: i64,
+    pub nisab: i64,
+    pub basis: String,
+    pub assets: Vec<AccountBalance>,
+    pub liabilities: Vec<AccountBalance>,
+    pub total_assets: i64,
+    pub total_liabilities: i64,
+    pub net_income: i64,
+    pub zakat_base: i64,
+    pub zakat_due: i64,
+}
 
-        if let Ok(0) = has_transfer_id {
-            conn.execute(
-                "ALTER TABLE transactions ADD COLUMN transfer_id INTEGER",
-                [],
-            )?;
-        }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ContainerStats {
+    pub container_id: i64,
+    pub name: String,
+    pub archived: bool,
+    pub transaction_count: i64,
+    pub first_date: Option<String>,
+    pub last_date: Option<String>,
+    pub account_count: i64,
+    pub archived_account_count: i64,
+    pub storage_share: f64,
+    pub estimated_bytes: i64,
+}
 
-        let has_transfer_account_id: Result<i64, _> = conn.query_row(
-            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='transfer_account_id'",
-            [],
-            |row| row.get(0),
-        );
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HousekeepingSuggestion {
+    pub kind: String,
+    pub id: i64,
+    pub name: String,
+    pub container_id: Option<i64>,
+    pub reason: String,
+    pub last_activity: Option<String>,
+    pub balance: i64,
+}
 
-        if let Ok(0) = has_transfer_account_id {
-            conn.execute(
-                "ALTER TABLE transactions ADD COLUMN transfer_account_id INTEGER",
-                [],
-            )?;
-        }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedItem {
+    pub kind: String,
+    pub id: i64,
+    pub name: String,
+    pub container_id: Option<i64>,
+}
 
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS categories (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL UNIQUE,
-                category_type TEXT NOT NULL DEFAULT 'expense',
-                is_default INTEGER NOT NULL DEFAULT 0
-            )",
-            [],
-        )?;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChannelFees {
+    pub channel: String,
+    pub gross_revenue: i64,
+    pub fees: i64,
+    pub fee_percent: f64,
+}
 
-        let has_category_type: Result<i64, _> = conn.query_row(
-            "SELECT COUNT(*) FROM pragma_table_info('categories') WHERE name='category_type'",
-            [],
-            |row| row.get(0),
-        );
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FeesReport {
+    pub start_date: String,
+    pub end_date: String,
+    pub channels: Vec<ChannelFees>,
+    pub total_gross_revenue: i64,
+    pub total_fees: i64,
+    pub fee_percent: f64,
+}
 
-        if let Ok(0) = has_category_type {
-            conn.execute(
-                "ALTER TABLE categories ADD COLUMN category_type TEXT NOT NULL DEFAULT 'expense'",
-                [],
-            )?;
-        }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PendingSale {
+    pub id: i64,
+    pub container_id: i64,
+    pub channel: String,
+    pub amount: i64,
+    pub transaction_id: i64,
+    pub date: String,
+    pub settlement_id: Option<i64>,
+}
 
-        Self::ensure_default_categories(&conn)?;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SettlementBatch {
+    pub container_id: i64,
+    pub channel: String,
+    pub payout_account_id: i64,
+    pub payout_amount: i64,
+    pub sale_ids: Vec<i64>,
+    pub date: Option<String>,
+}
 
-        let container_ids: Vec<i64> = {
-            let mut stmt = conn.prepare("SELECT id FROM containers")?;
-            let rows = stmt.query_map([], |row| row.get::<_, i64>(0))?;
-            rows.collect::<Result<Vec<i64>>>()?
-        };
-        for container_id in container_ids {
-            Self::ensure_default_equity_accounts(&conn, container_id)?;
-        }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClosedYear {
+    pub container_id: i64,
+    pub fiscal_year: i32,
+    pub net_income: i64,
+    pub transfer_id: Option<i64>,
+    pub closed_at: String,
+}
 
-        Ok(Database {
-            conn: Mutex::new(conn),
-        })
-    }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct YearEndClosing {
+    pub closed_year: ClosedYear,
+    pub retained_earnings_before: i64,
+    pub retained_earnings_after: i64,
+    pub profit_loss: ProfitLossReport,
+}
 
-    pub fn add_transaction(&self, transaction: NewTransaction) -> Result<Transaction> {
-        let conn = self.conn.lock().unwrap();
-        let date = Self::normalize_transaction_date(transaction.date)?;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct YearRollover {
+    pub container_id: i64,
+    pub fiscal_year: i32,
+    pub accounts: Vec<AccountBalance>,
+    pub total_assets: i64,
+    pub total_liabilities: i64,
+    pub total_equity: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Settlement {
+    pub id: i64,
+    pub container_id: i64,
+    pub channel: String,
+    pub payout_account_id: i64,
+    pub gross_amount: i64,
+    pub fee_amount: i64,
+    pub payout_amount: i64,
+    pub date: String,
+    pub sale_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurchaseQuote {
+    pub id: i64,
+    pub request_id: i64,
+    pub supplier: String,
+    pub unit_price: i64,
+    pub total: i64,
+    pub delivery_days: Option<i64>,
+    pub notes: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurchaseRequest {
+    pub id: i64,
+    pub container_id: i64,
+    pub item: String,
+    pub quantity: i64,
+    pub notes: String,
+    pub status: String,
+    pub chosen_quote_id: Option<i64>,
+    pub transaction_id: Option<i64>,
+    pub created_at: String,
+    pub quotes: Vec<PurchaseQuote>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewRenewal {
+    pub container_id: i64,
+    pub item: String,
+    pub cost: i64,
+    pub renewal_date: String,
+    pub account_id: i64,
+    pub category: Option<String>,
+    pub interval_months: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Renewal {
+    pub id: i64,
+    pub container_id: i64,
+    pub item: String,
+    pub cost: i64,
+    pub renewal_date: String,
+    pub account_id: i64,
+    pub category: String,
+    pub interval_months: u32,
+    pub last_paid_at: Option<String>,
+    pub last_transaction_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewRecurringRule {
+    pub container_id: i64,
+    pub account_id: i64,
+    pub amount: i64,
+    pub description: String,
+    pub category: Option<String>,
+    pub frequency: String,
+    pub interval: Option<u32>,
+    pub start_date: String,
+    pub end_date: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecurringRule {
+    pub id: i64,
+    pub container_id: i64,
+    pub account_id: i64,
+    pub amount: i64,
+    pub description: String,
+    pub category: String,
+    pub frequency: String,
+    pub interval: u32,
+    pub start_date: String,
+    pub end_date: Option<String>,
+    pub next_due: Option<String>,
+    pub occurrences: u32,
+    pub active: bool,
+    pub last_transaction_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Subscription {
+    pub source: String,
+    pub rule_id: Option<i64>,
+    pub description: String,
+    pub category: String,
+    pub monthly_amount: i64,
+    pub last_amount: Option<i64>,
+    pub previous_amount: Option<i64>,
+    pub last_date: Option<String>,
+    pub price_increased: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscriptionsReport {
+    pub subscriptions: Vec<Subscription>,
+    pub monthly_total: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PettyCash {
+    pub account_id: i64,
+    pub account_name: String,
+    pub float_amount: i64,
+    pub funding_account_id: i64,
+    pub balance: i64,
+    pub shortfall: i64,
+    pub last_replenished_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PettyCashReplenishment {
+    pub account_id: i64,
+    pub transfer_id: i64,
+    pub amount: i64,
+    pub period_start: String,
+    pub period_end: String,
+    pub expenses: Vec<ProfitLossLine>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CashCountSession {
+    pub id: i64,
+    pub account_id: i64,
+    pub account_name: String,
+    pub expected_balance: i64,
+    pub started_at: String,
+    pub latest: Option<CashCountRecord>,
+}
+
+/// A bank reconciliation for one account against a statement. The cleared
+/// balance is the opening balance plus everything reconciled in earlier
+/// sessions and the transactions marked in this one.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Reconciliation {
+    pub id: i64,
+    pub account_id: i64,
+    pub account_name: String,
+    pub statement_date: String,
+    pub statement_balance: i64,
+    pub cleared_balance: i64,
+    pub difference: i64,
+    pub marked_count: i64,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReconciliationCandidate {
+    pub transaction: Transaction,
+    pub marked: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CashCountRecord {
+    pub id: i64,
+    pub session_id: i64,
+    pub denominations: Vec<DenominationCount>,
+    pub counted_total: i64,
+    pub difference: i64,
+    pub recorded_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DenominationCount {
+    pub denomination: i64,
+    pub quantity: i64,
+    pub subtotal: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewGiro {
+    pub container_id: i64,
+    pub direction: String,
+    pub number: String,
+    pub counterparty: String,
+    pub amount: i64,
+    pub account_id: i64,
+    pub category: Option<String>,
+    pub issue_date: String,
+    pub maturity_date: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Giro {
+    pub id: i64,
+    pub container_id: i64,
+    pub direction: String,
+    pub number: String,
+    pub counterparty: String,
+    pub amount: i64,
+    pub account_id: i64,
+    pub category: String,
+    pub issue_date: String,
+    pub maturity_date: String,
+    pub status: String,
+    pub transaction_id: Option<i64>,
+    pub cleared_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoucherReport {
+    pub vouchers: Vec<Voucher>,
+    pub total_outstanding: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategoryForecast {
+    pub month: String,
+    pub amount: i64,
+    pub basis: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategorySuggestion {
+    pub category: String,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActionResult {
+    pub action: String,
+    pub message: String,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    pub original_error: String,
+    pub quarantined_path: String,
+    pub recovered_tables: Vec<String>,
+    pub failed_tables: Vec<String>,
+    pub recovered_rows: i64,
+    /// False when the profile could neither be opened nor recovered and the
+    /// app is running on `Database::open_placeholder`.
+    pub opened: bool,
+}
+
+impl RecoveryReport {
+    pub fn open_failed(error: String) -> Self {
+        RecoveryReport {
+            original_error: error,
+            quarantined_path: String::new(),
+            recovered_tables: Vec::new(),
+            failed_tables: Vec::new(),
+            recovered_rows: 0,
+            opened: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomField {
+    pub id: i64,
+    pub container_id: i64,
+    pub name: String,
+    pub field_type: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomFieldValue {
+    pub field_id: i64,
+    pub name: String,
+    pub field_type: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionComment {
+    pub id: i64,
+    pub transaction_id: i64,
+    pub author: String,
+    pub body: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionLocation {
+    pub transaction_id: i64,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub label: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LocationSummary {
+    pub location: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub income: i64,
+    pub expense: i64,
+    pub net: i64,
+    pub transaction_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RiskAlert {
+    pub kind: String,
+    pub date: String,
+    pub transaction_ids: Vec<i64>,
+    pub amount: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeeklyTotal {
+    pub week_start: String,
+    pub week_end: String,
+    pub income: i64,
+    pub expense: i64,
+    pub net: i64,
+    pub transaction_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionVersion {
+    pub id: i64,
+    pub transaction_id: i64,
+    pub amount: i64,
+    pub description: String,
+    pub category: String,
+    pub date: String,
+    pub account_id: i64,
+    pub replaced_at: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TransactionFilter {
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub category: Option<String>,
+    pub account_id: Option<i64>,
+    pub min_amount: Option<i64>,
+    pub max_amount: Option<i64>,
+    pub text: Option<String>,
+    pub tag: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionPage {
+    pub transactions: Vec<Transaction>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// Portable setup for a container: categories, learned category rules,
+/// custom fields, saved filters and recurring templates. Ids are left out so
+/// the pack can be imported into any container or installation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigurationPack {
+    pub version: u32,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub exported_at: String,
+    /// Chart of accounts; added in pack version 2.
+    #[serde(default)]
+    pub accounts: Vec<PackAccount>,
+    pub categories: Vec<PackCategory>,
+    pub category_rules: Vec<PackCategoryRule>,
+    pub custom_fields: Vec<PackCustomField>,
+    pub saved_filters: Vec<PackSavedFilter>,
+    pub recurring_templates: Vec<PackRecurringTemplate>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackAccount {
+    pub name: String,
+    pub account_type: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackCategory {
+    pub name: String,
+    pub category_type: String,
+    pub is_fee: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackCategoryRule {
+    pub description: String,
+    pub amount: i64,
+    pub category: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackCustomField {
+    pub name: String,
+    pub field_type: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackSavedFilter {
+    pub name: String,
+    pub filter: TransactionFilter,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackRecurringTemplate {
+    pub account_name: String,
+    pub amount: i64,
+    pub description: String,
+    pub category: String,
+    pub frequency: String,
+    pub interval: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PackImportResult {
+    pub pack_name: Option<String>,
+    pub accounts_added: usize,
+    pub categories_added: usize,
+    pub category_rules_added: usize,
+    pub custom_fields_added: usize,
+    pub saved_filters_added: usize,
+    pub recurring_templates_added: usize,
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedFilter {
+    pub id: i64,
+    pub container_id: i64,
+    pub name: String,
+    pub filter: TransactionFilter,
+    pub created_at: String,
+}
+
+/// Categories are shared by every container, so their entries carry no
+/// `container_id` and appear in every container's feed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangeEntry {
+    pub seq: i64,
+    pub entity: String,
+    pub entity_id: i64,
+    pub container_id: Option<i64>,
+    pub operation: String,
+    pub changed_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub entity: String,
+    pub entity_id: Option<i64>,
+    pub container_id: Option<i64>,
+    pub operation: String,
+    pub old_values: Option<serde_json::Value>,
+    pub new_values: Option<serde_json::Value>,
+    pub changed_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangeFeed {
+    pub changes: Vec<ChangeEntry>,
+    pub cursor: i64,
+    pub has_more: bool,
+    /// Entries after the requested cursor were pruned; the consumer has to
+    /// reload everything before continuing from `cursor`.
+    pub cursor_expired: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BalanceSnapshot {
+    pub account_id: i64,
+    pub date: String,
+    pub balance: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub created_at: String,
+    pub source_path: String,
+    pub table_counts: std::collections::BTreeMap<String, i64>,
+    pub checksum: String,
+    /// Last `audit_log` id contained in the backup; differential backups
+    /// start after it.
+    #[serde(default)]
+    pub audit_cursor: i64,
+}
+
+/// Audit entries recorded since a full backup, replayed on top of it to
+/// rebuild the database at any point after that backup.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DifferentialBackup {
+    pub base_checksum: String,
+    pub from_cursor: i64,
+    pub to_cursor: i64,
+    pub created_at: String,
+    pub changes: Vec<AuditEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DifferentialBackupSummary {
+    pub path: String,
+    pub base_checksum: String,
+    pub from_cursor: i64,
+    pub to_cursor: i64,
+    pub change_count: usize,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestoreTableChange {
+    pub table: String,
+    pub added: i64,
+    pub changed: i64,
+    pub removed: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestorePreview {
+    pub backup_path: String,
+    pub container_id: Option<i64>,
+    pub table: Option<String>,
+    pub tables: Vec<RestoreTableChange>,
+    pub skipped_tables: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PointInTimeRestore {
+    pub path: String,
+    pub applied_changes: usize,
+    pub skipped_changes: usize,
+    pub restored_to: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupTableCheck {
+    pub table: String,
+    pub expected_rows: Option<i64>,
+    pub actual_rows: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupVerification {
+    pub path: String,
+    pub integrity: String,
+    pub manifest_found: bool,
+    pub created_at: Option<String>,
+    pub tables: Vec<BackupTableCheck>,
+    pub checksum_matches: Option<bool>,
+    pub restorable: bool,
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UndoEntry {
+    pub id: i64,
+    pub container_id: i64,
+    pub label: String,
+    pub created_at: String,
+}
+
+/// One row touched by an undoable operation, as it was before and after.
+/// A missing side means the row did not exist.
+#[derive(Debug, Serialize, Deserialize)]
+struct UndoChange {
+    table: String,
+    id: i64,
+    before: Option<serde_json::Map<String, serde_json::Value>>,
+    after: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Returned, boxed in `rusqlite::Error::ToSqlConversionFailure`, when a name is
+/// already taken, so the UI can offer the suggestions instead of showing a
+/// raw UNIQUE constraint message.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NameConflict {
+    pub entity: String,
+    pub name: String,
+    pub suggestions: Vec<String>,
+}
+
+impl std::fmt::Display for NameConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "A {} named '{}' already exists", self.entity, self.name)
+    }
+}
+
+impl std::error::Error for NameConflict {}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstanceStatus {
+    pub primary: bool,
+    pub another_instance_running: bool,
+}
+
+pub struct Database {
+    conn: Mutex<Connection>,
+    db_path: PathBuf,
+    instance_lock_path: PathBuf,
+    instance_lock: Mutex<Option<Connection>>,
+}
+
+impl Database {
+    const DEFAULT_EQUITY_ACCOUNTS: [&'static str; 6] = [
+        "Modal Saham",
+        "Tambahan Modal Disetor",
+        "Laba Ditahan",
+        "Laba Tahun Berjalan",
+        "Pendapatan Komprehensif Lainnya",
+        "Ekuitas Lainnya",
+    ];
+    const RETAINED_EARNINGS_ACCOUNT: &'static str = "Laba Ditahan";
+    const CURRENT_EARNINGS_ACCOUNT: &'static str = "Laba Tahun Berjalan";
+    const DEFAULT_FALLBACK_CATEGORY: &'static str = "Beban Usaha Lainnya";
+    const DEFAULT_INCOME_CATEGORY: &'static str = "Penjualan";
+    const VOUCHER_LIABILITY_ACCOUNT: &'static str = "Utang Voucher";
+    const SETTLEMENT_CLEARING_ACCOUNT: &'static str = "Dana Belum Cair";
+    const BANK_FEE_CATEGORY: &'static str = "Beban Umum dan Administrasi";
+    const BANK_INTEREST_CATEGORY: &'static str = "Pendapatan Bunga";
+    const BANK_INTEREST_EXPENSE_CATEGORY: &'static str = "Beban Bunga";
+    const BANK_FEE_PATTERNS: [&'static str; 10] = [
+        "biaya admin",
+        "biaya adm",
+        "adm bank",
+        "biaya transfer",
+        "biaya materai",
+        "pajak bunga",
+        "pph bunga",
+        "pajak jasa giro",
+        "admin fee",
+        "bank charge",
+    ];
+    const BANK_INTEREST_PATTERNS: [&'static str; 3] = ["bunga", "jasa giro", "interest"];
+    const RENEWAL_COLUMNS: &'static str = "id, container_id, item, cost, renewal_date, account_id, category, interval_months, last_paid_at, last_transaction_id";
+    const DEFAULT_RENEWAL_CATEGORY: &'static str = "Beban Umum dan Administrasi";
+    const RECURRING_RULE_COLUMNS: &'static str = "id, container_id, account_id, amount, description, category, frequency, interval_count, start_date, end_date, next_due, occurrences, active, last_transaction_id";
+    const RECURRING_FREQUENCIES: [&'static str; 4] = ["daily", "weekly", "monthly", "yearly"];
+    const MAX_RECURRING_CATCH_UP: u32 = 400;
+    const MAX_UNDO_ENTRIES: i64 = 100;
+    const SUBSCRIPTION_LOOKBACK_MONTHS: u32 = 12;
+    const SUBSCRIPTION_MIN_OCCURRENCES: usize = 3;
+    const SUBSCRIPTION_GAP_DAYS: (i64, i64) = (25, 35);
+    const SUBSCRIPTION_STALE_DAYS: i64 = 45;
+    const GIRO_COLUMNS: &'static str = "id, container_id, direction, number, counterparty, amount, account_id, category, issue_date, maturity_date, status, transaction_id, cleared_at";
+    const TRANSACTION_COLUMNS: &'static str = "id, amount, description, category, date, container_id, COALESCE(account_id, 0) as account_id, COALESCE(transfer_id, 0) as transfer_id, COALESCE(transfer_account_id, 0) as transfer_account_id, needs_review, payment_method, notes, status";
+    const PAYMENT_METHODS: [&'static str; 5] = ["cash", "transfer", "qris", "e_wallet", "card"];
+    const TRANSACTION_STATUSES: [&'static str; 3] = ["pending", "cleared", "reconciled"];
+    const DEFAULT_MINOR_UNITS: u32 = 2;
+    const MAX_MINOR_UNITS: u32 = 4;
+    const WEEK_DAYS: [&'static str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+    const DEFAULT_WEEK_START: &'static str = "monday";
+    const DATE_FORMATS: [(&'static str, &'static str); 4] = [
+        ("YYYY-MM-DD", "%Y-%m-%d"),
+        ("DD/MM/YYYY", "%d/%m/%Y"),
+        ("DD-MM-YYYY", "%d-%m-%Y"),
+        ("MM/DD/YYYY", "%m/%d/%Y"),
+    ];
+    const DEFAULT_DATE_FORMAT: &'static str = "YYYY-MM-DD";
+    const MAX_LOAN_PACK_MONTHS: u32 = 36;
+    const CHANGE_FEED_PAGE_SIZE: i64 = 500;
+    const CHANGE_LOG_RETENTION_DAYS: i64 = 90;
+    /// Tables whose writes are not worth auditing: logs, caches and
+    /// bookkeeping the app maintains on its own.
+    const UNAUDITED_TABLES: [&'static str; 9] = [
+        "audit_log",
+        "change_log",
+        "undo_log",
+        "idempotency_keys",
+        "balance_snapshots",
+        "transaction_versions",
+        "transaction_deletions",
+        "import_sessions",
+        "category_corrections",
+    ];
+    /// Container tables that never hold amounts; see `container_has_amounts`.
+    const AMOUNT_FREE_TABLES: [&'static str; 9] = [
+        "containers",
+        "accounts",
+        "audit_log",
+        "change_log",
+        "undo_log",
+        "balance_snapshots",
+        "tags",
+        "custom_fields",
+        "funds",
+    ];
+    /// Every column outside `transactions` that holds an account id. Merging
+    /// moves all of them. Rows marked `true` are removed by the
+    /// `*_after_account_delete` triggers; `delete_account` refuses while any
+    /// other row still points at the account. New tables
+    /// with an account column belong here.
+    const ACCOUNT_REFERENCES: [(&'static str, &'static str, bool); 14] = [
+        ("balance_snapshots", "account_id", true),
+        ("reconciliations", "account_id", true),
+        ("statement_imports", "account_id", false),
+        ("statement_lines", "account_id", false),
+        ("envelopes", "account_id", false),
+        ("zakat_accounts", "account_id", false),
+        ("settlements", "payout_account_id", false),
+        ("renewals", "account_id", false),
+        ("recurring_rules", "account_id", false),
+        ("petty_cash", "account_id", false),
+        ("petty_cash", "funding_account_id", false),
+        ("cash_count_sessions", "account_id", false),
+        ("vouchers", "liability_account_id", false),
+        ("giros", "account_id", false),
+    ];
+    const RISK_DELETIONS_PER_DAY: usize = 3;
+    const STATS_ROW_OVERHEAD_BYTES: i64 = 48;
+    const DEFAULT_PAGE_SIZE: i64 = 100;
+    const MAX_PAGE_SIZE: i64 = 1000;
+    const MAX_QUERY_ROWS: usize = 1000;
+    const MAX_QUERY_CHARS: usize = 10_000;
+    const MAX_CSV_BYTES: usize = 10 * 1024 * 1024;
+    const MAX_CSV_ROWS: usize = 50_000;
+    const IMPORT_CHUNK_ROWS: usize = 1000;
+    const DEFAULT_IMPORT_PREVIEW_ROWS: usize = 20;
+    const CASH_BOOK_HEADER_SCAN_ROWS: usize = 10;
+    const MAX_IMPORT_PREVIEW_ROWS: usize = 200;
+    const MAX_IMPORT_ERRORS: usize = 100;
+    const MAX_NAME_CHARS: usize = 100;
+    const MAX_DESCRIPTION_CHARS: usize = 500;
+    const MAX_COMMENT_CHARS: usize = 4000;
+    const MAX_NOTES_CHARS: usize = 4000;
+    const DEFAULT_ZAKAT_RATE_BPS: i64 = 250;
+    const MAX_CATEGORY_SUGGESTIONS: usize = 3;
+    const MAX_FORECAST_MONTHS: u32 = 24;
+    const FORECAST_TRAILING_MONTHS: i32 = 3;
+    const CORRECTION_WEIGHT: f64 = 3.0;
+    const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+    /// Stored in `PRAGMA user_version` once `new` has run every migration.
+    /// Bump it whenever a migration is added so read-only opens can tell a
+    /// file that still needs upgrading.
+    const SCHEMA_VERSION: i64 = 2;
+    const QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+    const CUSTOM_FIELD_TYPES: [&'static str; 4] = ["text", "number", "date", "boolean"];
+    const CONFIGURATION_PACK_VERSION: u32 = 2;
+    const NAME_SUGGESTION_COUNT: usize = 3;
+    const SIMILAR_NAME_MAX_EDITS: usize = 2;
+    const ACCOUNT_TYPES: [&'static str; 4] = ["asset", "contra_asset", "liability", "equity"];
+    const MAX_SETUP_PACK_BYTES: u64 = 5 * 1024 * 1024;
+    const DEFAULT_CATEGORIES: [(&'static str, &'static str); 8] = [
+        ("Biaya Gaji", "expense"),
+        ("Beban Transportasi", "expense"),
+        ("Beban Penyusutan dan Amortisasi", "expense"),
+        ("Beban Sewa", "expense"),
+        ("Beban Umum dan Administrasi", "expense"),
+        ("Beban Pemasaran atau Promosi", "expense"),
+        ("Penjualan", "income"),
+        ("Beban Usaha Lainnya", "expense"),
+    ];
+    const LEGACY_CATEGORY_RENAMES: [(&'static str, &'static str, &'static str); 8] = [
+        ("Food & Dining", "Biaya Gaji", "expense"),
+        ("Transportation", "Beban Transportasi", "expense"),
+        ("Shopping", "Beban Penyusutan dan Amortisasi", "expense"),
+        ("Entertainment", "Beban Sewa", "expense"),
+        ("Bills & Utilities", "Beban Umum dan Administrasi", "expense"),
+        ("Healthcare", "Beban Pemasaran atau Promosi", "expense"),
+        ("Income", "Penjualan", "income"),
+        ("Other", "Beban Usaha Lainnya", "expense"),
+    ];
+
+    pub fn new(db_path: PathBuf) -> Result<Self> {
+        let instance_lock_path = Self::sidecar_path(&db_path, ".lock");
+        let instance_lock = Self::acquire_instance_lock(&instance_lock_path).or_else(|_| {
+            let _ = std::fs::remove_file(&instance_lock_path);
+            Self::acquire_instance_lock(&instance_lock_path)
+        })?;
+        let conn = Connection::open(&db_path)?;
+        conn.busy_timeout(Self::BUSY_TIMEOUT)?;
+        conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get::<_, String>(0))?;
+        Self::migrate(&conn)?;
+
+        Ok(Database {
+            conn: Mutex::new(conn),
+            db_path,
+            instance_lock_path,
+            instance_lock: Mutex::new(instance_lock),
+        })
+    }
+
+    /// Stands in for a profile that could not be opened, so the app still
+    /// starts and the user can pick another file. The schema lives in memory
+    /// and writes are refused.
+    pub fn open_placeholder(db_path: PathBuf) -> Result<Self> {
+        let conn = Connection::open_in_memory()?;
+        Self::migrate(&conn)?;
+        conn.execute_batch("PRAGMA query_only = ON")?;
+        Ok(Database {
+            conn: Mutex::new(conn),
+            instance_lock_path: Self::sidecar_path(&db_path, ".lock"),
+            db_path,
+            instance_lock: Mutex::new(None),
+        })
+    }
+
+    /// Creates missing tables, columns and triggers and records the schema
+    /// version.
+    fn migrate(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS containers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                created_at TEXT NOT NULL,
+                is_default INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+
+        let container_count: i64 = conn.query_row("SELECT COUNT(*) FROM containers", [], |row| row.get(0))?;
+        if container_count == 0 {
+            let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+            conn.execute(
+                "INSERT INTO containers (name, created_at, is_default) VALUES (?1, ?2, 1)",
+                ["Personal", &now],
+            )?;
+        }
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS transactions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                amount INTEGER NOT NULL,
+                description TEXT NOT NULL,
+                category TEXT NOT NULL,
+                date TEXT NOT NULL,
+                container_id INTEGER NOT NULL DEFAULT 1,
+                account_id INTEGER,
+                transfer_id INTEGER,
+                transfer_account_id INTEGER,
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_transactions_container_date
+             ON transactions(container_id, date)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS accounts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                account_type TEXT NOT NULL,
+                opening_balance INTEGER NOT NULL DEFAULT 0,
+                container_id INTEGER NOT NULL,
+                created_at TEXT NOT NULL,
+                UNIQUE(name, container_id),
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        let has_container_id: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='container_id'",
+            [],
+            |row| row.get(0)
+        );
+        
+        if let Ok(0) = has_container_id {
+            conn.execute(
+                "ALTER TABLE transactions ADD COLUMN container_id INTEGER NOT NULL DEFAULT 1",
+                [],
+            )?;
+        }
+
+        let has_account_id: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='account_id'",
+            [],
+            |row| row.get(0)
+        );
+
+        if let Ok(0) = has_account_id {
+            conn.execute(
+                "ALTER TABLE transactions ADD COLUMN account_id INTEGER",
+                [],
+            )?;
+        }
+
+        let has_transfer_id: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='transfer_id'",
+            [],
+            |row| row.get(0),
+        );
+
+        if let Ok(0) = has_transfer_id {
+            conn.execute(
+                "ALTER TABLE transactions ADD COLUMN transfer_id INTEGER",
+                [],
+            )?;
+        }
+
+        let has_transfer_account_id: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='transfer_account_id'",
+            [],
+            |row| row.get(0),
+        );
+
+        if let Ok(0) = has_transfer_account_id {
+            conn.execute(
+                "ALTER TABLE transactions ADD COLUMN transfer_account_id INTEGER",
+                [],
+            )?;
+        }
+
+        let has_needs_review: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='needs_review'",
+            [],
+            |row| row.get(0),
+        );
+
+        if let Ok(0) = has_needs_review {
+            conn.execute(
+                "ALTER TABLE transactions ADD COLUMN needs_review INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+
+        let has_payment_method: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='payment_method'",
+            [],
+            |row| row.get(0),
+        );
+
+        if let Ok(0) = has_payment_method {
+            conn.execute(
+                "ALTER TABLE transactions ADD COLUMN payment_method TEXT",
+                [],
+            )?;
+        }
+
+        let has_deleted_at: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='deleted_at'",
+            [],
+            |row| row.get(0),
+        );
+
+        if let Ok(0) = has_deleted_at {
+            conn.execute(
+                "ALTER TABLE transactions ADD COLUMN deleted_at TEXT",
+                [],
+            )?;
+        }
+
+        let has_status: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='status'",
+            [],
+            |row| row.get(0),
+        );
+
+        if let Ok(0) = has_status {
+            conn.execute(
+                "ALTER TABLE transactions ADD COLUMN status TEXT NOT NULL DEFAULT 'cleared'",
+                [],
+            )?;
+        }
+
+        let has_notes: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('transactions') WHERE name='notes'",
+            [],
+            |row| row.get(0),
+        );
+
+        if let Ok(0) = has_notes {
+            conn.execute(
+                "ALTER TABLE transactions ADD COLUMN notes TEXT",
+                [],
+            )?;
+        }
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS categories (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                category_type TEXT NOT NULL DEFAULT 'expense',
+                is_default INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+
+        let has_category_type: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('categories') WHERE name='category_type'",
+            [],
+            |row| row.get(0),
+        );
+
+        if let Ok(0) = has_category_type {
+            conn.execute(
+                "ALTER TABLE categories ADD COLUMN category_type TEXT NOT NULL DEFAULT 'expense'",
+                [],
+            )?;
+        }
+
+        for table in ["accounts", "categories", "containers"] {
+            let has_archived: i64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM pragma_table_info('{}') WHERE name='archived'", table),
+                [],
+                |row| row.get(0),
+            )?;
+            if has_archived == 0 {
+                conn.execute(
+                    &format!("ALTER TABLE {} ADD COLUMN archived INTEGER NOT NULL DEFAULT 0", table),
+                    [],
+                )?;
+            }
+        }
+
+        // NULL keeps the profile-wide `minor_units` setting.
+        let has_minor_units: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('containers') WHERE name='minor_units'",
+            [],
+            |row| row.get(0),
+        );
+
+        if let Ok(0) = has_minor_units {
+            conn.execute(
+                "ALTER TABLE containers ADD COLUMN minor_units INTEGER",
+                [],
+            )?;
+        }
+
+        let has_is_fee: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('categories') WHERE name='is_fee'",
+            [],
+            |row| row.get(0),
+        );
+
+        if let Ok(0) = has_is_fee {
+            conn.execute(
+                "ALTER TABLE categories ADD COLUMN is_fee INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+
+        let has_sort_order: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('categories') WHERE name='sort_order'",
+            [],
+            |row| row.get(0),
+        );
+
+        if let Ok(0) = has_sort_order {
+            conn.execute(
+                "ALTER TABLE categories ADD COLUMN sort_order INTEGER",
+                [],
+            )?;
+        }
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS idempotency_keys (
+                key TEXT PRIMARY KEY,
+                command TEXT NOT NULL,
+                result_id INTEGER NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS balance_snapshots (
+                account_id INTEGER NOT NULL,
+                snapshot_date TEXT NOT NULL,
+                balance INTEGER NOT NULL,
+                PRIMARY KEY (account_id, snapshot_date),
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS balance_snapshots_after_tx_insert
+             AFTER INSERT ON transactions
+             WHEN NEW.account_id IS NOT NULL
+             BEGIN
+                 DELETE FROM balance_snapshots
+                 WHERE account_id = NEW.account_id AND snapshot_date >= substr(NEW.date, 1, 10);
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS balance_snapshots_after_tx_update
+             AFTER UPDATE ON transactions
+             BEGIN
+                 DELETE FROM balance_snapshots
+                 WHERE account_id = OLD.account_id AND snapshot_date >= substr(OLD.date, 1, 10);
+                 DELETE FROM balance_snapshots
+                 WHERE account_id = NEW.account_id AND snapshot_date >= substr(NEW.date, 1, 10);
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS balance_snapshots_after_tx_delete
+             AFTER DELETE ON transactions
+             WHEN OLD.account_id IS NOT NULL
+             BEGIN
+                 DELETE FROM balance_snapshots
+                 WHERE account_id = OLD.account_id AND snapshot_date >= substr(OLD.date, 1, 10);
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS balance_snapshots_after_account_change
+             AFTER UPDATE OF opening_balance ON accounts
+             BEGIN
+                 DELETE FROM balance_snapshots WHERE account_id = NEW.id;
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS balance_snapshots_after_account_delete
+             AFTER DELETE ON accounts
+             BEGIN
+                 DELETE FROM balance_snapshots WHERE account_id = OLD.id;
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS custom_fields (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                field_type TEXT NOT NULL DEFAULT 'text',
+                created_at TEXT NOT NULL,
+                UNIQUE(container_id, name),
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS transaction_custom_values (
+                transaction_id INTEGER NOT NULL,
+                field_id INTEGER NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (transaction_id, field_id),
+                FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE,
+                FOREIGN KEY (field_id) REFERENCES custom_fields(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS custom_values_after_tx_delete
+             AFTER DELETE ON transactions
+             BEGIN
+                 DELETE FROM transaction_custom_values WHERE transaction_id = OLD.id;
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS transaction_comments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                transaction_id INTEGER NOT NULL,
+                author TEXT NOT NULL,
+                body TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS transaction_comments_after_tx_delete
+             AFTER DELETE ON transactions
+             BEGIN
+                 DELETE FROM transaction_comments WHERE transaction_id = OLD.id;
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS transaction_locations (
+                transaction_id INTEGER PRIMARY KEY,
+                latitude REAL NOT NULL,
+                longitude REAL NOT NULL,
+                label TEXT NOT NULL DEFAULT '',
+                FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS transaction_locations_after_tx_delete
+             AFTER DELETE ON transactions
+             BEGIN
+                 DELETE FROM transaction_locations WHERE transaction_id = OLD.id;
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS transaction_versions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                transaction_id INTEGER NOT NULL,
+                amount INTEGER NOT NULL,
+                description TEXT NOT NULL,
+                category TEXT NOT NULL,
+                date TEXT NOT NULL,
+                account_id INTEGER,
+                replaced_at TEXT NOT NULL,
+                FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_transaction_versions_transaction
+             ON transaction_versions(transaction_id)",
+            [],
+        )?;
+
+        // Only edits to the user-visible fields are versioned; flag toggles such
+        // as needs_review do not produce history entries.
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS transaction_versions_after_update
+             AFTER UPDATE ON transactions
+             WHEN OLD.amount IS NOT NEW.amount
+               OR OLD.description IS NOT NEW.description
+               OR OLD.category IS NOT NEW.category
+               OR OLD.date IS NOT NEW.date
+               OR OLD.account_id IS NOT NEW.account_id
+             BEGIN
+                 INSERT INTO transaction_versions (transaction_id, amount, description, category, date, account_id, replaced_at)
+                 VALUES (OLD.id, OLD.amount, OLD.description, OLD.category, OLD.date, OLD.account_id, datetime('now', 'localtime'));
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS transaction_versions_after_tx_delete
+             AFTER DELETE ON transactions
+             BEGIN
+                 DELETE FROM transaction_versions WHERE transaction_id = OLD.id;
+             END",
+            [],
+        )?;
+
+        // Full-text index over descriptions and categories. A system SQLite
+        // built without FTS5 cannot create it; search then falls back to LIKE.
+        let fts_existed: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'transactions_fts'",
+            [],
+            |row| row.get(0),
+        )?;
+        let fts_available = fts_existed > 0
+            || conn
+                .execute(
+                    "CREATE VIRTUAL TABLE transactions_fts USING fts5(
+                        description, category, content='transactions', content_rowid='id'
+                    )",
+                    [],
+                )
+                .is_ok();
+        if fts_available {
+            conn.execute(
+                "CREATE TRIGGER IF NOT EXISTS transactions_fts_after_insert
+                 AFTER INSERT ON transactions
+                 BEGIN
+                     INSERT INTO transactions_fts (rowid, description, category)
+                     VALUES (NEW.id, NEW.description, NEW.category);
+                 END",
+                [],
+            )?;
+            conn.execute(
+                "CREATE TRIGGER IF NOT EXISTS transactions_fts_after_delete
+                 AFTER DELETE ON transactions
+                 BEGIN
+                     INSERT INTO transactions_fts (transactions_fts, rowid, description, category)
+                     VALUES ('delete', OLD.id, OLD.description, OLD.category);
+                 END",
+                [],
+            )?;
+            conn.execute(
+                "CREATE TRIGGER IF NOT EXISTS transactions_fts_after_update
+                 AFTER UPDATE OF description, category ON transactions
+                 BEGIN
+                     INSERT INTO transactions_fts (transactions_fts, rowid, description, category)
+                     VALUES ('delete', OLD.id, OLD.description, OLD.category);
+                     INSERT INTO transactions_fts (rowid, description, category)
+                     VALUES (NEW.id, NEW.description, NEW.category);
+                 END",
+                [],
+            )?;
+            if fts_existed == 0 {
+                conn.execute("INSERT INTO transactions_fts (transactions_fts) VALUES ('rebuild')", [])?;
+            }
+        }
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS transaction_deletions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                transaction_id INTEGER NOT NULL,
+                container_id INTEGER NOT NULL,
+                amount INTEGER NOT NULL,
+                description TEXT NOT NULL,
+                category TEXT NOT NULL,
+                date TEXT NOT NULL,
+                transfer_id INTEGER,
+                deleted_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Deleted rows are kept here so the audit trail can still tell what
+        // was removed; change_log only records the id. A row is recorded when
+        // it is moved to the trash, so purging it later does not log it twice.
+        conn.execute("DROP TRIGGER IF EXISTS transaction_deletions_after_delete", [])?;
+        conn.execute(
+            "CREATE TRIGGER transaction_deletions_after_delete
+             AFTER DELETE ON transactions
+             WHEN OLD.deleted_at IS NULL
+             BEGIN
+                 INSERT INTO transaction_deletions (transaction_id, container_id, amount, description, category, date, transfer_id, deleted_at)
+                 VALUES (OLD.id, OLD.container_id, OLD.amount, OLD.description, OLD.category, OLD.date, OLD.transfer_id, datetime('now', 'localtime'));
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS transaction_deletions_after_trash
+             AFTER UPDATE OF deleted_at ON transactions
+             WHEN OLD.deleted_at IS NULL AND NEW.deleted_at IS NOT NULL
+             BEGIN
+                 INSERT INTO transaction_deletions (transaction_id, container_id, amount, description, category, date, transfer_id, deleted_at)
+                 VALUES (OLD.id, OLD.container_id, OLD.amount, OLD.description, OLD.category, OLD.date, OLD.transfer_id, NEW.deleted_at);
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS import_sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT NOT NULL,
+                columns_json TEXT NOT NULL,
+                row_count INTEGER NOT NULL,
+                mapping_json TEXT,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS statement_imports (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                account_id INTEGER NOT NULL,
+                start_date TEXT NOT NULL,
+                end_date TEXT NOT NULL,
+                imported_at TEXT NOT NULL,
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS statement_lines (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                statement_id INTEGER NOT NULL,
+                account_id INTEGER NOT NULL,
+                date TEXT NOT NULL,
+                amount INTEGER NOT NULL,
+                description TEXT NOT NULL,
+                transaction_id INTEGER,
+                FOREIGN KEY (statement_id) REFERENCES statement_imports(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS vouchers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                code TEXT NOT NULL,
+                amount INTEGER NOT NULL,
+                remaining INTEGER NOT NULL,
+                liability_account_id INTEGER NOT NULL,
+                issued_at TEXT NOT NULL,
+                expires_at TEXT,
+                UNIQUE(container_id, code),
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS voucher_redemptions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                voucher_id INTEGER NOT NULL,
+                amount INTEGER NOT NULL,
+                transaction_id INTEGER NOT NULL,
+                redeemed_at TEXT NOT NULL,
+                FOREIGN KEY (voucher_id) REFERENCES vouchers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS funds (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                grantor TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                UNIQUE(container_id, name),
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS transaction_funds (
+                transaction_id INTEGER PRIMARY KEY,
+                fund_id INTEGER NOT NULL,
+                FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE,
+                FOREIGN KEY (fund_id) REFERENCES funds(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS transaction_funds_after_tx_delete
+             AFTER DELETE ON transactions
+             BEGIN
+                 DELETE FROM transaction_funds WHERE transaction_id = OLD.id;
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS envelopes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                account_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                UNIQUE(account_id, name),
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // An envelope's balance is the sum of its entries: allocations and
+        // incoming moves are positive, spends and outgoing moves negative.
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS envelope_entries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                envelope_id INTEGER NOT NULL,
+                amount INTEGER NOT NULL,
+                transaction_id INTEGER UNIQUE,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (envelope_id) REFERENCES envelopes(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS envelope_entries_after_tx_delete
+             AFTER DELETE ON transactions
+             BEGIN
+                 DELETE FROM envelope_entries WHERE transaction_id = OLD.id;
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                UNIQUE(container_id, name),
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS transaction_tags (
+                transaction_id INTEGER NOT NULL,
+                tag_id INTEGER NOT NULL,
+                PRIMARY KEY (transaction_id, tag_id),
+                FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE,
+                FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS transaction_tags_after_tx_delete
+             AFTER DELETE ON transactions
+             BEGIN
+                 DELETE FROM transaction_tags WHERE transaction_id = OLD.id;
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS zakat_accounts (
+                account_id INTEGER PRIMARY KEY,
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS closed_years (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                fiscal_year INTEGER NOT NULL,
+                net_income INTEGER NOT NULL,
+                transfer_id INTEGER,
+                closed_at TEXT NOT NULL,
+                UNIQUE(container_id, fiscal_year),
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // Closed years reject new transactions and edits that would change
+        // their totals. Deletions are checked in delete_transaction so that
+        // removing a whole container still cascades.
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS closed_years_before_insert
+             BEFORE INSERT ON transactions
+             WHEN EXISTS (
+                 SELECT 1 FROM closed_years
+                 WHERE container_id = NEW.container_id
+                   AND fiscal_year = CAST(substr(NEW.date, 1, 4) AS INTEGER)
+             )
+             BEGIN
+                 SELECT RAISE(ABORT, 'Fiscal year is closed');
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS closed_years_before_update
+             BEFORE UPDATE ON transactions
+             WHEN (OLD.amount IS NOT NEW.amount OR OLD.date IS NOT NEW.date OR OLD.account_id IS NOT NEW.account_id)
+               AND EXISTS (
+                 SELECT 1 FROM closed_years
+                 WHERE container_id = OLD.container_id
+                   AND fiscal_year IN (CAST(substr(OLD.date, 1, 4) AS INTEGER), CAST(substr(NEW.date, 1, 4) AS INTEGER))
+             )
+             BEGIN
+                 SELECT RAISE(ABORT, 'Fiscal year is closed');
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS settlements (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                channel TEXT NOT NULL,
+                payout_account_id INTEGER NOT NULL,
+                gross_amount INTEGER NOT NULL,
+                fee_amount INTEGER NOT NULL,
+                payout_amount INTEGER NOT NULL,
+                date TEXT NOT NULL,
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS pending_sales (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                channel TEXT NOT NULL,
+                amount INTEGER NOT NULL,
+                transaction_id INTEGER NOT NULL,
+                date TEXT NOT NULL,
+                settlement_id INTEGER,
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE,
+                FOREIGN KEY (settlement_id) REFERENCES settlements(id) ON DELETE SET NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS pending_sales_after_tx_delete
+             AFTER DELETE ON transactions
+             BEGIN
+                 DELETE FROM pending_sales WHERE transaction_id = OLD.id AND settlement_id IS NULL;
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS purchase_requests (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                item TEXT NOT NULL,
+                quantity INTEGER NOT NULL,
+                notes TEXT NOT NULL DEFAULT '',
+                status TEXT NOT NULL DEFAULT 'open',
+                chosen_quote_id INTEGER,
+                transaction_id INTEGER,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS purchase_quotes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                request_id INTEGER NOT NULL,
+                supplier TEXT NOT NULL,
+                unit_price INTEGER NOT NULL,
+                delivery_days INTEGER,
+                notes TEXT NOT NULL DEFAULT '',
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (request_id) REFERENCES purchase_requests(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS renewals (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                item TEXT NOT NULL,
+                cost INTEGER NOT NULL,
+                renewal_date TEXT NOT NULL,
+                account_id INTEGER NOT NULL,
+                category TEXT NOT NULL,
+                interval_months INTEGER NOT NULL DEFAULT 12,
+                last_paid_at TEXT,
+                last_transaction_id INTEGER,
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // next_due is NULL once a rule has run past its end date.
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS recurring_rules (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                account_id INTEGER NOT NULL,
+                amount INTEGER NOT NULL,
+                description TEXT NOT NULL,
+                category TEXT NOT NULL,
+                frequency TEXT NOT NULL,
+                interval_count INTEGER NOT NULL DEFAULT 1,
+                start_date TEXT NOT NULL,
+                end_date TEXT,
+                next_due TEXT,
+                occurrences INTEGER NOT NULL DEFAULT 0,
+                active INTEGER NOT NULL DEFAULT 1,
+                last_transaction_id INTEGER,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS petty_cash (
+                account_id INTEGER PRIMARY KEY,
+                float_amount INTEGER NOT NULL,
+                funding_account_id INTEGER NOT NULL,
+                last_replenished_at TEXT,
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // A cash count keeps every recorded breakdown; recounts append a new
+        // record instead of replacing the previous one.
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS cash_count_sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                account_id INTEGER NOT NULL,
+                expected_balance INTEGER NOT NULL,
+                started_at TEXT NOT NULL,
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS cash_count_records (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id INTEGER NOT NULL,
+                counted_total INTEGER NOT NULL,
+                recorded_at TEXT NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES cash_count_sessions(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS cash_count_denominations (
+                record_id INTEGER NOT NULL,
+                denomination INTEGER NOT NULL,
+                quantity INTEGER NOT NULL,
+                PRIMARY KEY (record_id, denomination),
+                FOREIGN KEY (record_id) REFERENCES cash_count_records(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // Only one reconciliation per account is open at a time; finishing it
+        // locks the marked transactions.
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS reconciliations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                account_id INTEGER NOT NULL,
+                statement_date TEXT NOT NULL,
+                statement_balance INTEGER NOT NULL,
+                started_at TEXT NOT NULL,
+                finished_at TEXT,
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS reconciliation_items (
+                reconciliation_id INTEGER NOT NULL,
+                transaction_id INTEGER NOT NULL,
+                PRIMARY KEY (reconciliation_id, transaction_id),
+                FOREIGN KEY (reconciliation_id) REFERENCES reconciliations(id) ON DELETE CASCADE,
+                FOREIGN KEY (transaction_id) REFERENCES transactions(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS reconciliation_items_after_tx_delete
+             AFTER DELETE ON transactions
+             BEGIN
+                 DELETE FROM reconciliation_items WHERE transaction_id = OLD.id;
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS reconciliations_after_account_delete
+             AFTER DELETE ON accounts
+             BEGIN
+                 DELETE FROM reconciliation_items
+                 WHERE reconciliation_id IN (SELECT id FROM reconciliations WHERE account_id = OLD.id);
+                 DELETE FROM reconciliations WHERE account_id = OLD.id;
+             END",
+            [],
+        )?;
+
+        // Transactions in a finished reconciliation keep their amount, date,
+        // account and status. Detaching them from a deleted account is still
+        // allowed, as is removing the whole container.
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS reconciled_transactions_before_update
+             BEFORE UPDATE ON transactions
+             WHEN (OLD.amount IS NOT NEW.amount OR OLD.date IS NOT NEW.date
+                   OR (OLD.account_id IS NOT NEW.account_id AND NEW.account_id IS NOT NULL)
+                   OR NEW.status IS NOT 'reconciled' OR NEW.deleted_at IS NOT NULL)
+               AND EXISTS (
+                 SELECT 1 FROM reconciliation_items i
+                 JOIN reconciliations r ON r.id = i.reconciliation_id
+                 WHERE i.transaction_id = OLD.id AND r.finished_at IS NOT NULL
+             )
+             BEGIN
+                 SELECT RAISE(ABORT, 'Transaction is reconciled');
+             END",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS giros (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                direction TEXT NOT NULL,
+                number TEXT NOT NULL,
+                counterparty TEXT NOT NULL,
+                amount INTEGER NOT NULL,
+                account_id INTEGER NOT NULL,
+                category TEXT NOT NULL,
+                issue_date TEXT NOT NULL,
+                maturity_date TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                transaction_id INTEGER,
+                cleared_at TEXT,
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS category_corrections (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                description TEXT NOT NULL,
+                amount INTEGER NOT NULL,
+                category TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS saved_filters (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                filter_json TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                UNIQUE(container_id, name),
+                FOREIGN KEY (container_id) REFERENCES containers(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS undo_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                container_id INTEGER NOT NULL,
+                label TEXT NOT NULL,
+                changes_json TEXT NOT NULL,
+                undone INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS change_log (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                entity TEXT NOT NULL,
+                entity_id INTEGER NOT NULL,
+                container_id INTEGER,
+                operation TEXT NOT NULL,
+                changed_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Trashed transactions are reported as deleted and restored ones as
+        // created, so feed consumers never see rows that left the ledger.
+        conn.execute("DROP TRIGGER IF EXISTS change_log_transactions_updated", [])?;
+        conn.execute("DROP TRIGGER IF EXISTS change_log_transactions_deleted", [])?;
+        conn.execute(
+            "CREATE TRIGGER IF NOT EXISTS change_log_transactions_trashed
+             AFTER UPDATE OF deleted_at ON transactions
+             WHEN OLD.deleted_at IS NOT NEW.deleted_at
+             BEGIN
+                 INSERT INTO change_log (entity, entity_id, container_id, operation, changed_at)
+                 VALUES ('transactions', NEW.id, NEW.container_id,
+                         CASE WHEN NEW.deleted_at IS NULL THEN 'created' ELSE 'deleted' END,
+                         datetime('now', 'localtime'));
+             END",
+            [],
+        )?;
+
+        for (entity, container_expr_new, container_expr_old) in [
+            ("transactions", "NEW.container_id", "OLD.container_id"),
+            ("accounts", "NEW.container_id", "OLD.container_id"),
+            ("containers", "NEW.id", "OLD.id"),
+            ("categories", "NULL", "NULL"),
+        ] {
+            for (event, operation, row, container_expr) in [
+                ("INSERT", "created", "NEW", container_expr_new),
+                ("UPDATE", "updated", "NEW", container_expr_new),
+                ("DELETE", "deleted", "OLD", container_expr_old),
+            ] {
+                let when = match (entity, event) {
+                    ("transactions", "UPDATE") => "WHEN OLD.deleted_at IS NEW.deleted_at",
+                    ("transactions", "DELETE") => "WHEN OLD.deleted_at IS NULL",
+                    _ => "",
+                };
+                conn.execute(
+                    &format!(
+                        "CREATE TRIGGER IF NOT EXISTS change_log_{entity}_{operation}
+                         AFTER {event} ON {entity}
+                         {when}
+                         BEGIN
+                             INSERT INTO change_log (entity, entity_id, container_id, operation, changed_at)
+                             VALUES ('{entity}', {row}.id, {container_expr}, '{operation}', datetime('now', 'localtime'));
+                         END"
+                    ),
+                    [],
+                )?;
+            }
+        }
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                entity TEXT NOT NULL,
+                entity_id INTEGER,
+                container_id INTEGER,
+                operation TEXT NOT NULL,
+                old_json TEXT,
+                new_json TEXT,
+                changed_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_audit_log_entity_changed
+             ON audit_log(entity, changed_at)",
+            [],
+        )?;
+
+        Self::create_audit_triggers(conn)?;
+
+        Self::ensure_default_categories(conn)?;
+
+        let container_ids: Vec<i64> = {
+            let mut stmt = conn.prepare("SELECT id FROM containers")?;
+            let rows = stmt.query_map([], |row| row.get::<_, i64>(0))?;
+            rows.collect::<Result<Vec<i64>>>()?
+        };
+        for container_id in container_ids {
+            Self::ensure_default_equity_accounts(conn, container_id)?;
+        }
+
+        conn.execute_batch(&format!("PRAGMA user_version = {}", Self::SCHEMA_VERSION))?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.db_path
+    }
+
+    /// Every write to a user table is journaled in `audit_log` with the whole
+    /// old and new row. The triggers are rebuilt on each open so columns
+    /// added by later migrations are included.
+    fn create_audit_triggers(conn: &Connection) -> Result<()> {
+        let tables: Vec<String> = {
+            let mut stmt = conn.prepare(
+                "SELECT name FROM sqlite_master
+                 WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name NOT LIKE 'transactions_fts%'
+                 ORDER BY name",
+            )?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.collect::<Result<Vec<String>>>()?
+        };
+
+        for table in tables {
+            if Self::UNAUDITED_TABLES.contains(&table.as_str()) {
+                continue;
+            }
+            let columns: Vec<String> = {
+                let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1) ORDER BY cid")?;
+                let rows = stmt.query_map([&table], |row| row.get::<_, String>(0))?;
+                rows.collect::<Result<Vec<String>>>()?
+            };
+            let has_column = |name: &str| columns.iter().any(|column| column == name);
+            let row_json = |row: &str| {
+                let pairs: Vec<String> = columns
+                    .iter()
+                    .map(|column| format!("'{}', {}.\"{}\"", column, row, column))
+                    .collect();
+                format!("json_object({})", pairs.join(", "))
+            };
+            let id_expr = |row: &str| {
+                if has_column("id") {
+                    format!("{}.id", row)
+                } else {
+                    "NULL".to_string()
+                }
+            };
+            let container_expr = |row: &str| {
+                if table == "containers" {
+                    format!("{}.id", row)
+                } else if has_column("container_id") {
+                    format!("{}.container_id", row)
+                } else {
+                    "NULL".to_string()
+                }
+            };
+
+            for (event, operation, row, old_json, new_json) in [
+                ("INSERT", "created", "NEW", "NULL".to_string(), row_json("NEW")),
+                ("UPDATE", "updated", "NEW", row_json("OLD"), row_json("NEW")),
+                ("DELETE", "deleted", "OLD", row_json("OLD"), "NULL".to_string()),
+            ] {
+                conn.execute(&format!("DROP TRIGGER IF EXISTS audit_log_{}_{}", table, operation), [])?;
+                conn.execute(
+                    &format!(
+                        "CREATE TRIGGER audit_log_{table}_{operation}
+                         AFTER {event} ON \"{table}\"
+                         BEGIN
+                             INSERT INTO audit_log (entity, entity_id, container_id, operation, old_json, new_json, changed_at)
+                             VALUES ('{table}', {id}, {container}, '{operation}', {old_json}, {new_json}, datetime('now', 'localtime'));
+                         END",
+                        id = id_expr(row),
+                        container = container_expr(row),
+                    ),
+                    [],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn copy_to(&self, path: &Path) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// Copies the database to `path` and writes `<path>.manifest.json` next
+    /// to it. The manifest is computed from the copy itself, so
+    /// `verify_backup` can later tell whether the file changed on disk.
+    pub fn create_backup(&self, path: &Path) -> Result<BackupManifest> {
+        let audit_cursor: i64 = {
+            let conn = self.conn.lock().unwrap();
+            conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+            conn.query_row("SELECT COALESCE(MAX(id), 0) FROM audit_log", [], |row| row.get(0))?
+        };
+        let backup = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let (table_counts, checksum) = Self::backup_contents(&backup)?;
+        let manifest = BackupManifest {
+            created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            source_path: self.db_path.to_string_lossy().to_string(),
+            table_counts,
+            checksum,
+            audit_cursor,
+        };
+        let json = serde_json::to_string_pretty(&manifest)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        std::fs::write(Self::sidecar_path(path, ".manifest.json"), json)
+            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+        Ok(manifest)
+    }
+
+    /// Checks that a backup opens, passes `integrity_check`, and still has
+    /// the row counts and checksum recorded in its manifest. Backups made
+    /// without a manifest are only checked for integrity.
+    pub fn verify_backup(path: &Path) -> Result<BackupVerification> {
+        if !path.is_file() {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Backup not found at {}",
+                path.display()
+            )));
+        }
+        let mut verification = BackupVerification {
+            path: path.to_string_lossy().to_string(),
+            integrity: String::new(),
+            manifest_found: false,
+            created_at: None,
+            tables: Vec::new(),
+            checksum_matches: None,
+            restorable: false,
+            problems: Vec::new(),
+        };
+
+        let manifest: Option<BackupManifest> = match std::fs::read_to_string(Self::sidecar_path(path, ".manifest.json")) {
+            Ok(json) => match serde_json::from_str(&json) {
+                Ok(manifest) => Some(manifest),
+                Err(e) => {
+                    verification.problems.push(format!("Manifest is unreadable: {}", e));
+                    None
+                }
+            },
+            Err(_) => None,
+        };
+        verification.manifest_found = manifest.is_some();
+        verification.created_at = manifest.as_ref().map(|m| m.created_at.clone());
+
+        let opened = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).and_then(|backup| {
+            let integrity: String = backup.query_row("PRAGMA integrity_check(1)", [], |row| row.get(0))?;
+            Ok((backup, integrity))
+        });
+        let (backup, integrity) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                verification.integrity = e.to_string();
+                verification.problems.push(format!("Backup cannot be opened: {}", e));
+                return Ok(verification);
+            }
+        };
+        verification.integrity = integrity;
+        if verification.integrity != "ok" {
+            verification.problems.push(format!("Integrity check failed: {}", verification.integrity));
+            return Ok(verification);
+        }
+
+        let (actual_counts, checksum) = match Self::backup_contents(&backup) {
+            Ok(contents) => contents,
+            Err(e) => {
+                verification.problems.push(format!("Backup cannot be read: {}", e));
+                return Ok(verification);
+            }
+        };
+        if let Some(manifest) = &manifest {
+            let mut names: Vec<&String> = manifest.table_counts.keys().chain(actual_counts.keys()).collect();
+            names.sort();
+            names.dedup();
+            for name in names {
+                let expected_rows = manifest.table_counts.get(name).copied();
+                let actual_rows = actual_counts.get(name).copied();
+                if expected_rows != actual_rows {
+                    verification.problems.push(format!(
+                        "Table {} has {} rows, manifest recorded {}",
+                        name,
+                        actual_rows.map_or("no".to_string(), |n| n.to_string()),
+                        expected_rows.map_or("none".to_string(), |n| n.to_string())
+                    ));
+                }
+                verification.tables.push(BackupTableCheck {
+                    table: name.clone(),
+                    expected_rows,
+                    actual_rows,
+                });
+            }
+            let matches = manifest.checksum == checksum;
+            if !matches {
+                verification.problems.push("Content checksum does not match the manifest".to_string());
+            }
+            verification.checksum_matches = Some(matches);
+        } else {
+            verification.tables = actual_counts
+                .into_iter()
+                .map(|(table, rows)| BackupTableCheck {
+                    table,
+                    expected_rows: None,
+                    actual_rows: Some(rows),
+                })
+                .collect();
+        }
+
+        verification.restorable = verification.problems.is_empty();
+        Ok(verification)
+    }
+
+    fn read_backup_manifest(path: &Path) -> Result<BackupManifest> {
+        let json = std::fs::read_to_string(Self::sidecar_path(path, ".manifest.json")).map_err(|_| {
+            rusqlite::Error::InvalidParameterName(format!(
+                "{} has no backup manifest; create a full backup first",
+                path.display()
+            ))
+        })?;
+        serde_json::from_str(&json).map_err(|e| rusqlite::Error::InvalidParameterName(format!("Manifest is unreadable: {}", e)))
+    }
+
+    /// Writes every audit entry recorded since the full backup at `base_path`
+    /// to `path` as JSON. Each differential stands on its own: restoring needs
+    /// only the full backup and the newest differential.
+    pub fn create_differential_backup(&self, base_path: &Path, path: &Path) -> Result<DifferentialBackupSummary> {
+        let manifest = Self::read_backup_manifest(base_path)?;
+        let changes = self.get_audit_log_since(manifest.audit_cursor)?;
+        let backup = DifferentialBackup {
+            base_checksum: manifest.checksum,
+            from_cursor: manifest.audit_cursor,
+            to_cursor: changes.last().map_or(manifest.audit_cursor, |entry| entry.id),
+            created_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            changes,
+        };
+        let json =
+            serde_json::to_string(&backup).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        std::fs::write(path, json).map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+        Ok(DifferentialBackupSummary {
+            path: path.to_string_lossy().to_string(),
+            base_checksum: backup.base_checksum,
+            from_cursor: backup.from_cursor,
+            to_cursor: backup.to_cursor,
+            change_count: backup.changes.len(),
+            created_at: backup.created_at,
+        })
+    }
+
+    /// Rebuilds the database at `target` from a verified full backup plus,
+    /// optionally, a differential backup replayed up to `until` (`YYYY-MM-DD`
+    /// or `YYYY-MM-DD HH:MM:SS`). Tables the audit log skips keep their state
+    /// from the full backup.
+    pub fn restore_point_in_time(
+        base_path: &Path,
+        differential_path: Option<&Path>,
+        until: Option<&str>,
+        target: &Path,
+    ) -> Result<PointInTimeRestore> {
+        if target.exists() {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "A file already exists at {}",
+                target.display()
+            )));
+        }
+        let until = match until.map(str::trim).filter(|v| !v.is_empty()) {
+            Some(value) if value.len() > 10 => Some(
+                chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                    .map_err(|_| rusqlite::Error::InvalidParameterName(format!("Invalid date and time: {}", value)))?
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+            ),
+            Some(value) => Some(format!("{} 23:59:59", Self::parse_day(value)?.format("%Y-%m-%d"))),
+            None => None,
+        };
+
+        let verification = Self::verify_backup(base_path)?;
+        if !verification.restorable || verification.checksum_matches != Some(true) {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Full backup cannot be restored: {}",
+                verification.problems.join("; ")
+            )));
+        }
+        let changes = match differential_path {
+            Some(path) => {
+                let json =
+                    std::fs::read_to_string(path).map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+                let differential: DifferentialBackup = serde_json::from_str(&json).map_err(|e| {
+                    rusqlite::Error::InvalidParameterName(format!("Differential backup is unreadable: {}", e))
+                })?;
+                if differential.base_checksum != Self::read_backup_manifest(base_path)?.checksum {
+                    return Err(rusqlite::Error::InvalidParameterName(
+                        "Differential backup was made from a different full backup".to_string(),
+                    ));
+                }
+                differential.changes
+            }
+            None => Vec::new(),
+        };
+
+        std::fs::copy(base_path, target).map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+        let replayed = (|| -> Result<usize> {
+            let mut conn = Connection::open(target)?;
+            let tx = conn.transaction()?;
+            // Replayed rows already carry every effect the triggers had at
+            // the time; they are recreated when the file is next opened, and
+            // the full-text index is rebuilt then too.
+            let triggers: Vec<String> = {
+                let mut stmt = tx.prepare("SELECT name FROM sqlite_master WHERE type = 'trigger'")?;
+                let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+                rows.collect::<Result<Vec<String>>>()?
+            };
+            for trigger in triggers {
+                tx.execute(&format!("DROP TRIGGER \"{}\"", trigger), [])?;
+            }
+            tx.execute("DROP TABLE IF EXISTS transactions_fts", [])?;
+
+            let mut applied = 0;
+            for entry in &changes {
+                if until.as_ref().is_some_and(|until| entry.changed_at > *until) {
+                    break;
+                }
+                Self::replay_audit_entry(&tx, entry)?;
+                applied += 1;
+            }
+            tx.commit()?;
+            Ok(applied)
+        })();
+        let applied_changes = match replayed {
+            Ok(applied) => applied,
+            Err(e) => {
+                let _ = std::fs::remove_file(target);
+                return Err(e);
+            }
+        };
+
+        Ok(PointInTimeRestore {
+            path: target.to_string_lossy().to_string(),
+            applied_changes,
+            skipped_changes: changes.len() - applied_changes,
+            restored_to: until,
+        })
+    }
+
+    /// What `restore_from_backup` would change, per table: rows only in the
+    /// backup (added), rows in both that differ (changed) and rows only in
+    /// the current database (removed).
+    pub fn preview_restore(
+        &self,
+        backup_path: &Path,
+        container_id: Option<i64>,
+        table: Option<String>,
+    ) -> Result<RestorePreview> {
+        self.restore_rows(backup_path, container_id, table, false)
+    }
+
+    /// Brings the current database in line with a backup, row by row and
+    /// without replacing the file. With `container_id` only that container's
+    /// rows are touched; with `table` only that table.
+    pub fn restore_from_backup(
+        &self,
+        backup_path: &Path,
+        container_id: Option<i64>,
+        table: Option<String>,
+    ) -> Result<RestorePreview> {
+        self.restore_rows(backup_path, container_id, table, true)
+    }
+
+    fn restore_rows(
+        &self,
+        backup_path: &Path,
+        container_id: Option<i64>,
+        table: Option<String>,
+        apply: bool,
+    ) -> Result<RestorePreview> {
+        let verification = Self::verify_backup(backup_path)?;
+        if !verification.restorable {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Backup cannot be restored: {}",
+                verification.problems.join("; ")
+            )));
+        }
+        let table = table.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+
+        let mut conn = self.conn.lock().unwrap();
+        conn.execute("ATTACH DATABASE ?1 AS backup", [backup_path.to_string_lossy()])?;
+        let result = (|| -> Result<RestorePreview> {
+            let mut columns: HashMap<String, Vec<String>> = HashMap::new();
+            let table_names: Vec<String> = {
+                let mut stmt = conn.prepare(
+                    "SELECT name FROM main.sqlite_master
+                     WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name NOT LIKE 'transactions_fts%'
+                       AND name IN (SELECT name FROM backup.sqlite_master WHERE type = 'table')
+                     ORDER BY name",
+                )?;
+                let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+                rows.collect::<Result<Vec<String>>>()?
+            };
+            for name in &table_names {
+                let mut stmt = conn.prepare(
+                    "SELECT name FROM pragma_table_info(?1, 'main')
+                     WHERE name IN (SELECT name FROM pragma_table_info(?1, 'backup'))
+                     ORDER BY cid",
+                )?;
+                let rows = stmt.query_map([name], |row| row.get::<_, String>(0))?;
+                columns.insert(name.clone(), rows.collect::<Result<Vec<String>>>()?);
+            }
+            if let Some(table) = &table {
+                if !table_names.contains(table) || Self::UNAUDITED_TABLES.contains(&table.as_str()) {
+                    return Err(rusqlite::Error::InvalidParameterName(format!(
+                        "Table {} cannot be restored",
+                        table
+                    )));
+                }
+            }
+
+            let mut preview = RestorePreview {
+                backup_path: backup_path.to_string_lossy().to_string(),
+                container_id,
+                table: table.clone(),
+                tables: Vec::new(),
+                skipped_tables: Vec::new(),
+            };
+            let tx = conn.transaction()?;
+            for name in &table_names {
+                if Self::UNAUDITED_TABLES.contains(&name.as_str()) || table.as_ref().is_some_and(|t| t != name) {
+                    continue;
+                }
+                let scope = match container_id {
+                    Some(_) => match (
+                        Self::container_scope("main", name, &columns),
+                        Self::container_scope("backup", name, &columns),
+                    ) {
+                        (Some(main_scope), Some(backup_scope)) => Some((main_scope, backup_scope)),
+                        _ => {
+                            preview.skipped_tables.push(name.clone());
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+                let change = Self::restore_table(&tx, name, &columns[name], scope, container_id, apply)?;
+                preview.tables.push(change);
+            }
+            if apply {
+                tx.commit()?;
+            }
+            Ok(preview)
+        })();
+        conn.execute("DETACH DATABASE backup", [])?;
+        result
+    }
+
+    /// SQL condition limiting `schema.table` to the container bound as `?1`,
+    /// following `*_id` columns up to a table that has `container_id`. `None`
+    /// for tables shared by all containers, such as categories and settings.
+    fn container_scope(schema: &str, table: &str, columns: &HashMap<String, Vec<String>>) -> Option<String> {
+        let table_columns = columns.get(table)?;
+        let has_column = |name: &str| table_columns.iter().any(|column| column == name);
+        if table == "containers" {
+            return Some("id = ?1".to_string());
+        }
+        if has_column("container_id") {
+            return Some("container_id = ?1".to_string());
+        }
+        for (column, parent) in [
+            ("account_id", "accounts"),
+            ("envelope_id", "envelopes"),
+            ("session_id", "cash_count_sessions"),
+            ("record_id", "cash_count_records"),
+            ("request_id", "purchase_requests"),
+            ("voucher_id", "vouchers"),
+            ("statement_id", "statement_imports"),
+            ("reconciliation_id", "reconciliations"),
+            ("transaction_id", "transactions"),
+        ] {
+            if has_column(column) {
+                let parent_scope = Self::container_scope(schema, parent, columns)?;
+                return Some(format!(
+                    "{} IN (SELECT id FROM {}.{} WHERE {})",
+                    column, schema, parent, parent_scope
+                ));
+            }
+        }
+        None
+    }
+
+    fn restore_table(
+        conn: &Connection,
+        table: &str,
+        columns: &[String],
+        scope: Option<(String, String)>,
+        container_id: Option<i64>,
+        apply: bool,
+    ) -> Result<RestoreTableChange> {
+        let (main_scope, backup_scope) = scope
+            .clone()
+            .unwrap_or_else(|| ("1".to_string(), "1".to_string()));
+        let bind: Vec<i64> = scope.and(container_id).into_iter().collect();
+        let column_list = columns
+            .iter()
+            .map(|column| format!("\"{}\"", column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let has_id = columns.iter().any(|column| column == "id");
+        let mut change = RestoreTableChange {
+            table: table.to_string(),
+            added: 0,
+            changed: 0,
+            removed: 0,
+        };
+        let read_rows = |sql: &str| -> Result<Vec<Vec<Value>>> {
+            let mut stmt = conn.prepare(sql)?;
+            let column_count = stmt.column_count();
+            let rows = stmt.query_map(params_from_iter(&bind), |row| {
+                (0..column_count).map(|index| row.get::<_, Value>(index)).collect()
+            })?;
+            rows.collect()
+        };
+
+        let removed = if has_id {
+            read_rows(&format!(
+                "SELECT id FROM main.\"{table}\" WHERE {main_scope} AND id NOT IN (SELECT id FROM backup.\"{table}\")"
+            ))?
+        } else {
+            read_rows(&format!(
+                "SELECT {column_list} FROM main.\"{table}\" WHERE {main_scope}
+                 EXCEPT SELECT {column_list} FROM backup.\"{table}\" WHERE {backup_scope}"
+            ))?
+        };
+        change.removed = removed.len() as i64;
+        let incoming = read_rows(&format!(
+            "SELECT {column_list} FROM backup.\"{table}\" WHERE {backup_scope}
+             EXCEPT SELECT {column_list} FROM main.\"{table}\" WHERE {main_scope}"
+        ))?;
+        let id_index = columns.iter().position(|column| column == "id");
+        let exists_in_main = |row: &[Value]| -> Result<bool> {
+            let Some(index) = id_index else {
+                return Ok(false);
+            };
+            conn.query_row(
+                &format!("SELECT 1 FROM main.\"{}\" WHERE id = ?1", table),
+                [&row[index]],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|found| found.is_some())
+        };
+        for row in &incoming {
+            if exists_in_main(row)? {
+                change.changed += 1;
+            } else {
+                change.added += 1;
+            }
+        }
+        if !apply {
+            return Ok(change);
+        }
+
+        for row in removed {
+            if has_id {
+                conn.execute(&format!("DELETE FROM main.\"{}\" WHERE id = ?1", table), params_from_iter(row))?;
+            } else {
+                let condition = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(index, column)| format!("\"{}\" IS ?{}", column, index + 1))
+                    .collect::<Vec<_>>()
+                    .join(" AND ");
+                conn.execute(
+                    &format!("DELETE FROM main.\"{}\" WHERE {}", table, condition),
+                    params_from_iter(row),
+                )?;
+            }
+        }
+        let placeholders = vec!["?"; columns.len()].join(", ");
+        for row in incoming {
+            // Existing rows are updated in place so their triggers run as
+            // for an edit rather than a delete and insert.
+            if exists_in_main(&row)? {
+                let assignments = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(index, column)| format!("\"{}\" = ?{}", column, index + 1))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let id = row[id_index.unwrap_or_default()].clone();
+                let mut values = row;
+                values.push(id);
+                conn.execute(
+                    &format!(
+                        "UPDATE main.\"{}\" SET {} WHERE id = ?{}",
+                        table,
+                        assignments,
+                        values.len()
+                    ),
+                    params_from_iter(values),
+                )?;
+            } else {
+                conn.execute(
+                    &format!(
+                        "INSERT OR REPLACE INTO main.\"{}\" ({}) VALUES ({})",
+                        table, column_list, placeholders
+                    ),
+                    params_from_iter(row),
+                )?;
+            }
+        }
+        Ok(change)
+    }
+
+    fn get_audit_log_since(&self, cursor: i64) -> Result<Vec<AuditEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, entity, entity_id, container_id, operation, old_json, new_json, changed_at
+             FROM audit_log
+             WHERE id > ?1
+             ORDER BY id ASC",
+        )?;
+        let rows = stmt.query_map([cursor], Self::audit_entry_from_row)?;
+        rows.collect()
+    }
+
+    /// Applies one audited change and copies the entry into the target's own
+    /// audit log so its history continues past the full backup.
+    fn replay_audit_entry(conn: &Connection, entry: &AuditEntry) -> Result<()> {
+        let table = &entry.entity;
+        if let Some(serde_json::Value::Object(old)) = &entry.old_values {
+            let (condition, values): (String, Vec<Value>) = match old.get("id") {
+                Some(id) => ("id = ?1".to_string(), vec![Self::sql_value(id)]),
+                None => {
+                    let conditions: Vec<String> = old
+                        .keys()
+                        .enumerate()
+                        .map(|(index, column)| format!("\"{}\" IS ?{}", column, index + 1))
+                        .collect();
+                    (conditions.join(" AND "), old.values().map(Self::sql_value).collect())
+                }
+            };
+            conn.execute(
+                &format!("DELETE FROM \"{}\" WHERE {}", table, condition),
+                params_from_iter(values),
+            )?;
+        }
+        if let Some(serde_json::Value::Object(new)) = &entry.new_values {
+            let names: Vec<String> = new.keys().map(|column| format!("\"{}\"", column)).collect();
+            let placeholders = vec!["?"; names.len()].join(", ");
+            conn.execute(
+                &format!(
+                    "INSERT OR REPLACE INTO \"{}\" ({}) VALUES ({})",
+                    table,
+                    names.join(", "),
+                    placeholders
+                ),
+                params_from_iter(new.values().map(Self::sql_value)),
+            )?;
+        }
+        conn.execute(
+            "INSERT OR IGNORE INTO audit_log (id, entity, entity_id, container_id, operation, old_json, new_json, changed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                entry.id,
+                &entry.entity,
+                entry.entity_id,
+                entry.container_id,
+                &entry.operation,
+                entry.old_values.as_ref().map(|v| v.to_string()),
+                entry.new_values.as_ref().map(|v| v.to_string()),
+                &entry.changed_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Row counts per table and an FNV-1a checksum over every row, in table
+    /// name and rowid order. The full-text index is derived data and is left
+    /// out, like in `salvage_tables`.
+    fn backup_contents(conn: &Connection) -> Result<(std::collections::BTreeMap<String, i64>, String)> {
+        let tables: Vec<(String, bool)> = {
+            let mut stmt = conn.prepare(
+                "SELECT name, sql LIKE '%WITHOUT ROWID%' FROM sqlite_master
+                 WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name NOT LIKE 'transactions_fts%'
+                   AND sql IS NOT NULL
+                 ORDER BY name",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<Result<Vec<(String, bool)>>>()?
+        };
+
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+        let mut counts = std::collections::BTreeMap::new();
+        for (name, without_rowid) in tables {
+            feed(name.as_bytes());
+            let order = if without_rowid { "1" } else { "rowid" };
+            let mut stmt = conn.prepare(&format!("SELECT * FROM \"{}\" ORDER BY {}", name, order))?;
+            let column_count = stmt.column_count();
+            let mut rows = stmt.query([])?;
+            let mut count = 0;
+            while let Some(row) = rows.next()? {
+                count += 1;
+                for index in 0..column_count {
+                    match row.get_ref(index)? {
+                        rusqlite::types::ValueRef::Null => feed(&[0]),
+                        rusqlite::types::ValueRef::Integer(v) => {
+                            feed(&[1]);
+                            feed(&v.to_le_bytes());
+                        }
+                        rusqlite::types::ValueRef::Real(v) => {
+                            feed(&[2]);
+                            feed(&v.to_le_bytes());
+                        }
+                        rusqlite::types::ValueRef::Text(v) => {
+                            feed(&[3]);
+                            feed(&(v.len() as u64).to_le_bytes());
+                            feed(v);
+                        }
+                        rusqlite::types::ValueRef::Blob(v) => {
+                            feed(&[4]);
+                            feed(&(v.len() as u64).to_le_bytes());
+                            feed(v);
+                        }
+                    }
+                }
+            }
+            counts.insert(name, count);
+        }
+        Ok((counts, format!("{:016x}", hash)))
+    }
+
+    fn sidecar_path(db_path: &Path, suffix: &str) -> PathBuf {
+        let file_name = db_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "spent.db".to_string());
+        db_path.with_file_name(format!("{}{}", file_name, suffix))
+    }
+
+    fn acquire_instance_lock(lock_path: &Path) -> Result<Option<Connection>> {
+        let lock = Connection::open(lock_path)?;
+        lock.busy_timeout(std::time::Duration::ZERO)?;
+        lock.query_row("PRAGMA locking_mode = EXCLUSIVE", [], |row| row.get::<_, String>(0))?;
+
+        // In exclusive locking mode the write lock is held until the connection closes,
+        // so it is released automatically when the owning process exits or crashes.
+        let acquired = lock.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS instance (id INTEGER PRIMARY KEY, pid INTEGER NOT NULL);
+             INSERT OR REPLACE INTO instance (id, pid) VALUES (1, {});",
+            std::process::id()
+        ));
+        match acquired {
+            Ok(()) => Ok(Some(lock)),
+            Err(rusqlite::Error::SqliteFailure(err, _))
+                if err.code == rusqlite::ErrorCode::DatabaseBusy
+                    || err.code == rusqlite::ErrorCode::DatabaseLocked =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn get_instance_status(&self) -> Result<InstanceStatus> {
+        if self.is_opened_read_only()? {
+            // Viewing never claims the lock, so it cannot tell whether the
+            // owner has the profile open.
+            return Ok(InstanceStatus {
+                primary: false,
+                another_instance_running: false,
+            });
+        }
+        let mut instance_lock = self.instance_lock.lock().unwrap();
+        if instance_lock.is_none() {
+            *instance_lock = Self::acquire_instance_lock(&self.instance_lock_path)?;
+        }
+        let primary = instance_lock.is_some();
+
+        Ok(InstanceStatus {
+            primary,
+            another_instance_running: !primary,
+        })
+    }
+
+    pub fn open_with_recovery(db_path: PathBuf) -> Result<(Self, Option<RecoveryReport>)> {
+        let opened = Self::new(db_path.clone()).and_then(|database| {
+            let check: String = database
+                .conn
+                .lock()
+                .unwrap()
+                .query_row("PRAGMA quick_check(1)", [], |row| row.get(0))?;
+            if check == "ok" {
+                Ok(database)
+            } else {
+                Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CORRUPT),
+                    Some(check),
+                ))
+            }
+        });
+
+        match opened {
+            Ok(database) => Ok((database, None)),
+            Err(e) if Self::is_corruption(&e) => {
+                let report = Self::recover_database(&db_path, e.to_string())?;
+                Ok((Self::new(db_path)?, Some(report)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Opens a profile for viewing only. The file is opened read-only, so no
+    /// migrations run and no instance lock is taken; a profile whose schema
+    /// is older than this build must be opened normally once to upgrade it.
+    pub fn open_read_only(db_path: PathBuf) -> Result<Self> {
+        let conn = Connection::open_with_flags(&db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        conn.busy_timeout(Self::BUSY_TIMEOUT)?;
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version < Self::SCHEMA_VERSION {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "This profile was saved by an older version. Open it normally once to upgrade it before viewing it read-only"
+                    .to_string(),
+            ));
+        }
+        conn.execute_batch("PRAGMA query_only = ON")?;
+        let instance_lock_path = Self::sidecar_path(&db_path, ".lock");
+        Ok(Database {
+            conn: Mutex::new(conn),
+            db_path,
+            instance_lock_path,
+            instance_lock: Mutex::new(None),
+        })
+    }
+
+    /// Whether the file itself was opened read-only by `open_read_only`, as
+    /// opposed to a writable connection with `query_only` switched on.
+    pub fn is_opened_read_only(&self) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.is_readonly(rusqlite::DatabaseName::Main)
+    }
+
+    /// Switches the connection's `query_only` flag. While it is on SQLite
+    /// rejects writes with `SQLITE_READONLY`, which the commands report as
+    /// a permission error.
+    pub fn set_read_only(&self, read_only: bool) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch(if read_only {
+            "PRAGMA query_only = ON"
+        } else {
+            "PRAGMA query_only = OFF"
+        })
+    }
+
+    pub fn is_read_only(&self) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let query_only: bool = conn.query_row("PRAGMA query_only", [], |row| row.get(0))?;
+        Ok(query_only || conn.is_readonly(rusqlite::DatabaseName::Main)?)
+    }
+
+    pub fn name_conflict(error: &rusqlite::Error) -> Option<&NameConflict> {
+        match error {
+            rusqlite::Error::ToSqlConversionFailure(inner) => inner.downcast_ref::<NameConflict>(),
+            _ => None,
+        }
+    }
+
+    /// Existing container names, or account names within one container.
+    fn names_in_scope(conn: &Connection, entity: &str, container_id: Option<i64>) -> Result<Vec<String>> {
+        let (sql, params): (&str, Vec<i64>) = match entity {
+            "container" => ("SELECT name FROM containers ORDER BY name", Vec::new()),
+            "account" => (
+                "SELECT name FROM accounts WHERE container_id = ?1 ORDER BY name",
+                container_id.into_iter().collect(),
+            ),
+            _ => {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Unknown entity '{}'",
+                    entity
+                )))
+            }
+        };
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(params_from_iter(params), |row| row.get::<_, String>(0))?;
+        rows.collect()
+    }
+
+    fn ensure_name_available(entity: &str, name: &str, taken: &[String]) -> Result<()> {
+        if !taken.iter().any(|existing| existing == name) {
+            return Ok(());
+        }
+        let suggestions = (2..)
+            .map(|n| format!("{} {}", name, n))
+            .filter(|candidate| !taken.contains(candidate))
+            .take(Self::NAME_SUGGESTION_COUNT)
+            .collect();
+        Err(rusqlite::Error::ToSqlConversionFailure(Box::new(NameConflict {
+            entity: entity.to_string(),
+            name: name.to_string(),
+            suggestions,
+        })))
+    }
+
+    /// Existing names that could be confused with `name`: the same apart from
+    /// case, one containing the other, or within two edits.
+    pub fn find_similar_names(&self, entity: String, name: String, container_id: Option<i64>) -> Result<Vec<String>> {
+        let wanted = name.trim().to_lowercase();
+        if wanted.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.conn.lock().unwrap();
+        let names = Self::names_in_scope(&conn, entity.trim(), container_id)?;
+        Ok(names
+            .into_iter()
+            .filter(|existing| {
+                let existing = existing.to_lowercase();
+                existing.contains(&wanted)
+                    || wanted.contains(&existing)
+                    || Self::edit_distance(&existing, &wanted) <= Self::SIMILAR_NAME_MAX_EDITS
+            })
+            .collect())
+    }
+
+    fn edit_distance(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut previous: Vec<usize> = (0..=b.len()).collect();
+        for (i, ca) in a.chars().enumerate() {
+            let mut current = vec![i + 1; b.len() + 1];
+            for (j, cb) in b.iter().enumerate() {
+                let substitution = previous[j] + usize::from(ca != *cb);
+                current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            }
+            previous = current;
+        }
+        previous[b.len()]
+    }
+
+    pub fn is_read_only_error(error: &rusqlite::Error) -> bool {
+        matches!(
+            error,
+            rusqlite::Error::SqliteFailure(err, _) if err.code == rusqlite::ErrorCode::ReadOnly
+        )
+    }
+
+    fn is_corruption(error: &rusqlite::Error) -> bool {
+        matches!(
+            error,
+            rusqlite::Error::SqliteFailure(err, _)
+                if err.code == rusqlite::ErrorCode::DatabaseCorrupt
+                    || err.code == rusqlite::ErrorCode::NotADatabase
+        )
+    }
+
+    fn recover_database(db_path: &Path, original_error: String) -> Result<RecoveryReport> {
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        let quarantine_suffix = format!(".corrupt-{}", stamp);
+        let quarantined_path = Self::sidecar_path(db_path, &quarantine_suffix);
+        let recovered_path = Self::sidecar_path(db_path, ".recovering");
+        let io_error = |e: std::io::Error| rusqlite::Error::InvalidParameterName(e.to_string());
+
+        if recovered_path.exists() {
+            std::fs::remove_file(&recovered_path).map_err(io_error)?;
+        }
+
+        let mut report = RecoveryReport {
+            original_error,
+            quarantined_path: quarantined_path.to_string_lossy().to_string(),
+            recovered_tables: Vec::new(),
+            failed_tables: Vec::new(),
+            recovered_rows: 0,
+            opened: true,
+        };
+        if Self::salvage_tables(db_path, &recovered_path, &mut report).is_err() {
+            report.recovered_tables.clear();
+            report.recovered_rows = 0;
+            if recovered_path.exists() {
+                std::fs::remove_file(&recovered_path).map_err(io_error)?;
+            }
+        }
+
+        std::fs::rename(db_path, &quarantined_path).map_err(io_error)?;
+        for suffix in ["-wal", "-shm", "-journal"] {
+            let sidecar = Self::sidecar_path(db_path, suffix);
+            if sidecar.exists() {
+                let target = Self::sidecar_path(&quarantined_path, suffix);
+                std::fs::rename(&sidecar, target).map_err(io_error)?;
+            }
+        }
+        if recovered_path.exists() {
+            std::fs::rename(&recovered_path, db_path).map_err(io_error)?;
+        }
+
+        Ok(report)
+    }
+
+    fn salvage_tables(source_path: &Path, target_path: &Path, report: &mut RecoveryReport) -> Result<()> {
+        let source = Connection::open_with_flags(source_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        // The full-text index is left out; it is rebuilt when the recovered
+        // file is opened.
+        let tables: Vec<(String, String)> = {
+            let mut stmt = source.prepare(
+                "SELECT name, sql FROM sqlite_master
+                 WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name NOT LIKE 'transactions_fts%'
+                   AND sql IS NOT NULL",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<Result<Vec<(String, String)>>>()?
+        };
+
+        let target = Connection::open(target_path)?;
+        for (name, sql) in tables {
+            match Self::salvage_table(&source, &target, &name, &sql) {
+                Ok(rows) => {
+                    report.recovered_tables.push(name);
+                    report.recovered_rows += rows;
+                }
+                Err(_) => report.failed_tables.push(name),
+            }
+        }
+        Ok(())
+    }
+
+    fn salvage_table(source: &Connection, target: &Connection, name: &str, sql: &str) -> Result<i64> {
+        target.execute(sql, [])?;
+        let mut stmt = source.prepare(&format!("SELECT * FROM \"{}\"", name))?;
+        let column_count = stmt.column_count();
+        let placeholders = vec!["?"; column_count].join(", ");
+        let insert = format!("INSERT OR IGNORE INTO \"{}\" VALUES ({})", name, placeholders);
+
+        let tx = target.unchecked_transaction()?;
+        let mut copied = 0;
+        let mut rows = stmt.query([])?;
+        // Keep whatever was readable before the first damaged page.
+        while let Ok(Some(row)) = rows.next() {
+            let mut values = Vec::with_capacity(column_count);
+            for index in 0..column_count {
+                values.push(row.get::<_, Value>(index).unwrap_or(Value::Null));
+            }
+            copied += tx.execute(&insert, params_from_iter(values))? as i64;
+        }
+        tx.commit()?;
+        Ok(copied)
+    }
+
+    pub fn add_transaction(&self, transaction: NewTransaction) -> Result<Transaction> {
+        if let Some(description) = &transaction.description {
+            Self::ensure_max_chars("Description", description, Self::MAX_DESCRIPTION_CHARS)?;
+        }
+        if let Some(category) = &transaction.category {
+            Self::ensure_max_chars("Category", category, Self::MAX_NAME_CHARS)?;
+        }
+        let payment_method = Self::normalize_payment_method(transaction.payment_method)?;
+        let notes = Self::normalize_notes(transaction.notes)?;
+        let mut conn = self.conn.lock().unwrap();
+        let idempotency_key = Self::normalize_idempotency_key(transaction.idempotency_key);
+        if let Some(key) = &idempotency_key {
+            if let Some(existing_id) = Self::find_idempotent_result(&conn, key, "add_transaction")? {
+                return Self::fetch_transaction(&conn, existing_id).optional()?.ok_or_else(|| {
+                    rusqlite::Error::InvalidParameterName(
+                        "This request was already applied; the transaction it created has since been deleted"
+                            .to_string(),
+                    )
+                });
+            }
+        }
+
+        let date = Self::normalize_transaction_date(transaction.date)?;
+        let locale = Self::read_locale(&conn)?;
+        
+        let description = transaction
+            .description
+            .unwrap_or_else(|| i18n::translate(&locale, "default.untitled"));
+        let category = transaction
+            .category
+            .unwrap_or_else(|| Self::DEFAULT_FALLBACK_CATEGORY.to_string());
+        
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO transactions (amount, description, category, date, container_id, account_id, payment_method, notes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                transaction.amount,
+                &description,
+                &category,
+                &date,
+                transaction.container_id,
+                transaction.account_id,
+                &payment_method,
+                &notes,
+            ],
+        )?;
+
+        let id = tx.last_insert_rowid();
+        if let Some(key) = &idempotency_key {
+            Self::record_idempotency_key(&tx, key, "add_transaction", id)?;
+        }
+        Self::record_undo(&tx, transaction.container_id, "add_transaction", Self::undo_new("transactions", &[id]))?;
+        tx.commit()?;
+        
+        Ok(Transaction {
+            id,
+            amount: transaction.amount,
+            description,
+            category,
+            date,
+            container_id: transaction.container_id,
+            account_id: transaction.account_id,
+            transfer_id: 0,
+            transfer_account_id: 0,
+            needs_review: false,
+            payment_method,
+            notes,
+            status: "cleared".to_string(),
+        })
+    }
+
+    pub fn add_transfer(&self, transfer: NewTransfer) -> Result<i64> {
+        let NewTransfer {
+            container_id,
+            from_account_id,
+            to_account_id,
+            amount,
+            description,
+            date,
+            idempotency_key,
+        } = transfer;
+        if let Some(description) = &description {
+            Self::ensure_max_chars("Description", description, Self::MAX_DESCRIPTION_CHARS)?;
+        }
+        if from_account_id == to_account_id {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Source and destination accounts must be different".to_string(),
+            ));
+        }
+        if amount <= 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Transfer amount must be positive".to_string(),
+            ));
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        let idempotency_key = Self::normalize_idempotency_key(idempotency_key);
+        if let Some(key) = &idempotency_key {
+            if let Some(existing_id) = Self::find_idempotent_result(&conn, key, "add_transfer")? {
+                return Ok(existing_id);
+            }
+        }
+
+        let date = Self::normalize_transaction_date(date)?;
+        let locale = Self::read_locale(&conn)?;
+        let description = description.unwrap_or_else(|| i18n::translate(&locale, "default.transfer"));
+
+        let tx = conn.transaction()?;
+        let transfer_id =
+            Self::insert_transfer(&tx, container_id, from_account_id, to_account_id, amount, &description, &date)?;
+
+        if let Some(key) = &idempotency_key {
+            Self::record_idempotency_key(&tx, key, "add_transfer", transfer_id)?;
+        }
+        let leg_ids = Self::undo_ids(&tx, "transactions", "transfer_id", transfer_id)?;
+        Self::record_undo(&tx, container_id, "add_transfer", Self::undo_new("transactions", &leg_ids))?;
+        tx.commit()?;
+
+        Ok(transfer_id)
+    }
+
+    fn insert_transfer(
+        conn: &Connection,
+        container_id: i64,
+        from_account_id: i64,
+        to_account_id: i64,
+        amount: i64,
+        description: &str,
+        date: &str,
+    ) -> Result<i64> {
+        let transfer_id: i64 = conn.query_row(
+            "SELECT COALESCE(MAX(transfer_id), 0) + 1 FROM transactions",
+            [],
+            |row| row.get(0),
+        )?;
+
+        let debit_amount = -amount.abs();
+        let credit_amount = amount.abs();
+
+        conn.execute(
+            "INSERT INTO transactions (amount, description, category, date, container_id, account_id, transfer_id, transfer_account_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![debit_amount, description, "Transfer", date, container_id, from_account_id, transfer_id, to_account_id],
+        )?;
+
+        conn.execute(
+            "INSERT INTO transactions (amount, description, category, date, container_id, account_id, transfer_id, transfer_account_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![credit_amount, description, "Transfer", date, container_id, to_account_id, transfer_id, from_account_id],
+        )?;
+
+        Ok(transfer_id)
+    }
+
+    /// Pairs of opposite amounts booked on the same day in two different
+    /// accounts, usually the two halves of a transfer imported from separate
+    /// bank statements. Each transaction appears in at most one suggestion.
+    pub fn suggest_transfer_links(&self, container_id: i64) -> Result<Vec<TransferSuggestion>> {
+        let conn = self.conn.lock().unwrap();
+        let candidates: Vec<Transaction> = {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {}
+                 FROM transactions
+                 WHERE container_id = ?1 AND transfer_id IS NULL AND account_id IS NOT NULL AND amount != 0
+                   AND deleted_at IS NULL
+                 ORDER BY date ASC, id ASC",
+                Self::TRANSACTION_COLUMNS
+            ))?;
+            let rows = stmt.query_map([container_id], Self::transaction_from_row)?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+
+        let mut incoming_by_key: HashMap<(String, i64), Vec<usize>> = HashMap::new();
+        for (index, transaction) in candidates.iter().enumerate() {
+            if transaction.amount > 0 {
+                incoming_by_key
+                    .entry((Self::date_only(&transaction.date), transaction.amount))
+                    .or_default()
+                    .push(index);
+            }
+        }
+
+        let mut used = vec![false; candidates.len()];
+        let mut suggestions = Vec::new();
+        for (index, outgoing) in candidates.iter().enumerate() {
+            if outgoing.amount >= 0 {
+                continue;
+            }
+            let key = (Self::date_only(&outgoing.date), -outgoing.amount);
+            let Some(incoming) = incoming_by_key.get(&key) else {
+                continue;
+            };
+            let partner = incoming
+                .iter()
+                .copied()
+                .find(|&i| !used[i] && candidates[i].account_id != outgoing.account_id);
+            if let Some(partner) = partner {
+                used[index] = true;
+                used[partner] = true;
+                suggestions.push((index, partner));
+            }
+        }
+
+        let mut candidates: Vec<Option<Transaction>> = candidates.into_iter().map(Some).collect();
+        Ok(suggestions
+            .into_iter()
+            .filter_map(|(out, inc)| {
+                Some(TransferSuggestion {
+                    outgoing: candidates[out].take()?,
+                    incoming: candidates[inc].take()?,
+                })
+            })
+            .collect())
+    }
+
+    pub fn link_transfer(&self, outgoing_id: i64, incoming_id: i64) -> Result<i64> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let outgoing = Self::fetch_transaction(&tx, outgoing_id)?;
+        let incoming = Self::fetch_transaction(&tx, incoming_id)?;
+        if outgoing.transfer_id != 0 || incoming.transfer_id != 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Transaction is already part of a transfer".to_string(),
+            ));
+        }
+        if outgoing.amount >= 0 || outgoing.amount != -incoming.amount {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Transfer halves must have opposite amounts".to_string(),
+            ));
+        }
+        if outgoing.account_id == 0 || incoming.account_id == 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Both transactions must belong to an account".to_string(),
+            ));
+        }
+        if Self::date_only(&outgoing.date) != Self::date_only(&incoming.date) {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Transfer halves must be on the same day".to_string(),
+            ));
+        }
+        Self::ensure_same_container_accounts(&tx, outgoing.account_id, incoming.account_id)?;
+
+        let mut changes = Self::undo_capture(&tx, "transactions", &[outgoing_id, incoming_id])?;
+        changes.extend(Self::undo_capture(&tx, "transaction_funds", &[outgoing_id, incoming_id])?);
+
+        let transfer_id: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(transfer_id), 0) + 1 FROM transactions",
+            [],
+            |row| row.get(0),
+        )?;
+        for (transaction, other) in [(&outgoing, &incoming), (&incoming, &outgoing)] {
+            tx.execute(
+                "UPDATE transactions SET category = 'Transfer', transfer_id = ?1, transfer_account_id = ?2 WHERE id = ?3",
+                params![transfer_id, other.account_id, transaction.id],
+            )?;
+        }
+        tx.execute(
+            "DELETE FROM transaction_funds WHERE transaction_id IN (?1, ?2)",
+            params![outgoing_id, incoming_id],
+        )?;
+        Self::record_undo(&tx, outgoing.container_id, "link_transfer", changes)?;
+        tx.commit()?;
+
+        Ok(transfer_id)
+    }
+
+    /// Newest first. `id` breaks ties between rows sharing a date so pages
+    /// fetched with `offset` neither repeat nor skip rows.
+    pub fn get_transactions(
+        &self,
+        container_id: i64,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<Transaction>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM transactions
+             WHERE container_id = ?1 AND deleted_at IS NULL
+             ORDER BY date DESC, id DESC
+             LIMIT ?2 OFFSET ?3",
+            Self::TRANSACTION_COLUMNS
+        ))?;
+        // A negative LIMIT means no limit in SQLite.
+        let transactions = stmt.query_map(
+            params![container_id, limit.unwrap_or(-1), offset.unwrap_or(0).max(0)],
+            Self::transaction_from_row,
+        )?;
+
+        transactions.collect()
+    }
+
+    /// Ranked full-text search over descriptions and categories within a
+    /// container. Every word must match, as a prefix, so "sewa tok" finds
+    /// "Sewa toko Januari".
+    pub fn search_transactions(&self, container_id: i64, query: String, limit: Option<i64>) -> Result<Vec<Transaction>> {
+        Self::ensure_max_chars("Search", &query, Self::MAX_DESCRIPTION_CHARS)?;
+        let words: Vec<String> = query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect();
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+        let limit = limit.unwrap_or(Self::DEFAULT_PAGE_SIZE).clamp(1, Self::MAX_PAGE_SIZE);
+
+        let conn = self.conn.lock().unwrap();
+        let fts_available: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'transactions_fts'",
+            [],
+            |row| row.get(0),
+        )?;
+        if fts_available > 0 {
+            let match_query = words
+                .iter()
+                .map(|word| format!("\"{}\"*", word))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {}
+                 FROM transactions
+                 JOIN (
+                     SELECT rowid AS match_id, rank FROM transactions_fts WHERE transactions_fts MATCH ?2
+                 ) ON match_id = transactions.id
+                 WHERE container_id = ?1 AND deleted_at IS NULL
+                 ORDER BY rank, date DESC
+                 LIMIT ?3",
+                Self::TRANSACTION_COLUMNS
+            ))?;
+            let rows = stmt.query_map(params![container_id, match_query, limit], Self::transaction_from_row)?;
+            return rows.collect();
+        }
+
+        let mut clause = "container_id = ? AND deleted_at IS NULL".to_string();
+        let mut values = vec![Value::Integer(container_id)];
+        for word in &words {
+            clause.push_str(" AND (LOWER(description) LIKE ? OR LOWER(category) LIKE ?)");
+            values.push(Value::Text(format!("%{}%", word)));
+            values.push(Value::Text(format!("%{}%", word)));
+        }
+        values.push(Value::Integer(limit));
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM transactions WHERE {} ORDER BY date DESC LIMIT ?",
+            Self::TRANSACTION_COLUMNS,
+            clause
+        ))?;
+        let rows = stmt.query_map(params_from_iter(values.iter()), Self::transaction_from_row)?;
+        rows.collect()
+    }
+
+    pub fn get_transaction_count(&self, container_id: i64) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM transactions WHERE container_id = ?1 AND deleted_at IS NULL",
+            [container_id],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn get_transactions_by_account(
+        &self,
+        container_id: i64,
+        account_id: i64,
+        limit: Option<i64>,
+    ) -> Result<Vec<Transaction>> {
+        let conn = self.conn.lock().unwrap();
+        let base = format!(
+            "SELECT {}
+             FROM transactions
+             WHERE container_id = ?1 AND account_id = ?2 AND deleted_at IS NULL
+             ORDER BY date DESC",
+            Self::TRANSACTION_COLUMNS
+        );
+        let query = match limit {
+            Some(l) => format!("{} LIMIT {}", base, l),
+            None => base,
+        };
+
+        let mut stmt = conn.prepare(&query)?;
+        let transactions = stmt.query_map(params![container_id, account_id], Self::transaction_from_row)?;
+
+        transactions.collect()
+    }
+
+    pub fn get_transactions_by_category(
+        &self,
+        container_id: i64,
+        category: String,
+        limit: Option<i64>,
+    ) -> Result<Vec<Transaction>> {
+        let conn = self.conn.lock().unwrap();
+        let base = format!(
+            "SELECT {}
+             FROM transactions
+             WHERE container_id = ?1 AND category = ?2 AND deleted_at IS NULL
+             ORDER BY date DESC",
+            Self::TRANSACTION_COLUMNS
+        );
+        let query = match limit {
+            Some(l) => format!("{} LIMIT {}", base, l),
+            None => base,
+        };
+
+        let mut stmt = conn.prepare(&query)?;
+        let transactions = stmt.query_map(params![container_id, category], Self::transaction_from_row)?;
+
+        transactions.collect()
+    }
+
+    pub fn update_transaction(
+        &self,
+        id: i64,
+        amount: i64,
+        description: String,
+        category: String,
+        account_id: i64,
+        notes: Option<String>,
+    ) -> Result<Transaction> {
+        Self::ensure_max_chars("Description", &description, Self::MAX_DESCRIPTION_CHARS)?;
+        Self::ensure_max_chars("Category", &category, Self::MAX_NAME_CHARS)?;
+        let notes = Self::normalize_notes(notes)?;
+        let mut conn = self.conn.lock().unwrap();
+
+        let (transfer_id, container_id): (Option<i64>, i64) = conn.query_row(
+            "SELECT transfer_id, container_id FROM transactions WHERE id = ?1 AND deleted_at IS NULL",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        if transfer_id.is_some() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Cannot update transfer transaction".to_string(),
+            ));
+        }
+        
+        let tx = conn.transaction()?;
+        let changes = Self::undo_capture(&tx, "transactions", &[id])?;
+        tx.execute(
+            "UPDATE transactions SET amount = ?1, description = ?2, category = ?3, account_id = ?4, notes = ?5 WHERE id = ?6",
+            params![amount, description, category, account_id, notes, id],
+        )?;
+        Self::record_undo(&tx, container_id, "update_transaction", changes)?;
+        tx.commit()?;
+
+        Self::fetch_transaction(&conn, id)
+    }
+
+    pub fn set_transaction_payment_method(&self, id: i64, payment_method: Option<String>) -> Result<Transaction> {
+        let payment_method = Self::normalize_payment_method(payment_method)?;
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE transactions SET payment_method = ?1 WHERE id = ?2 AND transfer_id IS NULL AND deleted_at IS NULL",
+            params![payment_method, id],
+        )?;
+        if updated == 0 {
+            Self::fetch_transaction(&conn, id)?;
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Transfers do not have a payment method".to_string(),
+            ));
+        }
+        Self::fetch_transaction(&conn, id)
+    }
+
+    /// Income and expense per payment method for a month (`YYYY-MM`) or year
+    /// (`YYYY`). Transactions without a method are reported under `None`.
+    pub fn get_payment_method_totals(&self, container_id: i64, period: String) -> Result<Vec<PaymentMethodTotal>> {
+        let conn = self.conn.lock().unwrap();
+        let (start_date, end_date) = if period.len() == 7 {
+            Self::month_range(&period)?
+        } else {
+            Self::year_range(&period)?
+        };
+        let mut stmt = conn.prepare(
+            "SELECT t.payment_method,
+                    COALESCE(SUM(CASE WHEN COALESCE(c.category_type, 'expense') = 'income' THEN ABS(t.amount) ELSE 0 END), 0),
+                    COALESCE(SUM(CASE WHEN COALESCE(c.category_type, 'expense') = 'expense' THEN ABS(t.amount) ELSE 0 END), 0),
+                    COUNT(*)
+             FROM transactions t
+             LEFT JOIN categories c ON c.name = t.category
+             WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.transfer_id IS NULL
+               AND t.date >= ?2 AND t.date <= ?3
+             GROUP BY t.payment_method
+             ORDER BY t.payment_method IS NULL, t.payment_method ASC",
+        )?;
+        let rows = stmt.query_map(params![container_id, &start_date, &end_date], |row| {
+            Ok(PaymentMethodTotal {
+                payment_method: row.get(0)?,
+                income: row.get(1)?,
+                expense: row.get(2)?,
+                transaction_count: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Notes are free text for longer remarks; blank notes are stored as NULL.
+    fn normalize_notes(notes: Option<String>) -> Result<Option<String>> {
+        let notes = notes.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+        if let Some(notes) = &notes {
+            Self::ensure_max_chars("Notes", notes, Self::MAX_NOTES_CHARS)?;
+        }
+        Ok(notes)
+    }
+
+    fn normalize_payment_method(payment_method: Option<String>) -> Result<Option<String>> {
+        let Some(value) = payment_method
+            .map(|m| m.trim().to_lowercase().replace(['-', ' '], "_"))
+            .filter(|m| !m.is_empty())
+        else {
+            return Ok(None);
+        };
+        if !Self::PAYMENT_METHODS.contains(&value.as_str()) {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Unknown payment method '{}'. Expected one of: {}",
+                value,
+                Self::PAYMENT_METHODS.join(", ")
+            )));
+        }
+        Ok(Some(value))
+    }
+
+    pub fn set_transaction_review(&self, id: i64, needs_review: bool) -> Result<Transaction> {
+        let conn = self.conn.lock().unwrap();
+
+        let updated = conn.execute(
+            "UPDATE transactions SET needs_review = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+            params![needs_review as i64, id],
+        )?;
+
+        if updated == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+
+        Self::fetch_transaction(&conn, id)
+    }
+
+    /// Sets whether a transaction is still pending at the bank, has cleared,
+    /// or has been reconciled against a statement.
+    pub fn set_transaction_status(&self, id: i64, status: String) -> Result<Transaction> {
+        let status = Self::normalize_transaction_status(&status)?;
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE transactions SET status = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+            params![status, id],
+        )?;
+        if updated == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        Self::fetch_transaction(&conn, id)
+    }
+
+    /// Sets the status of several transactions at once and returns how many
+    /// were changed.
+    pub fn set_transactions_status(&self, ids: Vec<i64>, status: String) -> Result<usize> {
+        let status = Self::normalize_transaction_status(&status)?;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut updated = 0;
+        for id in &ids {
+            updated += tx.execute(
+                "UPDATE transactions SET status = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+                params![status, id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    fn normalize_transaction_status(status: &str) -> Result<String> {
+        let status = status.trim().to_lowercase();
+        if !Self::TRANSACTION_STATUSES.contains(&status.as_str()) {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Unknown status '{}'. Expected one of: {}",
+                status,
+                Self::TRANSACTION_STATUSES.join(", ")
+            )));
+        }
+        Ok(status)
+    }
+
+    pub fn get_transactions_needing_review(&self, container_id: i64) -> Result<Vec<Transaction>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM transactions
+             WHERE container_id = ?1 AND needs_review = 1 AND deleted_at IS NULL
+             ORDER BY date DESC, id DESC",
+            Self::TRANSACTION_COLUMNS
+        ))?;
+        let transactions = stmt.query_map([container_id], Self::transaction_from_row)?;
+
+        transactions.collect()
+    }
+
+    pub fn get_review_count(&self, container_id: i64) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM transactions WHERE container_id = ?1 AND needs_review = 1 AND deleted_at IS NULL",
+            [container_id],
+            |row| row.get(0),
+        )
+    }
+
+    fn fetch_transaction(conn: &Connection, id: i64) -> Result<Transaction> {
+        conn.query_row(
+            &format!("SELECT {} FROM transactions WHERE id = ?1 AND deleted_at IS NULL", Self::TRANSACTION_COLUMNS),
+            [id],
+            Self::transaction_from_row,
+        )
+    }
+
+    fn transaction_from_row(row: &rusqlite::Row) -> Result<Transaction> {
+        Ok(Transaction {
+            id: row.get(0)?,
+            amount: row.get(1)?,
+            description: row.get(2)?,
+            category: row.get(3)?,
+            date: row.get(4)?,
+            container_id: row.get(5)?,
+            account_id: row.get(6)?,
+            transfer_id: row.get(7)?,
+            transfer_account_id: row.get(8)?,
+            needs_review: row.get::<_, i64>(9)? == 1,
+            payment_method: row.get(10)?,
+            notes: row.get(11)?,
+            status: row.get(12)?,
+        })
+    }
+
+    pub fn get_monthly_balance(&self, container_id: i64) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        let current_month = chrono::Local::now().format("%Y-%m").to_string();
+        
+        let balance: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(amount), 0) FROM transactions WHERE container_id = ?1 AND date LIKE ?2 AND transfer_id IS NULL AND deleted_at IS NULL",
+            [&container_id.to_string(), &format!("{}%", current_month)],
+            |row| row.get(0),
+        )?;
+
+        Ok(balance)
+    }
+
+    /// Income and expense per week between two days, with weeks starting on
+    /// the configured `week_start` day. Weeks without activity are included
+    /// so charts get a continuous axis.
+    pub fn get_weekly_totals(&self, container_id: i64, start_date: String, end_date: String) -> Result<Vec<WeeklyTotal>> {
+        let start_day = Self::parse_day(&start_date)?;
+        let end_day = Self::parse_day(&end_date)?;
+        if end_day < start_day {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "End date must not be before start date".to_string(),
+            ));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let week_start = Self::read_week_start(&conn)?;
+        let first_weekday = Self::WEEK_DAYS.iter().position(|day| *day == week_start).unwrap_or(0) as i64;
+        let week_of = |day: chrono::NaiveDate| {
+            let offset = (day.weekday().num_days_from_monday() as i64 - first_weekday).rem_euclid(7);
+            day - chrono::Duration::days(offset)
+        };
+
+        let mut weeks: Vec<WeeklyTotal> = Vec::new();
+        let mut cursor = week_of(start_day);
+        while cursor <= end_day {
+            weeks.push(WeeklyTotal {
+                week_start: cursor.format("%Y-%m-%d").to_string(),
+                week_end: (cursor + chrono::Duration::days(6)).format("%Y-%m-%d").to_string(),
+                income: 0,
+                expense: 0,
+                net: 0,
+                transaction_count: 0,
+            });
+            cursor += chrono::Duration::days(7);
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT t.date, t.amount, COALESCE(c.category_type, 'expense')
+             FROM transactions t
+             LEFT JOIN categories c ON c.name = t.category
+             WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.transfer_id IS NULL
+               AND t.date >= ?2 AND t.date <= ?3",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                container_id,
+                format!("{} 00:00:00", start_day.format("%Y-%m-%d")),
+                format!("{} 23:59:59", end_day.format("%Y-%m-%d"))
+            ],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?)),
+        )?;
+        for row in rows {
+            let (date, amount, category_type) = row?;
+            let day = Self::parse_day(&Self::date_only(&date))?;
+            let index = ((week_of(day) - week_of(start_day)).num_days() / 7) as usize;
+            if let Some(week) = weeks.get_mut(index) {
+                if category_type == "income" {
+                    week.income += amount.abs();
+                } else {
+                    week.expense += amount.abs();
+                }
+                week.net = week.income - week.expense;
+                week.transaction_count += 1;
+            }
+        }
+
+        Ok(weeks)
+    }
+
+    /// With `exclude_pending`, transactions the bank has not cleared yet are
+    /// left out so the total matches the available balance.
+    pub fn get_all_time_balance(&self, container_id: i64, exclude_pending: bool) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        
+        let balance: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(amount), 0) FROM transactions
+             WHERE container_id = ?1 AND transfer_id IS NULL AND deleted_at IS NULL
+               AND (?2 = 0 OR status != 'pending')",
+            params![container_id, exclude_pending],
+            |row| row.get(0),
+        )?;
+
+        Ok(balance)
+    }
+
+    pub fn export_transactions_csv(&self, container_id: i64, redact: bool) -> Result<String> {
+        let conn = self.conn.lock().unwrap();
+        let minor_units = Self::read_minor_units(&conn, container_id)?;
+        let locale = Self::read_locale(&conn)?;
+        let date_format = Self::read_date_format(&conn)?;
+        let mut stmt = conn.prepare(
+            "SELECT id, amount, description, category, date FROM transactions WHERE container_id = ?1 AND deleted_at IS NULL ORDER BY date DESC"
+        )?;
+        
+        let mut csv = format!("{}\n", i18n::translate(&locale, "export.transactions.header"));
+        let rows = stmt.query_map([container_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+
+        let redaction_key = RandomState::new();
+        for row in rows {
+            let (id, amount, desc, cat, date) = row?;
+            let desc = if redact {
+                Self::redact_value(&redaction_key, &desc)
+            } else {
+                desc
+            };
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                id,
+                Self::format_minor_units(amount, minor_units),
+                desc,
+                cat,
+                Self::export_date(&date, &date_format)
+            ));
+        }
+
+        Ok(csv)
+    }
+
+    pub fn export_profit_loss_csv(&self, container_id: i64, year: String) -> Result<String> {
+        let (minor_units, locale) = {
+            let conn = self.conn.lock().unwrap();
+            (Self::read_minor_units(&conn, container_id)?, Self::read_locale(&conn)?)
+        };
+        let report = self.get_profit_and_loss_for_year(container_id, year)?;
+        Ok(Self::profit_loss_csv(report, minor_units, &locale))
+    }
+
+    fn profit_loss_csv(report: ProfitLossReport, minor_units: u32, locale: &str) -> String {
+        let t = |key: &str| i18n::translate(locale, key);
+        let mut csv = format!("{}\n", t("report.profit_loss.header"));
+
+        for line in report.income {
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                t("report.income"),
+                Self::csv_escape(&line.category),
+                Self::format_units_no_decimals(line.total, minor_units)
+            ));
+        }
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            t("report.income"),
+            t("report.total_income"),
+            Self::format_units_no_decimals(report.total_income, minor_units)
+        ));
+
+        for line in report.expense {
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                t("report.expense"),
+                Self::csv_escape(&line.category),
+                Self::format_units_no_decimals(line.total, minor_units)
+            ));
+        }
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            t("report.expense"),
+            t("report.total_expense"),
+            Self::format_units_no_decimals(report.total_expense, minor_units)
+        ));
+
+        csv.push_str(&format!(
+            "{},,{}\n",
+            t("report.net_income"),
+            Self::format_units_no_decimals(report.net_income, minor_units)
+        ));
+
+        csv
+    }
+
+    /// Profit and loss or balance sheet for a month (`YYYY-MM`) or year
+    /// (`YYYY`) as a Markdown document or an embeddable HTML fragment.
+    pub fn render_report(
+        &self,
+        container_id: i64,
+        report_type: String,
+        period: String,
+        format: String,
+    ) -> Result<String> {
+        let html = match format.as_str() {
+            "md" | "markdown" => false,
+            "html" => true,
+            _ => {
+                return Err(rusqlite::Error::InvalidParameterName(
+                    "Unsupported format. Expected md or html".to_string(),
+                ))
+            }
+        };
+        let monthly = period.len() == 7;
+        let (minor_units, locale) = {
+            let conn = self.conn.lock().unwrap();
+            (Self::read_minor_units(&conn, container_id)?, Self::read_locale(&conn)?)
+        };
+        let t = |key: &str| i18n::translate(&locale, key);
+
+        let (title, subtitle, sections, footer) = match report_type.as_str() {
+            "profit_loss" => {
+                let report = if monthly {
+                    self.get_profit_and_loss_for_month(container_id, period)?
+                } else {
+                    self.get_profit_and_loss_for_year(container_id, period)?
+                };
+                let subtitle = format!(
+                    "{} {} {} {}",
+                    t("sak.period"),
+                    i18n::long_date(&locale, &report.start_date),
+                    t("sak.to"),
+                    i18n::long_date(&locale, &report.end_date)
+                );
+                let lines = |lines: Vec<ProfitLossLine>| -> Vec<(String, i64)> {
+                    lines.into_iter().map(|line| (line.category, line.total)).collect()
+                };
+                let sections = vec![
+                    (
+                        t("report.income"),
+                        lines(report.income),
+                        (t("report.total_income"), report.total_income),
+                    ),
+                    (
+                        t("report.expense"),
+                        lines(report.expense),
+                        (t("report.total_expense"), report.total_expense),
+                    ),
+                ];
+                (
+                    t("sak.profit_loss.title"),
+                    subtitle,
+                    sections,
+                    (t("report.net_income"), report.net_income),
+                )
+            }
+            "balance_sheet" => {
+                let report = if monthly {
+                    self.get_balance_sheet_for_month(container_id, period)?
+                } else {
+                    self.get_balance_sheet_for_year(container_id, period)?
+                };
+                let subtitle = format!("{} {}", t("sak.as_of"), i18n::long_date(&locale, &report.as_of));
+                let lines = |accounts: Vec<AccountBalance>| -> Vec<(String, i64)> {
+                    accounts.into_iter().map(|account| (account.name, account.balance)).collect()
+                };
+                let sections = vec![
+                    (
+                        t("report.assets"),
+                        lines(report.assets),
+                        (t("report.total_assets"), report.total_assets),
+                    ),
+                    (
+                        t("report.liabilities"),
+                        lines(report.liabilities),
+                        (t("report.total_liabilities"), report.total_liabilities),
+                    ),
+                    (
+                        t("report.equity"),
+                        lines(report.equity),
+                        (t("report.total_equity"), report.total_equity),
+                    ),
+                ];
+                (
+                    t("sak.position.title"),
+                    subtitle,
+                    sections,
+                    (
+                        t("report.total_liabilities_equity"),
+                        report.total_liabilities + report.total_equity,
+                    ),
+                )
+            }
+            _ => {
+                return Err(rusqlite::Error::InvalidParameterName(
+                    "Unsupported report type. Expected profit_loss or balance_sheet".to_string(),
+                ))
+            }
+        };
+
+        let amount = |value: i64| Self::format_units_no_decimals(value, minor_units);
+        let mut out = String::new();
+        if html {
+            let e = Self::html_escape;
+            out.push_str(&format!("<h1>{}</h1>\n<p>{}</p>\n", e(&title), e(&subtitle)));
+            for (heading, lines, (total_label, total)) in sections {
+                out.push_str(&format!(
+                    "<h2>{}</h2>\n<table>\n<thead><tr><th>{}</th><th>{}</th></tr></thead>\n<tbody>\n",
+                    e(&heading),
+                    e(&t("render.item")),
+                    e(&t("render.amount"))
+                ));
+                for (label, value) in lines {
+                    out.push_str(&format!(
+                        "<tr><td>{}</td><td style=\"text-align:right\">{}</td></tr>\n",
+                        e(&label),
+                        amount(value)
+                    ));
+                }
+                out.push_str(&format!(
+                    "<tr><th>{}</th><th style=\"text-align:right\">{}</th></tr>\n</tbody>\n</table>\n",
+                    e(&total_label),
+                    amount(total)
+                ));
+            }
+            out.push_str(&format!(
+                "<p><strong>{}: {}</strong></p>\n",
+                e(&footer.0),
+                amount(footer.1)
+            ));
+        } else {
+            let e = Self::markdown_escape;
+            out.push_str(&format!("# {}\n\n{}\n", e(&title), e(&subtitle)));
+            for (heading, lines, (total_label, total)) in sections {
+                out.push_str(&format!(
+                    "\n## {}\n\n| {} | {} |\n| --- | ---: |\n",
+                    e(&heading),
+                    e(&t("render.item")),
+                    e(&t("render.amount"))
+                ));
+                for (label, value) in lines {
+                    out.push_str(&format!("| {} | {} |\n", e(&label), amount(value)));
+                }
+                out.push_str(&format!("| **{}** | **{}** |\n", e(&total_label), amount(total)));
+            }
+            out.push_str(&format!("\n**{}: {}**\n", e(&footer.0), amount(footer.1)));
+        }
+
+        Ok(out)
+    }
+
+    /// One short paragraph describing a month's income, expenses and profit
+    /// compared with the month before, for screen readers and chat sharing.
+    pub fn get_plain_summary(&self, container_id: i64, month: String, locale: Option<String>) -> Result<String> {
+        let (start_date, end_date) = Self::month_range(&month)?;
+        let first_day = Self::parse_day(&Self::date_only(&start_date))?;
+        let previous_month = first_day
+            .pred_opt()
+            .map(|day| day.format("%Y-%m").to_string())
+            .ok_or_else(|| rusqlite::Error::InvalidParameterName("Invalid month".to_string()))?;
+        let (previous_start, previous_end) = Self::month_range(&previous_month)?;
+
+        let conn = self.conn.lock().unwrap();
+        let minor_units = Self::read_minor_units(&conn, container_id)?;
+        let locale = match locale {
+            Some(locale) if i18n::is_supported(&locale) => locale,
+            Some(locale) => {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Unsupported locale '{}'",
+                    locale
+                )))
+            }
+            None => Self::read_locale(&conn)?,
+        };
+        let current = Self::profit_and_loss_for_range(&conn, container_id, start_date, end_date)?;
+        let previous = Self::profit_and_loss_for_range(&conn, container_id, previous_start, previous_end)?;
+        drop(conn);
+
+        let t = |key: &str| i18n::translate(&locale, key);
+        let money = |amount: i64| Self::compact_money(amount, minor_units, &locale);
+        let opening = if first_day.format("%Y-%m").to_string() == chrono::Local::now().format("%Y-%m").to_string() {
+            t("summary.this_month")
+        } else {
+            format!(
+                "{} {} {}",
+                t("summary.in"),
+                t(&format!("month.{}", first_day.format("%m"))),
+                first_day.format("%Y")
+            )
+        };
+        let result = if current.net_income < 0 {
+            format!("{} {}", t("summary.loss"), money(-current.net_income))
+        } else {
+            format!("{} {}", t("summary.profit"), money(current.net_income))
+        };
+
+        let mut summary = format!(
+            "{} {} {}, {} {}, {}",
+            opening,
+            t("summary.income"),
+            money(current.total_income),
+            t("summary.expense"),
+            money(current.total_expense),
+            result
+        );
+        if previous.net_income != 0 {
+            let change = ((current.net_income - previous.net_income) as f64 / previous.net_income.abs() as f64
+                * 100.0)
+                .round() as i64;
+            let comparison = match change {
+                0 => t("summary.unchanged"),
+                c if c > 0 => format!("{} {}% {}", t("summary.up"), c, t("summary.from_last_month")),
+                c => format!("{} {}% {}", t("summary.down"), -c, t("summary.from_last_month")),
+            };
+            summary.push_str(&format!(", {}", comparison));
+        }
+        summary.push('.');
+        Ok(summary)
+    }
+
+    /// `Rp12,3 jt` / `Rp12.3M` style amounts with one decimal at most.
+    fn compact_money(amount: i64, minor_units: u32, locale: &str) -> String {
+        let units = amount as f64 / 10_i64.pow(minor_units) as f64;
+        let (value, suffix) = if units.abs() >= 1_000_000_000.0 {
+            (units / 1_000_000_000.0, i18n::translate(locale, "summary.billion"))
+        } else if units.abs() >= 1_000_000.0 {
+            (units / 1_000_000.0, i18n::translate(locale, "summary.million"))
+        } else if units.abs() >= 1_000.0 {
+            (units / 1_000.0, i18n::translate(locale, "summary.thousand"))
+        } else {
+            (units, String::new())
+        };
+        let mut number = format!("{:.1}", value);
+        if number.ends_with(".0") {
+            number.truncate(number.len() - 2);
+        }
+        if locale != "en" {
+            number = number.replace('.', ",");
+        }
+        format!("Rp{}{}", number, suffix)
+    }
+
+    /// Runs one command palette line such as `new expense 50rb makan siang`,
+    /// `open report pnl last month` or `search bensin`. Indonesian verbs
+    /// (`pengeluaran`, `pemasukan`, `laporan`, `cari`) work too.
+    pub fn execute_action(&self, container_id: i64, action: String) -> Result<ActionResult> {
+        Self::ensure_max_chars("Action", &action, Self::MAX_DESCRIPTION_CHARS)?;
+        let words: Vec<&str> = action.split_whitespace().collect();
+        let mut rest = &words[..];
+        if let Some(first) = rest.first() {
+            if matches!(
+                first.to_lowercase().as_str(),
+                "new" | "add" | "open" | "show" | "catat" | "buka" | "lihat"
+            ) {
+                rest = &rest[1..];
+            }
+        }
+        let verb = rest.first().map(|word| word.to_lowercase()).unwrap_or_default();
+        let args = rest.get(1..).unwrap_or(&[]);
+
+        match verb.as_str() {
+            "expense" | "pengeluaran" | "keluar" => self.palette_add_transaction(container_id, false, args),
+            "income" | "pemasukan" | "masuk" => self.palette_add_transaction(container_id, true, args),
+            "report" | "laporan" => self.palette_report(container_id, args),
+            "search" | "find" | "cari" => self.palette_search(container_id, &args.join(" ")),
+            _ => Err(rusqlite::Error::InvalidParameterName(format!(
+                "Unknown action: {}",
+                action.trim()
+            ))),
+        }
+    }
+
+    fn palette_add_transaction(&self, container_id: i64, income: bool, args: &[&str]) -> Result<ActionResult> {
+        let (minor_units, locale, account_id) = {
+            let conn = self.conn.lock().unwrap();
+            (
+                Self::read_minor_units(&conn, container_id)?,
+                Self::read_locale(&conn)?,
+                Self::palette_default_account(&conn, container_id)?,
+            )
+        };
+        let amount = args
+            .first()
+            .and_then(|token| Self::parse_palette_amount(token, minor_units))
+            .ok_or_else(|| rusqlite::Error::InvalidParameterName("Expected an amount, e.g. 50000 or 50rb".to_string()))?;
+        let amount = if income { amount } else { -amount };
+        let description = args.get(1..).map(|words| words.join(" ")).filter(|text| !text.is_empty());
+
+        let suggestions = match &description {
+            Some(text) => self.suggest_category(container_id, text.clone(), amount)?,
+            None => Vec::new(),
+        };
+        let suggested = {
+            let conn = self.conn.lock().unwrap();
+            let wanted = if income { "income" } else { "expense" };
+            let mut matching = None;
+            for suggestion in suggestions {
+                let category_type: Option<String> = conn
+                    .query_row(
+                        "SELECT category_type FROM categories WHERE name = ?1",
+                        [&suggestion.category],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                if category_type.as_deref() == Some(wanted) {
+                    matching = Some(suggestion.category);
+                    break;
+                }
+            }
+            matching
+        };
+        let category = suggested.unwrap_or_else(|| {
+            if income {
+                Self::DEFAULT_INCOME_CATEGORY.to_string()
+            } else {
+                Self::DEFAULT_FALLBACK_CATEGORY.to_string()
+            }
+        });
+
+        let transaction = self.add_transaction(NewTransaction {
+            amount,
+            description,
+            category: Some(category),
+            container_id,
+            account_id,
+            date: None,
+            idempotency_key: None,
+            payment_method: None,
+            notes: None,
+        })?;
+        let message = format!(
+            "{}: {} - {}",
+            i18n::translate(&locale, if income { "type.income" } else { "type.expense" }),
+            Self::format_units_no_decimals(amount.abs(), minor_units),
+            transaction.description
+        );
+        Ok(ActionResult {
+            action: "add_transaction".to_string(),
+            message,
+            data: Self::palette_json(&transaction)?,
+        })
+    }
+
+    fn palette_report(&self, container_id: i64, args: &[&str]) -> Result<ActionResult> {
+        let kind = args.first().map(|word| word.to_lowercase()).unwrap_or_default();
+        let report_type = match kind.as_str() {
+            "pnl" | "pl" | "profit" | "profit_loss" | "labarugi" | "laba-rugi" => "profit_loss",
+            "balance" | "bs" | "balance_sheet" | "neraca" => "balance_sheet",
+            _ => {
+                return Err(rusqlite::Error::InvalidParameterName(
+                    "Expected a report: pnl or balance".to_string(),
+                ))
+            }
+        };
+        let period = Self::palette_period(&args.get(1..).unwrap_or(&[]).join(" ").to_lowercase())?;
+        let locale = {
+            let conn = self.conn.lock().unwrap();
+            Self::read_locale(&conn)?
+        };
+        let monthly = period.len() == 7;
+
+        let (title, data) = if report_type == "profit_loss" {
+            let report = if monthly {
+                self.get_profit_and_loss_for_month(container_id, period.clone())?
+            } else {
+                self.get_profit_and_loss_for_year(container_id, period.clone())?
+            };
+            ("sak.profit_loss.title", Self::palette_json(&report)?)
+        } else {
+            let report = if monthly {
+                self.get_balance_sheet_for_month(container_id, period.clone())?
+            } else {
+                self.get_balance_sheet_for_year(container_id, period.clone())?
+            };
+            ("sak.position.title", Self::palette_json(&report)?)
+        };
+
+        Ok(ActionResult {
+            action: report_type.to_string(),
+            message: format!("{} {}", i18n::translate(&locale, title), period),
+            data,
+        })
+    }
+
+    fn palette_search(&self, container_id: i64, text: &str) -> Result<ActionResult> {
+        if text.trim().is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Expected text to search for".to_string(),
+            ));
+        }
+        let conn = self.conn.lock().unwrap();
+        let locale = Self::read_locale(&conn)?;
+        let filter = TransactionFilter {
+            text: Some(text.trim().to_string()),
+            ..Default::default()
+        };
+        let page = Self::query_transaction_page(&conn, container_id, &filter, None, None)?;
+        Ok(ActionResult {
+            action: "search".to_string(),
+            message: format!("{} {}", page.total, i18n::translate(&locale, "palette.matches")),
+            data: Self::palette_json(&page)?,
+        })
+    }
+
+    /// The account used most recently for income or expenses, falling back
+    /// to the first asset account of the container.
+    fn palette_default_account(conn: &Connection, container_id: i64) -> Result<i64> {
+        let recent: Option<i64> = conn
+            .query_row(
+                "SELECT t.account_id
+                 FROM transactions t
+                 JOIN accounts a ON a.id = t.account_id AND a.archived = 0
+                 WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.transfer_id IS NULL AND a.name != ?2
+                 ORDER BY t.date DESC, t.id DESC
+                 LIMIT 1",
+                params![container_id, Self::SETTLEMENT_CLEARING_ACCOUNT],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(account_id) = recent {
+            return Ok(account_id);
+        }
+        conn.query_row(
+            "SELECT id FROM accounts
+             WHERE container_id = ?1 AND account_type = 'asset' AND archived = 0 AND name != ?2
+             ORDER BY id
+             LIMIT 1",
+            params![container_id, Self::SETTLEMENT_CLEARING_ACCOUNT],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| rusqlite::Error::InvalidParameterName("Create an asset account first".to_string()))
+    }
+
+    /// Accepts `50000`, `50.000` (thousand separators), `12.5` and the
+    /// `k`/`rb` (thousand) and `jt` (million) suffixes.
+    fn parse_palette_amount(token: &str, minor_units: u32) -> Option<i64> {
+        let token = token.to_lowercase();
+        let (number, multiplier) = if let Some(number) = token.strip_suffix("jt") {
+            (number, 1_000_000.0)
+        } else if let Some(number) = token.strip_suffix("rb").or_else(|| token.strip_suffix('k')) {
+            (number, 1_000.0)
+        } else {
+            (token.as_str(), 1.0)
+        };
+        let value = Self::parse_grouped_number(number)?;
+        if value <= 0.0 {
+            return None;
+        }
+        Some((value * multiplier * 10_f64.powi(minor_units as i32)).round() as i64)
+    }
+
+    fn palette_period(text: &str) -> Result<String> {
+        let today = chrono::Local::now().date_naive();
+        let last_month = today.with_day(1).and_then(|day| day.pred_opt()).unwrap_or(today);
+        let period = match text.trim() {
+            "" | "this month" | "bulan ini" => today.format("%Y-%m").to_string(),
+            "last month" | "bulan lalu" => last_month.format("%Y-%m").to_string(),
+            "this year" | "tahun ini" => today.year().to_string(),
+            "last year" | "tahun lalu" => (today.year() - 1).to_string(),
+            other => {
+                if other.len() == 7 {
+                    Self::month_range(other)?;
+                } else {
+                    Self::year_range(other)?;
+                }
+                other.to_string()
+            }
+        };
+        Ok(period)
+    }
+
+    fn palette_json<T: Serialize>(value: &T) -> Result<serde_json::Value> {
+        serde_json::to_value(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+    }
+
+    pub fn export_balance_sheet_csv(&self, container_id: i64, year: String) -> Result<String> {
+        let (minor_units, locale) = {
+            let conn = self.conn.lock().unwrap();
+            (Self::read_minor_units(&conn, container_id)?, Self::read_locale(&conn)?)
+        };
+        let report = self.get_balance_sheet_for_year(container_id, year)?;
+        Ok(Self::balance_sheet_csv(report, minor_units, &locale))
+    }
+
+    fn balance_sheet_csv(report: BalanceSheetReport, minor_units: u32, locale: &str) -> String {
+        let t = |key: &str| i18n::translate(locale, key);
+        let mut csv = format!("{}\n", t("report.balance_sheet.header"));
+
+        for account in report.assets {
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                t("report.assets"),
+                Self::csv_escape(&account.name),
+                Self::format_units_no_decimals(account.balance, minor_units)
+            ));
+        }
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            t("report.assets"),
+            t("report.total_assets"),
+            Self::format_units_no_decimals(report.total_assets, minor_units)
+        ));
+
+        for account in report.liabilities {
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                t("report.liabilities"),
+                Self::csv_escape(&account.name),
+                Self::format_units_no_decimals(account.balance, minor_units)
+            ));
+        }
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            t("report.liabilities"),
+            t("report.total_liabilities"),
+            Self::format_units_no_decimals(report.total_liabilities, minor_units)
+        ));
+
+        for account in report.equity {
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                t("report.equity"),
+                Self::csv_escape(&account.name),
+                Self::format_units_no_decimals(account.balance, minor_units)
+            ));
+        }
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            t("report.equity"),
+            t("report.total_equity"),
+            Self::format_units_no_decimals(report.total_equity, minor_units)
+        ));
+
+        let total_liabilities_equity = report.total_liabilities + report.total_equity;
+        csv.push_str(&format!(
+            "{},,{}\n",
+            t("report.total_liabilities_equity"),
+            Self::format_units_no_decimals(total_liabilities_equity, minor_units)
+        ));
+
+        csv
+    }
+
+    pub fn export_transactions_detail_csv(&self, container_id: i64, year: String) -> Result<String> {
+        let conn = self.conn.lock().unwrap();
+        let minor_units = Self::read_minor_units(&conn, container_id)?;
+        let locale = Self::read_locale(&conn)?;
+        let date_format = Self::read_date_format(&conn)?;
+        let (start_date, end_date) = Self::year_range_last_known(&conn, container_id, &year)?;
+
+        let container_name: String = conn.query_row(
+            "SELECT name FROM containers WHERE id = ?1",
+            [container_id],
+            |row| row.get(0),
+        )?;
+
+        let mut balances: HashMap<i64, i64> = HashMap::new();
+        let mut accounts_stmt = conn.prepare(
+            "SELECT id, opening_balance FROM accounts WHERE container_id = ?1",
+        )?;
+        let account_rows = accounts_stmt.query_map([container_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in account_rows {
+            let (id, opening_balance) = row?;
+            balances.insert(id, opening_balance);
+        }
+
+        let mut opening_stmt = conn.prepare(
+            "SELECT COALESCE(account_id, 0) as account_id, COALESCE(SUM(amount), 0) as total
+             FROM transactions
+             WHERE container_id = ?1 AND date < ?2 AND deleted_at IS NULL
+             GROUP BY account_id",
+        )?;
+        let opening_rows = opening_stmt.query_map(params![container_id, &start_date], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in opening_rows {
+            let (account_id, total) = row?;
+            let entry = balances.entry(account_id).or_insert(0);
+            *entry += total;
+        }
+
+        let mut csv = format!("{}\n", i18n::translate(&locale, "report.transactions.header"));
+        let mut stmt = conn.prepare(
+            "SELECT t.amount, t.description, t.category, t.date,
+                    COALESCE(t.account_id, 0) as account_id,
+                    COALESCE(t.transfer_id, 0) as transfer_id,
+                    COALESCE(t.transfer_account_id, 0) as transfer_account_id,
+                    COALESCE(a.name, '') as account_name,
+                    COALESCE(a.account_type, '') as account_type,
+                    COALESCE(c.category_type, 'expense') as category_type,
+                    COALESCE(ta.name, '') as transfer_account_name
+             FROM transactions t
+             LEFT JOIN accounts a ON a.id = t.account_id
+             LEFT JOIN categories c ON c.name = t.category
+             LEFT JOIN accounts ta ON ta.id = t.transfer_account_id
+             WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.date >= ?2 AND t.date <= ?3
+             ORDER BY t.date ASC, t.id ASC",
+        )?;
+        let rows = stmt.query_map(params![container_id, &start_date, &end_date], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, i64>(5)?,
+                row.get::<_, i64>(6)?,
+                row.get::<_, String>(7)?,
+                row.get::<_, String>(8)?,
+                row.get::<_, String>(9)?,
+                row.get::<_, String>(10)?,
+            ))
+        })?;
+
+        for row in rows {
+            let (amount, description, category, date, account_id, transfer_id, _transfer_account_id, account_name, account_type, category_type, transfer_account_name) = row?;
+
+            let is_transfer = transfer_id != 0 || category == "Transfer";
+            let tx_type = if is_transfer {
+                i18n::translate(&locale, "type.transfer")
+            } else if category_type == "income" {
+                i18n::translate(&locale, "type.income")
+            } else {
+                i18n::translate(&locale, "type.expense")
+            };
+
+            let display_category = if is_transfer {
+                if transfer_account_name.is_empty() {
+                    i18n::translate(&locale, "type.transfer")
+                } else {
+                    transfer_account_name
+                }
+            } else {
+                category
+            };
+
+            let balance_entry = balances.entry(account_id).or_insert(0);
+            *balance_entry += amount;
+
+            let is_debit_normal = account_type == "asset" || account_type == "contra_asset" || account_type.is_empty();
+            let (debit, credit) = if is_debit_normal {
+                if amount >= 0 {
+                    (amount, 0)
+                } else {
+                    (0, -amount)
+                }
+            } else if amount >= 0 {
+                (0, amount)
+            } else {
+                (-amount, 0)
+            };
+
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{}\n",
+                Self::csv_escape(&Self::export_date(&Self::date_only(&date), &date_format)),
+                Self::csv_escape(&description),
+                Self::csv_escape(&account_name),
+                Self::csv_escape(&display_category),
+                tx_type,
+                Self::format_units_no_decimals(debit, minor_units),
+                Self::format_units_no_decimals(credit, minor_units),
+                Self::format_units_no_decimals(*balance_entry, minor_units),
+                Self::csv_escape(&container_name)
+            ));
+        }
+
+        Ok(csv)
+    }
+
+    pub fn export_reports_csv(&self, container_id: i64, year: String) -> Result<ReportsCsvExport> {
+        Ok(ReportsCsvExport {
+            profit_loss: self.export_profit_loss_csv(container_id, year.clone())?,
+            balance_sheet: self.export_balance_sheet_csv(container_id, year.clone())?,
+            transactions: self.export_transactions_detail_csv(container_id, year)?,
+        })
+    }
+
+    pub fn export_sak_emkm_csv(&self, container_id: i64, year: String) -> Result<SakEmkmExport> {
+        let conn = self.conn.lock().unwrap();
+        let minor_units = Self::read_minor_units(&conn, container_id)?;
+        let locale = i18n::DEFAULT_LOCALE;
+        let label = |key: &str| Self::csv_escape(&i18n::translate(locale, key));
+        let amount = |value: i64| Self::format_units_no_decimals(value, minor_units);
+
+        let (start_date, end_date) = Self::year_range_last_known(&conn, container_id, &year)?;
+        let container_name: String = conn.query_row(
+            "SELECT name FROM containers WHERE id = ?1",
+            [container_id],
+            |row| row.get(0),
+        )?;
+        let profit_loss =
+            Self::profit_and_loss_for_range(&conn, container_id, start_date.clone(), end_date.clone())?;
+        let mut balance_sheet =
+            Self::balance_sheet_for_range(&conn, container_id, start_date.clone(), end_date.clone())?;
+        balance_sheet.equity.sort_by_key(|account| {
+            Self::DEFAULT_EQUITY_ACCOUNTS
+                .iter()
+                .position(|name| *name == account.name)
+                .unwrap_or(Self::DEFAULT_EQUITY_ACCOUNTS.len())
+        });
+        let as_of = Self::csv_escape(&i18n::long_date(locale, &balance_sheet.as_of));
+
+        let mut position = format!(
+            "{}\n{}\n{} {}\n\n",
+            Self::csv_escape(&container_name),
+            label("sak.position.title"),
+            label("sak.as_of"),
+            as_of
+        );
+        let sections = [
+            ("sak.assets", "sak.total_assets", &balance_sheet.assets, balance_sheet.total_assets),
+            ("sak.liabilities", "sak.total_liabilities", &balance_sheet.liabilities, balance_sheet.total_liabilities),
+            ("sak.equity", "sak.total_equity", &balance_sheet.equity, balance_sheet.total_equity),
+        ];
+        for (heading, total_label, accounts, total) in sections {
+            position.push_str(&format!("{},\n", label(heading)));
+            for account in accounts.iter() {
+                position.push_str(&format!(
+                    "{},{}\n",
+                    Self::csv_escape(&account.name),
+                    amount(account.balance)
+                ));
+            }
+            position.push_str(&format!("{},{}\n\n", label(total_label), amount(total)));
+        }
+        position.push_str(&format!(
+            "{},{}\n",
+            label("sak.total_liabilities_equity"),
+            amount(balance_sheet.total_liabilities + balance_sheet.total_equity)
+        ));
+
+        let (tax_lines, expense_lines): (Vec<&ProfitLossLine>, Vec<&ProfitLossLine>) = profit_loss
+            .expense
+            .iter()
+            .partition(|line| line.category.to_lowercase().contains("pajak penghasilan"));
+        let income_tax: i64 = tax_lines.iter().map(|line| line.total).sum();
+        let operating_expense = profit_loss.total_expense - income_tax;
+        let profit_before_tax = profit_loss.total_income - operating_expense;
+
+        let mut income_statement = format!(
+            "{}\n{}\n{} {} {} {}\n\n{},\n",
+            Self::csv_escape(&container_name),
+            label("sak.profit_loss.title"),
+            label("sak.period"),
+            Self::csv_escape(&i18n::long_date(locale, &profit_loss.start_date)),
+            label("sak.to"),
+            Self::csv_escape(&i18n::long_date(locale, &profit_loss.end_date)),
+            label("sak.revenue")
+        );
+        for line in &profit_loss.income {
+            income_statement.push_str(&format!(
+                "{},{}\n",
+                Self::csv_escape(&line.category),
+                amount(line.total)
+            ));
+        }
+        income_statement.push_str(&format!(
+            "{},{}\n\n{},\n",
+            label("sak.total_revenue"),
+            amount(profit_loss.total_income),
+            label("sak.expenses")
+        ));
+        for line in expense_lines {
+            income_statement.push_str(&format!(
+                "{},{}\n",
+                Self::csv_escape(&line.category),
+                amount(line.total)
+            ));
+        }
+        income_statement.push_str(&format!(
+            "{},{}\n\n{},{}\n{},{}\n{},{}\n",
+            label("sak.total_expenses"),
+            amount(operating_expense),
+            label("sak.profit_before_tax"),
+            amount(profit_before_tax),
+            label("sak.income_tax"),
+            amount(income_tax),
+            label("sak.profit_after_tax"),
+            amount(profit_before_tax - income_tax)
+        ));
+
+        let notes = format!(
+            "{}\n{}\n{} {}\n\n{}\n{} {}.\n\n{}\n{}\n{}\n{}\n",
+            Self::csv_escape(&container_name),
+            label("sak.notes.title"),
+            label("sak.as_of"),
+            as_of,
+            label("sak.notes.general"),
+            label("sak.notes.entity"),
+            Self::csv_escape(&container_name),
+            label("sak.notes.policies"),
+            label("sak.notes.compliance"),
+            label("sak.notes.basis"),
+            label("sak.notes.currency")
+        );
+
+        Ok(SakEmkmExport {
+            balance_sheet: position,
+            profit_loss: income_statement,
+            notes,
+        })
+    }
+
+    pub fn export_loan_pack_csv(&self, container_id: i64, months: u32) -> Result<String> {
+        if months == 0 || months > Self::MAX_LOAN_PACK_MONTHS {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Months must be between 1 and {}",
+                Self::MAX_LOAN_PACK_MONTHS
+            )));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let minor_units = Self::read_minor_units(&conn, container_id)?;
+        let locale = Self::read_locale(&conn)?;
+        let date_format = Self::read_date_format(&conn)?;
+        let t = |key: &str| i18n::translate(&locale, key);
+        let month_keys = Self::trailing_months(months);
+        let (start_date, _) = Self::month_range(&month_keys[0])?;
+        let (_, end_date) = Self::month_range(&month_keys[month_keys.len() - 1])?;
+
+        let container_name: String = conn.query_row(
+            "SELECT name FROM containers WHERE id = ?1",
+            [container_id],
+            |row| row.get(0),
+        )?;
+
+        let mut csv = format!(
+            "{},{}\n{},{},{}\n\n",
+            t("loan_pack.title"),
+            Self::csv_escape(&container_name),
+            t("loan_pack.period"),
+            Self::export_date(&Self::date_only(&start_date), &date_format),
+            Self::export_date(&Self::date_only(&end_date), &date_format)
+        );
+
+        let profit_loss =
+            Self::profit_and_loss_for_range(&conn, container_id, start_date.clone(), end_date.clone())?;
+        csv.push_str(&format!("{}\n", t("loan_pack.profit_loss")));
+        csv.push_str(&Self::profit_loss_csv(profit_loss, minor_units, &locale));
+
+        let balance_sheet =
+            Self::balance_sheet_for_range(&conn, container_id, start_date.clone(), end_date.clone())?;
+        csv.push_str(&format!("\n{}\n", t("loan_pack.balance_sheet")));
+        csv.push_str(&Self::balance_sheet_csv(balance_sheet, minor_units, &locale));
+
+        csv.push_str(&format!(
+            "\n{}\n{}\n",
+            t("loan_pack.cash_flow"),
+            t("loan_pack.cash_flow.header")
+        ));
+        let mut cash_stmt = conn.prepare(
+            "SELECT COALESCE(SUM(CASE WHEN t.amount > 0 THEN t.amount ELSE 0 END), 0),
+                    COALESCE(SUM(CASE WHEN t.amount < 0 THEN -t.amount ELSE 0 END), 0)
+             FROM transactions t
+             JOIN accounts a ON a.id = t.account_id AND a.account_type = 'asset'
+             LEFT JOIN accounts ta ON ta.id = t.transfer_account_id
+             WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.date >= ?2 AND t.date <= ?3
+               AND NOT (t.transfer_id IS NOT NULL AND COALESCE(ta.account_type, '') = 'asset')",
+        )?;
+        for month in &month_keys {
+            let (month_start, month_end) = Self::month_range(month)?;
+            let (cash_in, cash_out): (i64, i64) = cash_stmt.query_row(
+                params![container_id, &month_start, &month_end],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                month,
+                Self::format_units_no_decimals(cash_in, minor_units),
+                Self::format_units_no_decimals(cash_out, minor_units),
+                Self::format_units_no_decimals(cash_in - cash_out, minor_units)
+            ));
+        }
+
+        csv.push_str(&format!(
+            "\n{}\n{}\n",
+            t("loan_pack.trend"),
+            t("loan_pack.trend.header")
+        ));
+        for month in &month_keys {
+            let (month_start, month_end) = Self::month_range(month)?;
+            let report = Self::profit_and_loss_for_range(&conn, container_id, month_start, month_end)?;
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                month,
+                Self::format_units_no_decimals(report.total_income, minor_units),
+                Self::format_units_no_decimals(report.total_expense, minor_units),
+                Self::format_units_no_decimals(report.net_income, minor_units)
+            ));
+        }
+
+        Ok(csv)
+    }
+
+    /// Writes one container into a new SQLite file at `path`. The file is
+    /// built next to the destination and renamed over it once complete, so a
+    /// failed export leaves any existing file alone.
+    pub fn export_container_sqlite(&self, container_id: i64, path: &str) -> Result<()> {
+        let io_error = |e: std::io::Error| rusqlite::Error::InvalidParameterName(e.to_string());
+        let target = Path::new(path);
+        if self.is_database_file(target) {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Cannot export over the open database".to_string(),
+            ));
+        }
+        let temp_path = Self::sidecar_path(target, ".exporting");
+        if temp_path.exists() {
+            std::fs::remove_file(&temp_path).map_err(io_error)?;
+        }
+
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT id FROM containers WHERE id = ?1", [container_id], |row| {
+            row.get::<_, i64>(0)
+        })?;
+
+        conn.execute("ATTACH DATABASE ?1 AS export", [temp_path.to_string_lossy()])?;
+        let result = Self::copy_container_rows(&conn, container_id);
+        conn.execute("DETACH DATABASE export", [])?;
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(e);
+        }
+        std::fs::rename(&temp_path, target).map_err(io_error)?;
+        Ok(())
+    }
+
+    /// Whether `path` resolves to the open database or one of its sidecar
+    /// files. Paths that do not exist yet are resolved through their folder.
+    fn is_database_file(&self, path: &Path) -> bool {
+        let resolve = |path: &Path| {
+            path.canonicalize().ok().or_else(|| {
+                let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+                Some(parent.canonicalize().ok()?.join(path.file_name()?))
+            })
+        };
+        let Some(target) = resolve(path) else {
+            return false;
+        };
+        ["", "-wal", "-shm", "-journal", ".lock"]
+            .iter()
+            .any(|suffix| resolve(&Self::sidecar_path(&self.db_path, suffix)).as_ref() == Some(&target))
+    }
+
+    fn copy_container_rows(conn: &Connection, container_id: i64) -> Result<()> {
+        let container_transactions = "SELECT id FROM transactions WHERE container_id = ?1 AND deleted_at IS NULL";
+        let container_accounts = "SELECT id FROM accounts WHERE container_id = ?1";
+        let tables = [
+            ("containers", Some("id = ?1".to_string())),
+            ("categories", None),
+            ("accounts", Some("container_id = ?1".to_string())),
+            ("transactions", Some("container_id = ?1 AND deleted_at IS NULL".to_string())),
+            ("custom_fields", Some("container_id = ?1".to_string())),
+            ("transaction_custom_values", Some(format!("transaction_id IN ({})", container_transactions))),
+            ("transaction_comments", Some(format!("transaction_id IN ({})", container_transactions))),
+            ("transaction_versions", Some(format!("transaction_id IN ({})", container_transactions))),
+            ("transaction_locations", Some(format!("transaction_id IN ({})", container_transactions))),
+            ("tags", Some("container_id = ?1".to_string())),
+            ("transaction_tags", Some(format!("transaction_id IN ({})", container_transactions))),
+            ("saved_filters", Some("container_id = ?1".to_string())),
+            ("closed_years", Some("container_id = ?1".to_string())),
+            ("statement_imports", Some(format!("account_id IN ({})", container_accounts))),
+            ("statement_lines", Some(format!("account_id IN ({})", container_accounts))),
+        ];
+
+        let tx = conn.unchecked_transaction()?;
+        for (table, condition) in tables.iter() {
+            let schema: String = tx.query_row(
+                "SELECT sql FROM main.sqlite_master WHERE type = 'table' AND name = ?1",
+                [table],
+                |row| row.get(0),
+            )?;
+            let columns = &schema[schema.find('(').unwrap_or(0)..];
+            tx.execute(&format!("DROP TABLE IF EXISTS export.{}", table), [])?;
+            tx.execute(&format!("CREATE TABLE export.{} {}", table, columns), [])?;
+            match condition {
+                Some(condition) => tx.execute(
+                    &format!("INSERT INTO export.{table} SELECT * FROM main.{table} WHERE {condition}"),
+                    [container_id],
+                )?,
+                None => tx.execute(&format!("INSERT INTO export.{table} SELECT * FROM main.{table}"), [])?,
+            };
+        }
+        tx.commit()
+    }
+
+    pub fn delete_transaction(&self, id: i64) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let tx = conn.transaction()?;
+        let (container_id, changes) = Self::trash_transaction(&tx, id, &now)?;
+        Self::record_undo(&tx, container_id, "delete_transaction", changes)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Deletes many transactions in one SQL transaction. Rows that cannot be
+    /// deleted are reported and skipped; the rest are committed together and
+    /// undone as one step. Deleting either leg of a transfer removes both.
+    pub fn delete_transactions(&self, ids: Vec<i64>) -> Result<Vec<BulkDeleteResult>> {
+        let mut conn = self.conn.lock().unwrap();
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let tx = conn.transaction()?;
+        let mut changes_by_container: std::collections::BTreeMap<i64, Vec<UndoChange>> =
+            std::collections::BTreeMap::new();
+        let mut results = Vec::with_capacity(ids.len());
+        for id in ids {
+            let already_deleted = changes_by_container
+                .values()
+                .flatten()
+                .any(|change| change.id == id);
+            if already_deleted {
+                results.push(BulkDeleteResult {
+                    id,
+                    deleted: true,
+                    error: None,
+                });
+                continue;
+            }
+            match Self::trash_transaction(&tx, id, &now) {
+                Ok((container_id, changes)) => {
+                    changes_by_container.entry(container_id).or_default().extend(changes);
+                    results.push(BulkDeleteResult {
+                        id,
+                        deleted: true,
+                        error: None,
+                    });
+                }
+                Err(rusqlite::Error::QueryReturnedNoRows) => results.push(BulkDeleteResult {
+                    id,
+                    deleted: false,
+                    error: Some("Transaction not found".to_string()),
+                }),
+                Err(e @ rusqlite::Error::InvalidParameterName(_)) => results.push(BulkDeleteResult {
+                    id,
+                    deleted: false,
+                    error: Some(e.to_string()),
+                }),
+                Err(e) => return Err(e),
+            }
+        }
+        for (container_id, changes) in changes_by_container {
+            Self::record_undo(&tx, container_id, "delete_transactions", changes)?;
+        }
+        tx.commit()?;
+        Ok(results)
+    }
+
+    /// Moves a transaction, and the other leg when it is a transfer, to the
+    /// trash. Returns the container and the undo changes for the caller to
+    /// record.
+    fn trash_transaction(conn: &Connection, id: i64, now: &str) -> Result<(i64, Vec<UndoChange>)> {
+        let (transfer_id, container_id, date): (i64, i64, String) = conn.query_row(
+            "SELECT COALESCE(transfer_id, 0), container_id, date FROM transactions WHERE id = ?1 AND deleted_at IS NULL",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        let year: i32 = date.get(..4).and_then(|y| y.parse().ok()).unwrap_or(0);
+        if Self::is_year_closed(conn, container_id, year)? {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Fiscal year {} is closed",
+                year
+            )));
+        }
+
+        // Deleting only moves the row to the trash; purge_trash removes it
+        // for good.
+        let ids = if transfer_id != 0 {
+            Self::undo_ids(conn, "transactions", "transfer_id", transfer_id)?
+        } else {
+            vec![id]
+        };
+        for id in &ids {
+            if Self::is_transaction_reconciled(conn, *id)? {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Transaction {} is reconciled",
+                    id
+                )));
+            }
+        }
+        let changes = Self::undo_capture(conn, "transactions", &ids)?;
+        if transfer_id != 0 {
+            conn.execute(
+                "UPDATE transactions SET deleted_at = ?1 WHERE transfer_id = ?2 AND deleted_at IS NULL",
+                params![now, transfer_id],
+            )?;
+        } else {
+            conn.execute(
+                "UPDATE transactions SET deleted_at = ?1 WHERE id = ?2",
+                params![now, id],
+            )?;
+        }
+        Ok((container_id, changes))
+    }
+
+    pub fn get_trashed_transactions(&self, container_id: i64) -> Result<Vec<TrashedTransaction>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, deleted_at FROM transactions
+             WHERE container_id = ?1 AND deleted_at IS NOT NULL
+             ORDER BY deleted_at DESC, id DESC",
+            Self::TRANSACTION_COLUMNS
+        ))?;
+        let rows = stmt.query_map([container_id], |row| {
+            Ok(TrashedTransaction {
+                transaction: Self::transaction_from_row(row)?,
+                deleted_at: row.get(13)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn restore_transaction(&self, id: i64) -> Result<Transaction> {
+        let conn = self.conn.lock().unwrap();
+        let (transfer_id, container_id, date): (i64, i64, String) = conn.query_row(
+            "SELECT COALESCE(transfer_id, 0), container_id, date FROM transactions WHERE id = ?1 AND deleted_at IS NOT NULL",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        let year: i32 = date.get(..4).and_then(|y| y.parse().ok()).unwrap_or(0);
+        if Self::is_year_closed(&conn, container_id, year)? {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Fiscal year {} is closed",
+                year
+            )));
+        }
+
+        if transfer_id != 0 {
+            conn.execute(
+                "UPDATE transactions SET deleted_at = NULL WHERE transfer_id = ?1",
+                [transfer_id],
+            )?;
+        } else {
+            conn.execute("UPDATE transactions SET deleted_at = NULL WHERE id = ?1", [id])?;
+        }
+        Self::fetch_transaction(&conn, id)
+    }
+
+    pub fn purge_trash(&self, container_id: i64, older_than_days: Option<i64>) -> Result<usize> {
+        if older_than_days.is_some_and(|days| days < 0) {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Days must not be negative".to_string(),
+            ));
+        }
+        let conn = self.conn.lock().unwrap();
+        let cutoff = older_than_days.map(|days| {
+            (chrono::Local::now() - chrono::Duration::days(days))
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        });
+        conn.execute(
+            "DELETE FROM transactions
+             WHERE container_id = ?1 AND deleted_at IS NOT NULL
+               AND (?2 IS NULL OR deleted_at <= ?2)",
+            params![container_id, cutoff],
+        )
+    }
+
+    /// Reverts the newest operation in the container's undo log. Returns
+    /// `None` when there is nothing left to undo.
+    pub fn undo_last(&self, container_id: i64) -> Result<Option<UndoEntry>> {
+        self.step_undo_log(container_id, true)
+    }
+
+    /// Re-applies the operation most recently undone. Any new change clears
+    /// what could be redone.
+    pub fn redo_last(&self, container_id: i64) -> Result<Option<UndoEntry>> {
+        self.step_undo_log(container_id, false)
+    }
+
+    fn step_undo_log(&self, container_id: i64, undo: bool) -> Result<Option<UndoEntry>> {
+        let mut conn = self.conn.lock().unwrap();
+        let query = if undo {
+            "SELECT id, container_id, label, created_at, changes_json FROM undo_log
+             WHERE container_id = ?1 AND undone = 0 ORDER BY id DESC LIMIT 1"
+        } else {
+            "SELECT id, container_id, label, created_at, changes_json FROM undo_log
+             WHERE container_id = ?1 AND undone = 1 ORDER BY id ASC LIMIT 1"
+        };
+        let found = conn
+            .query_row(query, [container_id], |row| {
+                Ok((
+                    UndoEntry {
+                        id: row.get(0)?,
+                        container_id: row.get(1)?,
+                        label: row.get(2)?,
+                        created_at: row.get(3)?,
+                    },
+                    row.get::<_, String>(4)?,
+                ))
+            })
+            .optional()?;
+        let Some((entry, changes_json)) = found else {
+            return Ok(None);
+        };
+        let changes: Vec<UndoChange> = serde_json::from_str(&changes_json).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(e))
+        })?;
+
+        let tx = conn.transaction()?;
+        let ordered: Vec<&UndoChange> = if undo {
+            changes.iter().rev().collect()
+        } else {
+            changes.iter().collect()
+        };
+        for change in &ordered {
+            let (expected, target) = if undo {
+                (&change.after, &change.before)
+            } else {
+                (&change.before, &change.after)
+            };
+            let current = Self::undo_snapshot(&tx, &change.table, change.id)?;
+            if change.table == "transactions" && current.is_some() {
+                // Mirrors reconciled_transactions_before_update, which cannot
+                // see rows being deleted.
+                let locked_unchanged = target.as_ref().is_some_and(|target| {
+                    ["amount", "date", "account_id", "status", "deleted_at"].iter().all(|column| {
+                        current.as_ref().and_then(|row| row.get(*column)) == target.get(*column)
+                    })
+                });
+                if !locked_unchanged && Self::is_transaction_reconciled(&tx, change.id)? {
+                    return Err(rusqlite::Error::InvalidParameterName(format!(
+                        "Cannot {} {}: transaction {} is reconciled",
+                        if undo { "undo" } else { "redo" },
+                        entry.label,
+                        change.id
+                    )));
+                }
+            }
+            if current != *expected {
+                // Something else edited these rows since; replaying the entry
+                // would overwrite that edit, so it is dropped instead.
+                drop(tx);
+                conn.execute("DELETE FROM undo_log WHERE id = ?1", [entry.id])?;
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Cannot {} {}: the data has changed since",
+                    if undo { "undo" } else { "redo" },
+                    entry.label
+                )));
+            }
+            if change.table == "transactions" {
+                for row in [current.as_ref(), target.as_ref()].into_iter().flatten() {
+                    let date = row.get("date").and_then(|v| v.as_str()).unwrap_or_default();
+                    let year: i32 = date.get(..4).and_then(|y| y.parse().ok()).unwrap_or(0);
+                    let row_container = row.get("container_id").and_then(|v| v.as_i64()).unwrap_or(container_id);
+                    if Self::is_year_closed(&tx, row_container, year)? {
+                        return Err(rusqlite::Error::InvalidParameterName(format!(
+                            "Fiscal year {} is closed",
+                            year
+                        )));
+                    }
+                }
+            }
+        }
+        for change in ordered {
+            let target = if undo { &change.before } else { &change.after };
+            Self::write_undo_row(&tx, &change.table, change.id, target.as_ref())?;
+        }
+        tx.execute(
+            "UPDATE undo_log SET undone = ?1 WHERE id = ?2",
+            params![undo, entry.id],
+        )?;
+        tx.commit()?;
+        Ok(Some(entry))
+    }
+
+    /// Column that identifies a row in the undo log. Zakat and petty cash
+    /// settings have one row per account, fund links one per transaction, and
+    /// none of them has an id of its own.
+    fn undo_key(table: &str) -> &'static str {
+        match table {
+            "zakat_accounts" | "petty_cash" => "account_id",
+            "transaction_funds" => "transaction_id",
+            _ => "id",
+        }
+    }
+
+    fn undo_ids(conn: &Connection, table: &str, column: &str, value: i64) -> Result<Vec<i64>> {
+        let key = Self::undo_key(table);
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM {} WHERE {} = ?1 ORDER BY {}", key, table, column, key))?;
+        let rows = stmt.query_map([value], |row| row.get::<_, i64>(0))?;
+        rows.collect()
+    }
+
+    fn undo_snapshot(
+        conn: &Connection,
+        table: &str,
+        id: i64,
+    ) -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+        let mut stmt = conn.prepare(&format!("SELECT * FROM {} WHERE {} = ?1", table, Self::undo_key(table)))?;
+        let names: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        stmt.query_row([id], |row| {
+            let mut values = serde_json::Map::new();
+            for (index, name) in names.iter().enumerate() {
+                values.insert(name.clone(), Self::json_value(row.get_ref(index)?));
+            }
+            Ok(values)
+        })
+        .optional()
+    }
+
+    /// Rows created by the operation about to be recorded.
+    fn undo_new(table: &str, ids: &[i64]) -> Vec<UndoChange> {
+        ids.iter()
+            .map(|&id| UndoChange {
+                table: table.to_string(),
+                id,
+                before: None,
+                after: None,
+            })
+            .collect()
+    }
+
+    /// Rows as they are now, before the operation changes them.
+    fn undo_capture(conn: &Connection, table: &str, ids: &[i64]) -> Result<Vec<UndoChange>> {
+        ids.iter()
+            .map(|&id| {
+                Ok(UndoChange {
+                    table: table.to_string(),
+                    id,
+                    before: Self::undo_snapshot(conn, table, id)?,
+                    after: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Fills in the after side of each change, stores the entry, clears the
+    /// redo stack and trims the log to `MAX_UNDO_ENTRIES` per container.
+    fn record_undo(conn: &Connection, container_id: i64, label: &str, mut changes: Vec<UndoChange>) -> Result<()> {
+        for change in &mut changes {
+            change.after = Self::undo_snapshot(conn, &change.table, change.id)?;
+        }
+        let changes_json =
+            serde_json::to_string(&changes).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "DELETE FROM undo_log WHERE container_id = ?1 AND undone = 1",
+            [container_id],
+        )?;
+        conn.execute(
+            "INSERT INTO undo_log (container_id, label, changes_json, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![container_id, label, &changes_json, &now],
+        )?;
+        conn.execute(
+            "DELETE FROM undo_log
+             WHERE container_id = ?1
+               AND id NOT IN (SELECT id FROM undo_log WHERE container_id = ?1 ORDER BY id DESC LIMIT ?2)",
+            params![container_id, Self::MAX_UNDO_ENTRIES],
+        )?;
+        Ok(())
+    }
+
+    fn write_undo_row(
+        conn: &Connection,
+        table: &str,
+        id: i64,
+        row: Option<&serde_json::Map<String, serde_json::Value>>,
+    ) -> Result<()> {
+        let key = Self::undo_key(table);
+        let Some(row) = row else {
+            conn.execute(&format!("DELETE FROM {} WHERE {} = ?1", table, key), [id])?;
+            return Ok(());
+        };
+        let columns: Vec<&String> = row.keys().collect();
+        let mut values: Vec<Value> = row.values().map(Self::sql_value).collect();
+        let exists = Self::undo_snapshot(conn, table, id)?.is_some();
+        let sql = if exists {
+            let assignments: Vec<String> = columns
+                .iter()
+                .enumerate()
+                .map(|(index, column)| format!("\"{}\" = ?{}", column, index + 1))
+                .collect();
+            values.push(Value::Integer(id));
+            format!("UPDATE {} SET {} WHERE {} = ?{}", table, assignments.join(", "), key, values.len())
+        } else {
+            let names: Vec<String> = columns.iter().map(|column| format!("\"{}\"", column)).collect();
+            let placeholders = vec!["?"; columns.len()].join(", ");
+            format!("INSERT INTO {} ({}) VALUES ({})", table, names.join(", "), placeholders)
+        };
+        conn.execute(&sql, params_from_iter(values))?;
+        Ok(())
+    }
+
+    pub fn get_category_totals(&self, container_id: i64) -> Result<Vec<(String, i64)>> {
+        let conn = self.conn.lock().unwrap();
+        let current_month = chrono::Local::now().format("%Y-%m").to_string();
+        
+        let mut stmt = conn.prepare(
+            "SELECT t.category, SUM(ABS(t.amount)) as total 
+             FROM transactions t
+             LEFT JOIN categories c ON c.name = t.category
+             WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.date LIKE ?2 AND t.transfer_id IS NULL
+               AND COALESCE(c.category_type, 'expense') = 'expense'
+             GROUP BY t.category 
+             ORDER BY total DESC"
+        )?;
+        
+        let results = stmt.query_map([&container_id.to_string(), &format!("{}%", current_month)], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        
+        results.collect()
+    }
+
+    pub fn get_categories(&self) -> Result<Vec<Category>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT name, category_type, is_default FROM categories WHERE archived = 0
+             ORDER BY sort_order IS NULL, sort_order ASC, is_default DESC, name ASC",
+        )?;
+        
+        let categories = stmt.query_map([], |row| {
+            Ok(Category {
+                name: row.get(0)?,
+                category_type: row.get(1)?,
+                is_default: row.get::<_, i64>(2)? == 1,
+            })
+        })?;
+        categories.collect()
+    }
+
+    pub fn get_category_balances(&self, container_id: i64) -> Result<Vec<CategoryBalance>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT c.name, c.category_type, c.is_default,
+                    COALESCE(SUM(t.amount), 0) as balance
+             FROM categories c
+             LEFT JOIN transactions t
+               ON t.category = c.name
+              AND t.container_id = ?1
+              AND (t.transfer_id IS NULL OR t.transfer_id = 0)
+              AND t.deleted_at IS NULL
+             WHERE c.archived = 0
+             GROUP BY c.name, c.category_type, c.is_default
+             ORDER BY c.is_default DESC, c.name ASC",
+        )?;
+
+        let rows = stmt.query_map([container_id], |row| {
+            Ok(CategoryBalance {
+                name: row.get(0)?,
+                category_type: row.get(1)?,
+                is_default: row.get::<_, i64>(2)? == 1,
+                balance: row.get(3)?,
+            })
+        })?;
+
+        rows.collect()
+    }
+
+    pub fn suggest_category(
+        &self,
+        container_id: i64,
+        description: String,
+        amount: i64,
+    ) -> Result<Vec<CategorySuggestion>> {
+        let conn = self.conn.lock().unwrap();
+        let tokens = Self::category_tokens(&description, amount);
+        if tokens.len() <= 1 {
+            return Ok(Vec::new());
+        }
+
+        let mut examples: Vec<(String, i64, String, f64)> = Vec::new();
+        {
+            let mut stmt = conn.prepare(
+                "SELECT description, amount, category FROM transactions
+                 WHERE container_id = ?1 AND transfer_id IS NULL AND deleted_at IS NULL",
+            )?;
+            let rows = stmt.query_map([container_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, 1.0))
+            })?;
+            for row in rows {
+                examples.push(row?);
+            }
+        }
+        {
+            let mut stmt = conn.prepare(
+                "SELECT description, amount, category FROM category_corrections WHERE container_id = ?1",
+            )?;
+            let rows = stmt.query_map([container_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, Self::CORRECTION_WEIGHT))
+            })?;
+            for row in rows {
+                examples.push(row?);
+            }
+        }
+
+        // Multinomial naive Bayes with Laplace smoothing over description words.
+        let mut documents: HashMap<String, f64> = HashMap::new();
+        let mut token_counts: HashMap<String, HashMap<String, f64>> = HashMap::new();
+        let mut token_totals: HashMap<String, f64> = HashMap::new();
+        let mut vocabulary: std::collections::HashSet<String> = std::collections::HashSet::new();
+        let mut total_documents = 0.0;
+        for (text, value, category, weight) in examples {
+            *documents.entry(category.clone()).or_insert(0.0) += weight;
+            total_documents += weight;
+            let counts = token_counts.entry(category.clone()).or_default();
+            for token in Self::category_tokens(&text, value) {
+                *counts.entry(token.clone()).or_insert(0.0) += weight;
+                *token_totals.entry(category.clone()).or_insert(0.0) += weight;
+                vocabulary.insert(token);
+            }
+        }
+        if total_documents == 0.0 {
+            return Ok(Vec::new());
+        }
+
+        let vocabulary_size = vocabulary.len() as f64;
+        let mut scores: Vec<(String, f64)> = documents
+            .iter()
+            .map(|(category, count)| {
+                let counts = &token_counts[category];
+                let total = token_totals.get(category).copied().unwrap_or(0.0);
+                let mut score = (count / total_documents).ln();
+                for token in &tokens {
+                    let occurrences = counts.get(token).copied().unwrap_or(0.0);
+                    score += ((occurrences + 1.0) / (total + vocabulary_size)).ln();
+                }
+                (category.clone(), score)
+            })
+            .collect();
+
+        let best = scores.iter().map(|(_, score)| *score).fold(f64::NEG_INFINITY, f64::max);
+        let normalizer: f64 = scores.iter().map(|(_, score)| (score - best).exp()).sum();
+        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        Ok(scores
+            .into_iter()
+            .take(Self::MAX_CATEGORY_SUGGESTIONS)
+            .map(|(category, score)| CategorySuggestion {
+                category,
+                confidence: (score - best).exp() / normalizer,
+            })
+            .collect())
+    }
+
+    pub fn record_category_correction(
+        &self,
+        container_id: i64,
+        description: String,
+        amount: i64,
+        category: String,
+    ) -> Result<()> {
+        let description = description.trim().to_string();
+        let category = category.trim().to_string();
+        if description.is_empty() || category.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Description and category are required".to_string(),
+            ));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO category_corrections (container_id, description, amount, category, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![container_id, &description, amount, &category, &now],
+        )?;
+        Ok(())
+    }
+
+    /// Serializes the container's setup as a `ConfigurationPack`. Account
+    /// filters are dropped from saved filters and recurring templates refer
+    /// to their account by name, since ids do not carry over.
+    pub fn export_configuration_pack(&self, container_id: i64) -> Result<String> {
+        let conn = self.conn.lock().unwrap();
+        let accounts = {
+            let mut stmt = conn.prepare(
+                "SELECT name, account_type FROM accounts
+                 WHERE container_id = ?1 AND archived = 0
+                 ORDER BY id",
+            )?;
+            let rows = stmt.query_map([container_id], |row| {
+                Ok(PackAccount {
+                    name: row.get(0)?,
+                    account_type: row.get(1)?,
+                })
+            })?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+        let categories = {
+            let mut stmt = conn.prepare(
+                "SELECT name, category_type, is_fee FROM categories WHERE name != 'Transfer' ORDER BY name ASC",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok(PackCategory {
+                    name: row.get(0)?,
+                    category_type: row.get(1)?,
+                    is_fee: row.get(2)?,
+                })
+            })?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+        let category_rules = {
+            let mut stmt = conn.prepare(
+                "SELECT description, amount, category FROM category_corrections
+                 WHERE container_id = ?1
+                 GROUP BY description, amount, category
+                 ORDER BY MIN(id)",
+            )?;
+            let rows = stmt.query_map([container_id], |row| {
+                Ok(PackCategoryRule {
+                    description: row.get(0)?,
+                    amount: row.get(1)?,
+                    category: row.get(2)?,
+                })
+            })?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+        let custom_fields = {
+            let mut stmt =
+                conn.prepare("SELECT name, field_type FROM custom_fields WHERE container_id = ?1 ORDER BY id")?;
+            let rows = stmt.query_map([container_id], |row| {
+                Ok(PackCustomField {
+                    name: row.get(0)?,
+                    field_type: row.get(1)?,
+                })
+            })?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+        let saved_filters = {
+            let mut stmt =
+                conn.prepare("SELECT name, filter_json FROM saved_filters WHERE container_id = ?1 ORDER BY id")?;
+            let rows = stmt.query_map([container_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            rows.map(|row| {
+                let (name, filter_json) = row?;
+                let mut filter = Self::decode_filter(&filter_json)?;
+                filter.account_id = None;
+                Ok(PackSavedFilter { name, filter })
+            })
+            .collect::<Result<Vec<_>>>()?
+        };
+        let recurring_templates = {
+            let mut stmt = conn.prepare(
+                "SELECT a.name, r.amount, r.description, r.category, r.frequency, r.interval_count
+                 FROM recurring_rules r
+                 JOIN accounts a ON a.id = r.account_id
+                 WHERE r.container_id = ?1
+                 ORDER BY r.id",
+            )?;
+            let rows = stmt.query_map([container_id], |row| {
+                Ok(PackRecurringTemplate {
+                    account_name: row.get(0)?,
+                    amount: row.get(1)?,
+                    description: row.get(2)?,
+                    category: row.get(3)?,
+                    frequency: row.get(4)?,
+                    interval: row.get(5)?,
+                })
+            })?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+
+        let pack = ConfigurationPack {
+            version: Self::CONFIGURATION_PACK_VERSION,
+            name: None,
+            description: None,
+            exported_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            accounts,
+            categories,
+            category_rules,
+            custom_fields,
+            saved_filters,
+            recurring_templates,
+        };
+        serde_json::to_string_pretty(&pack).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+    }
+
+    /// Adds whatever the pack has that the container lacks; existing
+    /// categories, fields and filters with the same name are kept as they
+    /// are. Recurring templates are created paused, starting today, on the
+    /// account with the same name.
+    pub fn import_configuration_pack(&self, container_id: i64, json: String) -> Result<PackImportResult> {
+        let pack: ConfigurationPack = serde_json::from_str(&json)
+            .map_err(|e| rusqlite::Error::InvalidParameterName(format!("Invalid configuration pack: {}", e)))?;
+        if pack.version == 0 || pack.version > Self::CONFIGURATION_PACK_VERSION {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Configuration pack version {} is not supported; expected 1 to {}",
+                pack.version,
+                Self::CONFIGURATION_PACK_VERSION
+            )));
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT id FROM containers WHERE id = ?1", [container_id], |row| {
+            row.get::<_, i64>(0)
+        })?;
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let mut result = PackImportResult {
+            pack_name: pack.name.clone(),
+            ..Default::default()
+        };
+        let tx = conn.transaction()?;
+
+        for account in &pack.accounts {
+            let name = account.name.trim();
+            let account_type = account.account_type.trim().to_lowercase();
+            if name.is_empty()
+                || name.chars().count() > Self::MAX_NAME_CHARS
+                || !Self::ACCOUNT_TYPES.contains(&account_type.as_str())
+            {
+                result.skipped.push(format!("Account '{}': invalid name or type", account.name));
+                continue;
+            }
+            result.accounts_added += tx.execute(
+                "INSERT INTO accounts (name, account_type, opening_balance, container_id, created_at)
+                 SELECT ?1, ?2, 0, ?3, ?4
+                 WHERE NOT EXISTS (SELECT 1 FROM accounts WHERE container_id = ?3 AND name = ?1)",
+                params![name, &account_type, container_id, &now],
+            )?;
+        }
+
+        for category in &pack.categories {
+            let name = category.name.trim();
+            if name.is_empty() || name.chars().count() > Self::MAX_NAME_CHARS {
+                result.skipped.push(format!("Category '{}': invalid name", category.name));
+                continue;
+            }
+            result.categories_added += tx.execute(
+                "INSERT OR IGNORE INTO categories (name, category_type, is_default, is_fee) VALUES (?1, ?2, 0, ?3)",
+                params![name, category.category_type.trim(), category.is_fee],
+            )?;
+        }
+
+        for rule in &pack.category_rules {
+            let description = rule.description.trim();
+            let category = rule.category.trim();
+            if description.is_empty() || category.is_empty() {
+                result.skipped.push("Category rule without description or category".to_string());
+                continue;
+            }
+            result.category_rules_added += tx.execute(
+                "INSERT INTO category_corrections (container_id, description, amount, category, created_at)
+                 SELECT ?1, ?2, ?3, ?4, ?5
+                 WHERE NOT EXISTS (
+                     SELECT 1 FROM category_corrections
+                     WHERE container_id = ?1 AND description = ?2 AND amount = ?3 AND category = ?4
+                 )",
+                params![container_id, description, rule.amount, category, &now],
+            )?;
+        }
+
+        for field in &pack.custom_fields {
+            let name = field.name.trim();
+            let field_type = field.field_type.trim().to_lowercase();
+            if name.is_empty() || !Self::CUSTOM_FIELD_TYPES.contains(&field_type.as_str()) {
+                result.skipped.push(format!("Custom field '{}': invalid name or type", field.name));
+                continue;
+            }
+            result.custom_fields_added += tx.execute(
+                "INSERT OR IGNORE INTO custom_fields (container_id, name, field_type, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![container_id, name, &field_type, &now],
+            )?;
+        }
+
+        for saved in &pack.saved_filters {
+            let name = saved.name.trim();
+            if name.is_empty() || Self::validate_filter(&saved.filter).is_err() {
+                result.skipped.push(format!("Saved filter '{}': invalid filter", saved.name));
+                continue;
+            }
+            result.saved_filters_added += tx.execute(
+                "INSERT OR IGNORE INTO saved_filters (container_id, name, filter_json, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![container_id, name, Self::encode_filter(&saved.filter)?, &now],
+            )?;
+        }
+
+        for template in &pack.recurring_templates {
+            let frequency = template.frequency.trim().to_lowercase();
+            if template.amount == 0
+                || template.interval == 0
+                || template.description.trim().is_empty()
+                || !Self::RECURRING_FREQUENCIES.contains(&frequency.as_str())
+            {
+                result.skipped.push(format!("Recurring template '{}': invalid schedule", template.description));
+                continue;
+            }
+            let account_id: Option<i64> = tx
+                .query_row(
+                    "SELECT id FROM accounts WHERE container_id = ?1 AND name = ?2",
+                    params![container_id, template.account_name.trim()],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let Some(account_id) = account_id else {
+                result.skipped.push(format!(
+                    "Recurring template '{}': no account named '{}'",
+                    template.description, template.account_name
+                ));
+                continue;
+            };
+            tx.execute(
+                "INSERT INTO recurring_rules
+                    (container_id, account_id, amount, description, category, frequency, interval_count,
+                     start_date, next_due, active, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8, 0, ?9)",
+                params![
+                    container_id,
+                    account_id,
+                    template.amount,
+                    template.description.trim(),
+                    template.category.trim(),
+                    &frequency,
+                    template.interval,
+                    &today,
+                    &now
+                ],
+            )?;
+            result.recurring_templates_added += 1;
+        }
+
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /// Loads a setup pack saved as a local file, such as one shared by
+    /// another business or a consultant, and applies it like
+    /// `import_configuration_pack`.
+    pub fn load_setup_pack(&self, container_id: i64, path: &Path) -> Result<PackImportResult> {
+        let io_error = |e: std::io::Error| rusqlite::Error::InvalidParameterName(e.to_string());
+        let size = std::fs::metadata(path).map_err(io_error)?.len();
+        if size > Self::MAX_SETUP_PACK_BYTES {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Setup pack is too large".to_string(),
+            ));
+        }
+        let json = std::fs::read_to_string(path).map_err(io_error)?;
+        self.import_configuration_pack(container_id, json)
+    }
+
+    pub fn forecast_category(
+        &self,
+        container_id: i64,
+        category: String,
+        months_ahead: u32,
+    ) -> Result<Vec<CategoryForecast>> {
+        if months_ahead == 0 || months_ahead > Self::MAX_FORECAST_MONTHS {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Months ahead must be between 1 and {}",
+                Self::MAX_FORECAST_MONTHS
+            )));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let today = chrono::Local::now().date_naive();
+        let current = today.year() * 12 + today.month0() as i32;
+        let month_key = |index: i32| format!("{:04}-{:02}", index.div_euclid(12), index.rem_euclid(12) + 1);
+
+        let mut totals: HashMap<i32, i64> = HashMap::new();
+        {
+            let mut stmt = conn.prepare(
+                "SELECT substr(date, 1, 7) as month, SUM(amount)
+                 FROM transactions
+                 WHERE container_id = ?1 AND category = ?2 AND substr(date, 1, 7) < ?3 AND deleted_at IS NULL
+                 GROUP BY month",
+            )?;
+            let rows = stmt.query_map(params![container_id, category.trim(), month_key(current)], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?;
+            for row in rows {
+                let (month, total) = row?;
+                if let Ok(parsed) = chrono::NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d") {
+                    totals.insert(parsed.year() * 12 + parsed.month0() as i32, total);
+                }
+            }
+        }
+
+        let first = totals.keys().min().copied();
+        let trailing_average = match first {
+            Some(first) => {
+                let start = (current - Self::FORECAST_TRAILING_MONTHS).max(first);
+                let months = (current - start).max(1) as i64;
+                let sum: i64 = (start..current).map(|index| totals.get(&index).copied().unwrap_or(0)).sum();
+                sum / months
+            }
+            None => 0,
+        };
+
+        Ok((1..=months_ahead as i32)
+            .map(|offset| {
+                let index = current + offset;
+                let seasonal: Vec<i64> = match first {
+                    Some(first) => (1..)
+                        .map(|years| index - 12 * years)
+                        .take_while(|past| *past >= first)
+                        .map(|past| totals.get(&past).copied().unwrap_or(0))
+                        .collect(),
+                    None => Vec::new(),
+                };
+                let (amount, basis) = if seasonal.is_empty() {
+                    (trailing_average, "trailing_average")
+                } else {
+                    (seasonal.iter().sum::<i64>() / seasonal.len() as i64, "seasonal")
+                };
+                CategoryForecast {
+                    month: month_key(index),
+                    amount,
+                    basis: basis.to_string(),
+                }
+            })
+            .collect())
+    }
+
+    fn category_tokens(description: &str, amount: i64) -> Vec<String> {
+        let mut tokens: Vec<String> = description
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.chars().count() >= 2)
+            .map(|word| word.to_string())
+            .collect();
+        tokens.push(if amount >= 0 { "#income" } else { "#expense" }.to_string());
+        tokens
+    }
+
+    pub fn get_accounts(&self, container_id: i64) -> Result<Vec<Account>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, account_type, opening_balance, container_id, created_at
+             FROM accounts
+             WHERE container_id = ?1 AND archived = 0
+             ORDER BY name ASC"
+        )?;
+
+        let accounts = stmt.query_map([container_id], |row| {
+            Ok(Account {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                account_type: row.get(2)?,
+                opening_balance: row.get(3)?,
+                container_id: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })?;
+
+        accounts.collect()
+    }
+
+    /// With `exclude_pending`, balances only count transactions that have
+    /// cleared or been reconciled.
+    pub fn get_account_balances(&self, container_id: i64, exclude_pending: bool) -> Result<Vec<AccountBalance>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT a.id, a.name, a.account_type, a.opening_balance, a.container_id, a.created_at,
+                    COALESCE(SUM(t.amount), 0) + a.opening_balance AS balance
+             FROM accounts a
+             LEFT JOIN transactions t ON t.account_id = a.id AND t.deleted_at IS NULL
+                                     AND (?2 = 0 OR t.status != 'pending')
+             WHERE a.container_id = ?1 AND a.archived = 0
+             GROUP BY a.id
+             ORDER BY a.name ASC"
+        )?;
+
+        let accounts = stmt.query_map(params![container_id, exclude_pending], |row| {
+            Ok(AccountBalance {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                account_type: row.get(2)?,
+                opening_balance: row.get(3)?,
+                container_id: row.get(4)?,
+                created_at: row.get(5)?,
+                balance: row.get(6)?,
+            })
+        })?;
+
+        accounts.collect()
+    }
+
+    pub fn get_balance_history(
+        &self,
+        account_id: i64,
+        start_date: String,
+        end_date: String,
+    ) -> Result<Vec<BalanceSnapshot>> {
+        let conn = self.conn.lock().unwrap();
+        let start = Self::parse_day(&start_date)?;
+        let today = chrono::Local::now().date_naive();
+        let end = Self::parse_day(&end_date)?.min(today);
+
+        // Stored snapshots are only written by refresh_balance_snapshots; days
+        // after the last stored one are worked out here without writing, so
+        // the history also works on a read-only profile.
+        let mut snapshots = {
+            let mut stmt = conn.prepare(
+                "SELECT account_id, snapshot_date, balance
+                 FROM balance_snapshots
+                 WHERE account_id = ?1 AND snapshot_date >= ?2 AND snapshot_date <= ?3
+                 ORDER BY snapshot_date ASC",
+            )?;
+            let rows = stmt.query_map(
+                params![
+                    account_id,
+                    start.format("%Y-%m-%d").to_string(),
+                    end.format("%Y-%m-%d").to_string()
+                ],
+                |row| {
+                    Ok(BalanceSnapshot {
+                        account_id: row.get(0)?,
+                        date: row.get(1)?,
+                        balance: row.get(2)?,
+                    })
+                },
+            )?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+        let start_key = start.format("%Y-%m-%d").to_string();
+        snapshots.extend(
+            Self::missing_balance_snapshots(&conn, account_id, end)?
+                .into_iter()
+                .filter(|snapshot| snapshot.date >= start_key),
+        );
+
+        Ok(snapshots)
+    }
+
+    /// Stores the daily balances of every account up to today, so later
+    /// history lookups only have to compute the days since. Returns the
+    /// number of snapshots written.
+    pub fn refresh_balance_snapshots(&self) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let today = chrono::Local::now().date_naive();
+        let tx = conn.transaction()?;
+        let account_ids: Vec<i64> = {
+            let mut stmt = tx.prepare("SELECT id FROM accounts")?;
+            let rows = stmt.query_map([], |row| row.get::<_, i64>(0))?;
+            rows.collect::<Result<Vec<i64>>>()?
+        };
+        let mut written = 0;
+        for account_id in account_ids {
+            written += Self::ensure_balance_snapshots(&tx, account_id, today)?;
+        }
+        tx.commit()?;
+        Ok(written)
+    }
+
+    pub fn add_account(
+        &self,
+        container_id: i64,
+        name: String,
+        account_type: String,
+        opening_balance: i64,
+    ) -> Result<Account> {
+        Self::ensure_max_chars("Name", &name, Self::MAX_NAME_CHARS)?;
+        let mut conn = self.conn.lock().unwrap();
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let name = name.trim().to_string();
+        let account_type = account_type.trim().to_string();
+        let taken = Self::names_in_scope(&conn, "account", Some(container_id))?;
+        Self::ensure_name_available("account", &name, &taken)?;
+
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO accounts (name, account_type, opening_balance, container_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            [
+                &name,
+                &account_type,
+                &opening_balance.to_string(),
+                &container_id.to_string(),
+                &now,
+            ],
+        )?;
+
+        let id = tx.last_insert_rowid();
+        Self::record_undo(&tx, container_id, "add_account", Self::undo_new("accounts", &[id]))?;
+        tx.commit()?;
+
+        Ok(Account {
+            id,
+            name,
+            account_type,
+            opening_balance,
+            container_id,
+            created_at: now,
+        })
+    }
+
+    pub fn update_account(&self, id: i64, name: String, opening_balance: i64) -> Result<Account> {
+        Self::ensure_max_chars("Name", &name, Self::MAX_NAME_CHARS)?;
+        let mut conn = self.conn.lock().unwrap();
+        let name = name.trim().to_string();
+
+        let tx = conn.transaction()?;
+        let changes = Self::undo_capture(&tx, "accounts", &[id])?;
+        let updated = tx.execute(
+            "UPDATE accounts SET name = ?1, opening_balance = ?2 WHERE id = ?3",
+            params![name, opening_balance, id],
+        )?;
+        if updated > 0 {
+            let container_id: i64 = tx.query_row("SELECT container_id FROM accounts WHERE id = ?1", [id], |row| row.get(0))?;
+            Self::record_undo(&tx, container_id, "update_account", changes)?;
+        }
+        tx.commit()?;
+
+        let account = conn.query_row(
+            "SELECT id, name, account_type, opening_balance, container_id, created_at
+             FROM accounts
+             WHERE id = ?1",
+            [id],
+            |row| {
+                Ok(Account {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    account_type: row.get(2)?,
+                    opening_balance: row.get(3)?,
+                    container_id: row.get(4)?,
+                    created_at: row.get(5)?,
+                })
+            },
+        )?;
+
+        Ok(account)
+    }
+
+    pub fn delete_account(&self, id: i64) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let container_id: Option<i64> = conn
+            .query_row("SELECT container_id FROM accounts WHERE id = ?1", [id], |row| row.get(0))
+            .optional()?;
+
+        let mut in_use = Vec::new();
+        for (table, column, dropped) in Self::ACCOUNT_REFERENCES {
+            if dropped || in_use.contains(&table) {
+                continue;
+            }
+            let used: bool = conn.query_row(
+                &format!("SELECT EXISTS(SELECT 1 FROM {} WHERE {} = ?1)", table, column),
+                [id],
+                |row| row.get(0),
+            )?;
+            if used {
+                in_use.push(table);
+            }
+        }
+        if !in_use.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Account is still used by {}; merge it into another account instead",
+                in_use.join(", ")
+            )));
+        }
+
+        let tx = conn.transaction()?;
+        let mut changes = Self::undo_capture(&tx, "accounts", &[id])?;
+        let transaction_ids = Self::undo_ids(&tx, "transactions", "account_id", id)?;
+        changes.extend(Self::undo_capture(&tx, "transactions", &transaction_ids)?);
+
+        tx.execute(
+            "UPDATE transactions SET account_id = NULL WHERE account_id = ?1",
+            [id],
+        )?;
+
+        tx.execute("DELETE FROM accounts WHERE id = ?1", [id])?;
+        if let Some(container_id) = container_id {
+            Self::record_undo(&tx, container_id, "delete_account", changes)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn reassign_account(
+        &self,
+        from_account_id: i64,
+        to_account_id: i64,
+        start_date: Option<String>,
+        end_date: Option<String>,
+    ) -> Result<usize> {
+        if from_account_id == to_account_id {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Source and destination accounts must be different".to_string(),
+            ));
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        Self::ensure_same_container_accounts(&conn, from_account_id, to_account_id)?;
+
+        let start = match start_date.as_deref().filter(|v| !v.trim().is_empty()) {
+            Some(value) => format!("{} 00:00:00", Self::parse_day(value)?.format("%Y-%m-%d")),
+            None => "0000-00-00 00:00:00".to_string(),
+        };
+        let end = match end_date.as_deref().filter(|v| !v.trim().is_empty()) {
+            Some(value) => format!("{} 23:59:59", Self::parse_day(value)?.format("%Y-%m-%d")),
+            None => "9999-12-31 23:59:59".to_string(),
+        };
+
+        let container_id: i64 = conn.query_row(
+            "SELECT container_id FROM accounts WHERE id = ?1",
+            [from_account_id],
+            |row| row.get(0),
+        )?;
+        let tx = conn.transaction()?;
+        let (updated, changes) = Self::reassign_account_rows(&tx, from_account_id, to_account_id, &start, &end)?;
+        Self::record_undo(&tx, container_id, "reassign_account", changes)?;
+        tx.commit()?;
+
+        Ok(updated)
+    }
+
+    pub fn merge_accounts(&self, source_id: i64, target_id: i64) -> Result<Account> {
+        if source_id == target_id {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Cannot merge an account into itself".to_string(),
+            ));
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        Self::ensure_same_container_accounts(&conn, source_id, target_id)?;
+
+        let container_id: i64 =
+            conn.query_row("SELECT container_id FROM accounts WHERE id = ?1", [source_id], |row| row.get(0))?;
+        let tx = conn.transaction()?;
+        // Balance snapshots are rebuilt on demand, so they stay out of the
+        // undo entry. Accounts go last so undo recreates the source first.
+        let mut changes: Vec<UndoChange> = Vec::new();
+        for (table, column, _) in Self::ACCOUNT_REFERENCES {
+            if table == "balance_snapshots" {
+                continue;
+            }
+            let ids: Vec<i64> = Self::undo_ids(&tx, table, column, source_id)?
+                .into_iter()
+                .filter(|id| !changes.iter().any(|change| change.table == table && change.id == *id))
+                .collect();
+            changes.extend(Self::undo_capture(&tx, table, &ids)?);
+        }
+        let accounts = Self::undo_capture(&tx, "accounts", &[source_id, target_id])?;
+        let (_, moved) =
+            Self::reassign_account_rows(&tx, source_id, target_id, "0000-00-00 00:00:00", "9999-12-31 23:59:59")?;
+        changes.extend(moved);
+        changes.extend(accounts);
+        tx.execute(
+            "UPDATE accounts
+             SET opening_balance = opening_balance + (SELECT opening_balance FROM accounts WHERE id = ?1)
+             WHERE id = ?2",
+            params![source_id, target_id],
+        )?;
+        // Envelope names are unique per account, so clashing ones keep the
+        // source account's name as a suffix.
+        tx.execute(
+            "UPDATE envelopes
+             SET name = name || ' (' || (SELECT name FROM accounts WHERE id = ?1) || ')'
+             WHERE account_id = ?1
+               AND name IN (SELECT name FROM envelopes WHERE account_id = ?2)",
+            params![source_id, target_id],
+        )?;
+        for (table, column, _) in Self::ACCOUNT_REFERENCES {
+            if table == "balance_snapshots" {
+                continue;
+            }
+            // Tables keyed by account (zakat, petty cash) keep the target's row.
+            tx.execute(
+                &format!("UPDATE OR IGNORE {} SET {} = ?1 WHERE {} = ?2", table, column, column),
+                params![target_id, source_id],
+            )?;
+            tx.execute(&format!("DELETE FROM {} WHERE {} = ?1", table, column), [source_id])?;
+        }
+        tx.execute(
+            "DELETE FROM balance_snapshots WHERE account_id IN (?1, ?2)",
+            params![source_id, target_id],
+        )?;
+        tx.execute("DELETE FROM accounts WHERE id = ?1", [source_id])?;
+        Self::record_undo(&tx, container_id, "merge_accounts", changes)?;
+
+        let account = tx.query_row(
+            "SELECT id, name, account_type, opening_balance, container_id, created_at
+             FROM accounts
+             WHERE id = ?1",
+            [target_id],
+            |row| {
+                Ok(Account {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    account_type: row.get(2)?,
+                    opening_balance: row.get(3)?,
+                    container_id: row.get(4)?,
+                    created_at: row.get(5)?,
+                })
+            },
+        )?;
+        tx.commit()?;
+
+        Ok(account)
+    }
+
+    fn ensure_same_container_accounts(conn: &Connection, first_id: i64, second_id: i64) -> Result<()> {
+        let containers: Vec<i64> = {
+            let mut stmt = conn.prepare("SELECT container_id FROM accounts WHERE id IN (?1, ?2)")?;
+            let rows = stmt.query_map(params![first_id, second_id], |row| row.get::<_, i64>(0))?;
+            rows.collect::<Result<Vec<i64>>>()?
+        };
+        if containers.len() != 2 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        if containers[0] != containers[1] {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Accounts belong to different containers".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn reassign_account_rows(
+        conn: &Connection,
+        from_account_id: i64,
+        to_account_id: i64,
+        start: &str,
+        end: &str,
+    ) -> Result<(usize, Vec<UndoChange>)> {
+        let select_ids = |sql: &str| -> Result<Vec<i64>> {
+            let mut stmt = conn.prepare(sql)?;
+            let rows = stmt.query_map(params![from_account_id, to_account_id, start, end], |row| row.get::<_, i64>(0))?;
+            rows.collect()
+        };
+        // Rows leaving the account, plus the other legs of transfers between
+        // the two accounts, which end up in the trash.
+        let locked = select_ids(
+            "SELECT id FROM transactions
+             WHERE (account_id = ?1 OR (account_id = ?2 AND transfer_account_id = ?1))
+               AND date >= ?3 AND date <= ?4
+             ORDER BY id",
+        )?;
+        for id in locked {
+            if Self::is_transaction_reconciled(conn, id)? {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Transaction {} is reconciled; reopen its reconciliation before moving it",
+                    id
+                )));
+            }
+        }
+
+        let touched = select_ids(
+            "SELECT id FROM transactions
+             WHERE (account_id = ?1 OR transfer_account_id = ?1)
+               AND date >= ?3 AND date <= ?4
+             ORDER BY id",
+        )?;
+        let changes = Self::undo_capture(conn, "transactions", &touched)?;
+
+        // Both legs of a transfer share the same date, so the range keeps pairs together.
+        let mut updated = conn.execute(
+            "UPDATE transactions SET account_id = ?1
+             WHERE account_id = ?2 AND date >= ?3 AND date <= ?4",
+            params![to_account_id, from_account_id, start, end],
+        )?;
+        updated += conn.execute(
+            "UPDATE transactions SET transfer_account_id = ?1
+             WHERE transfer_account_id = ?2 AND date >= ?3 AND date <= ?4",
+            params![to_account_id, from_account_id, start, end],
+        )?;
+
+        // Transfers between the two accounts now point at themselves and carry
+        // no value, so they go to the trash.
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "UPDATE transactions SET deleted_at = ?2
+             WHERE transfer_id IS NOT NULL AND account_id = ?1 AND transfer_account_id = ?1
+               AND deleted_at IS NULL AND date >= ?3 AND date <= ?4",
+            params![to_account_id, now, start, end],
+        )?;
+
+        Ok((updated, changes))
+    }
+
+    pub fn add_category(&self, name: String, category_type: String) -> Result<()> {
+        Self::ensure_max_chars("Category name", &name, Self::MAX_NAME_CHARS)?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO categories (name, category_type, is_default) VALUES (?1, ?2, 0)",
+            [name, category_type],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_category(&self, name: String) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM categories WHERE name = ?1 AND is_default = 0",
+            [name],
+        )?;
+        Ok(())
+    }
+
+    pub fn update_category(
+        &self,
+        old_name: String,
+        new_name: String,
+        category_type: String,
+    ) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        Self::rename_category(&tx, &old_name, &new_name, &category_type)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Applies several category edits at once. Either every edit succeeds or
+    /// none are kept.
+    pub fn update_categories(&self, edits: Vec<CategoryEdit>) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for edit in &edits {
+            Self::rename_category(&tx, &edit.old_name, &edit.new_name, &edit.category_type)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn rename_category(conn: &Connection, old_name: &str, new_name: &str, category_type: &str) -> Result<()> {
+        Self::ensure_max_chars("Category name", new_name, Self::MAX_NAME_CHARS)?;
+        let old_name = old_name.trim();
+        let new_name = new_name.trim();
+        let category_type = category_type.trim();
+
+        if new_name.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Category name cannot be empty".to_string(),
+            ));
+        }
+
+        let updated_rows = conn.execute(
+            "UPDATE categories
+             SET name = ?1, category_type = ?2
+             WHERE name = ?3",
+            params![new_name, category_type, old_name],
+        )?;
+
+        if updated_rows == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+
+        conn.execute(
+            "UPDATE transactions SET category = ?1 WHERE category = ?2",
+            params![new_name, old_name],
+        )?;
+        Ok(())
+    }
+
+    /// Stores the order categories are offered in. Listed categories come
+    /// first in the given order; any category left out falls back to the
+    /// default ordering after them.
+    pub fn reorder_categories(&self, ordered_names: Vec<String>) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("UPDATE categories SET sort_order = NULL", [])?;
+        let mut seen = std::collections::HashSet::new();
+        for (position, name) in ordered_names.iter().enumerate() {
+            let name = name.trim();
+            if !seen.insert(name.to_string()) {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Category '{}' is listed more than once",
+                    name
+                )));
+            }
+            let updated = tx.execute(
+                "UPDATE categories SET sort_order = ?1 WHERE name = ?2",
+                params![position as i64, name],
+            )?;
+            if updated == 0 {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Unknown category '{}'",
+                    name
+                )));
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Moves every transaction in a container from one category to another,
+    /// optionally within an inclusive date range. Transfers are left alone.
+    /// Returns the number of transactions changed.
+    pub fn bulk_update_category(
+        &self,
+        container_id: i64,
+        from_category: String,
+        to_category: String,
+        start_date: Option<String>,
+        end_date: Option<String>,
+    ) -> Result<usize> {
+        let from_category = from_category.trim().to_string();
+        let to_category = to_category.trim().to_string();
+        if from_category == to_category {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Source and destination categories must be different".to_string(),
+            ));
+        }
+        let start = match start_date.as_deref().filter(|v| !v.trim().is_empty()) {
+            Some(value) => format!("{} 00:00:00", Self::parse_day(value)?.format("%Y-%m-%d")),
+            None => "0000-00-00 00:00:00".to_string(),
+        };
+        let end = match end_date.as_deref().filter(|v| !v.trim().is_empty()) {
+            Some(value) => format!("{} 23:59:59", Self::parse_day(value)?.format("%Y-%m-%d")),
+            None => "9999-12-31 23:59:59".to_string(),
+        };
+
+        let mut conn = self.conn.lock().unwrap();
+        let exists: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM categories WHERE name = ?1",
+            [&to_category],
+            |row| row.get(0),
+        )?;
+        if exists == 0 {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Category {} does not exist",
+                to_category
+            )));
+        }
+
+        let tx = conn.transaction()?;
+        let ids: Vec<i64> = {
+            let mut stmt = tx.prepare(
+                "SELECT id FROM transactions
+                 WHERE container_id = ?1 AND category = ?2 AND transfer_id IS NULL AND deleted_at IS NULL
+                   AND date >= ?3 AND date <= ?4
+                 ORDER BY id",
+            )?;
+            let rows = stmt.query_map(params![container_id, &from_category, &start, &end], |row| row.get::<_, i64>(0))?;
+            rows.collect::<Result<Vec<i64>>>()?
+        };
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let changes = Self::undo_capture(&tx, "transactions", &ids)?;
+        let updated = tx.execute(
+            "UPDATE transactions SET category = ?1
+             WHERE container_id = ?2 AND category = ?3 AND transfer_id IS NULL AND deleted_at IS NULL
+               AND date >= ?4 AND date <= ?5",
+            params![&to_category, container_id, &from_category, &start, &end],
+        )?;
+        Self::record_undo(&tx, container_id, "bulk_update_category", changes)?;
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    pub fn get_available_months(&self, container_id: i64) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT strftime('%Y-%m', date) as month 
+             FROM transactions 
+             WHERE container_id = ?1 AND deleted_at IS NULL
+             ORDER BY month DESC"
+        )?;
         
-        let description = transaction.description.unwrap_or_else(|| "Untitled".to_string());
-        let category = transaction
-            .category
-            .unwrap_or_else(|| Self::DEFAULT_FALLBACK_CATEGORY.to_string());
+        let months = stmt.query_map([container_id], |row| row.get(0))?;
+        months.collect()
+    }
+
+    pub fn get_balance_for_month(&self, container_id: i64, month: String) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        
+        let balance: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(amount), 0) FROM transactions WHERE container_id = ?1 AND date LIKE ?2 AND transfer_id IS NULL AND deleted_at IS NULL",
+            [&container_id.to_string(), &format!("{}%", month)],
+            |row| row.get(0),
+        )?;
+
+        Ok(balance)
+    }
+
+    /// Transactions dated between `start_date` and `end_date` (YYYY-MM-DD),
+    /// both days inclusive, newest first.
+    pub fn get_transactions_for_range(
+        &self,
+        container_id: i64,
+        start_date: String,
+        end_date: String,
+    ) -> Result<Vec<Transaction>> {
+        let start_day = Self::parse_day(&start_date)?;
+        let end_day = Self::parse_day(&end_date)?;
+        if end_day < start_day {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "End date must not be before start date".to_string(),
+            ));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM transactions
+             WHERE container_id = ?1 AND date >= ?2 AND date <= ?3 AND deleted_at IS NULL
+             ORDER BY date DESC, id DESC",
+            Self::TRANSACTION_COLUMNS
+        ))?;
+        let transactions = stmt.query_map(
+            params![
+                container_id,
+                format!("{} 00:00:00", start_day.format("%Y-%m-%d")),
+                format!("{} 23:59:59", end_day.format("%Y-%m-%d"))
+            ],
+            Self::transaction_from_row,
+        )?;
+
+        transactions.collect()
+    }
+
+    pub fn get_transactions_for_month(&self, container_id: i64, month: String, limit: Option<i64>) -> Result<Vec<Transaction>> {
+        let conn = self.conn.lock().unwrap();
+        let base_query = format!(
+            "SELECT {} FROM transactions WHERE container_id = {} AND date LIKE '{}%' AND deleted_at IS NULL ORDER BY date DESC",
+            Self::TRANSACTION_COLUMNS, container_id, month
+        );
+        
+        let query = match limit {
+            Some(l) => format!("{} LIMIT {}", base_query, l),
+            None => base_query,
+        };
+
+        let mut stmt = conn.prepare(&query)?;
+        let transactions = stmt.query_map([], Self::transaction_from_row)?;
+
+        transactions.collect()
+    }
+
+    pub fn get_category_totals_for_month(&self, container_id: i64, month: String) -> Result<Vec<(String, i64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT t.category, SUM(ABS(t.amount)) as total 
+             FROM transactions t
+             LEFT JOIN categories c ON c.name = t.category
+             WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.date LIKE ?2 AND t.transfer_id IS NULL
+               AND COALESCE(c.category_type, 'expense') = 'expense'
+             GROUP BY t.category 
+             ORDER BY total DESC"
+        )?;
+
+        let results = stmt.query_map([&container_id.to_string(), &format!("{}%", month)], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
         
+        results.collect()
+    }
+
+    pub fn get_profit_and_loss_for_month(&self, container_id: i64, month: String) -> Result<ProfitLossReport> {
+        let conn = self.conn.lock().unwrap();
+        let (start_date, end_date) = Self::month_range(&month)?;
+        Self::profit_and_loss_for_range(&conn, container_id, start_date, end_date)
+    }
+
+    pub fn get_balance_sheet_for_month(&self, container_id: i64, month: String) -> Result<BalanceSheetReport> {
+        let conn = self.conn.lock().unwrap();
+        let (start_date, end_date) = Self::month_range(&month)?;
+        Self::balance_sheet_for_range(&conn, container_id, start_date, end_date)
+    }
+
+    pub fn get_profit_and_loss_for_year(&self, container_id: i64, year: String) -> Result<ProfitLossReport> {
+        let conn = self.conn.lock().unwrap();
+        let (start_date, end_date) = Self::year_range_last_known(&conn, container_id, &year)?;
+        Self::profit_and_loss_for_range(&conn, container_id, start_date, end_date)
+    }
+
+    pub fn get_balance_sheet_for_year(&self, container_id: i64, year: String) -> Result<BalanceSheetReport> {
+        let conn = self.conn.lock().unwrap();
+        let (start_date, end_date) = Self::year_range_last_known(&conn, container_id, &year)?;
+        Self::balance_sheet_for_range(&conn, container_id, start_date, end_date)
+    }
+
+    fn profit_and_loss_for_range(
+        conn: &Connection,
+        container_id: i64,
+        start_date: String,
+        end_date: String,
+    ) -> Result<ProfitLossReport> {
+        Self::profit_and_loss_for_tag(conn, container_id, start_date, end_date, None)
+    }
+
+    /// Profit and loss limited to transactions carrying `tag`, or all of
+    /// them when no tag is given.
+    fn profit_and_loss_for_tag(
+        conn: &Connection,
+        container_id: i64,
+        start_date: String,
+        end_date: String,
+        tag: Option<&str>,
+    ) -> Result<ProfitLossReport> {
+
+        let mut income_stmt = conn.prepare(
+            "SELECT t.category, SUM(ABS(t.amount)) as total
+             FROM transactions t
+             LEFT JOIN categories c ON c.name = t.category
+             WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.transfer_id IS NULL
+               AND t.date >= ?2 AND t.date <= ?3
+               AND COALESCE(c.category_type, 'expense') = 'income'
+               AND (?4 IS NULL OR t.id IN (
+                   SELECT tt.transaction_id FROM transaction_tags tt
+                   JOIN tags g ON g.id = tt.tag_id
+                   WHERE g.container_id = ?1 AND g.name = ?4))
+             GROUP BY t.category
+             ORDER BY total DESC",
+        )?;
+        let income_iter = income_stmt.query_map(
+            params![container_id, &start_date, &end_date, tag],
+            |row| {
+                Ok(ProfitLossLine {
+                    category: row.get(0)?,
+                    total: row.get(1)?,
+                })
+            },
+        )?;
+        let income: Vec<ProfitLossLine> = income_iter.collect::<Result<Vec<_>>>()?;
+
+        let mut expense_stmt = conn.prepare(
+            "SELECT t.category, SUM(ABS(t.amount)) as total
+             FROM transactions t
+             LEFT JOIN categories c ON c.name = t.category
+             WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.transfer_id IS NULL
+               AND t.date >= ?2 AND t.date <= ?3
+               AND COALESCE(c.category_type, 'expense') = 'expense'
+               AND (?4 IS NULL OR t.id IN (
+                   SELECT tt.transaction_id FROM transaction_tags tt
+                   JOIN tags g ON g.id = tt.tag_id
+                   WHERE g.container_id = ?1 AND g.name = ?4))
+             GROUP BY t.category
+             ORDER BY total DESC",
+        )?;
+        let expense_iter = expense_stmt.query_map(
+            params![container_id, &start_date, &end_date, tag],
+            |row| {
+                Ok(ProfitLossLine {
+                    category: row.get(0)?,
+                    total: row.get(1)?,
+                })
+            },
+        )?;
+        let expense: Vec<ProfitLossLine> = expense_iter.collect::<Result<Vec<_>>>()?;
+
+        let total_income: i64 = income.iter().map(|line| line.total).sum();
+        let total_expense: i64 = expense.iter().map(|line| line.total).sum();
+        let net_income = total_income - total_expense;
+
+        Ok(ProfitLossReport {
+            start_date,
+            end_date,
+            income,
+            expense,
+            total_income,
+            total_expense,
+            net_income,
+        })
+    }
+
+    fn balance_sheet_for_range(
+        conn: &Connection,
+        container_id: i64,
+        start_date: String,
+        end_date: String,
+    ) -> Result<BalanceSheetReport> {
+
+        let mut stmt = conn.prepare(
+            "SELECT a.id, a.name, a.account_type, a.opening_balance, a.container_id, a.created_at,
+                    COALESCE(SUM(t.amount), 0) + a.opening_balance AS balance
+             FROM accounts a
+             LEFT JOIN transactions t ON t.account_id = a.id AND t.date <= ?2 AND t.deleted_at IS NULL
+             WHERE a.container_id = ?1
+             GROUP BY a.id
+             ORDER BY a.name ASC",
+        )?;
+
+        let accounts_iter = stmt.query_map(params![container_id, &end_date], |row| {
+            Ok(AccountBalance {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                account_type: row.get(2)?,
+                opening_balance: row.get(3)?,
+                container_id: row.get(4)?,
+                created_at: row.get(5)?,
+                balance: row.get(6)?,
+            })
+        })?;
+
+        let mut assets = Vec::new();
+        let mut liabilities = Vec::new();
+        let mut equity = Vec::new();
+
+        for account in accounts_iter {
+            let account = account?;
+            match account.account_type.as_str() {
+                "asset" | "contra_asset" => assets.push(account),
+                "liability" => liabilities.push(account),
+                _ => equity.push(account),
+            }
+        }
+
+        let total_income: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(ABS(t.amount)), 0)
+             FROM transactions t
+             LEFT JOIN categories c ON c.name = t.category
+             WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.transfer_id IS NULL
+               AND t.date >= ?2 AND t.date <= ?3
+               AND COALESCE(c.category_type, 'expense') = 'income'",
+            params![container_id, &start_date, &end_date],
+            |row| row.get(0),
+        )?;
+
+        let total_expense: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(ABS(t.amount)), 0)
+             FROM transactions t
+             LEFT JOIN categories c ON c.name = t.category
+             WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.transfer_id IS NULL
+               AND t.date >= ?2 AND t.date <= ?3
+               AND COALESCE(c.category_type, 'expense') = 'expense'",
+            params![container_id, &start_date, &end_date],
+            |row| row.get(0),
+        )?;
+
+        let net_income = total_income - total_expense;
+
+        equity.retain(|account| account.name != "Laba Tahun Berjalan");
+        equity.push(AccountBalance {
+            id: 0,
+            name: "Laba Tahun Berjalan".to_string(),
+            account_type: "equity".to_string(),
+            opening_balance: 0,
+            balance: net_income,
+            container_id,
+            created_at: end_date.clone(),
+        });
+
+        let total_assets: i64 = assets.iter().map(|a| a.balance).sum();
+        let total_liabilities: i64 = liabilities.iter().map(|a| a.balance).sum();
+        let total_equity: i64 = equity.iter().map(|a| a.balance).sum();
+
+        Ok(BalanceSheetReport {
+            as_of: end_date,
+            assets,
+            liabilities,
+            equity,
+            total_assets,
+            total_liabilities,
+            total_equity,
+        })
+    }
+
+    /// Closes a finished fiscal year: the year's net income moves from
+    /// current-year earnings into retained earnings through a transfer dated
+    /// the first day of the next year, and the year's transactions are
+    /// locked against new entries and changes to amounts, dates or accounts.
+    pub fn run_year_end_close(&self, container_id: i64, fiscal_year: String) -> Result<YearEndClosing> {
+        let (start_date, end_date) = Self::year_range(&fiscal_year)?;
+        let year: i32 = fiscal_year.trim().parse().map_err(|_| {
+            rusqlite::Error::InvalidParameterName("Invalid year".to_string())
+        })?;
+        if year >= chrono::Local::now().year() {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Fiscal year {} has not ended yet",
+                year
+            )));
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        if Self::is_year_closed(&conn, container_id, year)? {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Fiscal year {} is already closed",
+                year
+            )));
+        }
+        let locale = Self::read_locale(&conn)?;
+        let profit_loss = Self::profit_and_loss_for_range(&conn, container_id, start_date, end_date.clone())?;
+        let net_income = profit_loss.net_income;
+
+        Self::ensure_default_equity_accounts(&conn, container_id)?;
+        let equity_account = |name: &str| -> Result<i64> {
+            conn.query_row(
+                "SELECT id FROM accounts WHERE container_id = ?1 AND name = ?2",
+                params![container_id, name],
+                |row| row.get(0),
+            )
+        };
+        let retained_id = equity_account(Self::RETAINED_EARNINGS_ACCOUNT)?;
+        let current_id = equity_account(Self::CURRENT_EARNINGS_ACCOUNT)?;
+        let retained_earnings_before: i64 = conn.query_row(
+            "SELECT a.opening_balance + COALESCE(SUM(t.amount), 0)
+             FROM accounts a
+             LEFT JOIN transactions t ON t.account_id = a.id AND t.date <= ?2 AND t.deleted_at IS NULL
+             WHERE a.id = ?1
+             GROUP BY a.id",
+            params![retained_id, &end_date],
+            |row| row.get(0),
+        )?;
+
+        let closed_at = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let closing_date = format!("{}-01-01 00:00:00", year + 1);
+        let description = format!("{} {}", i18n::translate(&locale, "default.year_end_close"), year);
+
+        let tx = conn.transaction()?;
+        let transfer_id = match net_income {
+            0 => None,
+            n if n > 0 => Some(Self::insert_transfer(&tx, container_id, current_id, retained_id, n, &description, &closing_date)?),
+            n => Some(Self::insert_transfer(&tx, container_id, retained_id, current_id, -n, &description, &closing_date)?),
+        };
+        tx.execute(
+            "INSERT INTO closed_years (container_id, fiscal_year, net_income, transfer_id, closed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![container_id, year, net_income, transfer_id, &closed_at],
+        )?;
+        tx.commit()?;
+
+        Ok(YearEndClosing {
+            closed_year: ClosedYear {
+                container_id,
+                fiscal_year: year,
+                net_income,
+                transfer_id,
+                closed_at,
+            },
+            retained_earnings_before,
+            retained_earnings_after: retained_earnings_before + net_income,
+            profit_loss,
+        })
+    }
+
+    pub fn get_closed_years(&self, container_id: i64) -> Result<Vec<ClosedYear>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT container_id, fiscal_year, net_income, transfer_id, closed_at
+             FROM closed_years
+             WHERE container_id = ?1
+             ORDER BY fiscal_year ASC",
+        )?;
+        let rows = stmt.query_map([container_id], |row| {
+            Ok(ClosedYear {
+                container_id: row.get(0)?,
+                fiscal_year: row.get(1)?,
+                net_income: row.get(2)?,
+                transfer_id: row.get(3)?,
+                closed_at: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Opening balances for the year after a closed `fiscal_year`. Retained
+    /// earnings absorb every profit up to the close so the opening balance
+    /// sheet always balances. With `new_container_name` the accounts are
+    /// copied into a fresh container with these opening balances; without it
+    /// the balances are only reported, as the same container already carries
+    /// them forward through its transactions.
+    pub fn roll_over_year(
+        &self,
+        container_id: i64,
+        fiscal_year: String,
+        new_container_name: Option<String>,
+    ) -> Result<YearRollover> {
+        if let Some(name) = &new_container_name {
+            Self::ensure_max_chars("Name", name, Self::MAX_NAME_CHARS)?;
+        }
+        let year: i32 = fiscal_year.trim().parse().map_err(|_| {
+            rusqlite::Error::InvalidParameterName("Invalid year".to_string())
+        })?;
+
+        let mut conn = self.conn.lock().unwrap();
+        if !Self::is_year_closed(&conn, container_id, year)? {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Close fiscal year {} first",
+                year
+            )));
+        }
+        let opening_date = format!("{}-01-01 00:00:00", year + 1);
+
+        let mut accounts: Vec<AccountBalance> = {
+            let mut stmt = conn.prepare(
+                "SELECT a.id, a.name, a.account_type, a.opening_balance, a.container_id, a.created_at,
+                        a.opening_balance + COALESCE(SUM(t.amount), 0) AS balance, a.archived
+                 FROM accounts a
+                 LEFT JOIN transactions t ON t.account_id = a.id AND t.date <= ?2 AND t.deleted_at IS NULL
+                 WHERE a.container_id = ?1
+                 GROUP BY a.id
+                 ORDER BY a.name ASC",
+            )?;
+            let rows = stmt.query_map(params![container_id, &opening_date], |row| {
+                Ok((
+                    AccountBalance {
+                        id: row.get(0)?,
+                        name: row.get(1)?,
+                        account_type: row.get(2)?,
+                        opening_balance: row.get(3)?,
+                        container_id: row.get(4)?,
+                        created_at: row.get(5)?,
+                        balance: row.get(6)?,
+                    },
+                    row.get::<_, i64>(7)? == 1,
+                ))
+            })?;
+            let mut accounts = Vec::new();
+            for row in rows {
+                let (account, archived) = row?;
+                if !archived || account.balance != 0 {
+                    accounts.push(account);
+                }
+            }
+            accounts
+        };
+
+        let total_assets: i64 = accounts
+            .iter()
+            .filter(|a| matches!(a.account_type.as_str(), "asset" | "contra_asset"))
+            .map(|a| a.balance)
+            .sum();
+        let total_liabilities: i64 = accounts
+            .iter()
+            .filter(|a| a.account_type == "liability")
+            .map(|a| a.balance)
+            .sum();
+        let other_equity: i64 = accounts
+            .iter()
+            .filter(|a| {
+                !matches!(a.account_type.as_str(), "asset" | "contra_asset" | "liability")
+                    && a.name != Self::RETAINED_EARNINGS_ACCOUNT
+                    && a.name != Self::CURRENT_EARNINGS_ACCOUNT
+            })
+            .map(|a| a.balance)
+            .sum();
+        for account in accounts.iter_mut() {
+            if account.name == Self::CURRENT_EARNINGS_ACCOUNT {
+                account.balance = 0;
+            } else if account.name == Self::RETAINED_EARNINGS_ACCOUNT {
+                account.balance = total_assets - total_liabilities - other_equity;
+            }
+        }
+
+        let target_container_id = match new_container_name {
+            Some(name) => {
+                let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+                let tx = conn.transaction()?;
+                tx.execute(
+                    "INSERT INTO containers (name, created_at, is_default) VALUES (?1, ?2, 0)",
+                    params![&name, &now],
+                )?;
+                let new_id = tx.last_insert_rowid();
+                for account in accounts.iter_mut() {
+                    tx.execute(
+                        "INSERT INTO accounts (name, account_type, opening_balance, container_id, created_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                        params![&account.name, &account.account_type, account.balance, new_id, &now],
+                    )?;
+                    account.id = tx.last_insert_rowid();
+                    account.opening_balance = account.balance;
+                    account.container_id = new_id;
+                    account.created_at = now.clone();
+                }
+                Self::ensure_default_equity_accounts(&tx, new_id)?;
+                tx.commit()?;
+                new_id
+            }
+            None => container_id,
+        };
+
+        let total_equity = accounts
+            .iter()
+            .filter(|a| !matches!(a.account_type.as_str(), "asset" | "contra_asset" | "liability"))
+            .map(|a| a.balance)
+            .sum();
+        Ok(YearRollover {
+            container_id: target_container_id,
+            fiscal_year: year + 1,
+            accounts,
+            total_assets,
+            total_liabilities,
+            total_equity,
+        })
+    }
+
+    fn is_year_closed(conn: &Connection, container_id: i64, year: i32) -> Result<bool> {
+        let closed: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM closed_years WHERE container_id = ?1 AND fiscal_year = ?2",
+            params![container_id, year],
+            |row| row.get(0),
+        )?;
+        Ok(closed > 0)
+    }
+
+    pub fn get_saved_filters(&self, container_id: i64) -> Result<Vec<SavedFilter>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, container_id, name, filter_json, created_at
+             FROM saved_filters
+             WHERE container_id = ?1
+             ORDER BY name ASC",
+        )?;
+        let rows = stmt.query_map([container_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+
+        let mut filters = Vec::new();
+        for row in rows {
+            let (id, container_id, name, filter_json, created_at) = row?;
+            filters.push(SavedFilter {
+                id,
+                container_id,
+                name,
+                filter: Self::decode_filter(&filter_json)?,
+                created_at,
+            });
+        }
+        Ok(filters)
+    }
+
+    pub fn save_filter(&self, container_id: i64, name: String, filter: TransactionFilter) -> Result<SavedFilter> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Filter name cannot be empty".to_string(),
+            ));
+        }
+        Self::validate_filter(&filter)?;
+
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO saved_filters (container_id, name, filter_json, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![container_id, &name, Self::encode_filter(&filter)?, &now],
+        )?;
+
+        Ok(SavedFilter {
+            id: conn.last_insert_rowid(),
+            container_id,
+            name,
+            filter,
+            created_at: now,
+        })
+    }
+
+    pub fn update_saved_filter(&self, id: i64, name: String, filter: TransactionFilter) -> Result<()> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Filter name cannot be empty".to_string(),
+            ));
+        }
+        Self::validate_filter(&filter)?;
+
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE saved_filters SET name = ?1, filter_json = ?2 WHERE id = ?3",
+            params![&name, Self::encode_filter(&filter)?, id],
+        )?;
+        if updated == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        Ok(())
+    }
+
+    pub fn delete_saved_filter(&self, id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM saved_filters WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    pub fn run_saved_filter(&self, id: i64, limit: Option<i64>, offset: Option<i64>) -> Result<TransactionPage> {
+        let conn = self.conn.lock().unwrap();
+        let (container_id, filter_json): (i64, String) = conn.query_row(
+            "SELECT container_id, filter_json FROM saved_filters WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let filter = Self::decode_filter(&filter_json)?;
+        Self::query_transaction_page(&conn, container_id, &filter, limit, offset)
+    }
+
+    fn query_transaction_page(
+        conn: &Connection,
+        container_id: i64,
+        filter: &TransactionFilter,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<TransactionPage> {
+        let limit = limit.unwrap_or(Self::DEFAULT_PAGE_SIZE).clamp(1, Self::MAX_PAGE_SIZE);
+        let offset = offset.unwrap_or(0).max(0);
+        let (clause, mut values) = Self::transaction_filter_clause(container_id, filter)?;
+
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM transactions WHERE {}", clause),
+            params_from_iter(values.iter()),
+            |row| row.get(0),
+        )?;
+
+        values.push(Value::Integer(limit));
+        values.push(Value::Integer(offset));
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM transactions
+             WHERE {}
+             ORDER BY date DESC, id DESC
+             LIMIT ? OFFSET ?",
+            Self::TRANSACTION_COLUMNS, clause
+        ))?;
+        let transactions = stmt
+            .query_map(params_from_iter(values.iter()), Self::transaction_from_row)?
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(TransactionPage {
+            transactions,
+            total,
+            limit,
+            offset,
+        })
+    }
+
+    fn transaction_filter_clause(container_id: i64, filter: &TransactionFilter) -> Result<(String, Vec<Value>)> {
+        let mut conditions = vec!["container_id = ?".to_string(), "deleted_at IS NULL".to_string()];
+        let mut values = vec![Value::Integer(container_id)];
+
+        if let Some(start) = filter.start_date.as_deref().filter(|v| !v.trim().is_empty()) {
+            let start = Self::parse_day(start)?;
+            conditions.push("date >= ?".to_string());
+            values.push(Value::Text(format!("{} 00:00:00", start.format("%Y-%m-%d"))));
+        }
+        if let Some(end) = filter.end_date.as_deref().filter(|v| !v.trim().is_empty()) {
+            let end = Self::parse_day(end)?;
+            conditions.push("date <= ?".to_string());
+            values.push(Value::Text(format!("{} 23:59:59", end.format("%Y-%m-%d"))));
+        }
+        if let Some(category) = filter.category.as_deref().filter(|v| !v.trim().is_empty()) {
+            conditions.push("category = ?".to_string());
+            values.push(Value::Text(category.trim().to_string()));
+        }
+        if let Some(account_id) = filter.account_id {
+            conditions.push("account_id = ?".to_string());
+            values.push(Value::Integer(account_id));
+        }
+        if let Some(min_amount) = filter.min_amount {
+            conditions.push("ABS(amount) >= ?".to_string());
+            values.push(Value::Integer(min_amount));
+        }
+        if let Some(max_amount) = filter.max_amount {
+            conditions.push("ABS(amount) <= ?".to_string());
+            values.push(Value::Integer(max_amount));
+        }
+        if let Some(text) = filter.text.as_deref().filter(|v| !v.trim().is_empty()) {
+            conditions.push("description LIKE ? ESCAPE '\\'".to_string());
+            let escaped = text
+                .trim()
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            values.push(Value::Text(format!("%{}%", escaped)));
+        }
+        if let Some(tag) = filter.tag.as_deref().filter(|v| !v.trim().is_empty()) {
+            conditions.push(
+                "id IN (SELECT tt.transaction_id FROM transaction_tags tt
+                        JOIN tags g ON g.id = tt.tag_id
+                        WHERE g.container_id = transactions.container_id AND g.name = ?)"
+                    .to_string(),
+            );
+            values.push(Value::Text(Self::normalize_tag(tag)));
+        }
+        if let Some(status) = filter.status.as_deref().filter(|v| !v.trim().is_empty()) {
+            conditions.push("status = ?".to_string());
+            values.push(Value::Text(Self::normalize_transaction_status(status)?));
+        }
+
+        Ok((conditions.join(" AND "), values))
+    }
+
+    fn validate_filter(filter: &TransactionFilter) -> Result<()> {
+        Self::transaction_filter_clause(0, filter).map(|_| ())
+    }
+
+    fn encode_filter(filter: &TransactionFilter) -> Result<String> {
+        serde_json::to_string(filter).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+    }
+
+    fn decode_filter(value: &str) -> Result<TransactionFilter> {
+        serde_json::from_str(value).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+        })
+    }
+
+    pub fn get_custom_fields(&self, container_id: i64) -> Result<Vec<CustomField>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, container_id, name, field_type, created_at
+             FROM custom_fields
+             WHERE container_id = ?1
+             ORDER BY name ASC",
+        )?;
+        let fields = stmt.query_map([container_id], |row| {
+            Ok(CustomField {
+                id: row.get(0)?,
+                container_id: row.get(1)?,
+                name: row.get(2)?,
+                field_type: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?;
+        fields.collect()
+    }
+
+    pub fn add_custom_field(&self, container_id: i64, name: String, field_type: String) -> Result<CustomField> {
+        let name = name.trim().to_string();
+        let field_type = field_type.trim().to_lowercase();
+        if name.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Field name cannot be empty".to_string(),
+            ));
+        }
+        if !Self::CUSTOM_FIELD_TYPES.contains(&field_type.as_str()) {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Unsupported field type '{}'",
+                field_type
+            )));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO custom_fields (container_id, name, field_type, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![container_id, &name, &field_type, &now],
+        )?;
+
+        Ok(CustomField {
+            id: conn.last_insert_rowid(),
+            container_id,
+            name,
+            field_type,
+            created_at: now,
+        })
+    }
+
+    pub fn rename_custom_field(&self, id: i64, name: String) -> Result<()> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Field name cannot be empty".to_string(),
+            ));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE custom_fields SET name = ?1 WHERE id = ?2",
+            params![&name, id],
+        )?;
+        if updated == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        Ok(())
+    }
+
+    pub fn delete_custom_field(&self, id: i64) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM transaction_custom_values WHERE field_id = ?1", [id])?;
+        tx.execute("DELETE FROM custom_fields WHERE id = ?1", [id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn set_transaction_custom_value(
+        &self,
+        transaction_id: i64,
+        field_id: i64,
+        value: Option<String>,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let (field_type, matches_container): (String, bool) = conn.query_row(
+            "SELECT f.field_type, f.container_id = t.container_id
+             FROM custom_fields f, transactions t
+             WHERE f.id = ?1 AND t.id = ?2",
+            params![field_id, transaction_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        if !matches_container {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Field belongs to a different container".to_string(),
+            ));
+        }
+
+        let value = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        match value {
+            Some(value) => {
+                Self::validate_custom_value(&field_type, &value)?;
+                conn.execute(
+                    "INSERT INTO transaction_custom_values (transaction_id, field_id, value) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(transaction_id, field_id) DO UPDATE SET value = excluded.value",
+                    params![transaction_id, field_id, &value],
+                )?;
+            }
+            None => {
+                conn.execute(
+                    "DELETE FROM transaction_custom_values WHERE transaction_id = ?1 AND field_id = ?2",
+                    params![transaction_id, field_id],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_transaction_custom_values(&self, transaction_id: i64) -> Result<Vec<CustomFieldValue>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT f.id, f.name, f.field_type, v.value
+             FROM transaction_custom_values v
+             JOIN custom_fields f ON f.id = v.field_id
+             WHERE v.transaction_id = ?1
+             ORDER BY f.name ASC",
+        )?;
+        let values = stmt.query_map([transaction_id], |row| {
+            Ok(CustomFieldValue {
+                field_id: row.get(0)?,
+                name: row.get(1)?,
+                field_type: row.get(2)?,
+                value: row.get(3)?,
+            })
+        })?;
+        values.collect()
+    }
+
+    fn validate_custom_value(field_type: &str, value: &str) -> Result<()> {
+        let valid = match field_type {
+            "number" => value.parse::<f64>().is_ok(),
+            "date" => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+            "boolean" => value == "true" || value == "false",
+            _ => true,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(rusqlite::Error::InvalidParameterName(format!(
+                "Invalid {} value '{}'",
+                field_type, value
+            )))
+        }
+    }
+
+    pub fn set_transaction_location(
+        &self,
+        transaction_id: i64,
+        latitude: f64,
+        longitude: f64,
+        label: Option<String>,
+    ) -> Result<TransactionLocation> {
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Coordinates are out of range".to_string(),
+            ));
+        }
+        let label = label.unwrap_or_default().trim().to_string();
+        Self::ensure_max_chars("Location", &label, Self::MAX_NAME_CHARS)?;
+
+        let conn = self.conn.lock().unwrap();
+        Self::fetch_transaction(&conn, transaction_id)?;
+        conn.execute(
+            "INSERT INTO transaction_locations (transaction_id, latitude, longitude, label) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(transaction_id) DO UPDATE SET
+                latitude = excluded.latitude,
+                longitude = excluded.longitude,
+                label = excluded.label",
+            params![transaction_id, latitude, longitude, &label],
+        )?;
+
+        Ok(TransactionLocation {
+            transaction_id,
+            latitude,
+            longitude,
+            label,
+        })
+    }
+
+    pub fn get_transaction_location(&self, transaction_id: i64) -> Result<Option<TransactionLocation>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT transaction_id, latitude, longitude, label FROM transaction_locations WHERE transaction_id = ?1",
+            [transaction_id],
+            |row| {
+                Ok(TransactionLocation {
+                    transaction_id: row.get(0)?,
+                    latitude: row.get(1)?,
+                    longitude: row.get(2)?,
+                    label: row.get(3)?,
+                })
+            },
+        )
+        .optional()
+    }
+
+    pub fn clear_transaction_location(&self, transaction_id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM transaction_locations WHERE transaction_id = ?1",
+            [transaction_id],
+        )?;
+        Ok(())
+    }
+
+    /// Income and expense per location. Labelled locations are grouped by
+    /// label, unlabelled ones by coordinates rounded to roughly 100 metres.
+    pub fn get_spending_by_location(
+        &self,
+        container_id: i64,
+        start_date: Option<String>,
+        end_date: Option<String>,
+    ) -> Result<Vec<LocationSummary>> {
+        let start_date = match start_date {
+            Some(value) => format!("{} 00:00:00", Self::parse_day(&value)?.format("%Y-%m-%d")),
+            None => String::new(),
+        };
+        let end_date = match end_date {
+            Some(value) => format!("{} 23:59:59", Self::parse_day(&value)?.format("%Y-%m-%d")),
+            None => "9999-12-31 23:59:59".to_string(),
+        };
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT COALESCE(NULLIF(l.label, ''), printf('%.3f, %.3f', l.latitude, l.longitude)) AS location,
+                    AVG(l.latitude),
+                    AVG(l.longitude),
+                    COALESCE(SUM(CASE WHEN COALESCE(c.category_type, 'expense') = 'income' THEN ABS(t.amount) ELSE 0 END), 0),
+                    COALESCE(SUM(CASE WHEN COALESCE(c.category_type, 'expense') = 'expense' THEN ABS(t.amount) ELSE 0 END), 0),
+                    COUNT(*)
+             FROM transaction_locations l
+             JOIN transactions t ON t.id = l.transaction_id
+             LEFT JOIN categories c ON c.name = t.category
+             WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.transfer_id IS NULL
+               AND t.date >= ?2 AND t.date <= ?3
+             GROUP BY location",
+        )?;
+        let rows = stmt.query_map(params![container_id, &start_date, &end_date], |row| {
+            let income: i64 = row.get(3)?;
+            let expense: i64 = row.get(4)?;
+            Ok(LocationSummary {
+                location: row.get(0)?,
+                latitude: row.get(1)?,
+                longitude: row.get(2)?,
+                income,
+                expense,
+                net: income - expense,
+                transaction_count: row.get(5)?,
+            })
+        })?;
+        let mut summaries = rows.collect::<Result<Vec<_>>>()?;
+        summaries.sort_by(|a, b| b.net.cmp(&a.net).then_with(|| a.location.cmp(&b.location)));
+
+        Ok(summaries)
+    }
+
+    /// Prior versions of a transaction, newest first. Each entry holds the
+    /// values the transaction had until `replaced_at`.
+    pub fn get_transaction_history(&self, transaction_id: i64) -> Result<Vec<TransactionVersion>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, transaction_id, amount, description, category, date, COALESCE(account_id, 0), replaced_at
+             FROM transaction_versions
+             WHERE transaction_id = ?1
+             ORDER BY id DESC",
+        )?;
+        let versions = stmt.query_map([transaction_id], |row| {
+            Ok(TransactionVersion {
+                id: row.get(0)?,
+                transaction_id: row.get(1)?,
+                amount: row.get(2)?,
+                description: row.get(3)?,
+                category: row.get(4)?,
+                date: row.get(5)?,
+                account_id: row.get(6)?,
+                replaced_at: row.get(7)?,
+            })
+        })?;
+        versions.collect()
+    }
+
+    /// Staff activity worth a second look during `period` (`YYYY-MM` or
+    /// `YYYY`): days with many deleted transactions (`frequent_deletions`),
+    /// income removed within a day of being recorded (`void_after_sale`) and
+    /// amount edits made after the transaction's month had ended
+    /// (`late_amount_edit`).
+    pub fn get_risk_alerts(&self, container_id: i64, period: String) -> Result<Vec<RiskAlert>> {
+        let conn = self.conn.lock().unwrap();
+        let (start_date, end_date) = if period.len() == 7 {
+            Self::month_range(&period)?
+        } else {
+            Self::year_range(&period)?
+        };
+        let mut alerts = Vec::new();
+
+        let mut by_day: std::collections::BTreeMap<String, Vec<(i64, i64)>> = std::collections::BTreeMap::new();
+        {
+            let mut stmt = conn.prepare(
+                "SELECT date(d.deleted_at), d.transaction_id, ABS(d.amount)
+                 FROM transaction_deletions d
+                 WHERE d.container_id = ?1 AND d.deleted_at >= ?2 AND d.deleted_at <= ?3
+                 ORDER BY d.id",
+            )?;
+            let rows = stmt.query_map(params![container_id, &start_date, &end_date], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))
+            })?;
+            for row in rows {
+                let (day, transaction_id, amount) = row?;
+                by_day.entry(day).or_default().push((transaction_id, amount));
+            }
+        }
+        for (day, deletions) in by_day {
+            if deletions.len() < Self::RISK_DELETIONS_PER_DAY {
+                continue;
+            }
+            alerts.push(RiskAlert {
+                kind: "frequent_deletions".to_string(),
+                date: day,
+                transaction_ids: deletions.iter().map(|(id, _)| *id).collect(),
+                amount: deletions.iter().map(|(_, amount)| amount).sum(),
+            });
+        }
+
+        {
+            let mut stmt = conn.prepare(
+                "SELECT d.transaction_id, ABS(d.amount), d.deleted_at
+                 FROM transaction_deletions d
+                 LEFT JOIN categories c ON c.name = d.category
+                 WHERE d.container_id = ?1 AND d.deleted_at >= ?2 AND d.deleted_at <= ?3
+                   AND d.transfer_id IS NULL
+                   AND COALESCE(c.category_type, 'expense') = 'income'
+                   AND julianday(d.deleted_at) - julianday((
+                       SELECT MAX(a.changed_at) FROM audit_log a
+                       WHERE a.entity = 'transactions' AND a.operation = 'created'
+                         AND a.entity_id = d.transaction_id AND a.changed_at <= d.deleted_at
+                   )) <= 1.0
+                 ORDER BY d.deleted_at",
+            )?;
+            let rows = stmt.query_map(params![container_id, &start_date, &end_date], |row| {
+                Ok(RiskAlert {
+                    kind: "void_after_sale".to_string(),
+                    transaction_ids: vec![row.get(0)?],
+                    amount: row.get(1)?,
+                    date: row.get(2)?,
+                })
+            })?;
+            for row in rows {
+                alerts.push(row?);
+            }
+        }
+
+        {
+            let mut stmt = conn.prepare(
+                "SELECT v.transaction_id,
+                        ABS(COALESCE((SELECT n.amount FROM transaction_versions n
+                                      WHERE n.transaction_id = v.transaction_id AND n.id > v.id
+                                      ORDER BY n.id LIMIT 1), t.amount) - v.amount) AS difference,
+                        v.replaced_at
+                 FROM transaction_versions v
+                 JOIN transactions t ON t.id = v.transaction_id
+                 WHERE t.container_id = ?1 AND v.replaced_at >= ?2 AND v.replaced_at <= ?3
+                   AND date(v.replaced_at) >= date(v.date, 'start of month', '+1 month')
+                 ORDER BY v.replaced_at",
+            )?;
+            let rows = stmt.query_map(params![container_id, &start_date, &end_date], |row| {
+                Ok(RiskAlert {
+                    kind: "late_amount_edit".to_string(),
+                    transaction_ids: vec![row.get(0)?],
+                    amount: row.get(1)?,
+                    date: row.get(2)?,
+                })
+            })?;
+            for row in rows {
+                let alert = row?;
+                if alert.amount != 0 {
+                    alerts.push(alert);
+                }
+            }
+        }
+
+        alerts.sort_by(|a, b| a.date.cmp(&b.date));
+        Ok(alerts)
+    }
+
+    pub fn get_transaction_comments(&self, transaction_id: i64) -> Result<Vec<TransactionComment>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, transaction_id, author, body, created_at
+             FROM transaction_comments
+             WHERE transaction_id = ?1
+             ORDER BY created_at ASC, id ASC",
+        )?;
+        let comments = stmt.query_map([transaction_id], |row| {
+            Ok(TransactionComment {
+                id: row.get(0)?,
+                transaction_id: row.get(1)?,
+                author: row.get(2)?,
+                body: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?;
+        comments.collect()
+    }
+
+    pub fn add_transaction_comment(
+        &self,
+        transaction_id: i64,
+        author: String,
+        body: String,
+    ) -> Result<TransactionComment> {
+        Self::ensure_max_chars("Author", &author, Self::MAX_NAME_CHARS)?;
+        Self::ensure_max_chars("Comment", &body, Self::MAX_COMMENT_CHARS)?;
+        let author = author.trim().to_string();
+        let body = body.trim().to_string();
+        if author.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Comment author cannot be empty".to_string(),
+            ));
+        }
+        if body.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Comment cannot be empty".to_string(),
+            ));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT id FROM transactions WHERE id = ?1 AND deleted_at IS NULL", [transaction_id], |row| {
+            row.get::<_, i64>(0)
+        })?;
+
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
         conn.execute(
-            "INSERT INTO transactions (amount, description, category, date, container_id, account_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            [
-                &transaction.amount.to_string(),
-                &description,
+            "INSERT INTO transaction_comments (transaction_id, author, body, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![transaction_id, &author, &body, &now],
+        )?;
+
+        Ok(TransactionComment {
+            id: conn.last_insert_rowid(),
+            transaction_id,
+            author,
+            body,
+            created_at: now,
+        })
+    }
+
+    pub fn delete_transaction_comment(&self, id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute("DELETE FROM transaction_comments WHERE id = ?1", [id])?;
+        if deleted == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        Ok(())
+    }
+
+    pub fn issue_voucher(
+        &self,
+        container_id: i64,
+        code: String,
+        amount: i64,
+        account_id: i64,
+        expires_at: Option<String>,
+    ) -> Result<Voucher> {
+        let code = code.trim().to_uppercase();
+        if code.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Voucher code cannot be empty".to_string(),
+            ));
+        }
+        if amount <= 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Voucher amount must be positive".to_string(),
+            ));
+        }
+        let expires_at = match expires_at.as_deref().filter(|v| !v.trim().is_empty()) {
+            Some(value) => Some(Self::parse_day(value)?.format("%Y-%m-%d").to_string()),
+            None => None,
+        };
+
+        let mut conn = self.conn.lock().unwrap();
+        let account_container: i64 = conn.query_row(
+            "SELECT container_id FROM accounts WHERE id = ?1",
+            [account_id],
+            |row| row.get(0),
+        )?;
+        if account_container != container_id {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Account belongs to a different container".to_string(),
+            ));
+        }
+
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let description = format!("Voucher {}", code);
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR IGNORE INTO accounts (name, account_type, opening_balance, container_id, created_at)
+             VALUES (?1, 'liability', 0, ?2, ?3)",
+            params![Self::VOUCHER_LIABILITY_ACCOUNT, container_id, &now],
+        )?;
+        let liability_account_id: i64 = tx.query_row(
+            "SELECT id FROM accounts WHERE name = ?1 AND container_id = ?2",
+            params![Self::VOUCHER_LIABILITY_ACCOUNT, container_id],
+            |row| row.get(0),
+        )?;
+
+        // The cash received and the liability it creates move together, so the pair
+        // is linked like a transfer and stays out of the profit and loss.
+        let transfer_id: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(transfer_id), 0) + 1 FROM transactions",
+            [],
+            |row| row.get(0),
+        )?;
+        for (leg_account, counterpart) in [(account_id, liability_account_id), (liability_account_id, account_id)] {
+            tx.execute(
+                "INSERT INTO transactions (amount, description, category, date, container_id, account_id, transfer_id, transfer_account_id)
+                 VALUES (?1, ?2, 'Transfer', ?3, ?4, ?5, ?6, ?7)",
+                params![amount, &description, &now, container_id, leg_account, transfer_id, counterpart],
+            )?;
+        }
+
+        tx.execute(
+            "INSERT INTO vouchers (container_id, code, amount, remaining, liability_account_id, issued_at, expires_at)
+             VALUES (?1, ?2, ?3, ?3, ?4, ?5, ?6)",
+            params![container_id, &code, amount, liability_account_id, &now, &expires_at],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.commit()?;
+
+        Ok(Voucher {
+            id,
+            container_id,
+            code,
+            amount,
+            remaining: amount,
+            liability_account_id,
+            issued_at: now,
+            expires_at,
+        })
+    }
+
+    pub fn redeem_voucher(
+        &self,
+        container_id: i64,
+        code: String,
+        amount: i64,
+        category: Option<String>,
+    ) -> Result<Voucher> {
+        if amount <= 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Redeemed amount must be positive".to_string(),
+            ));
+        }
+        let category = category
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| Self::DEFAULT_INCOME_CATEGORY.to_string());
+
+        let mut conn = self.conn.lock().unwrap();
+        let voucher = Self::fetch_voucher(&conn, container_id, &code.trim().to_uppercase())?;
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        if voucher.expires_at.as_deref().is_some_and(|expires| expires < today.as_str()) {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Voucher has expired".to_string(),
+            ));
+        }
+        if amount > voucher.remaining {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Amount exceeds the voucher balance".to_string(),
+            ));
+        }
+
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO transactions (amount, description, category, date, container_id, account_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                -amount,
+                format!("Voucher {}", voucher.code),
                 &category,
-                &date,
-                &transaction.container_id.to_string(),
-                &transaction.account_id.to_string(),
+                &now,
+                container_id,
+                voucher.liability_account_id
             ],
         )?;
+        let transaction_id = tx.last_insert_rowid();
+        tx.execute(
+            "INSERT INTO voucher_redemptions (voucher_id, amount, transaction_id, redeemed_at) VALUES (?1, ?2, ?3, ?4)",
+            params![voucher.id, amount, transaction_id, &now],
+        )?;
+        tx.execute(
+            "UPDATE vouchers SET remaining = remaining - ?1 WHERE id = ?2",
+            params![amount, voucher.id],
+        )?;
+        let voucher = Self::fetch_voucher(&tx, container_id, &voucher.code)?;
+        tx.commit()?;
 
-        let id = conn.last_insert_rowid();
-        
-        Ok(Transaction {
-            id,
-            amount: transaction.amount,
-            description,
-            category,
-            date,
-            container_id: transaction.container_id,
-            account_id: transaction.account_id,
-            transfer_id: 0,
-            transfer_account_id: 0,
+        Ok(voucher)
+    }
+
+    pub fn get_outstanding_vouchers(&self, container_id: i64) -> Result<VoucherReport> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, container_id, code, amount, remaining, liability_account_id, issued_at, expires_at
+             FROM vouchers
+             WHERE container_id = ?1 AND remaining > 0
+             ORDER BY issued_at ASC, id ASC",
+        )?;
+        let vouchers = stmt
+            .query_map([container_id], Self::voucher_from_row)?
+            .collect::<Result<Vec<Voucher>>>()?;
+        let total_outstanding = vouchers.iter().map(|v| v.remaining).sum();
+
+        Ok(VoucherReport {
+            vouchers,
+            total_outstanding,
+        })
+    }
+
+    fn fetch_voucher(conn: &Connection, container_id: i64, code: &str) -> Result<Voucher> {
+        conn.query_row(
+            "SELECT id, container_id, code, amount, remaining, liability_account_id, issued_at, expires_at
+             FROM vouchers
+             WHERE container_id = ?1 AND code = ?2",
+            params![container_id, code],
+            Self::voucher_from_row,
+        )
+    }
+
+    fn voucher_from_row(row: &rusqlite::Row) -> Result<Voucher> {
+        Ok(Voucher {
+            id: row.get(0)?,
+            container_id: row.get(1)?,
+            code: row.get(2)?,
+            amount: row.get(3)?,
+            remaining: row.get(4)?,
+            liability_account_id: row.get(5)?,
+            issued_at: row.get(6)?,
+            expires_at: row.get(7)?,
+        })
+    }
+
+    pub fn add_fund(&self, container_id: i64, name: String, grantor: String) -> Result<Fund> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Fund name cannot be empty".to_string(),
+            ));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO funds (container_id, name, grantor, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![container_id, &name, grantor.trim(), &now],
+        )?;
+
+        Self::fetch_fund(&conn, conn.last_insert_rowid())
+    }
+
+    pub fn get_funds(&self, container_id: i64) -> Result<Vec<Fund>> {
+        let conn = self.conn.lock().unwrap();
+        let fund_ids: Vec<i64> = {
+            let mut stmt = conn.prepare("SELECT id FROM funds WHERE container_id = ?1 ORDER BY name ASC")?;
+            let rows = stmt.query_map([container_id], |row| row.get::<_, i64>(0))?;
+            rows.collect::<Result<Vec<i64>>>()?
+        };
+        fund_ids
+            .into_iter()
+            .map(|fund_id| Self::fetch_fund(&conn, fund_id))
+            .collect()
+    }
+
+    pub fn delete_fund(&self, id: i64) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM transaction_funds WHERE fund_id = ?1", [id])?;
+        tx.execute("DELETE FROM funds WHERE id = ?1", [id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn set_transaction_fund(&self, transaction_id: i64, fund_id: Option<i64>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let transaction = Self::fetch_transaction(&conn, transaction_id)?;
+        match fund_id {
+            Some(fund_id) => {
+                if transaction.transfer_id != 0 {
+                    return Err(rusqlite::Error::InvalidParameterName(
+                        "Transfers cannot be assigned to a fund".to_string(),
+                    ));
+                }
+                let fund_container: i64 = conn.query_row(
+                    "SELECT container_id FROM funds WHERE id = ?1",
+                    [fund_id],
+                    |row| row.get(0),
+                )?;
+                if fund_container != transaction.container_id {
+                    return Err(rusqlite::Error::InvalidParameterName(
+                        "Fund belongs to a different container".to_string(),
+                    ));
+                }
+                conn.execute(
+                    "INSERT INTO transaction_funds (transaction_id, fund_id) VALUES (?1, ?2)
+                     ON CONFLICT(transaction_id) DO UPDATE SET fund_id = excluded.fund_id",
+                    params![transaction_id, fund_id],
+                )?;
+            }
+            None => {
+                conn.execute(
+                    "DELETE FROM transaction_funds WHERE transaction_id = ?1",
+                    [transaction_id],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_fund_report(&self, fund_id: i64) -> Result<FundReport> {
+        let conn = self.conn.lock().unwrap();
+        let fund = Self::fetch_fund(&conn, fund_id)?;
+
+        let spending: Vec<ProfitLossLine> = {
+            let mut stmt = conn.prepare(
+                "SELECT t.category, SUM(-t.amount) as total
+                 FROM transactions t
+                 JOIN transaction_funds f ON f.transaction_id = t.id
+                 WHERE f.fund_id = ?1 AND t.amount < 0 AND t.deleted_at IS NULL
+                 GROUP BY t.category
+                 ORDER BY total DESC",
+            )?;
+            let rows = stmt.query_map([fund_id], |row| {
+                Ok(ProfitLossLine {
+                    category: row.get(0)?,
+                    total: row.get(1)?,
+                })
+            })?;
+            rows.collect::<Result<Vec<ProfitLossLine>>>()?
+        };
+
+        let transactions: Vec<Transaction> = {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {}
+                 FROM transactions
+                 WHERE id IN (SELECT transaction_id FROM transaction_funds WHERE fund_id = ?1)
+                   AND deleted_at IS NULL
+                 ORDER BY date ASC, id ASC",
+                Self::TRANSACTION_COLUMNS
+            ))?;
+            let rows = stmt.query_map([fund_id], Self::transaction_from_row)?;
+            rows.collect::<Result<Vec<Transaction>>>()?
+        };
+
+        Ok(FundReport {
+            fund,
+            spending,
+            transactions,
         })
     }
 
-    pub fn add_transfer(
-        &self,
-        container_id: i64,
-        from_account_id: i64,
-        to_account_id: i64,
-        amount: i64,
-        description: Option<String>,
-        date: Option<String>,
-    ) -> Result<i64> {
-        if from_account_id == to_account_id {
+    fn fetch_fund(conn: &Connection, id: i64) -> Result<Fund> {
+        conn.query_row(
+            "SELECT f.id, f.container_id, f.name, f.grantor, f.created_at,
+                    COALESCE(SUM(CASE WHEN t.amount > 0 THEN t.amount ELSE 0 END), 0),
+                    COALESCE(SUM(CASE WHEN t.amount < 0 THEN -t.amount ELSE 0 END), 0)
+             FROM funds f
+             LEFT JOIN transaction_funds tf ON tf.fund_id = f.id
+             LEFT JOIN transactions t ON t.id = tf.transaction_id AND t.deleted_at IS NULL
+             WHERE f.id = ?1
+             GROUP BY f.id",
+            [id],
+            |row| {
+                let received: i64 = row.get(5)?;
+                let spent: i64 = row.get(6)?;
+                Ok(Fund {
+                    id: row.get(0)?,
+                    container_id: row.get(1)?,
+                    name: row.get(2)?,
+                    grantor: row.get(3)?,
+                    created_at: row.get(4)?,
+                    received,
+                    spent,
+                    remaining: received - spent,
+                })
+            },
+        )
+    }
+
+    pub fn add_envelope(&self, account_id: i64, name: String) -> Result<Envelope> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Envelope name cannot be empty".to_string(),
+            ));
+        }
+        Self::ensure_max_chars("Name", &name, Self::MAX_NAME_CHARS)?;
+
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO envelopes (account_id, name, created_at)
+             SELECT id, ?2, ?3 FROM accounts WHERE id = ?1",
+            params![account_id, &name, &now],
+        )?;
+        if conn.changes() == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+
+        Self::fetch_envelope(&conn, conn.last_insert_rowid())
+    }
+
+    pub fn get_envelopes(&self, account_id: i64) -> Result<Vec<Envelope>> {
+        let conn = self.conn.lock().unwrap();
+        Self::account_envelopes(&conn, account_id)
+    }
+
+    /// Deletes an envelope; whatever it held becomes unassigned again.
+    pub fn delete_envelope(&self, id: i64) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM envelope_entries WHERE envelope_id = ?1", [id])?;
+        tx.execute("DELETE FROM envelopes WHERE id = ?1", [id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Earmarks part of the account's unassigned balance for an envelope. A
+    /// negative amount hands money back from the envelope.
+    pub fn allocate_to_envelope(&self, envelope_id: i64, amount: i64) -> Result<Envelope> {
+        if amount == 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Amount cannot be zero".to_string(),
+            ));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let envelope = Self::fetch_envelope(&conn, envelope_id)?;
+        if amount > 0 {
+            let (account_balance, envelope_total) = Self::envelope_totals(&conn, envelope.account_id)?;
+            if amount > account_balance - envelope_total {
+                return Err(rusqlite::Error::InvalidParameterName(
+                    "Amount exceeds the account's unassigned balance".to_string(),
+                ));
+            }
+        } else if -amount > envelope.balance {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Envelope does not hold that much".to_string(),
+            ));
+        }
+
+        Self::insert_envelope_entry(&conn, envelope_id, amount, None)?;
+        Self::fetch_envelope(&conn, envelope_id)
+    }
+
+    pub fn move_between_envelopes(&self, from_id: i64, to_id: i64, amount: i64) -> Result<Vec<Envelope>> {
+        if amount <= 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Amount must be positive".to_string(),
+            ));
+        }
+        if from_id == to_id {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Cannot move money into the same envelope".to_string(),
+            ));
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        let from = Self::fetch_envelope(&conn, from_id)?;
+        let to = Self::fetch_envelope(&conn, to_id)?;
+        if from.account_id != to.account_id {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Envelopes belong to different accounts".to_string(),
+            ));
+        }
+        if amount > from.balance {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Envelope does not hold that much".to_string(),
+            ));
+        }
+
+        let tx = conn.transaction()?;
+        Self::insert_envelope_entry(&tx, from_id, -amount, None)?;
+        Self::insert_envelope_entry(&tx, to_id, amount, None)?;
+        tx.commit()?;
+
+        Ok(vec![
+            Self::fetch_envelope(&conn, from_id)?,
+            Self::fetch_envelope(&conn, to_id)?,
+        ])
+    }
+
+    /// Pays an expense on the envelope's account out of the envelope.
+    /// Deleting the transaction later releases the amount back.
+    pub fn spend_from_envelope(&self, envelope_id: i64, transaction_id: i64) -> Result<Envelope> {
+        let conn = self.conn.lock().unwrap();
+        let envelope = Self::fetch_envelope(&conn, envelope_id)?;
+        let transaction = Self::fetch_transaction(&conn, transaction_id)?;
+        if transaction.account_id != envelope.account_id {
             return Err(rusqlite::Error::InvalidParameterName(
-                "Source and destination accounts must be different".to_string(),
+                "Transaction belongs to a different account".to_string(),
             ));
         }
-        if amount <= 0 {
+        if transaction.amount >= 0 {
             return Err(rusqlite::Error::InvalidParameterName(
-                "Transfer amount must be positive".to_string(),
+                "Only outgoing transactions can be paid from an envelope".to_string(),
+            ));
+        }
+        if -transaction.amount > envelope.balance {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Envelope does not hold that much".to_string(),
+            ));
+        }
+        let already_spent: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM envelope_entries WHERE transaction_id = ?1",
+            [transaction_id],
+            |row| row.get(0),
+        )?;
+        if already_spent > 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Transaction is already paid from an envelope".to_string(),
             ));
         }
 
+        Self::insert_envelope_entry(&conn, envelope_id, transaction.amount, Some(transaction_id))?;
+        Self::fetch_envelope(&conn, envelope_id)
+    }
+
+    /// Compares the envelopes of an account with its real balance. A positive
+    /// difference is money not yet assigned to any envelope; a negative one
+    /// means the envelopes promise more than the account holds.
+    pub fn get_envelope_reconciliation(&self, account_id: i64) -> Result<EnvelopeReconciliation> {
         let conn = self.conn.lock().unwrap();
-        let date = Self::normalize_transaction_date(date)?;
-        let description = description.unwrap_or_else(|| "Transfer".to_string());
+        let (account_balance, envelope_total) = Self::envelope_totals(&conn, account_id)?;
+        let difference = account_balance - envelope_total;
+        Ok(EnvelopeReconciliation {
+            account_id,
+            account_balance,
+            envelope_total,
+            difference,
+            is_balanced: difference == 0,
+            envelopes: Self::account_envelopes(&conn, account_id)?,
+        })
+    }
 
-        let transfer_id: i64 = conn.query_row(
-            "SELECT COALESCE(MAX(transfer_id), 0) + 1 FROM transactions",
-            [],
-            |row| row.get(0),
+    fn envelope_totals(conn: &Connection, account_id: i64) -> Result<(i64, i64)> {
+        conn.query_row(
+            "SELECT a.opening_balance + COALESCE((SELECT SUM(t.amount) FROM transactions t WHERE t.account_id = a.id AND t.deleted_at IS NULL), 0),
+                    COALESCE((SELECT SUM(e.amount) FROM envelope_entries e
+                              JOIN envelopes v ON v.id = e.envelope_id
+                              WHERE v.account_id = a.id
+                                AND (e.transaction_id IS NULL
+                                     OR e.transaction_id IN (SELECT id FROM transactions WHERE deleted_at IS NULL))), 0)
+             FROM accounts a
+             WHERE a.id = ?1",
+            [account_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+    }
+
+    fn insert_envelope_entry(conn: &Connection, envelope_id: i64, amount: i64, transaction_id: Option<i64>) -> Result<()> {
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO envelope_entries (envelope_id, amount, transaction_id, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![envelope_id, amount, transaction_id, &now],
         )?;
+        Ok(())
+    }
 
-        let debit_amount = -amount.abs();
-        let credit_amount = amount.abs();
+    fn account_envelopes(conn: &Connection, account_id: i64) -> Result<Vec<Envelope>> {
+        let envelope_ids: Vec<i64> = {
+            let mut stmt = conn.prepare("SELECT id FROM envelopes WHERE account_id = ?1 ORDER BY name ASC")?;
+            let rows = stmt.query_map([account_id], |row| row.get::<_, i64>(0))?;
+            rows.collect::<Result<Vec<i64>>>()?
+        };
+        envelope_ids
+            .into_iter()
+            .map(|envelope_id| Self::fetch_envelope(conn, envelope_id))
+            .collect()
+    }
 
-        conn.execute(
-            "INSERT INTO transactions (amount, description, category, date, container_id, account_id, transfer_id, transfer_account_id)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            [
-                &debit_amount.to_string(),
-                &description,
-                "Transfer",
-                &date,
-                &container_id.to_string(),
-                &from_account_id.to_string(),
-                &transfer_id.to_string(),
-                &to_account_id.to_string(),
-            ],
+    fn fetch_envelope(conn: &Connection, id: i64) -> Result<Envelope> {
+        conn.query_row(
+            "SELECT v.id, v.account_id, v.name, COALESCE(SUM(e.amount), 0), v.created_at
+             FROM envelopes v
+             LEFT JOIN envelope_entries e ON e.envelope_id = v.id
+                 AND (e.transaction_id IS NULL
+                      OR e.transaction_id IN (SELECT id FROM transactions WHERE deleted_at IS NULL))
+             WHERE v.id = ?1
+             GROUP BY v.id",
+            [id],
+            |row| {
+                Ok(Envelope {
+                    id: row.get(0)?,
+                    account_id: row.get(1)?,
+                    name: row.get(2)?,
+                    balance: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            },
+        )
+    }
+
+    /// Tags a transaction, creating the tag in the transaction's container
+    /// on first use. Returns the transaction's tags afterwards.
+    pub fn add_transaction_tag(&self, transaction_id: i64, tag: String) -> Result<Vec<String>> {
+        let name = Self::normalize_tag(&tag);
+        if name.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Tag cannot be empty".to_string(),
+            ));
+        }
+        Self::ensure_max_chars("Tag", &name, Self::MAX_NAME_CHARS)?;
+
+        let mut conn = self.conn.lock().unwrap();
+        let transaction = Self::fetch_transaction(&conn, transaction_id)?;
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR IGNORE INTO tags (container_id, name, created_at) VALUES (?1, ?2, ?3)",
+            params![transaction.container_id, &name, &now],
+        )?;
+        tx.execute(
+            "INSERT OR IGNORE INTO transaction_tags (transaction_id, tag_id)
+             SELECT ?1, id FROM tags WHERE container_id = ?2 AND name = ?3",
+            params![transaction_id, transaction.container_id, &name],
         )?;
+        tx.commit()?;
+
+        Self::transaction_tags(&conn, transaction_id)
+    }
 
+    pub fn remove_transaction_tag(&self, transaction_id: i64, tag: String) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO transactions (amount, description, category, date, container_id, account_id, transfer_id, transfer_account_id)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            [
-                &credit_amount.to_string(),
-                &description,
-                "Transfer",
-                &date,
-                &container_id.to_string(),
-                &to_account_id.to_string(),
-                &transfer_id.to_string(),
-                &from_account_id.to_string(),
-            ],
+            "DELETE FROM transaction_tags
+             WHERE transaction_id = ?1
+               AND tag_id IN (SELECT g.id FROM tags g
+                              JOIN transactions t ON t.container_id = g.container_id
+                              WHERE t.id = ?1 AND g.name = ?2)",
+            params![transaction_id, Self::normalize_tag(&tag)],
         )?;
-
-        Ok(transfer_id)
+        Self::transaction_tags(&conn, transaction_id)
     }
 
-    pub fn get_transactions(&self, container_id: i64, limit: Option<i64>) -> Result<Vec<Transaction>> {
+    pub fn get_transaction_tags(&self, transaction_id: i64) -> Result<Vec<String>> {
         let conn = self.conn.lock().unwrap();
-        let query = match limit {
-            Some(l) => format!("SELECT id, amount, description, category, date, container_id, COALESCE(account_id, 0) as account_id, COALESCE(transfer_id, 0) as transfer_id, COALESCE(transfer_account_id, 0) as transfer_account_id FROM transactions WHERE container_id = {} ORDER BY date DESC LIMIT {}", container_id, l),
-            None => format!("SELECT id, amount, description, category, date, container_id, COALESCE(account_id, 0) as account_id, COALESCE(transfer_id, 0) as transfer_id, COALESCE(transfer_account_id, 0) as transfer_account_id FROM transactions WHERE container_id = {} ORDER BY date DESC", container_id),
-        };
+        Self::transaction_tags(&conn, transaction_id)
+    }
 
-        let mut stmt = conn.prepare(&query)?;
-        let transactions = stmt.query_map([], |row| {
-            Ok(Transaction {
+    pub fn get_tags(&self, container_id: i64) -> Result<Vec<Tag>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT g.id, g.container_id, g.name, COUNT(tt.transaction_id)
+             FROM tags g
+             LEFT JOIN transaction_tags tt ON tt.tag_id = g.id
+                 AND tt.transaction_id IN (SELECT id FROM transactions WHERE deleted_at IS NULL)
+             WHERE g.container_id = ?1
+             GROUP BY g.id
+             ORDER BY g.name ASC",
+        )?;
+        let rows = stmt.query_map([container_id], |row| {
+            Ok(Tag {
                 id: row.get(0)?,
-                amount: row.get(1)?,
-                description: row.get(2)?,
-                category: row.get(3)?,
-                date: row.get(4)?,
-                container_id: row.get(5)?,
-                account_id: row.get(6)?,
-                transfer_id: row.get(7)?,
-                transfer_account_id: row.get(8)?,
+                container_id: row.get(1)?,
+                name: row.get(2)?,
+                transaction_count: row.get(3)?,
             })
         })?;
+        rows.collect()
+    }
 
-        transactions.collect()
+    pub fn delete_tag(&self, id: i64) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM transaction_tags WHERE tag_id = ?1", [id])?;
+        tx.execute("DELETE FROM tags WHERE id = ?1", [id])?;
+        tx.commit()?;
+        Ok(())
     }
 
-    pub fn get_transactions_by_account(
+    pub fn get_profit_and_loss_for_tag(
         &self,
         container_id: i64,
-        account_id: i64,
-        limit: Option<i64>,
-    ) -> Result<Vec<Transaction>> {
+        tag: String,
+        start_date: String,
+        end_date: String,
+    ) -> Result<ProfitLossReport> {
+        let start_day = Self::parse_day(&start_date)?;
+        let end_day = Self::parse_day(&end_date)?;
+        if end_day < start_day {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "End date must not be before start date".to_string(),
+            ));
+        }
         let conn = self.conn.lock().unwrap();
-        let base = "SELECT id, amount, description, category, date, container_id, COALESCE(account_id, 0) as account_id, COALESCE(transfer_id, 0) as transfer_id, COALESCE(transfer_account_id, 0) as transfer_account_id
-                   FROM transactions
-                   WHERE container_id = ?1 AND account_id = ?2
-                   ORDER BY date DESC";
-        let query = match limit {
-            Some(l) => format!("{} LIMIT {}", base, l),
-            None => base.to_string(),
-        };
+        Self::profit_and_loss_for_tag(
+            &conn,
+            container_id,
+            format!("{} 00:00:00", start_day.format("%Y-%m-%d")),
+            format!("{} 23:59:59", end_day.format("%Y-%m-%d")),
+            Some(&Self::normalize_tag(&tag)),
+        )
+    }
 
-        let mut stmt = conn.prepare(&query)?;
-        let transactions = stmt.query_map(params![container_id, account_id], |row| {
-            Ok(Transaction {
-                id: row.get(0)?,
-                amount: row.get(1)?,
-                description: row.get(2)?,
-                category: row.get(3)?,
-                date: row.get(4)?,
-                container_id: row.get(5)?,
-                account_id: row.get(6)?,
-                transfer_id: row.get(7)?,
-                transfer_account_id: row.get(8)?,
-            })
-        })?;
+    fn transaction_tags(conn: &Connection, transaction_id: i64) -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(
+            "SELECT g.name FROM transaction_tags tt
+             JOIN tags g ON g.id = tt.tag_id
+             WHERE tt.transaction_id = ?1
+             ORDER BY g.name ASC",
+        )?;
+        let rows = stmt.query_map([transaction_id], |row| row.get::<_, String>(0))?;
+        rows.collect()
+    }
 
-        transactions.collect()
+    fn normalize_tag(tag: &str) -> String {
+        tag.trim().trim_start_matches('#').trim().to_lowercase()
     }
 
-    pub fn get_transactions_by_category(
-        &self,
-        container_id: i64,
-        category: String,
-        limit: Option<i64>,
-    ) -> Result<Vec<Transaction>> {
+    pub fn set_account_zakat(&self, account_id: i64, included: bool) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        let base = "SELECT id, amount, description, category, date, container_id, COALESCE(account_id, 0) as account_id, COALESCE(transfer_id, 0) as transfer_id, COALESCE(transfer_account_id, 0) as transfer_account_id
-                   FROM transactions
-                   WHERE container_id = ?1 AND category = ?2
-                   ORDER BY date DESC";
-        let query = match limit {
-            Some(l) => format!("{} LIMIT {}", base, l),
-            None => base.to_string(),
+        if included {
+            conn.execute(
+                "INSERT OR IGNORE INTO zakat_accounts (account_id) VALUES (?1)",
+                [account_id],
+            )?;
+        } else {
+            conn.execute("DELETE FROM zakat_accounts WHERE account_id = ?1", [account_id])?;
+        }
+        Ok(())
+    }
+
+    pub fn get_zakat_account_ids(&self, container_id: i64) -> Result<Vec<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT z.account_id FROM zakat_accounts z
+             JOIN accounts a ON a.id = z.account_id
+             WHERE a.container_id = ?1
+             ORDER BY z.account_id ASC",
+        )?;
+        let rows = stmt.query_map([container_id], |row| row.get::<_, i64>(0))?;
+        rows.collect()
+    }
+
+    pub fn set_zakat_settings(&self, rate_bps: i64, nisab: i64) -> Result<()> {
+        if !(0..=10_000).contains(&rate_bps) {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Zakat rate must be between 0 and 10000 basis points".to_string(),
+            ));
+        }
+        if nisab < 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Nisab cannot be negative".to_string(),
+            ));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        Self::write_setting(&conn, "zakat_rate_bps", &rate_bps.to_string())?;
+        Self::write_setting(&conn, "zakat_nisab", &nisab.to_string())
+    }
+
+    /// Zakat perniagaan for a year: flagged asset balances minus flagged
+    /// liabilities at the period end. Without flagged accounts the period's
+    /// net income is used as the base instead.
+    pub fn get_zakat_report(&self, container_id: i64, period: String) -> Result<ZakatReport> {
+        let conn = self.conn.lock().unwrap();
+        let rate_bps = Self::read_setting(&conn, "zakat_rate_bps")?
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(Self::DEFAULT_ZAKAT_RATE_BPS);
+        let nisab = Self::read_setting(&conn, "zakat_nisab")?
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(0);
+
+        let (start_date, end_date) = Self::year_range_last_known(&conn, container_id, &period)?;
+        let flagged: std::collections::HashSet<i64> = {
+            let mut stmt = conn.prepare("SELECT account_id FROM zakat_accounts")?;
+            let rows = stmt.query_map([], |row| row.get::<_, i64>(0))?;
+            rows.collect::<Result<std::collections::HashSet<i64>>>()?
         };
 
-        let mut stmt = conn.prepare(&query)?;
-        let transactions = stmt.query_map(params![container_id, category], |row| {
-            Ok(Transaction {
-                id: row.get(0)?,
-                amount: row.get(1)?,
-                description: row.get(2)?,
-                category: row.get(3)?,
-                date: row.get(4)?,
-                container_id: row.get(5)?,
-                account_id: row.get(6)?,
-                transfer_id: row.get(7)?,
-                transfer_account_id: row.get(8)?,
-            })
-        })?;
+        let profit_loss =
+            Self::profit_and_loss_for_range(&conn, container_id, start_date.clone(), end_date.clone())?;
+        let balance_sheet =
+            Self::balance_sheet_for_range(&conn, container_id, start_date.clone(), end_date.clone())?;
+
+        let assets: Vec<AccountBalance> = balance_sheet
+            .assets
+            .into_iter()
+            .filter(|account| flagged.contains(&account.id))
+            .collect();
+        let liabilities: Vec<AccountBalance> = balance_sheet
+            .liabilities
+            .into_iter()
+            .filter(|account| flagged.contains(&account.id))
+            .collect();
+        let total_assets: i64 = assets.iter().map(|a| a.balance).sum();
+        let total_liabilities: i64 = liabilities.iter().map(|a| a.balance).sum();
 
-        transactions.collect()
+        let (basis, zakat_base) = if assets.is_empty() && liabilities.is_empty() {
+            ("profit", profit_loss.net_income)
+        } else {
+            ("assets", total_assets - total_liabilities)
+        };
+        let zakat_due = if zakat_base > 0 && zakat_base >= nisab {
+            (zakat_base as i128 * rate_bps as i128 / 10_000) as i64
+        } else {
+            0
+        };
+
+        Ok(ZakatReport {
+            period_start: start_date,
+            period_end: end_date,
+            rate_bps,
+            nisab,
+            basis: basis.to_string(),
+            assets,
+            liabilities,
+            total_assets,
+            total_liabilities,
+            net_income: profit_loss.net_income,
+            zakat_base,
+            zakat_due,
+        })
     }
 
-    pub fn update_transaction(
-        &self,
-        id: i64,
-        amount: i64,
-        description: String,
-        category: String,
-        account_id: i64,
-    ) -> Result<Transaction> {
+    pub fn add_renewal(&self, renewal: NewRenewal) -> Result<Renewal> {
+        let item = renewal.item.trim().to_string();
+        if item.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Renewal item cannot be empty".to_string(),
+            ));
+        }
+        if renewal.cost <= 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Renewal cost must be positive".to_string(),
+            ));
+        }
+        let interval_months = renewal.interval_months.unwrap_or(12);
+        if interval_months == 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Renewal interval must be at least one month".to_string(),
+            ));
+        }
+        let renewal_date = Self::parse_day(&renewal.renewal_date)?.format("%Y-%m-%d").to_string();
+        let category = renewal
+            .category
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| Self::DEFAULT_RENEWAL_CATEGORY.to_string());
+
+        let conn = self.conn.lock().unwrap();
+        let account_container: i64 = conn.query_row(
+            "SELECT container_id FROM accounts WHERE id = ?1",
+            [renewal.account_id],
+            |row| row.get(0),
+        )?;
+        if account_container != renewal.container_id {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Account belongs to a different container".to_string(),
+            ));
+        }
+
+        conn.execute(
+            "INSERT INTO renewals (container_id, item, cost, renewal_date, account_id, category, interval_months)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                renewal.container_id,
+                &item,
+                renewal.cost,
+                &renewal_date,
+                renewal.account_id,
+                &category,
+                interval_months
+            ],
+        )?;
+
+        Self::fetch_renewal(&conn, conn.last_insert_rowid())
+    }
+
+    pub fn get_renewals(&self, container_id: i64) -> Result<Vec<Renewal>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM renewals WHERE container_id = ?1 ORDER BY renewal_date ASC, id ASC",
+            Self::RENEWAL_COLUMNS
+        ))?;
+        let renewals = stmt.query_map([container_id], Self::renewal_from_row)?;
+        renewals.collect()
+    }
+
+    pub fn get_upcoming_renewals(&self, container_id: i64, days: i64) -> Result<Vec<Renewal>> {
+        let conn = self.conn.lock().unwrap();
+        let horizon = (chrono::Local::now().date_naive() + chrono::Duration::days(days.max(0)))
+            .format("%Y-%m-%d")
+            .to_string();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM renewals
+             WHERE container_id = ?1 AND renewal_date <= ?2
+             ORDER BY renewal_date ASC, id ASC",
+            Self::RENEWAL_COLUMNS
+        ))?;
+        let renewals = stmt.query_map(params![container_id, &horizon], Self::renewal_from_row)?;
+        renewals.collect()
+    }
+
+    pub fn pay_renewal(&self, id: i64, date: Option<String>) -> Result<Renewal> {
+        let mut conn = self.conn.lock().unwrap();
+        let renewal = Self::fetch_renewal(&conn, id)?;
+        let date = Self::normalize_transaction_date(date)?;
+        let next_date = Self::parse_day(&renewal.renewal_date)?
+            .checked_add_months(chrono::Months::new(renewal.interval_months))
+            .ok_or_else(|| rusqlite::Error::InvalidParameterName("Invalid renewal date".to_string()))?
+            .format("%Y-%m-%d")
+            .to_string();
+
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO transactions (amount, description, category, date, container_id, account_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                -renewal.cost,
+                &renewal.item,
+                &renewal.category,
+                &date,
+                renewal.container_id,
+                renewal.account_id
+            ],
+        )?;
+        let transaction_id = tx.last_insert_rowid();
+        tx.execute(
+            "UPDATE renewals SET renewal_date = ?1, last_paid_at = ?2, last_transaction_id = ?3 WHERE id = ?4",
+            params![&next_date, &date, transaction_id, id],
+        )?;
+        let renewal = Self::fetch_renewal(&tx, id)?;
+        tx.commit()?;
+
+        Ok(renewal)
+    }
+
+    pub fn delete_renewal(&self, id: i64) -> Result<()> {
         let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM renewals WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    fn fetch_renewal(conn: &Connection, id: i64) -> Result<Renewal> {
+        conn.query_row(
+            &format!("SELECT {} FROM renewals WHERE id = ?1", Self::RENEWAL_COLUMNS),
+            [id],
+            Self::renewal_from_row,
+        )
+    }
+
+    fn renewal_from_row(row: &rusqlite::Row) -> Result<Renewal> {
+        Ok(Renewal {
+            id: row.get(0)?,
+            container_id: row.get(1)?,
+            item: row.get(2)?,
+            cost: row.get(3)?,
+            renewal_date: row.get(4)?,
+            account_id: row.get(5)?,
+            category: row.get(6)?,
+            interval_months: row.get(7)?,
+            last_paid_at: row.get(8)?,
+            last_transaction_id: row.get(9)?,
+        })
+    }
+
+    pub fn add_recurring_rule(&self, rule: NewRecurringRule) -> Result<RecurringRule> {
+        let description = rule.description.trim().to_string();
+        if description.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Description cannot be empty".to_string(),
+            ));
+        }
+        Self::ensure_max_chars("Description", &description, Self::MAX_DESCRIPTION_CHARS)?;
+        if rule.amount == 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Amount cannot be zero".to_string(),
+            ));
+        }
+        let frequency = rule.frequency.trim().to_lowercase();
+        if !Self::RECURRING_FREQUENCIES.contains(&frequency.as_str()) {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Frequency must be one of: {}",
+                Self::RECURRING_FREQUENCIES.join(", ")
+            )));
+        }
+        let interval = rule.interval.unwrap_or(1);
+        if interval == 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Interval must be at least one".to_string(),
+            ));
+        }
+        let start_date = Self::parse_day(&rule.start_date)?.format("%Y-%m-%d").to_string();
+        let end_date = match rule.end_date.as_deref().filter(|v| !v.trim().is_empty()) {
+            Some(value) => {
+                let end = Self::parse_day(value)?.format("%Y-%m-%d").to_string();
+                if end < start_date {
+                    return Err(rusqlite::Error::InvalidParameterName(
+                        "End date must not be before start date".to_string(),
+                    ));
+                }
+                Some(end)
+            }
+            None => None,
+        };
+        let category = rule
+            .category
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| Self::DEFAULT_FALLBACK_CATEGORY.to_string());
 
-        let transfer_id: Option<i64> = conn.query_row(
-            "SELECT transfer_id FROM transactions WHERE id = ?1",
-            [id],
+        let conn = self.conn.lock().unwrap();
+        let account_container: i64 = conn.query_row(
+            "SELECT container_id FROM accounts WHERE id = ?1",
+            [rule.account_id],
             |row| row.get(0),
         )?;
-
-        if transfer_id.is_some() {
+        if account_container != rule.container_id {
             return Err(rusqlite::Error::InvalidParameterName(
-                "Cannot update transfer transaction".to_string(),
+                "Account belongs to a different container".to_string(),
             ));
         }
-        
-        conn.execute(
-            "UPDATE transactions SET amount = ?1, description = ?2, category = ?3, account_id = ?4 WHERE id = ?5",
-            params![amount, description, category, account_id, id],
-        )?;
 
-        let transaction = conn.query_row(
-            "SELECT id, amount, description, category, date, container_id, COALESCE(account_id, 0) as account_id, COALESCE(transfer_id, 0) as transfer_id, COALESCE(transfer_account_id, 0) as transfer_account_id FROM transactions WHERE id = ?1",
-            [id],
-            |row| {
-                Ok(Transaction {
-                    id: row.get(0)?,
-                    amount: row.get(1)?,
-                    description: row.get(2)?,
-                    category: row.get(3)?,
-                    date: row.get(4)?,
-                    container_id: row.get(5)?,
-                    account_id: row.get(6)?,
-                    transfer_id: row.get(7)?,
-                    transfer_account_id: row.get(8)?,
-                })
-            },
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO recurring_rules
+                (container_id, account_id, amount, description, category, frequency, interval_count,
+                 start_date, end_date, next_due, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?8, ?10)",
+            params![
+                rule.container_id,
+                rule.account_id,
+                rule.amount,
+                &description,
+                &category,
+                &frequency,
+                interval,
+                &start_date,
+                &end_date,
+                &now
+            ],
         )?;
 
-        Ok(transaction)
+        Self::fetch_recurring_rule(&conn, conn.last_insert_rowid())
     }
 
-    pub fn get_monthly_balance(&self, container_id: i64) -> Result<i64> {
+    pub fn get_recurring_rules(&self, container_id: i64) -> Result<Vec<RecurringRule>> {
         let conn = self.conn.lock().unwrap();
-        let current_month = chrono::Local::now().format("%Y-%m").to_string();
-        
-        let balance: i64 = conn.query_row(
-            "SELECT COALESCE(SUM(amount), 0) FROM transactions WHERE container_id = ?1 AND date LIKE ?2 AND transfer_id IS NULL",
-            [&container_id.to_string(), &format!("{}%", current_month)],
-            |row| row.get(0),
-        )?;
-
-        Ok(balance)
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM recurring_rules
+             WHERE container_id = ?1
+             ORDER BY next_due IS NULL, next_due ASC, id ASC",
+            Self::RECURRING_RULE_COLUMNS
+        ))?;
+        let rules = stmt.query_map([container_id], Self::recurring_rule_from_row)?;
+        rules.collect()
     }
 
-    pub fn get_all_time_balance(&self, container_id: i64) -> Result<i64> {
+    /// Pauses or resumes a rule. A resumed rule catches up on the dates it
+    /// missed the next time rules are run.
+    pub fn set_recurring_rule_active(&self, id: i64, active: bool) -> Result<RecurringRule> {
         let conn = self.conn.lock().unwrap();
-        
-        let balance: i64 = conn.query_row(
-            "SELECT COALESCE(SUM(amount), 0) FROM transactions WHERE container_id = ?1 AND transfer_id IS NULL",
-            [container_id],
-            |row| row.get(0),
+        let updated = conn.execute(
+            "UPDATE recurring_rules SET active = ?1 WHERE id = ?2",
+            params![active as i64, id],
         )?;
-
-        Ok(balance)
+        if updated == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        Self::fetch_recurring_rule(&conn, id)
     }
 
-    pub fn export_transactions_csv(&self, container_id: i64) -> Result<String> {
+    /// Removes a rule. Transactions it already created are kept.
+    pub fn delete_recurring_rule(&self, id: i64) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, amount, description, category, date FROM transactions WHERE container_id = ?1 ORDER BY date DESC"
-        )?;
-        
-        let mut csv = String::from("ID,Amount,Description,Category,Date\n");
-        let rows = stmt.query_map([container_id], |row| {
-            Ok((
-                row.get::<_, i64>(0)?,
-                row.get::<_, i64>(1)?,
-                row.get::<_, String>(2)?,
-                row.get::<_, String>(3)?,
-                row.get::<_, String>(4)?,
-            ))
-        })?;
+        conn.execute("DELETE FROM recurring_rules WHERE id = ?1", [id])?;
+        Ok(())
+    }
 
-        for row in rows {
-            let (id, amount, desc, cat, date) = row?;
-            let dollars = (amount as f64) / 100.0;
-            csv.push_str(&format!("{},{:.2},{},{},{}\n", id, dollars, desc, cat, date));
+    /// Inserts a transaction for every active rule occurrence due on or
+    /// before today and advances each schedule. Runs at startup and on
+    /// demand; an occurrence is never posted twice because the schedule
+    /// moves forward in the same database transaction.
+    pub fn run_recurring_rules(&self) -> Result<Vec<Transaction>> {
+        let mut conn = self.conn.lock().unwrap();
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let due: Vec<RecurringRule> = {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM recurring_rules
+                 WHERE active = 1 AND next_due IS NOT NULL AND next_due <= ?1
+                 ORDER BY next_due ASC, id ASC",
+                Self::RECURRING_RULE_COLUMNS
+            ))?;
+            let rows = stmt.query_map([&today], Self::recurring_rule_from_row)?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+
+        let tx = conn.transaction()?;
+        let mut created = Vec::new();
+        for rule in due {
+            let start = Self::parse_day(&rule.start_date)?;
+            let mut occurrences = rule.occurrences;
+            let mut next_due = rule.next_due.clone();
+            let mut last_transaction_id = rule.last_transaction_id;
+            let mut posted = 0;
+            while let Some(due_date) = next_due.clone().filter(|date| *date <= today) {
+                if posted >= Self::MAX_RECURRING_CATCH_UP {
+                    break;
+                }
+                tx.execute(
+                    "INSERT INTO transactions (amount, description, category, date, container_id, account_id)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        rule.amount,
+                        &rule.description,
+                        &rule.category,
+                        format!("{} 00:00:00", due_date),
+                        rule.container_id,
+                        rule.account_id
+                    ],
+                )?;
+                let transaction_id = tx.last_insert_rowid();
+                created.push(Self::fetch_transaction(&tx, transaction_id)?);
+                last_transaction_id = Some(transaction_id);
+                occurrences += 1;
+                posted += 1;
+                next_due = Self::recurring_occurrence(start, &rule.frequency, rule.interval, occurrences)
+                    .map(|date| date.format("%Y-%m-%d").to_string())
+                    .filter(|date| rule.end_date.as_ref().is_none_or(|end| date <= end));
+            }
+            tx.execute(
+                "UPDATE recurring_rules SET next_due = ?1, occurrences = ?2, last_transaction_id = ?3 WHERE id = ?4",
+                params![&next_due, occurrences, last_transaction_id, rule.id],
+            )?;
         }
+        tx.commit()?;
 
-        Ok(csv)
+        Ok(created)
     }
 
-    pub fn export_profit_loss_csv(&self, container_id: i64, year: String) -> Result<String> {
-        let report = self.get_profit_and_loss_for_year(container_id, year)?;
-        let mut csv = String::from("Bagian,Kategori,Nilai\n");
+    /// Fixed monthly spending: active expense rules converted to a monthly
+    /// amount, plus expenses in the last year that repeat roughly every
+    /// month under the same description. Amounts are positive. An entry is
+    /// flagged when its latest payment is higher than the one before.
+    pub fn get_subscriptions_report(&self, container_id: i64) -> Result<SubscriptionsReport> {
+        let conn = self.conn.lock().unwrap();
+        let today = chrono::Local::now().date_naive();
+        let since = (today - chrono::Months::new(Self::SUBSCRIPTION_LOOKBACK_MONTHS))
+            .format("%Y-%m-%d 00:00:00")
+            .to_string();
 
-        for line in report.income {
-            csv.push_str(&format!(
-                "Pendapatan,{},{}\n",
-                Self::csv_escape(&line.category),
-                Self::format_units_no_decimals(line.total)
-            ));
+        // Expense payments grouped by description: `series` holds ranges of
+        // `payments`, which is sorted by description and then date.
+        let payments: Vec<Transaction> = {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {}
+                 FROM transactions
+                 WHERE container_id = ?1 AND transfer_id IS NULL AND amount < 0 AND date >= ?2
+                   AND TRIM(description) != '' AND deleted_at IS NULL
+                 ORDER BY LOWER(TRIM(description)) ASC, date ASC, id ASC",
+                Self::TRANSACTION_COLUMNS
+            ))?;
+            let rows = stmt.query_map(params![container_id, &since], Self::transaction_from_row)?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+        let same_description = |a: &str, b: &str| a.trim().to_lowercase() == b.trim().to_lowercase();
+        let mut series: Vec<std::ops::Range<usize>> = Vec::new();
+        for (index, payment) in payments.iter().enumerate() {
+            match series.last_mut() {
+                Some(range) if same_description(&payments[range.start].description, &payment.description) => {
+                    range.end = index + 1;
+                }
+                _ => series.push(index..index + 1),
+            }
         }
-        csv.push_str(&format!(
-            "Pendapatan,Total Pendapatan,{}\n",
-            Self::format_units_no_decimals(report.total_income)
-        ));
+        let history = |description: &str| {
+            series
+                .iter()
+                .find(|range| same_description(&payments[range.start].description, description))
+                .map(|range| &payments[range.clone()])
+                .unwrap_or(&[])
+        };
+        let subscription = |source: &str,
+                            rule_id: Option<i64>,
+                            description: String,
+                            category: String,
+                            monthly_amount: i64,
+                            history: &[Transaction]| {
+            let last = history.last();
+            let previous = history.len().checked_sub(2).and_then(|index| history.get(index));
+            Subscription {
+                source: source.to_string(),
+                rule_id,
+                description,
+                category,
+                monthly_amount,
+                last_amount: last.map(|t| -t.amount),
+                previous_amount: previous.map(|t| -t.amount),
+                last_date: last.map(|t| t.date.clone()),
+                price_increased: matches!((last, previous), (Some(last), Some(previous)) if last.amount < previous.amount),
+            }
+        };
 
-        for line in report.expense {
-            csv.push_str(&format!(
-                "Beban,{},{}\n",
-                Self::csv_escape(&line.category),
-                Self::format_units_no_decimals(line.total)
+        let mut subscriptions = Vec::new();
+        {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM recurring_rules
+                 WHERE container_id = ?1 AND active = 1 AND next_due IS NOT NULL AND amount < 0
+                 ORDER BY description ASC",
+                Self::RECURRING_RULE_COLUMNS
+            ))?;
+            let rules = stmt.query_map([container_id], Self::recurring_rule_from_row)?;
+            for rule in rules {
+                let rule = rule?;
+                let amount = -rule.amount;
+                let interval = rule.interval as i64;
+                let monthly_amount = match rule.frequency.as_str() {
+                    "daily" => amount * 365 / (12 * interval),
+                    "weekly" => amount * 52 / (12 * interval),
+                    "yearly" => amount / (12 * interval),
+                    _ => amount / interval,
+                };
+                let payments = history(&rule.description);
+                subscriptions.push(subscription(
+                    "rule",
+                    Some(rule.id),
+                    rule.description,
+                    rule.category,
+                    monthly_amount,
+                    payments,
+                ));
+            }
+        }
+
+        let (min_gap, max_gap) = Self::SUBSCRIPTION_GAP_DAYS;
+        for range in &series {
+            let entries = &payments[range.clone()];
+            let latest = &entries[entries.len() - 1];
+            if entries.len() < Self::SUBSCRIPTION_MIN_OCCURRENCES
+                || subscriptions
+                    .iter()
+                    .any(|s: &Subscription| same_description(&s.description, &latest.description))
+            {
+                continue;
+            }
+            let days: Vec<chrono::NaiveDate> = entries
+                .iter()
+                .filter_map(|t| Self::parse_day(&Self::date_only(&t.date)).ok())
+                .collect();
+            let monthly = days.len() == entries.len()
+                && days.windows(2).all(|pair| {
+                    let gap = (pair[1] - pair[0]).num_days();
+                    gap >= min_gap && gap <= max_gap
+                });
+            let recent = days
+                .last()
+                .is_some_and(|last| (today - *last).num_days() <= Self::SUBSCRIPTION_STALE_DAYS);
+            if !monthly || !recent {
+                continue;
+            }
+            subscriptions.push(subscription(
+                "detected",
+                None,
+                latest.description.clone(),
+                latest.category.clone(),
+                -latest.amount,
+                entries,
             ));
         }
-        csv.push_str(&format!(
-            "Beban,Total Beban,{}\n",
-            Self::format_units_no_decimals(report.total_expense)
-        ));
 
-        csv.push_str(&format!(
-            "Laba Bersih,,{}\n",
-            Self::format_units_no_decimals(report.net_income)
-        ));
+        let monthly_total = subscriptions.iter().map(|s| s.monthly_amount).sum();
+        Ok(SubscriptionsReport {
+            subscriptions,
+            monthly_total,
+        })
+    }
 
-        Ok(csv)
+    /// The `n`th occurrence counted from the start date. Months are always
+    /// added to the start date, so a rule starting on the 31st falls on the
+    /// last day of shorter months without drifting afterwards.
+    fn recurring_occurrence(
+        start: chrono::NaiveDate,
+        frequency: &str,
+        interval: u32,
+        n: u32,
+    ) -> Option<chrono::NaiveDate> {
+        let steps = interval.checked_mul(n)?;
+        match frequency {
+            "daily" => start.checked_add_days(chrono::Days::new(steps as u64)),
+            "weekly" => start.checked_add_days(chrono::Days::new(steps as u64 * 7)),
+            "monthly" => start.checked_add_months(chrono::Months::new(steps)),
+            "yearly" => start.checked_add_months(chrono::Months::new(steps.checked_mul(12)?)),
+            _ => None,
+        }
     }
 
-    pub fn export_balance_sheet_csv(&self, container_id: i64, year: String) -> Result<String> {
-        let report = self.get_balance_sheet_for_year(container_id, year)?;
-        let mut csv = String::from("Bagian,Akun,Saldo\n");
+    fn fetch_recurring_rule(conn: &Connection, id: i64) -> Result<RecurringRule> {
+        conn.query_row(
+            &format!("SELECT {} FROM recurring_rules WHERE id = ?1", Self::RECURRING_RULE_COLUMNS),
+            [id],
+            Self::recurring_rule_from_row,
+        )
+    }
 
-        for account in report.assets {
-            csv.push_str(&format!(
-                "Aset,{},{}\n",
-                Self::csv_escape(&account.name),
-                Self::format_units_no_decimals(account.balance)
-            ));
-        }
-        csv.push_str(&format!(
-            "Aset,Total Aset,{}\n",
-            Self::format_units_no_decimals(report.total_assets)
-        ));
+    fn recurring_rule_from_row(row: &rusqlite::Row) -> Result<RecurringRule> {
+        Ok(RecurringRule {
+            id: row.get(0)?,
+            container_id: row.get(1)?,
+            account_id: row.get(2)?,
+            amount: row.get(3)?,
+            description: row.get(4)?,
+            category: row.get(5)?,
+            frequency: row.get(6)?,
+            interval: row.get(7)?,
+            start_date: row.get(8)?,
+            end_date: row.get(9)?,
+            next_due: row.get(10)?,
+            occurrences: row.get(11)?,
+            active: row.get::<_, i64>(12)? == 1,
+            last_transaction_id: row.get(13)?,
+        })
+    }
 
-        for account in report.liabilities {
-            csv.push_str(&format!(
-                "Liabilitas,{},{}\n",
-                Self::csv_escape(&account.name),
-                Self::format_units_no_decimals(account.balance)
+    pub fn set_petty_cash(&self, account_id: i64, float_amount: i64, funding_account_id: i64) -> Result<PettyCash> {
+        if float_amount <= 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Float amount must be positive".to_string(),
             ));
         }
-        csv.push_str(&format!(
-            "Liabilitas,Total Liabilitas,{}\n",
-            Self::format_units_no_decimals(report.total_liabilities)
-        ));
-
-        for account in report.equity {
-            csv.push_str(&format!(
-                "Ekuitas,{},{}\n",
-                Self::csv_escape(&account.name),
-                Self::format_units_no_decimals(account.balance)
+        if account_id == funding_account_id {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Petty cash cannot be funded from itself".to_string(),
             ));
         }
-        csv.push_str(&format!(
-            "Ekuitas,Total Ekuitas,{}\n",
-            Self::format_units_no_decimals(report.total_equity)
-        ));
 
-        let total_liabilities_equity = report.total_liabilities + report.total_equity;
-        csv.push_str(&format!(
-            "Total Liabilitas & Ekuitas,,{}\n",
-            Self::format_units_no_decimals(total_liabilities_equity)
-        ));
+        let conn = self.conn.lock().unwrap();
+        Self::ensure_same_container_accounts(&conn, account_id, funding_account_id)?;
+        conn.execute(
+            "INSERT INTO petty_cash (account_id, float_amount, funding_account_id) VALUES (?1, ?2, ?3)
+             ON CONFLICT(account_id) DO UPDATE SET
+                float_amount = excluded.float_amount,
+                funding_account_id = excluded.funding_account_id",
+            params![account_id, float_amount, funding_account_id],
+        )?;
 
-        Ok(csv)
+        Self::fetch_petty_cash(&conn, account_id)
     }
 
-    pub fn export_transactions_detail_csv(&self, container_id: i64, year: String) -> Result<String> {
+    pub fn remove_petty_cash(&self, account_id: i64) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        let (start_date, end_date) = Self::year_range_last_known(&conn, container_id, &year)?;
+        conn.execute("DELETE FROM petty_cash WHERE account_id = ?1", [account_id])?;
+        Ok(())
+    }
 
-        let container_name: String = conn.query_row(
-            "SELECT name FROM containers WHERE id = ?1",
-            [container_id],
-            |row| row.get(0),
-        )?;
+    pub fn get_petty_cash_accounts(&self, container_id: i64) -> Result<Vec<PettyCash>> {
+        let conn = self.conn.lock().unwrap();
+        let account_ids: Vec<i64> = {
+            let mut stmt = conn.prepare(
+                "SELECT p.account_id FROM petty_cash p
+                 JOIN accounts a ON a.id = p.account_id
+                 WHERE a.container_id = ?1
+                 ORDER BY a.name ASC",
+            )?;
+            let rows = stmt.query_map([container_id], |row| row.get::<_, i64>(0))?;
+            rows.collect::<Result<Vec<i64>>>()?
+        };
+        account_ids
+            .into_iter()
+            .map(|account_id| Self::fetch_petty_cash(&conn, account_id))
+            .collect()
+    }
 
-        let mut balances: HashMap<i64, i64> = HashMap::new();
-        let mut accounts_stmt = conn.prepare(
-            "SELECT id, opening_balance FROM accounts WHERE container_id = ?1",
-        )?;
-        let account_rows = accounts_stmt.query_map([container_id], |row| {
-            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
-        })?;
-        for row in account_rows {
-            let (id, opening_balance) = row?;
-            balances.insert(id, opening_balance);
+    pub fn replenish_petty_cash(&self, account_id: i64) -> Result<PettyCashReplenishment> {
+        let mut conn = self.conn.lock().unwrap();
+        let petty_cash = Self::fetch_petty_cash(&conn, account_id)?;
+        if petty_cash.shortfall <= 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Petty cash is already at its float".to_string(),
+            ));
         }
 
-        let mut opening_stmt = conn.prepare(
-            "SELECT COALESCE(account_id, 0) as account_id, COALESCE(SUM(amount), 0) as total
-             FROM transactions
-             WHERE container_id = ?1 AND date < ?2
-             GROUP BY account_id",
+        let container_id: i64 = conn.query_row(
+            "SELECT container_id FROM accounts WHERE id = ?1",
+            [account_id],
+            |row| row.get(0),
         )?;
-        let opening_rows = opening_stmt.query_map(params![container_id, &start_date], |row| {
-            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
-        })?;
-        for row in opening_rows {
-            let (account_id, total) = row?;
-            let entry = balances.entry(account_id).or_insert(0);
-            *entry += total;
-        }
+        let period_start = match &petty_cash.last_replenished_at {
+            Some(date) => date.clone(),
+            None => conn.query_row(
+                "SELECT COALESCE(MIN(date), '0000-00-00 00:00:00') FROM transactions WHERE account_id = ?1 AND deleted_at IS NULL",
+                [account_id],
+                |row| row.get(0),
+            )?,
+        };
+        let period_end = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+        let expenses: Vec<ProfitLossLine> = {
+            let mut stmt = conn.prepare(
+                "SELECT category, SUM(-amount) as total
+                 FROM transactions
+                 WHERE account_id = ?1 AND transfer_id IS NULL AND amount < 0
+                   AND date >= ?2 AND date <= ?3 AND deleted_at IS NULL
+                 GROUP BY category
+                 ORDER BY total DESC",
+            )?;
+            let rows = stmt.query_map(params![account_id, &period_start, &period_end], |row| {
+                Ok(ProfitLossLine {
+                    category: row.get(0)?,
+                    total: row.get(1)?,
+                })
+            })?;
+            rows.collect::<Result<Vec<ProfitLossLine>>>()?
+        };
 
-        let mut csv = String::from("Tanggal,Deskripsi,Akun,Kategori,Tipe,Debit,Kredit,Saldo,Container\n");
-        let mut stmt = conn.prepare(
-            "SELECT t.amount, t.description, t.category, t.date,
-                    COALESCE(t.account_id, 0) as account_id,
-                    COALESCE(t.transfer_id, 0) as transfer_id,
-                    COALESCE(t.transfer_account_id, 0) as transfer_account_id,
-                    COALESCE(a.name, '') as account_name,
-                    COALESCE(a.account_type, '') as account_type,
-                    COALESCE(c.category_type, 'expense') as category_type,
-                    COALESCE(ta.name, '') as transfer_account_name
-             FROM transactions t
-             LEFT JOIN accounts a ON a.id = t.account_id
-             LEFT JOIN categories c ON c.name = t.category
-             LEFT JOIN accounts ta ON ta.id = t.transfer_account_id
-             WHERE t.container_id = ?1 AND t.date >= ?2 AND t.date <= ?3
-             ORDER BY t.date ASC, t.id ASC",
+        let locale = Self::read_locale(&conn)?;
+        let description = format!(
+            "{} {}",
+            i18n::translate(&locale, "default.petty_cash_replenishment"),
+            petty_cash.account_name
+        );
+        let tx = conn.transaction()?;
+        let transfer_id = Self::insert_transfer(
+            &tx,
+            container_id,
+            petty_cash.funding_account_id,
+            account_id,
+            petty_cash.shortfall,
+            &description,
+            &period_end,
         )?;
-        let rows = stmt.query_map(params![container_id, &start_date, &end_date], |row| {
-            Ok((
-                row.get::<_, i64>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, String>(2)?,
-                row.get::<_, String>(3)?,
-                row.get::<_, i64>(4)?,
-                row.get::<_, i64>(5)?,
-                row.get::<_, i64>(6)?,
-                row.get::<_, String>(7)?,
-                row.get::<_, String>(8)?,
-                row.get::<_, String>(9)?,
-                row.get::<_, String>(10)?,
-            ))
-        })?;
-
-        for row in rows {
-            let (amount, description, category, date, account_id, transfer_id, _transfer_account_id, account_name, account_type, category_type, transfer_account_name) = row?;
-
-            let tx_type = if transfer_id != 0 || category == "Transfer" {
-                "Transfer"
-            } else if category_type == "income" {
-                "Income"
-            } else {
-                "Expense"
-            };
-
-            let display_category = if tx_type == "Transfer" {
-                if transfer_account_name.is_empty() {
-                    "Transfer".to_string()
-                } else {
-                    transfer_account_name
-                }
-            } else {
-                category
-            };
+        tx.execute(
+            "UPDATE petty_cash SET last_replenished_at = ?1 WHERE account_id = ?2",
+            params![&period_end, account_id],
+        )?;
+        tx.commit()?;
 
-            let balance_entry = balances.entry(account_id).or_insert(0);
-            *balance_entry += amount;
+        Ok(PettyCashReplenishment {
+            account_id,
+            transfer_id,
+            amount: petty_cash.shortfall,
+            period_start,
+            period_end,
+            expenses,
+        })
+    }
 
-            let is_debit_normal = account_type == "asset" || account_type == "contra_asset" || account_type.is_empty();
-            let (debit, credit) = if is_debit_normal {
-                if amount >= 0 {
-                    (amount, 0)
-                } else {
-                    (0, -amount)
-                }
-            } else if amount >= 0 {
-                (0, amount)
-            } else {
-                (-amount, 0)
-            };
+    fn fetch_petty_cash(conn: &Connection, account_id: i64) -> Result<PettyCash> {
+        conn.query_row(
+            "SELECT p.account_id, a.name, p.float_amount, p.funding_account_id, p.last_replenished_at,
+                    a.opening_balance + COALESCE((SELECT SUM(t.amount) FROM transactions t WHERE t.account_id = a.id AND t.deleted_at IS NULL), 0)
+             FROM petty_cash p
+             JOIN accounts a ON a.id = p.account_id
+             WHERE p.account_id = ?1",
+            [account_id],
+            |row| {
+                let float_amount: i64 = row.get(2)?;
+                let balance: i64 = row.get(5)?;
+                Ok(PettyCash {
+                    account_id: row.get(0)?,
+                    account_name: row.get(1)?,
+                    float_amount,
+                    funding_account_id: row.get(3)?,
+                    balance,
+                    shortfall: float_amount - balance,
+                    last_replenished_at: row.get(4)?,
+                })
+            },
+        )
+    }
 
-            csv.push_str(&format!(
-                "{},{},{},{},{},{},{},{},{}\n",
-                Self::csv_escape(&Self::date_only(&date)),
-                Self::csv_escape(&description),
-                Self::csv_escape(&account_name),
-                Self::csv_escape(&display_category),
-                tx_type,
-                Self::format_units_no_decimals(debit),
-                Self::format_units_no_decimals(credit),
-                Self::format_units_no_decimals(*balance_entry),
-                Self::csv_escape(&container_name)
+    /// Opens a cash count for an account, fixing the balance the drawer is
+    /// expected to hold at this moment.
+    pub fn start_cash_count(&self, account_id: i64) -> Result<CashCountSession> {
+        let conn = self.conn.lock().unwrap();
+        let expected_balance: i64 = conn.query_row(
+            "SELECT a.opening_balance + COALESCE((SELECT SUM(t.amount) FROM transactions t WHERE t.account_id = a.id AND t.deleted_at IS NULL), 0)
+             FROM accounts a
+             WHERE a.id = ?1",
+            [account_id],
+            |row| row.get(0),
+        )?;
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO cash_count_sessions (account_id, expected_balance, started_at) VALUES (?1, ?2, ?3)",
+            params![account_id, expected_balance, &now],
+        )?;
+        Self::fetch_cash_count_session(&conn, conn.last_insert_rowid())
+    }
+
+    /// Records a breakdown of notes and coins, keyed by face value in whole
+    /// currency units (`{100000: 3, 50000: 1}`). The total is computed here
+    /// and every count is kept, so earlier counts remain available when a
+    /// closing is disputed.
+    pub fn record_denomination_count(
+        &self,
+        session_id: i64,
+        denominations: std::collections::BTreeMap<i64, i64>,
+    ) -> Result<CashCountRecord> {
+        if denominations.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "At least one denomination is required".to_string(),
             ));
         }
+        for (denomination, quantity) in &denominations {
+            if *denomination <= 0 {
+                return Err(rusqlite::Error::InvalidParameterName(
+                    "Denominations must be positive".to_string(),
+                ));
+            }
+            if *quantity < 0 {
+                return Err(rusqlite::Error::InvalidParameterName(
+                    "Quantities must not be negative".to_string(),
+                ));
+            }
+        }
 
-        Ok(csv)
+        let mut conn = self.conn.lock().unwrap();
+        let container_id: i64 = conn.query_row(
+            "SELECT a.container_id FROM cash_count_sessions s JOIN accounts a ON a.id = s.account_id WHERE s.id = ?1",
+            [session_id],
+            |row| row.get(0),
+        )?;
+        let unit = 10_i64.pow(Self::read_minor_units(&conn, container_id)?);
+        let counted_total = denominations
+            .iter()
+            .try_fold(0_i64, |total, (denomination, quantity)| {
+                denomination
+                    .checked_mul(*quantity)
+                    .and_then(|value| value.checked_mul(unit))
+                    .and_then(|value| total.checked_add(value))
+            })
+            .ok_or_else(|| rusqlite::Error::InvalidParameterName("Counted total is too large".to_string()))?;
+
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO cash_count_records (session_id, counted_total, recorded_at) VALUES (?1, ?2, ?3)",
+            params![session_id, counted_total, &now],
+        )?;
+        let record_id = tx.last_insert_rowid();
+        for (denomination, quantity) in &denominations {
+            tx.execute(
+                "INSERT INTO cash_count_denominations (record_id, denomination, quantity) VALUES (?1, ?2, ?3)",
+                params![record_id, denomination, quantity],
+            )?;
+        }
+        tx.commit()?;
+
+        Self::fetch_cash_count_record(&conn, record_id)
     }
 
-    pub fn export_reports_csv(&self, container_id: i64, year: String) -> Result<ReportsCsvExport> {
-        Ok(ReportsCsvExport {
-            profit_loss: self.export_profit_loss_csv(container_id, year.clone())?,
-            balance_sheet: self.export_balance_sheet_csv(container_id, year.clone())?,
-            transactions: self.export_transactions_detail_csv(container_id, year)?,
+    /// Every count recorded for a session, oldest first.
+    pub fn get_cash_count_history(&self, session_id: i64) -> Result<Vec<CashCountRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let record_ids: Vec<i64> = {
+            let mut stmt = conn.prepare(
+                "SELECT id FROM cash_count_records WHERE session_id = ?1 ORDER BY id ASC",
+            )?;
+            let rows = stmt.query_map([session_id], |row| row.get::<_, i64>(0))?;
+            rows.collect::<Result<Vec<i64>>>()?
+        };
+        record_ids
+            .into_iter()
+            .map(|record_id| Self::fetch_cash_count_record(&conn, record_id))
+            .collect()
+    }
+
+    pub fn get_cash_count_sessions(&self, account_id: i64) -> Result<Vec<CashCountSession>> {
+        let conn = self.conn.lock().unwrap();
+        let session_ids: Vec<i64> = {
+            let mut stmt = conn.prepare(
+                "SELECT id FROM cash_count_sessions WHERE account_id = ?1 ORDER BY id DESC",
+            )?;
+            let rows = stmt.query_map([account_id], |row| row.get::<_, i64>(0))?;
+            rows.collect::<Result<Vec<i64>>>()?
+        };
+        session_ids
+            .into_iter()
+            .map(|session_id| Self::fetch_cash_count_session(&conn, session_id))
+            .collect()
+    }
+
+    fn fetch_cash_count_session(conn: &Connection, session_id: i64) -> Result<CashCountSession> {
+        let (account_id, account_name, expected_balance, started_at, latest_id): (i64, String, i64, String, Option<i64>) =
+            conn.query_row(
+                "SELECT s.account_id, a.name, s.expected_balance, s.started_at,
+                        (SELECT MAX(r.id) FROM cash_count_records r WHERE r.session_id = s.id)
+                 FROM cash_count_sessions s
+                 JOIN accounts a ON a.id = s.account_id
+                 WHERE s.id = ?1",
+                [session_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+            )?;
+        let latest = match latest_id {
+            Some(record_id) => Some(Self::fetch_cash_count_record(conn, record_id)?),
+            None => None,
+        };
+        Ok(CashCountSession {
+            id: session_id,
+            account_id,
+            account_name,
+            expected_balance,
+            started_at,
+            latest,
         })
     }
 
-    pub fn delete_transaction(&self, id: i64) -> Result<()> {
+    /// Opens a reconciliation of an account against a bank statement dated
+    /// `statement_date` (`YYYY-MM-DD`) with the given closing balance.
+    pub fn start_reconciliation(
+        &self,
+        account_id: i64,
+        statement_date: String,
+        statement_balance: i64,
+    ) -> Result<Reconciliation> {
+        let statement_date = Self::parse_day(&statement_date)?.format("%Y-%m-%d").to_string();
         let conn = self.conn.lock().unwrap();
-        let transfer_id: i64 = conn.query_row(
-            "SELECT COALESCE(transfer_id, 0) FROM transactions WHERE id = ?1",
-            [id],
-            |row| row.get(0),
+        conn.query_row("SELECT id FROM accounts WHERE id = ?1", [account_id], |row| row.get::<_, i64>(0))?;
+        let open: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM reconciliations WHERE account_id = ?1 AND finished_at IS NULL",
+                [account_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if open.is_some() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "This account already has a reconciliation in progress".to_string(),
+            ));
+        }
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO reconciliations (account_id, statement_date, statement_balance, started_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![account_id, &statement_date, statement_balance, &now],
         )?;
+        Self::fetch_reconciliation(&conn, conn.last_insert_rowid())
+    }
 
-        if transfer_id != 0 {
-            conn.execute("DELETE FROM transactions WHERE transfer_id = ?1", [transfer_id])?;
-        } else {
-            conn.execute("DELETE FROM transactions WHERE id = ?1", [id])?;
+    /// Marks transactions as matching the statement, or unmarks them when
+    /// `marked` is false. Only transactions of the reconciled account dated
+    /// on or before the statement, and not locked by an earlier
+    /// reconciliation, can be marked.
+    pub fn mark_reconciliation_transactions(
+        &self,
+        reconciliation_id: i64,
+        transaction_ids: Vec<i64>,
+        marked: bool,
+    ) -> Result<Reconciliation> {
+        let mut conn = self.conn.lock().unwrap();
+        let (account_id, statement_date) = Self::open_reconciliation(&conn, reconciliation_id)?;
+        let tx = conn.transaction()?;
+        for transaction_id in &transaction_ids {
+            if !marked {
+                tx.execute(
+                    "DELETE FROM reconciliation_items WHERE reconciliation_id = ?1 AND transaction_id = ?2",
+                    params![reconciliation_id, transaction_id],
+                )?;
+                continue;
+            }
+            let transaction = Self::fetch_transaction(&tx, *transaction_id)?;
+            if transaction.account_id != account_id {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Transaction {} belongs to another account",
+                    transaction_id
+                )));
+            }
+            if transaction.date.as_str() > format!("{} 23:59:59", statement_date).as_str() {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Transaction {} is dated after the statement",
+                    transaction_id
+                )));
+            }
+            if Self::is_transaction_reconciled(&tx, *transaction_id)? {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Transaction {} is already reconciled",
+                    transaction_id
+                )));
+            }
+            tx.execute(
+                "INSERT OR IGNORE INTO reconciliation_items (reconciliation_id, transaction_id) VALUES (?1, ?2)",
+                params![reconciliation_id, transaction_id],
+            )?;
         }
-        Ok(())
+        tx.commit()?;
+        Self::fetch_reconciliation(&conn, reconciliation_id)
     }
 
-    pub fn get_category_totals(&self, container_id: i64) -> Result<Vec<(String, i64)>> {
+    pub fn get_reconciliation(&self, reconciliation_id: i64) -> Result<Reconciliation> {
         let conn = self.conn.lock().unwrap();
-        let current_month = chrono::Local::now().format("%Y-%m").to_string();
-        
-        let mut stmt = conn.prepare(
-            "SELECT t.category, SUM(ABS(t.amount)) as total 
-             FROM transactions t
-             LEFT JOIN categories c ON c.name = t.category
-             WHERE t.container_id = ?1 AND t.date LIKE ?2 AND t.transfer_id IS NULL
-               AND COALESCE(c.category_type, 'expense') = 'expense'
-             GROUP BY t.category 
-             ORDER BY total DESC"
-        )?;
-        
-        let results = stmt.query_map([&container_id.to_string(), &format!("{}%", current_month)], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
-        })?;
-        
-        results.collect()
+        Self::fetch_reconciliation(&conn, reconciliation_id)
     }
 
-    pub fn get_categories(&self) -> Result<Vec<Category>> {
+    pub fn get_reconciliations(&self, account_id: i64) -> Result<Vec<Reconciliation>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT name, category_type, is_default FROM categories ORDER BY is_default DESC, name ASC",
-        )?;
-        
-        let categories = stmt.query_map([], |row| {
-            Ok(Category {
-                name: row.get(0)?,
-                category_type: row.get(1)?,
-                is_default: row.get::<_, i64>(2)? == 1,
-            })
-        })?;
-        categories.collect()
+        let ids: Vec<i64> = {
+            let mut stmt = conn.prepare("SELECT id FROM reconciliations WHERE account_id = ?1 ORDER BY id DESC")?;
+            let rows = stmt.query_map([account_id], |row| row.get::<_, i64>(0))?;
+            rows.collect::<Result<Vec<i64>>>()?
+        };
+        ids.into_iter()
+            .map(|id| Self::fetch_reconciliation(&conn, id))
+            .collect()
     }
 
-    pub fn get_category_balances(&self, container_id: i64) -> Result<Vec<CategoryBalance>> {
+    /// Transactions that can be ticked off in a reconciliation: those of the
+    /// account up to the statement date that no finished reconciliation has
+    /// locked, oldest first.
+    pub fn get_reconciliation_candidates(&self, reconciliation_id: i64) -> Result<Vec<ReconciliationCandidate>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT c.name, c.category_type, c.is_default,
-                    COALESCE(SUM(t.amount), 0) as balance
-             FROM categories c
-             LEFT JOIN transactions t
-               ON t.category = c.name
-              AND t.container_id = ?1
-              AND (t.transfer_id IS NULL OR t.transfer_id = 0)
-             GROUP BY c.name, c.category_type, c.is_default
-             ORDER BY c.is_default DESC, c.name ASC",
+        let (account_id, statement_date): (i64, String) = conn.query_row(
+            "SELECT account_id, statement_date FROM reconciliations WHERE id = ?1",
+            [reconciliation_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {},
+                    EXISTS (SELECT 1 FROM reconciliation_items i
+                            WHERE i.reconciliation_id = ?3 AND i.transaction_id = transactions.id)
+             FROM transactions
+             WHERE account_id = ?1 AND deleted_at IS NULL AND date <= ?2
+               AND id NOT IN (SELECT i.transaction_id FROM reconciliation_items i
+                              JOIN reconciliations r ON r.id = i.reconciliation_id
+                              WHERE r.finished_at IS NOT NULL AND r.id != ?3)
+             ORDER BY date ASC, id ASC",
+            Self::TRANSACTION_COLUMNS
+        ))?;
+        let rows = stmt.query_map(
+            params![account_id, format!("{} 23:59:59", statement_date), reconciliation_id],
+            |row| {
+                Ok(ReconciliationCandidate {
+                    transaction: Self::transaction_from_row(row)?,
+                    marked: row.get::<_, i64>(13)? == 1,
+                })
+            },
         )?;
+        rows.collect()
+    }
 
-        let rows = stmt.query_map([container_id], |row| {
-            Ok(CategoryBalance {
-                name: row.get(0)?,
-                category_type: row.get(1)?,
-                is_default: row.get::<_, i64>(2)? == 1,
-                balance: row.get(3)?,
-            })
-        })?;
+    /// Closes a reconciliation once the cleared balance matches the
+    /// statement. Marked transactions become `reconciled` and are locked
+    /// against edits.
+    pub fn finish_reconciliation(&self, reconciliation_id: i64) -> Result<Reconciliation> {
+        let mut conn = self.conn.lock().unwrap();
+        Self::open_reconciliation(&conn, reconciliation_id)?;
+        let reconciliation = Self::fetch_reconciliation(&conn, reconciliation_id)?;
+        if reconciliation.difference != 0 {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "The cleared balance differs from the statement by {}",
+                reconciliation.difference
+            )));
+        }
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE transactions SET status = 'reconciled'
+             WHERE id IN (SELECT transaction_id FROM reconciliation_items WHERE reconciliation_id = ?1)",
+            [reconciliation_id],
+        )?;
+        tx.execute(
+            "UPDATE reconciliations SET finished_at = ?1 WHERE id = ?2",
+            params![&now, reconciliation_id],
+        )?;
+        tx.commit()?;
+        Self::fetch_reconciliation(&conn, reconciliation_id)
+    }
 
-        rows.collect()
+    /// Discards a reconciliation that has not been finished.
+    pub fn cancel_reconciliation(&self, reconciliation_id: i64) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        Self::open_reconciliation(&conn, reconciliation_id)?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM reconciliation_items WHERE reconciliation_id = ?1",
+            [reconciliation_id],
+        )?;
+        tx.execute("DELETE FROM reconciliations WHERE id = ?1", [reconciliation_id])?;
+        tx.commit()?;
+        Ok(())
     }
 
-    pub fn get_accounts(&self, container_id: i64) -> Result<Vec<Account>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, name, account_type, opening_balance, container_id, created_at
-             FROM accounts
-             WHERE container_id = ?1
-             ORDER BY name ASC"
+    /// The account and statement date of a reconciliation that is still open.
+    fn open_reconciliation(conn: &Connection, reconciliation_id: i64) -> Result<(i64, String)> {
+        let (account_id, statement_date, finished_at): (i64, String, Option<String>) = conn.query_row(
+            "SELECT account_id, statement_date, finished_at FROM reconciliations WHERE id = ?1",
+            [reconciliation_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
         )?;
+        if finished_at.is_some() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Reconciliation is already finished".to_string(),
+            ));
+        }
+        Ok((account_id, statement_date))
+    }
 
-        let accounts = stmt.query_map([container_id], |row| {
-            Ok(Account {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                account_type: row.get(2)?,
-                opening_balance: row.get(3)?,
-                container_id: row.get(4)?,
-                created_at: row.get(5)?,
-            })
-        })?;
+    fn is_transaction_reconciled(conn: &Connection, transaction_id: i64) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM reconciliation_items i
+                            JOIN reconciliations r ON r.id = i.reconciliation_id
+                            WHERE i.transaction_id = ?1 AND r.finished_at IS NOT NULL)",
+            [transaction_id],
+            |row| row.get::<_, i64>(0).map(|exists| exists == 1),
+        )
+    }
 
-        accounts.collect()
+    fn fetch_reconciliation(conn: &Connection, reconciliation_id: i64) -> Result<Reconciliation> {
+        conn.query_row(
+            "SELECT r.id, r.account_id, a.name, r.statement_date, r.statement_balance, r.started_at, r.finished_at,
+                    a.opening_balance
+                      + COALESCE((SELECT SUM(t.amount) FROM reconciliation_items i
+                                  JOIN reconciliations p ON p.id = i.reconciliation_id
+                                  JOIN transactions t ON t.id = i.transaction_id
+                                  WHERE p.account_id = r.account_id AND t.deleted_at IS NULL
+                                    AND (p.id = r.id OR (p.finished_at IS NOT NULL AND p.id < r.id))), 0),
+                    (SELECT COUNT(*) FROM reconciliation_items i WHERE i.reconciliation_id = r.id)
+             FROM reconciliations r
+             JOIN accounts a ON a.id = r.account_id
+             WHERE r.id = ?1",
+            [reconciliation_id],
+            |row| {
+                let statement_balance: i64 = row.get(4)?;
+                let cleared_balance: i64 = row.get(7)?;
+                Ok(Reconciliation {
+                    id: row.get(0)?,
+                    account_id: row.get(1)?,
+                    account_name: row.get(2)?,
+                    statement_date: row.get(3)?,
+                    statement_balance,
+                    cleared_balance,
+                    difference: statement_balance - cleared_balance,
+                    marked_count: row.get(8)?,
+                    started_at: row.get(5)?,
+                    finished_at: row.get(6)?,
+                })
+            },
+        )
     }
 
-    pub fn get_account_balances(&self, container_id: i64) -> Result<Vec<AccountBalance>> {
-        let conn = self.conn.lock().unwrap();
+    fn fetch_cash_count_record(conn: &Connection, record_id: i64) -> Result<CashCountRecord> {
+        let (session_id, counted_total, expected_balance, recorded_at, container_id): (i64, i64, i64, String, i64) =
+            conn.query_row(
+                "SELECT r.session_id, r.counted_total, s.expected_balance, r.recorded_at, a.container_id
+                 FROM cash_count_records r
+                 JOIN cash_count_sessions s ON s.id = r.session_id
+                 JOIN accounts a ON a.id = s.account_id
+                 WHERE r.id = ?1",
+                [record_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+            )?;
+        let unit = 10_i64.pow(Self::read_minor_units(conn, container_id)?);
         let mut stmt = conn.prepare(
-            "SELECT a.id, a.name, a.account_type, a.opening_balance, a.container_id, a.created_at,
-                    COALESCE(SUM(t.amount), 0) + a.opening_balance AS balance
-             FROM accounts a
-             LEFT JOIN transactions t ON t.account_id = a.id
-             WHERE a.container_id = ?1
-             GROUP BY a.id
-             ORDER BY a.name ASC"
+            "SELECT denomination, quantity FROM cash_count_denominations
+             WHERE record_id = ?1
+             ORDER BY denomination DESC",
         )?;
-
-        let accounts = stmt.query_map([container_id], |row| {
-            Ok(AccountBalance {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                account_type: row.get(2)?,
-                opening_balance: row.get(3)?,
-                container_id: row.get(4)?,
-                created_at: row.get(5)?,
-                balance: row.get(6)?,
+        let rows = stmt.query_map([record_id], |row| {
+            let denomination: i64 = row.get(0)?;
+            let quantity: i64 = row.get(1)?;
+            Ok(DenominationCount {
+                denomination,
+                quantity,
+                subtotal: denomination * quantity * unit,
             })
         })?;
-
-        accounts.collect()
+        Ok(CashCountRecord {
+            id: record_id,
+            session_id,
+            denominations: rows.collect::<Result<Vec<_>>>()?,
+            counted_total,
+            difference: counted_total - expected_balance,
+            recorded_at,
+        })
     }
 
-    pub fn add_account(
-        &self,
-        container_id: i64,
-        name: String,
-        account_type: String,
-        opening_balance: i64,
-    ) -> Result<Account> {
-        let conn = self.conn.lock().unwrap();
-        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-        let name = name.trim().to_string();
-        let account_type = account_type.trim().to_string();
+    pub fn add_giro(&self, giro: NewGiro) -> Result<Giro> {
+        let NewGiro {
+            container_id,
+            direction,
+            number,
+            counterparty,
+            amount,
+            account_id,
+            category,
+            issue_date,
+            maturity_date,
+        } = giro;
+        let direction = direction.trim().to_lowercase();
+        if direction != "received" && direction != "issued" {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Giro direction must be 'received' or 'issued'".to_string(),
+            ));
+        }
+        let number = number.trim().to_string();
+        if number.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Giro number cannot be empty".to_string(),
+            ));
+        }
+        if amount <= 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Giro amount must be positive".to_string(),
+            ));
+        }
+        let issue_date = Self::parse_day(&issue_date)?.format("%Y-%m-%d").to_string();
+        let maturity_date = Self::parse_day(&maturity_date)?.format("%Y-%m-%d").to_string();
+        let category = category
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| {
+                if direction == "received" {
+                    Self::DEFAULT_INCOME_CATEGORY.to_string()
+                } else {
+                    Self::DEFAULT_FALLBACK_CATEGORY.to_string()
+                }
+            });
 
-        conn.execute(
-            "INSERT INTO accounts (name, account_type, opening_balance, container_id, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            [
-                &name,
-                &account_type,
-                &opening_balance.to_string(),
-                &container_id.to_string(),
-                &now,
-            ],
+        let conn = self.conn.lock().unwrap();
+        let account_container: i64 = conn.query_row(
+            "SELECT container_id FROM accounts WHERE id = ?1",
+            [account_id],
+            |row| row.get(0),
         )?;
+        if account_container != container_id {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Account belongs to a different container".to_string(),
+            ));
+        }
 
-        let id = conn.last_insert_rowid();
-
-        Ok(Account {
-            id,
-            name,
-            account_type,
-            opening_balance,
-            container_id,
-            created_at: now,
-        })
+        conn.execute(
+            "INSERT INTO giros (container_id, direction, number, counterparty, amount, account_id, category, issue_date, maturity_date)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                container_id,
+                &direction,
+                &number,
+                counterparty.trim(),
+                amount,
+                account_id,
+                &category,
+                &issue_date,
+                &maturity_date
+            ],
+        )?;
+
+        Self::fetch_giro(&conn, conn.last_insert_rowid())
     }
 
-    pub fn update_account(&self, id: i64, name: String, opening_balance: i64) -> Result<Account> {
+    pub fn get_giros(&self, container_id: i64, status: Option<String>) -> Result<Vec<Giro>> {
         let conn = self.conn.lock().unwrap();
-        let name = name.trim().to_string();
+        let status = status.map(|s| s.trim().to_lowercase()).filter(|s| !s.is_empty());
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM giros
+             WHERE container_id = ?1 AND (?2 IS NULL OR status = ?2)
+             ORDER BY maturity_date ASC, id ASC",
+            Self::GIRO_COLUMNS
+        ))?;
+        let giros = stmt.query_map(params![container_id, status], Self::giro_from_row)?;
+        giros.collect()
+    }
 
-        conn.execute(
-            "UPDATE accounts SET name = ?1, opening_balance = ?2 WHERE id = ?3",
-            params![name, opening_balance, id],
-        )?;
+    pub fn clear_giro(&self, id: i64, date: Option<String>) -> Result<Giro> {
+        let mut conn = self.conn.lock().unwrap();
+        let giro = Self::fetch_giro(&conn, id)?;
+        if giro.status != "pending" {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Giro is already {}",
+                giro.status
+            )));
+        }
 
-        let account = conn.query_row(
-            "SELECT id, name, account_type, opening_balance, container_id, created_at
-             FROM accounts
-             WHERE id = ?1",
-            [id],
-            |row| {
-                Ok(Account {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    account_type: row.get(2)?,
-                    opening_balance: row.get(3)?,
-                    container_id: row.get(4)?,
-                    created_at: row.get(5)?,
-                })
-            },
+        let date = Self::normalize_transaction_date(date)?;
+        let amount = if giro.direction == "received" { giro.amount } else { -giro.amount };
+        let description = if giro.counterparty.is_empty() {
+            format!("Giro {}", giro.number)
+        } else {
+            format!("Giro {} - {}", giro.number, giro.counterparty)
+        };
+
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO transactions (amount, description, category, date, container_id, account_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![amount, &description, &giro.category, &date, giro.container_id, giro.account_id],
+        )?;
+        let transaction_id = tx.last_insert_rowid();
+        tx.execute(
+            "UPDATE giros SET status = 'cleared', transaction_id = ?1, cleared_at = ?2 WHERE id = ?3",
+            params![transaction_id, &date, id],
         )?;
+        let giro = Self::fetch_giro(&tx, id)?;
+        tx.commit()?;
 
-        Ok(account)
+        Ok(giro)
     }
 
-    pub fn delete_account(&self, id: i64) -> Result<()> {
+    pub fn cancel_giro(&self, id: i64) -> Result<Giro> {
         let conn = self.conn.lock().unwrap();
-
-        conn.execute(
-            "UPDATE transactions SET account_id = NULL WHERE account_id = ?1",
+        let updated = conn.execute(
+            "UPDATE giros SET status = 'cancelled' WHERE id = ?1 AND status = 'pending'",
             [id],
         )?;
+        if updated == 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Only pending giros can be cancelled".to_string(),
+            ));
+        }
+        Self::fetch_giro(&conn, id)
+    }
 
-        conn.execute("DELETE FROM accounts WHERE id = ?1", [id])?;
-        Ok(())
+    fn fetch_giro(conn: &Connection, id: i64) -> Result<Giro> {
+        conn.query_row(
+            &format!("SELECT {} FROM giros WHERE id = ?1", Self::GIRO_COLUMNS),
+            [id],
+            Self::giro_from_row,
+        )
     }
 
-    pub fn add_category(&self, name: String, category_type: String) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "INSERT INTO categories (name, category_type, is_default) VALUES (?1, ?2, 0)",
-            [name, category_type],
+    fn giro_from_row(row: &rusqlite::Row) -> Result<Giro> {
+        Ok(Giro {
+            id: row.get(0)?,
+            container_id: row.get(1)?,
+            direction: row.get(2)?,
+            number: row.get(3)?,
+            counterparty: row.get(4)?,
+            amount: row.get(5)?,
+            account_id: row.get(6)?,
+            category: row.get(7)?,
+            issue_date: row.get(8)?,
+            maturity_date: row.get(9)?,
+            status: row.get(10)?,
+            transaction_id: row.get(11)?,
+            cleared_at: row.get(12)?,
+        })
+    }
+
+    /// Books a sale paid through an aggregator (QRIS, marketplace) as revenue
+    /// right away, held in the clearing account until the payout arrives.
+    pub fn record_pending_sale(
+        &self,
+        container_id: i64,
+        channel: String,
+        amount: i64,
+        category: Option<String>,
+        description: Option<String>,
+        date: Option<String>,
+    ) -> Result<PendingSale> {
+        let channel = channel.trim().to_string();
+        if channel.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Channel cannot be empty".to_string(),
+            ));
+        }
+        Self::ensure_max_chars("Channel", &channel, Self::MAX_NAME_CHARS)?;
+        if amount <= 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Sale amount must be positive".to_string(),
+            ));
+        }
+        let category = category
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| Self::DEFAULT_INCOME_CATEGORY.to_string());
+        let description = description
+            .map(|d| d.trim().to_string())
+            .filter(|d| !d.is_empty())
+            .unwrap_or_else(|| channel.clone());
+        Self::ensure_max_chars("Description", &description, Self::MAX_DESCRIPTION_CHARS)?;
+        let date = Self::normalize_transaction_date(date)?;
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let clearing_account_id = Self::ensure_clearing_account(&tx, container_id)?;
+        tx.execute(
+            "INSERT INTO transactions (amount, description, category, date, container_id, account_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![amount, &description, &category, &date, container_id, clearing_account_id],
         )?;
-        Ok(())
+        let transaction_id = tx.last_insert_rowid();
+        tx.execute(
+            "INSERT INTO pending_sales (container_id, channel, amount, transaction_id, date) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![container_id, &channel, amount, transaction_id, &date],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.commit()?;
+
+        Ok(PendingSale {
+            id,
+            container_id,
+            channel,
+            amount,
+            transaction_id,
+            date,
+            settlement_id: None,
+        })
     }
 
-    pub fn delete_category(&self, name: String) -> Result<()> {
+    pub fn get_pending_sales(&self, container_id: i64, channel: Option<String>) -> Result<Vec<PendingSale>> {
         let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "DELETE FROM categories WHERE name = ?1 AND is_default = 0",
-            [name],
+        let mut stmt = conn.prepare(
+            "SELECT p.id, p.container_id, p.channel, p.amount, p.transaction_id, p.date, p.settlement_id
+             FROM pending_sales p
+             JOIN transactions t ON t.id = p.transaction_id
+             WHERE p.container_id = ?1 AND p.settlement_id IS NULL AND (?2 IS NULL OR p.channel = ?2)
+               AND t.deleted_at IS NULL
+             ORDER BY p.date ASC, p.id ASC",
         )?;
-        Ok(())
+        let rows = stmt.query_map(params![container_id, channel], |row| {
+            Ok(PendingSale {
+                id: row.get(0)?,
+                container_id: row.get(1)?,
+                channel: row.get(2)?,
+                amount: row.get(3)?,
+                transaction_id: row.get(4)?,
+                date: row.get(5)?,
+                settlement_id: row.get(6)?,
+            })
+        })?;
+        rows.collect()
     }
 
-    pub fn update_category(
-        &self,
-        old_name: String,
-        new_name: String,
-        category_type: String,
-    ) -> Result<()> {
-        let mut conn = self.conn.lock().unwrap();
-        let old_name = old_name.trim().to_string();
-        let new_name = new_name.trim().to_string();
-        let category_type = category_type.trim().to_string();
-
-        if new_name.is_empty() {
+    /// Matches a payout against pending sales. The difference between the
+    /// sales and the amount received is booked as a fee, and the payout moves
+    /// from the clearing account to `payout_account_id` as a transfer.
+    pub fn record_settlement(&self, batch: SettlementBatch) -> Result<Settlement> {
+        let SettlementBatch {
+            container_id,
+            channel,
+            payout_account_id,
+            payout_amount,
+            sale_ids,
+            date,
+        } = batch;
+        let channel = channel.trim().to_string();
+        if sale_ids.is_empty() {
             return Err(rusqlite::Error::InvalidParameterName(
-                "Category name cannot be empty".to_string(),
+                "Select at least one pending sale".to_string(),
+            ));
+        }
+        for (index, sale_id) in sale_ids.iter().enumerate() {
+            if sale_ids[..index].contains(sale_id) {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Sale {} is selected more than once",
+                    sale_id
+                )));
+            }
+        }
+        if payout_amount < 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Payout amount cannot be negative".to_string(),
             ));
         }
+        let date = Self::normalize_transaction_date(date)?;
 
+        let mut conn = self.conn.lock().unwrap();
+        let locale = Self::read_locale(&conn)?;
         let tx = conn.transaction()?;
-        let updated_rows = tx.execute(
-            "UPDATE categories
-             SET name = ?1, category_type = ?2
-             WHERE name = ?3",
-            params![&new_name, &category_type, &old_name],
+        let payout_container: i64 = tx.query_row(
+            "SELECT container_id FROM accounts WHERE id = ?1",
+            [payout_account_id],
+            |row| row.get(0),
         )?;
+        if payout_container != container_id {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Account belongs to a different container".to_string(),
+            ));
+        }
 
-        if updated_rows == 0 {
-            return Err(rusqlite::Error::QueryReturnedNoRows);
+        let mut gross_amount = 0;
+        for sale_id in &sale_ids {
+            let (sale_container, sale_channel, amount, settlement_id, trashed): (i64, String, i64, Option<i64>, bool) =
+                tx.query_row(
+                    "SELECT p.container_id, p.channel, p.amount, p.settlement_id, t.deleted_at IS NOT NULL
+                     FROM pending_sales p
+                     JOIN transactions t ON t.id = p.transaction_id
+                     WHERE p.id = ?1",
+                    [sale_id],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+                )?;
+            if trashed {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Sale {} is in the trash",
+                    sale_id
+                )));
+            }
+            if sale_container != container_id || sale_channel != channel {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Sale {} does not belong to channel {}",
+                    sale_id, channel
+                )));
+            }
+            if settlement_id.is_some() {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Sale {} is already settled",
+                    sale_id
+                )));
+            }
+            gross_amount += amount;
+        }
+        if payout_amount > gross_amount {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Payout exceeds the selected sales".to_string(),
+            ));
+        }
+
+        let fee_amount = gross_amount - payout_amount;
+        let clearing_account_id = Self::ensure_clearing_account(&tx, container_id)?;
+        if fee_amount > 0 {
+            tx.execute(
+                "INSERT INTO transactions (amount, description, category, date, container_id, account_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    -fee_amount,
+                    format!("{} {}", i18n::translate(&locale, "default.settlement_fee"), channel),
+                    Self::BANK_FEE_CATEGORY,
+                    &date,
+                    container_id,
+                    clearing_account_id
+                ],
+            )?;
+        }
+        if payout_amount > 0 {
+            Self::insert_transfer(
+                &tx,
+                container_id,
+                clearing_account_id,
+                payout_account_id,
+                payout_amount,
+                &format!("{} {}", i18n::translate(&locale, "default.settlement"), channel),
+                &date,
+            )?;
         }
 
         tx.execute(
-            "UPDATE transactions SET category = ?1 WHERE category = ?2",
-            params![&new_name, &old_name],
+            "INSERT INTO settlements (container_id, channel, payout_account_id, gross_amount, fee_amount, payout_amount, date)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![container_id, &channel, payout_account_id, gross_amount, fee_amount, payout_amount, &date],
         )?;
+        let id = tx.last_insert_rowid();
+        for sale_id in &sale_ids {
+            tx.execute(
+                "UPDATE pending_sales SET settlement_id = ?1 WHERE id = ?2",
+                params![id, sale_id],
+            )?;
+        }
         tx.commit()?;
-        Ok(())
+
+        Ok(Settlement {
+            id,
+            container_id,
+            channel,
+            payout_account_id,
+            gross_amount,
+            fee_amount,
+            payout_amount,
+            date,
+            sale_count: sale_ids.len() as i64,
+        })
     }
 
-    pub fn get_available_months(&self, container_id: i64) -> Result<Vec<String>> {
+    /// Unused custom categories, accounts without activity for twelve months
+    /// and containers without any transactions. Each suggestion can be acted
+    /// on with `set_archived(kind, id, true)`.
+    pub fn get_housekeeping_suggestions(&self) -> Result<Vec<HousekeepingSuggestion>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT DISTINCT strftime('%Y-%m', date) as month 
-             FROM transactions 
-             WHERE container_id = ?1
-             ORDER BY month DESC"
-        )?;
-        
-        let months = stmt.query_map([container_id], |row| row.get(0))?;
-        months.collect()
+        let cutoff = (chrono::Local::now().date_naive() - chrono::Months::new(12))
+            .format("%Y-%m-%d 00:00:00")
+            .to_string();
+        let mut suggestions = Vec::new();
+
+        {
+            let mut stmt = conn.prepare(
+                "SELECT c.id, c.name
+                 FROM categories c
+                 WHERE c.archived = 0 AND c.is_default = 0
+                   AND NOT EXISTS (SELECT 1 FROM transactions t WHERE t.category = c.name AND t.deleted_at IS NULL)
+                 ORDER BY c.name ASC",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok(HousekeepingSuggestion {
+                    kind: "category".to_string(),
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    container_id: None,
+                    reason: "unused_category".to_string(),
+                    last_activity: None,
+                    balance: 0,
+                })
+            })?;
+            for row in rows {
+                suggestions.push(row?);
+            }
+        }
+
+        {
+            let mut stmt = conn.prepare(
+                "SELECT a.id, a.name, a.container_id, MAX(t.date),
+                        a.opening_balance + COALESCE(SUM(t.amount), 0)
+                 FROM accounts a
+                 JOIN containers c ON c.id = a.container_id AND c.archived = 0
+                 LEFT JOIN transactions t ON t.account_id = a.id AND t.deleted_at IS NULL
+                 WHERE a.archived = 0 AND a.created_at < ?1
+                 GROUP BY a.id
+                 HAVING MAX(t.date) IS NULL OR MAX(t.date) < ?1
+                 ORDER BY a.container_id ASC, a.name ASC",
+            )?;
+            let rows = stmt.query_map([&cutoff], |row| {
+                Ok(HousekeepingSuggestion {
+                    kind: "account".to_string(),
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    container_id: Some(row.get(2)?),
+                    reason: "inactive_account".to_string(),
+                    last_activity: row.get(3)?,
+                    balance: row.get(4)?,
+                })
+            })?;
+            for row in rows {
+                suggestions.push(row?);
+            }
+        }
+
+        {
+            let mut stmt = conn.prepare(
+                "SELECT c.id, c.name
+                 FROM containers c
+                 WHERE c.archived = 0 AND c.is_default = 0
+                   AND NOT EXISTS (SELECT 1 FROM transactions t WHERE t.container_id = c.id AND t.deleted_at IS NULL)
+                 ORDER BY c.created_at ASC",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                let id: i64 = row.get(0)?;
+                Ok(HousekeepingSuggestion {
+                    kind: "container".to_string(),
+                    id,
+                    name: row.get(1)?,
+                    container_id: Some(id),
+                    reason: "empty_container".to_string(),
+                    last_activity: None,
+                    balance: 0,
+                })
+            })?;
+            for row in rows {
+                suggestions.push(row?);
+            }
+        }
+
+        Ok(suggestions)
     }
 
-    pub fn get_balance_for_month(&self, container_id: i64, month: String) -> Result<i64> {
+    pub fn set_archived(&self, kind: String, id: i64, archived: bool) -> Result<()> {
+        let table = match kind.as_str() {
+            "category" => "categories",
+            "account" => "accounts",
+            "container" => "containers",
+            _ => {
+                return Err(rusqlite::Error::InvalidParameterName(
+                    "Unknown item kind. Expected category, account or container".to_string(),
+                ))
+            }
+        };
+
         let conn = self.conn.lock().unwrap();
-        
-        let balance: i64 = conn.query_row(
-            "SELECT COALESCE(SUM(amount), 0) FROM transactions WHERE container_id = ?1 AND date LIKE ?2 AND transfer_id IS NULL",
-            [&container_id.to_string(), &format!("{}%", month)],
-            |row| row.get(0),
+        if archived && table != "accounts" {
+            let is_default: i64 = conn.query_row(
+                &format!("SELECT is_default FROM {} WHERE id = ?1", table),
+                [id],
+                |row| row.get(0),
+            )?;
+            if is_default == 1 {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "Cannot archive the default {}",
+                    kind
+                )));
+            }
+        }
+        let updated = conn.execute(
+            &format!("UPDATE {} SET archived = ?1 WHERE id = ?2", table),
+            params![archived as i64, id],
         )?;
-
-        Ok(balance)
+        if updated == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        Ok(())
     }
 
-    pub fn get_transactions_for_month(&self, container_id: i64, month: String, limit: Option<i64>) -> Result<Vec<Transaction>> {
+    pub fn get_archived_items(&self) -> Result<Vec<ArchivedItem>> {
         let conn = self.conn.lock().unwrap();
-        let base_query = format!(
-            "SELECT id, amount, description, category, date, container_id, COALESCE(account_id, 0) as account_id, COALESCE(transfer_id, 0) as transfer_id, COALESCE(transfer_account_id, 0) as transfer_account_id FROM transactions WHERE container_id = {} AND date LIKE '{}%' ORDER BY date DESC",
-            container_id, month
-        );
-        
-        let query = match limit {
-            Some(l) => format!("{} LIMIT {}", base_query, l),
-            None => base_query,
-        };
-
-        let mut stmt = conn.prepare(&query)?;
-        let transactions = stmt.query_map([], |row| {
-            Ok(Transaction {
-                id: row.get(0)?,
-                amount: row.get(1)?,
-                description: row.get(2)?,
-                category: row.get(3)?,
-                date: row.get(4)?,
-                container_id: row.get(5)?,
-                account_id: row.get(6)?,
-                transfer_id: row.get(7)?,
-                transfer_account_id: row.get(8)?,
+        let mut stmt = conn.prepare(
+            "SELECT 'category', id, name, NULL FROM categories WHERE archived = 1
+             UNION ALL
+             SELECT 'account', id, name, container_id FROM accounts WHERE archived = 1
+             UNION ALL
+             SELECT 'container', id, name, id FROM containers WHERE archived = 1
+             ORDER BY 1 ASC, 3 ASC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(ArchivedItem {
+                kind: row.get(0)?,
+                id: row.get(1)?,
+                name: row.get(2)?,
+                container_id: row.get(3)?,
             })
         })?;
-
-        transactions.collect()
+        rows.collect()
     }
 
-    pub fn get_category_totals_for_month(&self, container_id: i64, month: String) -> Result<Vec<(String, i64)>> {
+    pub fn set_category_fee(&self, name: String, is_fee: bool) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT t.category, SUM(ABS(t.amount)) as total 
-             FROM transactions t
-             LEFT JOIN categories c ON c.name = t.category
-             WHERE t.container_id = ?1 AND t.date LIKE ?2 AND t.transfer_id IS NULL
-               AND COALESCE(c.category_type, 'expense') = 'expense'
-             GROUP BY t.category 
-             ORDER BY total DESC"
+        let updated = conn.execute(
+            "UPDATE categories SET is_fee = ?1 WHERE name = ?2",
+            params![is_fee as i64, name.trim()],
         )?;
+        if updated == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        Ok(())
+    }
 
-        let results = stmt.query_map([&container_id.to_string(), &format!("{}%", month)], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
-        })?;
-        
-        results.collect()
+    pub fn get_fee_categories(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT name FROM categories WHERE is_fee = 1 ORDER BY name ASC")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
     }
 
-    pub fn get_profit_and_loss_for_month(&self, container_id: i64, month: String) -> Result<ProfitLossReport> {
+    /// Fees as a share of gross revenue per channel for a month (`YYYY-MM`) or
+    /// year (`YYYY`). Settled aggregator payouts are reported per channel with
+    /// their withheld fees; everything else is grouped by payment method, with
+    /// fees taken from categories flagged as fees.
+    pub fn get_fees_report(&self, container_id: i64, period: String) -> Result<FeesReport> {
         let conn = self.conn.lock().unwrap();
-        let (start_date, end_date) = Self::month_range(&month)?;
+        let (start_date, end_date) = if period.len() == 7 {
+            Self::month_range(&period)?
+        } else {
+            Self::year_range(&period)?
+        };
+        let other_channel = i18n::translate(&Self::read_locale(&conn)?, "default.other_channel");
+
+        let mut totals: Vec<(String, i64, i64)> = Vec::new();
+        let mut add = |channel: String, gross: i64, fees: i64| {
+            match totals.iter_mut().find(|(name, _, _)| *name == channel) {
+                Some(entry) => {
+                    entry.1 += gross;
+                    entry.2 += fees;
+                }
+                None => totals.push((channel, gross, fees)),
+            }
+        };
 
-        let mut income_stmt = conn.prepare(
-            "SELECT t.category, SUM(ABS(t.amount)) as total
-             FROM transactions t
-             LEFT JOIN categories c ON c.name = t.category
-             WHERE t.container_id = ?1 AND t.transfer_id IS NULL
-               AND t.date >= ?2 AND t.date <= ?3
-               AND COALESCE(c.category_type, 'expense') = 'income'
-             GROUP BY t.category
-             ORDER BY total DESC",
-        )?;
-        let income_iter = income_stmt.query_map(
-            params![container_id, &start_date, &end_date],
-            |row| {
-                Ok(ProfitLossLine {
-                    category: row.get(0)?,
-                    total: row.get(1)?,
-                })
-            },
-        )?;
-        let income: Vec<ProfitLossLine> = income_iter.collect::<Result<Vec<_>>>()?;
+        {
+            let mut stmt = conn.prepare(
+                "SELECT channel, SUM(gross_amount), SUM(fee_amount)
+                 FROM settlements
+                 WHERE container_id = ?1 AND date >= ?2 AND date <= ?3
+                 GROUP BY channel",
+            )?;
+            let rows = stmt.query_map(params![container_id, &start_date, &end_date], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))
+            })?;
+            for row in rows {
+                let (channel, gross, fees) = row?;
+                add(channel, gross, fees);
+            }
+        }
 
-        let mut expense_stmt = conn.prepare(
-            "SELECT t.category, SUM(ABS(t.amount)) as total
+        // Pending sales and their payout fees live in the clearing account and
+        // are already covered by the settlements above.
+        let mut stmt = conn.prepare(
+            "SELECT t.payment_method,
+                    COALESCE(SUM(CASE WHEN COALESCE(c.category_type, 'expense') = 'income' THEN ABS(t.amount) ELSE 0 END), 0),
+                    COALESCE(SUM(CASE WHEN COALESCE(c.is_fee, 0) = 1 THEN -t.amount ELSE 0 END), 0)
              FROM transactions t
              LEFT JOIN categories c ON c.name = t.category
-             WHERE t.container_id = ?1 AND t.transfer_id IS NULL
+             WHERE t.container_id = ?1 AND t.deleted_at IS NULL AND t.transfer_id IS NULL
                AND t.date >= ?2 AND t.date <= ?3
-               AND COALESCE(c.category_type, 'expense') = 'expense'
-             GROUP BY t.category
-             ORDER BY total DESC",
+               AND (t.account_id IS NULL
+                    OR t.account_id NOT IN (SELECT id FROM accounts WHERE container_id = ?1 AND name = ?4))
+             GROUP BY t.payment_method
+             ORDER BY t.payment_method IS NULL, t.payment_method ASC",
         )?;
-        let expense_iter = expense_stmt.query_map(
-            params![container_id, &start_date, &end_date],
+        let rows = stmt.query_map(
+            params![container_id, &start_date, &end_date, Self::SETTLEMENT_CLEARING_ACCOUNT],
             |row| {
-                Ok(ProfitLossLine {
-                    category: row.get(0)?,
-                    total: row.get(1)?,
-                })
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
             },
         )?;
-        let expense: Vec<ProfitLossLine> = expense_iter.collect::<Result<Vec<_>>>()?;
+        for row in rows {
+            let (payment_method, gross, fees) = row?;
+            if gross != 0 || fees != 0 {
+                add(payment_method.unwrap_or_else(|| other_channel.clone()), gross, fees);
+            }
+        }
 
-        let total_income: i64 = income.iter().map(|line| line.total).sum();
-        let total_expense: i64 = expense.iter().map(|line| line.total).sum();
-        let net_income = total_income - total_expense;
+        let percent = |fees: i64, gross: i64| {
+            if gross > 0 {
+                (fees as f64 / gross as f64 * 10_000.0).round() / 100.0
+            } else {
+                0.0
+            }
+        };
+        let total_gross_revenue: i64 = totals.iter().map(|(_, gross, _)| gross).sum();
+        let total_fees: i64 = totals.iter().map(|(_, _, fees)| fees).sum();
+        let mut channels: Vec<ChannelFees> = totals
+            .into_iter()
+            .map(|(channel, gross_revenue, fees)| ChannelFees {
+                fee_percent: percent(fees, gross_revenue),
+                channel,
+                gross_revenue,
+                fees,
+            })
+            .collect();
+        channels.sort_by(|a, b| b.fees.cmp(&a.fees).then_with(|| a.channel.cmp(&b.channel)));
 
-        Ok(ProfitLossReport {
+        Ok(FeesReport {
             start_date,
             end_date,
-            income,
-            expense,
-            total_income,
-            total_expense,
-            net_income,
+            channels,
+            total_gross_revenue,
+            total_fees,
+            fee_percent: percent(total_fees, total_gross_revenue),
         })
     }
 
-    pub fn get_balance_sheet_for_month(&self, container_id: i64, month: String) -> Result<BalanceSheetReport> {
-        let conn = self.conn.lock().unwrap();
-        let (start_date, end_date) = Self::month_range(&month)?;
+    fn ensure_clearing_account(conn: &Connection, container_id: i64) -> Result<i64> {
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT OR IGNORE INTO accounts (name, account_type, opening_balance, container_id, created_at)
+             VALUES (?1, 'asset', 0, ?2, ?3)",
+            params![Self::SETTLEMENT_CLEARING_ACCOUNT, container_id, &now],
+        )?;
+        conn.query_row(
+            "SELECT id FROM accounts WHERE name = ?1 AND container_id = ?2",
+            params![Self::SETTLEMENT_CLEARING_ACCOUNT, container_id],
+            |row| row.get(0),
+        )
+    }
 
-        let mut stmt = conn.prepare(
-            "SELECT a.id, a.name, a.account_type, a.opening_balance, a.container_id, a.created_at,
-                    COALESCE(SUM(t.amount), 0) + a.opening_balance AS balance
-             FROM accounts a
-             LEFT JOIN transactions t ON t.account_id = a.id AND t.date <= ?2
-             WHERE a.container_id = ?1
-             GROUP BY a.id
-             ORDER BY a.name ASC",
+    pub fn add_purchase_request(
+        &self,
+        container_id: i64,
+        item: String,
+        quantity: i64,
+        notes: Option<String>,
+    ) -> Result<PurchaseRequest> {
+        let item = item.trim().to_string();
+        if item.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Item cannot be empty".to_string(),
+            ));
+        }
+        Self::ensure_max_chars("Item", &item, Self::MAX_DESCRIPTION_CHARS)?;
+        if quantity <= 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Quantity must be positive".to_string(),
+            ));
+        }
+        let notes = notes.unwrap_or_default().trim().to_string();
+
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO purchase_requests (container_id, item, quantity, notes, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![container_id, &item, quantity, &notes, &now],
         )?;
 
-        let accounts_iter = stmt.query_map(params![container_id, &end_date], |row| {
-            Ok(AccountBalance {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                account_type: row.get(2)?,
-                opening_balance: row.get(3)?,
-                container_id: row.get(4)?,
-                created_at: row.get(5)?,
-                balance: row.get(6)?,
-            })
-        })?;
+        Self::fetch_purchase_request(&conn, conn.last_insert_rowid())
+    }
 
-        let mut assets = Vec::new();
-        let mut liabilities = Vec::new();
-        let mut equity = Vec::new();
+    pub fn add_purchase_quote(
+        &self,
+        request_id: i64,
+        supplier: String,
+        unit_price: i64,
+        delivery_days: Option<i64>,
+        notes: Option<String>,
+    ) -> Result<PurchaseRequest> {
+        let supplier = supplier.trim().to_string();
+        if supplier.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Supplier cannot be empty".to_string(),
+            ));
+        }
+        Self::ensure_max_chars("Supplier", &supplier, Self::MAX_NAME_CHARS)?;
+        if unit_price <= 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Unit price must be positive".to_string(),
+            ));
+        }
+        let notes = notes.unwrap_or_default().trim().to_string();
 
-        for account in accounts_iter {
-            let account = account?;
-            match account.account_type.as_str() {
-                "asset" | "contra_asset" => assets.push(account),
-                "liability" => liabilities.push(account),
-                _ => equity.push(account),
-            }
+        let conn = self.conn.lock().unwrap();
+        let request = Self::fetch_purchase_request(&conn, request_id)?;
+        if request.status != "open" {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Purchase request is already {}",
+                request.status
+            )));
         }
+        if unit_price.checked_mul(request.quantity).is_none() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Quote total is too large".to_string(),
+            ));
+        }
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO purchase_quotes (request_id, supplier, unit_price, delivery_days, notes, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![request_id, &supplier, unit_price, delivery_days, &notes, &now],
+        )?;
 
-        let total_income: i64 = conn.query_row(
-            "SELECT COALESCE(SUM(ABS(t.amount)), 0)
-             FROM transactions t
-             LEFT JOIN categories c ON c.name = t.category
-             WHERE t.container_id = ?1 AND t.transfer_id IS NULL
-               AND t.date >= ?2 AND t.date <= ?3
-               AND COALESCE(c.category_type, 'expense') = 'income'",
-            params![container_id, &start_date, &end_date],
-            |row| row.get(0),
+        Self::fetch_purchase_request(&conn, request_id)
+    }
+
+    pub fn delete_purchase_quote(&self, id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM purchase_quotes
+             WHERE id = ?1
+               AND request_id IN (SELECT id FROM purchase_requests WHERE status = 'open')",
+            [id],
         )?;
+        Ok(())
+    }
 
-        let total_expense: i64 = conn.query_row(
-            "SELECT COALESCE(SUM(ABS(t.amount)), 0)
-             FROM transactions t
-             LEFT JOIN categories c ON c.name = t.category
-             WHERE t.container_id = ?1 AND t.transfer_id IS NULL
-               AND t.date >= ?2 AND t.date <= ?3
-               AND COALESCE(c.category_type, 'expense') = 'expense'",
-            params![container_id, &start_date, &end_date],
+    pub fn get_purchase_requests(&self, container_id: i64, status: Option<String>) -> Result<Vec<PurchaseRequest>> {
+        let conn = self.conn.lock().unwrap();
+        let request_ids: Vec<i64> = {
+            let mut stmt = conn.prepare(
+                "SELECT id FROM purchase_requests
+                 WHERE container_id = ?1 AND (?2 IS NULL OR status = ?2)
+                 ORDER BY created_at DESC, id DESC",
+            )?;
+            let rows = stmt.query_map(params![container_id, status], |row| row.get::<_, i64>(0))?;
+            rows.collect::<Result<Vec<i64>>>()?
+        };
+        request_ids
+            .into_iter()
+            .map(|request_id| Self::fetch_purchase_request(&conn, request_id))
+            .collect()
+    }
+
+    /// Picks the winning quote and books its total as an expense on
+    /// `account_id`, closing the request as ordered.
+    pub fn accept_purchase_quote(
+        &self,
+        quote_id: i64,
+        account_id: i64,
+        category: Option<String>,
+        date: Option<String>,
+    ) -> Result<PurchaseRequest> {
+        let mut conn = self.conn.lock().unwrap();
+        let request_id: i64 = conn.query_row(
+            "SELECT request_id FROM purchase_quotes WHERE id = ?1",
+            [quote_id],
             |row| row.get(0),
         )?;
+        let request = Self::fetch_purchase_request(&conn, request_id)?;
+        if request.status != "open" {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Purchase request is already {}",
+                request.status
+            )));
+        }
+        let account_container: i64 = conn.query_row(
+            "SELECT container_id FROM accounts WHERE id = ?1",
+            [account_id],
+            |row| row.get(0),
+        )?;
+        if account_container != request.container_id {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Account belongs to a different container".to_string(),
+            ));
+        }
+        let Some(quote) = request.quotes.iter().find(|quote| quote.id == quote_id) else {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        };
 
-        let net_income = total_income - total_expense;
-
-        equity.retain(|account| account.name != "Laba Tahun Berjalan");
-        equity.push(AccountBalance {
-            id: 0,
-            name: "Laba Tahun Berjalan".to_string(),
-            account_type: "equity".to_string(),
-            opening_balance: 0,
-            balance: net_income,
-            container_id,
-            created_at: end_date.clone(),
-        });
+        let date = Self::normalize_transaction_date(date)?;
+        let category = category
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| Self::DEFAULT_FALLBACK_CATEGORY.to_string());
+        let description = format!("{} x{} - {}", request.item, request.quantity, quote.supplier);
 
-        let total_assets: i64 = assets.iter().map(|a| a.balance).sum();
-        let total_liabilities: i64 = liabilities.iter().map(|a| a.balance).sum();
-        let total_equity: i64 = equity.iter().map(|a| a.balance).sum();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO transactions (amount, description, category, date, container_id, account_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![-quote.total, &description, &category, &date, request.container_id, account_id],
+        )?;
+        let transaction_id = tx.last_insert_rowid();
+        tx.execute(
+            "UPDATE purchase_requests SET status = 'ordered', chosen_quote_id = ?1, transaction_id = ?2 WHERE id = ?3",
+            params![quote_id, transaction_id, request_id],
+        )?;
+        let request = Self::fetch_purchase_request(&tx, request_id)?;
+        tx.commit()?;
 
-        Ok(BalanceSheetReport {
-            as_of: end_date,
-            assets,
-            liabilities,
-            equity,
-            total_assets,
-            total_liabilities,
-            total_equity,
-        })
+        Ok(request)
     }
 
-    pub fn get_profit_and_loss_for_year(&self, container_id: i64, year: String) -> Result<ProfitLossReport> {
+    pub fn cancel_purchase_request(&self, id: i64) -> Result<PurchaseRequest> {
         let conn = self.conn.lock().unwrap();
-        let (start_date, end_date) = Self::year_range_last_known(&conn, container_id, &year)?;
-
-        let mut income_stmt = conn.prepare(
-            "SELECT t.category, SUM(ABS(t.amount)) as total
-             FROM transactions t
-             LEFT JOIN categories c ON c.name = t.category
-             WHERE t.container_id = ?1 AND t.transfer_id IS NULL
-               AND t.date >= ?2 AND t.date <= ?3
-               AND COALESCE(c.category_type, 'expense') = 'income'
-             GROUP BY t.category
-             ORDER BY total DESC",
+        let updated = conn.execute(
+            "UPDATE purchase_requests SET status = 'cancelled' WHERE id = ?1 AND status = 'open'",
+            [id],
         )?;
-        let income_iter = income_stmt.query_map(
-            params![container_id, &start_date, &end_date],
+        if updated == 0 {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Only open purchase requests can be cancelled".to_string(),
+            ));
+        }
+        Self::fetch_purchase_request(&conn, id)
+    }
+
+    /// Loads a request with its quotes, cheapest total first.
+    fn fetch_purchase_request(conn: &Connection, id: i64) -> Result<PurchaseRequest> {
+        let mut request = conn.query_row(
+            "SELECT id, container_id, item, quantity, notes, status, chosen_quote_id, transaction_id, created_at
+             FROM purchase_requests
+             WHERE id = ?1",
+            [id],
             |row| {
-                Ok(ProfitLossLine {
-                    category: row.get(0)?,
-                    total: row.get(1)?,
+                Ok(PurchaseRequest {
+                    id: row.get(0)?,
+                    container_id: row.get(1)?,
+                    item: row.get(2)?,
+                    quantity: row.get(3)?,
+                    notes: row.get(4)?,
+                    status: row.get(5)?,
+                    chosen_quote_id: row.get(6)?,
+                    transaction_id: row.get(7)?,
+                    created_at: row.get(8)?,
+                    quotes: Vec::new(),
                 })
             },
         )?;
-        let income: Vec<ProfitLossLine> = income_iter.collect::<Result<Vec<_>>>()?;
 
-        let mut expense_stmt = conn.prepare(
-            "SELECT t.category, SUM(ABS(t.amount)) as total
-             FROM transactions t
-             LEFT JOIN categories c ON c.name = t.category
-             WHERE t.container_id = ?1 AND t.transfer_id IS NULL
-               AND t.date >= ?2 AND t.date <= ?3
-               AND COALESCE(c.category_type, 'expense') = 'expense'
-             GROUP BY t.category
-             ORDER BY total DESC",
+        let mut stmt = conn.prepare(
+            "SELECT id, request_id, supplier, unit_price, delivery_days, notes, created_at
+             FROM purchase_quotes
+             WHERE request_id = ?1
+             ORDER BY unit_price ASC, delivery_days ASC, id ASC",
         )?;
-        let expense_iter = expense_stmt.query_map(
-            params![container_id, &start_date, &end_date],
+        let quantity = request.quantity;
+        let quotes = stmt.query_map([id], |row| {
+            let unit_price: i64 = row.get(3)?;
+            let total = unit_price
+                .checked_mul(quantity)
+                .ok_or_else(|| rusqlite::Error::InvalidParameterName("Quote total is too large".to_string()))?;
+            Ok(PurchaseQuote {
+                id: row.get(0)?,
+                request_id: row.get(1)?,
+                supplier: row.get(2)?,
+                unit_price,
+                total,
+                delivery_days: row.get(4)?,
+                notes: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })?;
+        request.quotes = quotes.collect::<Result<Vec<_>>>()?;
+
+        Ok(request)
+    }
+
+    pub fn get_changes_since(&self, container_id: i64, cursor: i64) -> Result<ChangeFeed> {
+        let conn = self.conn.lock().unwrap();
+        // Sequence numbers are never reused, so anything below the oldest
+        // remaining entry (or the last one handed out) was pruned.
+        let pruned_through: i64 = conn.query_row(
+            "SELECT COALESCE(
+                 (SELECT MIN(seq) FROM change_log),
+                 (SELECT seq FROM sqlite_sequence WHERE name = 'change_log') + 1,
+                 1
+             ) - 1",
+            [],
+            |row| row.get(0),
+        )?;
+        let mut stmt = conn.prepare(
+            "SELECT seq, entity, entity_id, container_id, operation, changed_at
+             FROM change_log
+             WHERE seq > ?1 AND (container_id = ?2 OR container_id IS NULL)
+             ORDER BY seq ASC
+             LIMIT ?3",
+        )?;
+        let rows = stmt.query_map(
+            params![cursor, container_id, Self::CHANGE_FEED_PAGE_SIZE + 1],
             |row| {
-                Ok(ProfitLossLine {
-                    category: row.get(0)?,
-                    total: row.get(1)?,
+                Ok(ChangeEntry {
+                    seq: row.get(0)?,
+                    entity: row.get(1)?,
+                    entity_id: row.get(2)?,
+                    container_id: row.get(3)?,
+                    operation: row.get(4)?,
+                    changed_at: row.get(5)?,
                 })
             },
         )?;
-        let expense: Vec<ProfitLossLine> = expense_iter.collect::<Result<Vec<_>>>()?;
+        let mut changes = rows.collect::<Result<Vec<_>>>()?;
+
+        let has_more = changes.len() as i64 > Self::CHANGE_FEED_PAGE_SIZE;
+        changes.truncate(Self::CHANGE_FEED_PAGE_SIZE as usize);
+        let cursor_expired = cursor < pruned_through;
+        let cursor = changes.last().map(|change| change.seq).unwrap_or(cursor.max(pruned_through));
+
+        Ok(ChangeFeed {
+            changes,
+            cursor,
+            has_more,
+            cursor_expired,
+        })
+    }
+
+    /// Drops change feed entries older than `CHANGE_LOG_RETENTION_DAYS`.
+    /// Consumers that fall further behind get `cursor_expired` and resync.
+    pub fn prune_change_log(&self) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM change_log WHERE changed_at < datetime('now', 'localtime', ?1)",
+            [format!("-{} days", Self::CHANGE_LOG_RETENTION_DAYS)],
+        )
+    }
+
+    /// Newest first. Dates are `YYYY-MM-DD` and inclusive.
+    pub fn get_audit_log(
+        &self,
+        entity: Option<String>,
+        start_date: Option<String>,
+        end_date: Option<String>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> Result<Vec<AuditEntry>> {
+        let entity = entity.map(|e| e.trim().to_string()).filter(|e| !e.is_empty());
+        let start = match start_date.as_deref().filter(|v| !v.trim().is_empty()) {
+            Some(value) => format!("{} 00:00:00", Self::parse_day(value)?.format("%Y-%m-%d")),
+            None => "0000-00-00 00:00:00".to_string(),
+        };
+        let end = match end_date.as_deref().filter(|v| !v.trim().is_empty()) {
+            Some(value) => format!("{} 23:59:59", Self::parse_day(value)?.format("%Y-%m-%d")),
+            None => "9999-12-31 23:59:59".to_string(),
+        };
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, entity, entity_id, container_id, operation, old_json, new_json, changed_at
+             FROM audit_log
+             WHERE (?1 IS NULL OR entity = ?1) AND changed_at >= ?2 AND changed_at <= ?3
+             ORDER BY id DESC
+             LIMIT ?4 OFFSET ?5",
+        )?;
+        let rows = stmt.query_map(
+            params![entity, &start, &end, limit.unwrap_or(-1), offset.unwrap_or(0).max(0)],
+            Self::audit_entry_from_row,
+        )?;
+        rows.collect()
+    }
+
+    fn audit_entry_from_row(row: &rusqlite::Row) -> Result<AuditEntry> {
+        let parse_json = |index: usize| -> Result<Option<serde_json::Value>> {
+            row.get::<_, Option<String>>(index)?
+                .map(|json| {
+                    serde_json::from_str(&json).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e))
+                    })
+                })
+                .transpose()
+        };
+        Ok(AuditEntry {
+            id: row.get(0)?,
+            entity: row.get(1)?,
+            entity_id: row.get(2)?,
+            container_id: row.get(3)?,
+            operation: row.get(4)?,
+            old_values: parse_json(5)?,
+            new_values: parse_json(6)?,
+            changed_at: row.get(7)?,
+        })
+    }
+
+    pub fn get_minor_units(&self, container_id: i64) -> Result<u32> {
+        let conn = self.conn.lock().unwrap();
+        Self::read_minor_units(&conn, container_id)
+    }
+
+    /// Sets how many decimal places a container's amounts have, e.g. 0 for
+    /// rupiah or 3 for grams of gold. Stored amounts are integers in those
+    /// units, so the value can only change while the container has none.
+    pub fn set_minor_units(&self, container_id: i64, minor_units: u32) -> Result<u32> {
+        if minor_units > Self::MAX_MINOR_UNITS {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Minor units must be between 0 and {}",
+                Self::MAX_MINOR_UNITS
+            )));
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.query_row("SELECT id FROM containers WHERE id = ?1", [container_id], |row| row.get::<_, i64>(0))?;
+        if Self::read_minor_units(&tx, container_id)? != minor_units && Self::container_has_amounts(&tx, container_id)? {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Decimal places can only be changed before the container has any amounts".to_string(),
+            ));
+        }
+        tx.execute(
+            "UPDATE containers SET minor_units = ?1 WHERE id = ?2",
+            params![minor_units, container_id],
+        )?;
+        tx.commit()?;
+        Ok(minor_units)
+    }
+
+    pub fn get_locale(&self) -> Result<String> {
+        let conn = self.conn.lock().unwrap();
+        Self::read_display_locale(&conn)
+    }
 
-        let total_income: i64 = income.iter().map(|line| line.total).sum();
-        let total_expense: i64 = expense.iter().map(|line| line.total).sum();
-        let net_income = total_income - total_expense;
+    pub fn set_locale(&self, locale: String) -> Result<String> {
+        let locale = locale.trim().to_lowercase();
+        if !i18n::is_supported(&locale) {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Unsupported locale '{}'",
+                locale
+            )));
+        }
 
-        Ok(ProfitLossReport {
-            start_date,
-            end_date,
-            income,
-            expense,
-            total_income,
-            total_expense,
-            net_income,
-        })
+        let conn = self.conn.lock().unwrap();
+        Self::write_setting(&conn, "locale", &locale)?;
+        Ok(locale)
     }
 
-    pub fn get_balance_sheet_for_year(&self, container_id: i64, year: String) -> Result<BalanceSheetReport> {
+    pub fn get_week_start(&self) -> Result<String> {
         let conn = self.conn.lock().unwrap();
-        let (start_date, end_date) = Self::year_range_last_known(&conn, container_id, &year)?;
+        Self::read_week_start(&conn)
+    }
 
-        let mut stmt = conn.prepare(
-            "SELECT a.id, a.name, a.account_type, a.opening_balance, a.container_id, a.created_at,
-                    COALESCE(SUM(t.amount), 0) + a.opening_balance AS balance
-             FROM accounts a
-             LEFT JOIN transactions t ON t.account_id = a.id AND t.date <= ?2
-             WHERE a.container_id = ?1
-             GROUP BY a.id
-             ORDER BY a.name ASC",
-        )?;
+    pub fn set_week_start(&self, week_start: String) -> Result<String> {
+        let week_start = week_start.trim().to_lowercase();
+        if !Self::WEEK_DAYS.contains(&week_start.as_str()) {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Unsupported week start '{}'",
+                week_start
+            )));
+        }
 
-        let accounts_iter = stmt.query_map(params![container_id, &end_date], |row| {
-            Ok(AccountBalance {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                account_type: row.get(2)?,
-                opening_balance: row.get(3)?,
-                container_id: row.get(4)?,
-                created_at: row.get(5)?,
-                balance: row.get(6)?,
-            })
+        let conn = self.conn.lock().unwrap();
+        Self::write_setting(&conn, "week_start", &week_start)?;
+        Ok(week_start)
+    }
+
+    pub fn get_date_format(&self) -> Result<String> {
+        let conn = self.conn.lock().unwrap();
+        Self::read_date_format(&conn)
+    }
+
+    pub fn set_date_format(&self, date_format: String) -> Result<String> {
+        let date_format = date_format.trim().to_uppercase();
+        if !Self::DATE_FORMATS.iter().any(|(name, _)| *name == date_format) {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "Unsupported date format '{}'",
+                date_format
+            )));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        Self::write_setting(&conn, "date_format", &date_format)?;
+        Ok(date_format)
+    }
+
+    pub fn get_app_state(&self) -> Result<AppState> {
+        let conn = self.conn.lock().unwrap();
+        let stored_container_id = Self::read_setting(&conn, "last_container_id")?
+            .and_then(|v| v.parse::<i64>().ok());
+
+        let existing: Option<i64> = match stored_container_id {
+            Some(id) => conn
+                .query_row("SELECT id FROM containers WHERE id = ?1", [id], |row| row.get(0))
+                .optional()?,
+            None => None,
+        };
+        let last_container_id = match existing {
+            Some(id) => id,
+            None => conn.query_row(
+                "SELECT id FROM containers ORDER BY is_default DESC, created_at ASC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )?,
+        };
+
+        let onboarding_completed = Self::read_setting(&conn, "onboarding_completed")?
+            .map(|v| v == "1")
+            .unwrap_or(false);
+
+        let readonly_queries_enabled = Self::read_setting(&conn, "readonly_queries_enabled")?
+            .map(|v| v == "1")
+            .unwrap_or(false);
+
+        Ok(AppState {
+            last_container_id,
+            locale: Self::read_display_locale(&conn)?,
+            onboarding_completed,
+            readonly_queries_enabled,
+        })
+    }
+
+    pub fn set_last_container(&self, container_id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT id FROM containers WHERE id = ?1", [container_id], |row| {
+            row.get::<_, i64>(0)
         })?;
+        Self::write_setting(&conn, "last_container_id", &container_id.to_string())
+    }
 
-        let mut assets = Vec::new();
-        let mut liabilities = Vec::new();
-        let mut equity = Vec::new();
+    pub fn set_onboarding_completed(&self, completed: bool) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        Self::write_setting(&conn, "onboarding_completed", if completed { "1" } else { "0" })
+    }
 
-        for account in accounts_iter {
-            let account = account?;
-            match account.account_type.as_str() {
-                "asset" | "contra_asset" => assets.push(account),
-                "liability" => liabilities.push(account),
-                _ => equity.push(account),
-            }
+    pub fn set_readonly_queries_enabled(&self, enabled: bool) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        Self::write_setting(&conn, "readonly_queries_enabled", if enabled { "1" } else { "0" })
+    }
+
+    pub fn run_readonly_query(&self, sql: String) -> Result<QueryResult> {
+        Self::ensure_max_chars("Query", &sql, Self::MAX_QUERY_CHARS)?;
+        let conn = self.conn.lock().unwrap();
+        let enabled = Self::read_setting(&conn, "readonly_queries_enabled")?.as_deref() == Some("1");
+        if !enabled {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Read-only queries are disabled".to_string(),
+            ));
         }
 
-        let total_income: i64 = conn.query_row(
-            "SELECT COALESCE(SUM(ABS(t.amount)), 0)
-             FROM transactions t
-             LEFT JOIN categories c ON c.name = t.category
-             WHERE t.container_id = ?1 AND t.transfer_id IS NULL
-               AND t.date >= ?2 AND t.date <= ?3
-               AND COALESCE(c.category_type, 'expense') = 'income'",
-            params![container_id, &start_date, &end_date],
-            |row| row.get(0),
-        )?;
+        let sql = sql.trim().trim_end_matches(';').trim();
+        let keyword = sql
+            .split_whitespace()
+            .next()
+            .unwrap_or("")
+            .to_uppercase();
+        if keyword != "SELECT" && keyword != "WITH" {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Only SELECT statements are allowed".to_string(),
+            ));
+        }
+        if sql.contains(';') {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Only a single statement is allowed".to_string(),
+            ));
+        }
 
-        let total_expense: i64 = conn.query_row(
-            "SELECT COALESCE(SUM(ABS(t.amount)), 0)
-             FROM transactions t
-             LEFT JOIN categories c ON c.name = t.category
-             WHERE t.container_id = ?1 AND t.transfer_id IS NULL
-               AND t.date >= ?2 AND t.date <= ?3
-               AND COALESCE(c.category_type, 'expense') = 'expense'",
-            params![container_id, &start_date, &end_date],
-            |row| row.get(0),
-        )?;
+        let mut stmt = conn.prepare(sql)?;
+        if !stmt.readonly() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Only read-only statements are allowed".to_string(),
+            ));
+        }
 
-        let net_income = total_income - total_expense;
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+        let column_count = columns.len();
 
-        equity.retain(|account| account.name != "Laba Tahun Berjalan");
-        equity.push(AccountBalance {
-            id: 0,
-            name: "Laba Tahun Berjalan".to_string(),
-            account_type: "equity".to_string(),
-            opening_balance: 0,
-            balance: net_income,
-            container_id,
-            created_at: end_date.clone(),
+        let interrupt = conn.get_interrupt_handle();
+        let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+        let watchdog = std::thread::spawn(move || {
+            if let Err(std::sync::mpsc::RecvTimeoutError::Timeout) = done_rx.recv_timeout(Self::QUERY_TIMEOUT) {
+                interrupt.interrupt();
+            }
         });
 
-        let total_assets: i64 = assets.iter().map(|a| a.balance).sum();
-        let total_liabilities: i64 = liabilities.iter().map(|a| a.balance).sum();
-        let total_equity: i64 = equity.iter().map(|a| a.balance).sum();
+        let collected = (|| -> Result<(Vec<Vec<serde_json::Value>>, bool)> {
+            let mut rows = stmt.query([])?;
+            let mut result = Vec::new();
+            while let Some(row) = rows.next()? {
+                if result.len() == Self::MAX_QUERY_ROWS {
+                    return Ok((result, true));
+                }
+                let mut values = Vec::with_capacity(column_count);
+                for index in 0..column_count {
+                    values.push(Self::json_value(row.get_ref(index)?));
+                }
+                result.push(values);
+            }
+            Ok((result, false))
+        })();
 
-        Ok(BalanceSheetReport {
-            as_of: end_date,
-            assets,
-            liabilities,
-            equity,
-            total_assets,
-            total_liabilities,
-            total_equity,
+        let _ = done_tx.send(());
+        let _ = watchdog.join();
+
+        let (rows, truncated) = collected.map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(err, _) if err.code == rusqlite::ErrorCode::OperationInterrupted => {
+                rusqlite::Error::InvalidParameterName("Query exceeded the time limit".to_string())
+            }
+            other => other,
+        })?;
+
+        Ok(QueryResult {
+            columns,
+            rows,
+            truncated,
         })
     }
 
+    /// Inverse of `json_value`, for rows stored as JSON in the undo and
+    /// audit logs.
+    fn sql_value(value: &serde_json::Value) -> Value {
+        match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(v) => Value::Integer(i64::from(*v)),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(v) => Value::Integer(v),
+                None => Value::Real(n.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::String(v) => Value::Text(v.clone()),
+            serde_json::Value::Array(bytes) => {
+                Value::Blob(bytes.iter().map(|b| b.as_u64().unwrap_or_default() as u8).collect())
+            }
+            serde_json::Value::Object(_) => Value::Null,
+        }
+    }
+
+    fn json_value(value: rusqlite::types::ValueRef) -> serde_json::Value {
+        match value {
+            rusqlite::types::ValueRef::Null => serde_json::Value::Null,
+            rusqlite::types::ValueRef::Integer(v) => serde_json::Value::from(v),
+            rusqlite::types::ValueRef::Real(v) => serde_json::Value::from(v),
+            rusqlite::types::ValueRef::Text(v) => serde_json::Value::from(String::from_utf8_lossy(v).to_string()),
+            rusqlite::types::ValueRef::Blob(v) => serde_json::Value::from(v.to_vec()),
+        }
+    }
+
     pub fn get_containers(&self) -> Result<Vec<Container>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT id, name, created_at, is_default FROM containers ORDER BY is_default DESC, created_at ASC")?;
+        let mut stmt = conn.prepare("SELECT id, name, created_at, is_default FROM containers WHERE archived = 0 ORDER BY is_default DESC, created_at ASC")?;
         
         let containers = stmt.query_map([], |row| {
             Ok(Container {
@@ -1447,9 +11012,67 @@ impl Database {
         containers.collect()
     }
 
+    /// Size and activity of every container, archived ones included, so the
+    /// container screen can show what an archive or merge would touch.
+    /// Storage share is estimated from the text each container's transactions
+    /// carry plus a fixed per-row overhead, and scaled to the database file size.
+    pub fn get_container_stats(&self) -> Result<Vec<ContainerStats>> {
+        let conn = self.conn.lock().unwrap();
+        let page_count: i64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+        let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        let file_bytes = page_count * page_size;
+
+        let mut stmt = conn.prepare(
+            "SELECT c.id, c.name, c.archived,
+                    (SELECT COUNT(*) FROM transactions t WHERE t.container_id = c.id AND t.deleted_at IS NULL),
+                    (SELECT MIN(t.date) FROM transactions t WHERE t.container_id = c.id AND t.deleted_at IS NULL),
+                    (SELECT MAX(t.date) FROM transactions t WHERE t.container_id = c.id AND t.deleted_at IS NULL),
+                    (SELECT COUNT(*) FROM accounts a WHERE a.container_id = c.id AND a.archived = 0),
+                    (SELECT COUNT(*) FROM accounts a WHERE a.container_id = c.id AND a.archived = 1),
+                    (SELECT COALESCE(SUM(LENGTH(CAST(t.description AS BLOB)) + LENGTH(CAST(t.category AS BLOB))
+                                         + LENGTH(t.date) + ?1), 0)
+                       FROM transactions t WHERE t.container_id = c.id AND t.deleted_at IS NULL)
+             FROM containers c
+             ORDER BY c.is_default DESC, c.created_at ASC",
+        )?;
+        let rows = stmt.query_map([Self::STATS_ROW_OVERHEAD_BYTES], |row| {
+            Ok((
+                ContainerStats {
+                    container_id: row.get(0)?,
+                    name: row.get(1)?,
+                    archived: row.get::<_, i64>(2)? == 1,
+                    transaction_count: row.get(3)?,
+                    first_date: row.get(4)?,
+                    last_date: row.get(5)?,
+                    account_count: row.get(6)?,
+                    archived_account_count: row.get(7)?,
+                    storage_share: 0.0,
+                    estimated_bytes: 0,
+                },
+                row.get::<_, i64>(8)?,
+            ))
+        })?;
+        let rows = rows.collect::<Result<Vec<_>>>()?;
+
+        let total_weight: i64 = rows.iter().map(|(_, weight)| weight).sum();
+        Ok(rows
+            .into_iter()
+            .map(|(mut stats, weight)| {
+                if total_weight > 0 {
+                    stats.storage_share = weight as f64 / total_weight as f64;
+                    stats.estimated_bytes = (stats.storage_share * file_bytes as f64).round() as i64;
+                }
+                stats
+            })
+            .collect())
+    }
+
     pub fn add_container(&self, name: String) -> Result<Container> {
+        Self::ensure_max_chars("Name", &name, Self::MAX_NAME_CHARS)?;
         let conn = self.conn.lock().unwrap();
         let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let taken = Self::names_in_scope(&conn, "container", None)?;
+        Self::ensure_name_available("container", &name, &taken)?;
         
         conn.execute(
             "INSERT INTO containers (name, created_at, is_default) VALUES (?1, ?2, 0)",
@@ -1486,6 +11109,7 @@ impl Database {
     }
 
     pub fn update_container(&self, id: i64, name: String) -> Result<Container> {
+        Self::ensure_max_chars("Name", &name, Self::MAX_NAME_CHARS)?;
         let conn = self.conn.lock().unwrap();
         
         conn.execute(
@@ -1555,53 +11179,326 @@ impl Database {
             }
         }
 
-        for (name, category_type) in Self::DEFAULT_CATEGORIES {
-            conn.execute(
-                "INSERT OR IGNORE INTO categories (name, category_type, is_default) VALUES (?1, ?2, 1)",
-                params![name, category_type],
-            )?;
-            conn.execute(
-                "UPDATE categories SET category_type = ?1, is_default = 1 WHERE name = ?2",
-                params![category_type, name],
-            )?;
+        for (name, category_type) in Self::DEFAULT_CATEGORIES {
+            conn.execute(
+                "INSERT OR IGNORE INTO categories (name, category_type, is_default) VALUES (?1, ?2, 1)",
+                params![name, category_type],
+            )?;
+            conn.execute(
+                "UPDATE categories SET category_type = ?1, is_default = 1 WHERE name = ?2",
+                params![category_type, name],
+            )?;
+        }
+
+        conn.execute(
+            "UPDATE categories
+             SET is_default = 0
+             WHERE name NOT IN (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                Self::DEFAULT_CATEGORIES[0].0,
+                Self::DEFAULT_CATEGORIES[1].0,
+                Self::DEFAULT_CATEGORIES[2].0,
+                Self::DEFAULT_CATEGORIES[3].0,
+                Self::DEFAULT_CATEGORIES[4].0,
+                Self::DEFAULT_CATEGORIES[5].0,
+                Self::DEFAULT_CATEGORIES[6].0,
+                Self::DEFAULT_CATEGORIES[7].0,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn ensure_default_equity_accounts(conn: &Connection, container_id: i64) -> Result<()> {
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        for name in Self::DEFAULT_EQUITY_ACCOUNTS {
+            conn.execute(
+                "INSERT OR IGNORE INTO accounts (name, account_type, opening_balance, container_id, created_at)
+                 VALUES (?1, 'equity', 0, ?2, ?3)",
+                params![name, container_id, &now],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn ensure_balance_snapshots(conn: &Connection, account_id: i64, end: chrono::NaiveDate) -> Result<usize> {
+        let snapshots = Self::missing_balance_snapshots(conn, account_id, end)?;
+        let mut insert = conn.prepare(
+            "INSERT OR REPLACE INTO balance_snapshots (account_id, snapshot_date, balance) VALUES (?1, ?2, ?3)",
+        )?;
+        for snapshot in &snapshots {
+            insert.execute(params![snapshot.account_id, &snapshot.date, snapshot.balance])?;
+        }
+        Ok(snapshots.len())
+    }
+
+    /// Daily balances after the last stored snapshot up to `end`, or from the
+    /// account's first day when none are stored.
+    fn missing_balance_snapshots(conn: &Connection, account_id: i64, end: chrono::NaiveDate) -> Result<Vec<BalanceSnapshot>> {
+        let last: Option<(String, i64)> = conn
+            .query_row(
+                "SELECT snapshot_date, balance
+                 FROM balance_snapshots
+                 WHERE account_id = ?1
+                 ORDER BY snapshot_date DESC
+                 LIMIT 1",
+                [account_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        let (mut day, mut balance) = match last {
+            Some((date, balance)) => {
+                let last_day = Self::parse_day(&date)?;
+                if last_day >= end {
+                    return Ok(Vec::new());
+                }
+                (last_day.succ_opt().unwrap_or(last_day), balance)
+            }
+            None => {
+                let (created_at, opening_balance, first_tx): (String, i64, Option<String>) = conn.query_row(
+                    "SELECT a.created_at, a.opening_balance,
+                            (SELECT MIN(t.date) FROM transactions t WHERE t.account_id = a.id AND t.deleted_at IS NULL)
+                     FROM accounts a
+                     WHERE a.id = ?1",
+                    [account_id],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )?;
+                let created = Self::parse_day(&Self::date_only(&created_at))?;
+                let first = match first_tx {
+                    Some(date) => Self::parse_day(&Self::date_only(&date))?.min(created),
+                    None => created,
+                };
+                (first, opening_balance)
+            }
+        };
+
+        if day > end {
+            return Ok(Vec::new());
+        }
+
+        let mut daily_totals: HashMap<String, i64> = HashMap::new();
+        {
+            let mut stmt = conn.prepare(
+                "SELECT substr(date, 1, 10) as day, COALESCE(SUM(amount), 0)
+                 FROM transactions
+                 WHERE account_id = ?1 AND date >= ?2 AND date <= ?3 AND deleted_at IS NULL
+                 GROUP BY day",
+            )?;
+            let rows = stmt.query_map(
+                params![
+                    account_id,
+                    format!("{} 00:00:00", day.format("%Y-%m-%d")),
+                    format!("{} 23:59:59", end.format("%Y-%m-%d"))
+                ],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+            )?;
+            for row in rows {
+                let (date, total) = row?;
+                daily_totals.insert(date, total);
+            }
+        }
+
+        let mut snapshots = Vec::new();
+        while day <= end {
+            let key = day.format("%Y-%m-%d").to_string();
+            balance += daily_totals.get(&key).copied().unwrap_or(0);
+            snapshots.push(BalanceSnapshot {
+                account_id,
+                date: key,
+                balance,
+            });
+            day = match day.succ_opt() {
+                Some(next) => next,
+                None => break,
+            };
+        }
+
+        Ok(snapshots)
+    }
+
+    fn normalize_idempotency_key(key: Option<String>) -> Option<String> {
+        key.map(|k| k.trim().to_string()).filter(|k| !k.is_empty())
+    }
+
+    fn find_idempotent_result(conn: &Connection, key: &str, command: &str) -> Result<Option<i64>> {
+        let existing: Option<(String, i64)> = conn
+            .query_row(
+                "SELECT command, result_id FROM idempotency_keys WHERE key = ?1",
+                [key],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        match existing {
+            Some((stored_command, result_id)) if stored_command == command => Ok(Some(result_id)),
+            Some((stored_command, _)) => Err(rusqlite::Error::InvalidParameterName(format!(
+                "Idempotency key already used by {}",
+                stored_command
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    fn record_idempotency_key(conn: &Connection, key: &str, command: &str, result_id: i64) -> Result<()> {
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO idempotency_keys (key, command, result_id, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![key, command, result_id, &now],
+        )?;
+        Ok(())
+    }
+
+    fn read_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+        conn.query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| row.get(0))
+            .optional()
+    }
+
+    fn write_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    /// A container's decimal places. Containers that never set their own
+    /// fall back to the profile-wide setting used before it was per
+    /// container, then to the default.
+    fn read_minor_units(conn: &Connection, container_id: i64) -> Result<u32> {
+        let own: Option<i64> = conn
+            .query_row("SELECT minor_units FROM containers WHERE id = ?1", [container_id], |row| row.get(0))
+            .optional()?
+            .flatten();
+        if let Some(value) = own.and_then(|v| u32::try_from(v).ok()).filter(|v| *v <= Self::MAX_MINOR_UNITS) {
+            return Ok(value);
+        }
+        let value = Self::read_setting(conn, "minor_units")?;
+        Ok(value
+            .and_then(|v| v.parse::<u32>().ok())
+            .filter(|v| *v <= Self::MAX_MINOR_UNITS)
+            .unwrap_or(Self::DEFAULT_MINOR_UNITS))
+    }
+
+    /// Whether a container already stores amounts, which would be misread if
+    /// its decimal places changed. Accounts only count with a non-zero
+    /// opening balance; any other row that belongs to the container, other
+    /// than logs and plain labels, counts.
+    fn container_has_amounts(conn: &Connection, container_id: i64) -> Result<bool> {
+        let opening_balances: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM accounts WHERE container_id = ?1 AND opening_balance != 0",
+            [container_id],
+            |row| row.get(0),
+        )?;
+        if opening_balances > 0 {
+            return Ok(true);
+        }
+        let columns = Self::table_columns(conn, "main")?;
+        for table in columns.keys() {
+            if Self::AMOUNT_FREE_TABLES.contains(&table.as_str()) {
+                continue;
+            }
+            let Some(scope) = Self::container_scope("main", table, &columns) else {
+                continue;
+            };
+            let rows: i64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM main.{} WHERE {}", table, scope),
+                [container_id],
+                |row| row.get(0),
+            )?;
+            if rows > 0 {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Column names of every ordinary table in `schema`.
+    fn table_columns(conn: &Connection, schema: &str) -> Result<HashMap<String, Vec<String>>> {
+        let table_names: Vec<String> = {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT name FROM {}.sqlite_master
+                 WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name NOT LIKE 'transactions_fts%'",
+                schema
+            ))?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.collect::<Result<Vec<String>>>()?
+        };
+        let mut columns = HashMap::new();
+        for name in table_names {
+            let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1, ?2) ORDER BY cid")?;
+            let rows = stmt.query_map([&name, schema], |row| row.get::<_, String>(0))?;
+            columns.insert(name, rows.collect::<Result<Vec<String>>>()?);
+        }
+        Ok(columns)
+    }
+
+    /// The locale shown as selected in the UI while none has been picked.
+    fn read_display_locale(conn: &Connection) -> Result<String> {
+        let locale = Self::read_locale(conn)?;
+        if locale == i18n::UNSET_LOCALE {
+            return Ok(i18n::DEFAULT_LOCALE.to_string());
         }
+        Ok(locale)
+    }
 
-        conn.execute(
-            "UPDATE categories
-             SET is_default = 0
-             WHERE name NOT IN (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            params![
-                Self::DEFAULT_CATEGORIES[0].0,
-                Self::DEFAULT_CATEGORIES[1].0,
-                Self::DEFAULT_CATEGORIES[2].0,
-                Self::DEFAULT_CATEGORIES[3].0,
-                Self::DEFAULT_CATEGORIES[4].0,
-                Self::DEFAULT_CATEGORIES[5].0,
-                Self::DEFAULT_CATEGORIES[6].0,
-                Self::DEFAULT_CATEGORIES[7].0,
-            ],
-        )?;
+    /// The chosen locale, or `i18n::UNSET_LOCALE` when none has been picked.
+    fn read_locale(conn: &Connection) -> Result<String> {
+        let value = Self::read_setting(conn, "locale")?;
+        Ok(value
+            .filter(|v| i18n::is_supported(v))
+            .unwrap_or_else(|| i18n::UNSET_LOCALE.to_string()))
+    }
 
-        Ok(())
+    fn read_week_start(conn: &Connection) -> Result<String> {
+        let value = Self::read_setting(conn, "week_start")?;
+        Ok(value
+            .filter(|v| Self::WEEK_DAYS.contains(&v.as_str()))
+            .unwrap_or_else(|| Self::DEFAULT_WEEK_START.to_string()))
     }
 
-    fn ensure_default_equity_accounts(conn: &Connection, container_id: i64) -> Result<()> {
-        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-        for name in Self::DEFAULT_EQUITY_ACCOUNTS {
-            conn.execute(
-                "INSERT OR IGNORE INTO accounts (name, account_type, opening_balance, container_id, created_at)
-                 VALUES (?1, 'equity', 0, ?2, ?3)",
-                params![name, container_id, &now],
-            )?;
+    fn read_date_format(conn: &Connection) -> Result<String> {
+        let value = Self::read_setting(conn, "date_format")?;
+        Ok(value
+            .filter(|v| Self::DATE_FORMATS.iter().any(|(name, _)| name == v))
+            .unwrap_or_else(|| Self::DEFAULT_DATE_FORMAT.to_string()))
+    }
+
+    /// Rewrites the day part of a stored date in the configured display
+    /// format, dropping the time whichever format is chosen.
+    fn export_date(date: &str, date_format: &str) -> String {
+        let pattern = Self::DATE_FORMATS
+            .iter()
+            .find(|(name, _)| *name == date_format)
+            .map(|(_, pattern)| *pattern);
+        match (pattern, chrono::NaiveDate::parse_from_str(&Self::date_only(date), "%Y-%m-%d")) {
+            (Some(pattern), Ok(day)) => day.format(pattern).to_string(),
+            _ => date.to_string(),
         }
-        Ok(())
     }
 
-    fn format_units_no_decimals(cents: i64) -> String {
-        let units = (cents as f64 / 100.0).round() as i64;
+    fn format_units_no_decimals(amount: i64, minor_units: u32) -> String {
+        let units = (amount as f64 / 10_i64.pow(minor_units) as f64).round() as i64;
         units.to_string()
     }
 
+    fn format_minor_units(amount: i64, minor_units: u32) -> String {
+        if minor_units == 0 {
+            return amount.to_string();
+        }
+        let divisor = 10_i64.pow(minor_units);
+        let sign = if amount < 0 { "-" } else { "" };
+        let abs = amount.unsigned_abs();
+        format!(
+            "{}{}.{:0width$}",
+            sign,
+            abs / divisor as u64,
+            abs % divisor as u64,
+            width = minor_units as usize
+        )
+    }
+
     fn csv_escape(value: &str) -> String {
         if value.contains(',') || value.contains('"') || value.contains('\n') || value.contains('\r') {
             let escaped = value.replace('"', "\"\"");
@@ -1611,6 +11508,29 @@ impl Database {
         }
     }
 
+    fn html_escape(value: &str) -> String {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    fn markdown_escape(value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            if matches!(c, '\\' | '|' | '*' | '_' | '`' | '#' | '[' | ']' | '<' | '>') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+
+    fn redact_value(key: &RandomState, value: &str) -> String {
+        format!("REDACTED-{:016x}", key.hash_one(value))
+    }
+
     fn date_only(value: &str) -> String {
         value.split(' ').next().unwrap_or(value).to_string()
     }
@@ -1640,145 +11560,763 @@ impl Database {
             (year, month_num + 1)
         };
 
-        let end = chrono::NaiveDate::from_ymd_opt(next_year, next_month, 1)
-            .and_then(|d| d.pred_opt())
-            .ok_or_else(|| rusqlite::Error::InvalidParameterName("Invalid month".to_string()))?;
+        let end = chrono::NaiveDate::from_ymd_opt(next_year, next_month, 1)
+            .and_then(|d| d.pred_opt())
+            .ok_or_else(|| rusqlite::Error::InvalidParameterName("Invalid month".to_string()))?;
+
+        let start_date = format!("{} 00:00:00", start.format("%Y-%m-%d"));
+        let end_date = format!("{} 23:59:59", end.format("%Y-%m-%d"));
+
+        Ok((start_date, end_date))
+    }
+
+    fn trailing_months(months: u32) -> Vec<String> {
+        let today = chrono::Local::now().date_naive();
+        let current = today.year() * 12 + today.month0() as i32;
+        (0..months as i32)
+            .rev()
+            .map(|offset| {
+                let index = current - offset;
+                format!("{:04}-{:02}", index.div_euclid(12), index.rem_euclid(12) + 1)
+            })
+            .collect()
+    }
+
+    fn year_range(year: &str) -> Result<(String, String)> {
+        let year_num: i32 = year.parse().map_err(|_| {
+            rusqlite::Error::InvalidParameterName("Invalid year".to_string())
+        })?;
+        let start = chrono::NaiveDate::from_ymd_opt(year_num, 1, 1).ok_or_else(|| {
+            rusqlite::Error::InvalidParameterName("Invalid year".to_string())
+        })?;
+        let end = chrono::NaiveDate::from_ymd_opt(year_num, 12, 31).ok_or_else(|| {
+            rusqlite::Error::InvalidParameterName("Invalid year".to_string())
+        })?;
+
+        let start_date = format!("{} 00:00:00", start.format("%Y-%m-%d"));
+        let end_date = format!("{} 23:59:59", end.format("%Y-%m-%d"));
+        Ok((start_date, end_date))
+    }
+
+    fn year_range_last_known(conn: &Connection, container_id: i64, year: &str) -> Result<(String, String)> {
+        let (start_date, year_end) = Self::year_range(year)?;
+        let last_known: Option<String> = conn.query_row(
+            "SELECT MAX(date)
+             FROM transactions
+             WHERE container_id = ?1 AND date >= ?2 AND date <= ?3 AND deleted_at IS NULL",
+            params![container_id, &start_date, &year_end],
+            |row| row.get(0),
+        )?;
+        let end_date = last_known.unwrap_or(year_end);
+        Ok((start_date, end_date))
+    }
+
+    fn ensure_max_chars(field: &str, value: &str, max: usize) -> Result<()> {
+        if value.len() > max && value.chars().count() > max {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "{} is too long (maximum {} characters)",
+                field, max
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rejects oversized CSV payloads before any lock is taken, so a huge
+    /// paste cannot hold the connection while it is parsed row by row.
+    fn ensure_csv_limits(content: &str, has_header: bool) -> Result<()> {
+        if content.len() > Self::MAX_CSV_BYTES {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "CSV is too large ({} MB maximum)",
+                Self::MAX_CSV_BYTES / (1024 * 1024)
+            )));
+        }
+        let rows = ReaderBuilder::new()
+            .has_headers(has_header)
+            .flexible(true)
+            .from_reader(content.as_bytes())
+            .records()
+            .take(Self::MAX_CSV_ROWS + 1)
+            .count();
+        if rows > Self::MAX_CSV_ROWS {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "CSV has too many rows ({} maximum)",
+                Self::MAX_CSV_ROWS
+            )));
+        }
+        Ok(())
+    }
+
+    fn parse_day(value: &str) -> Result<chrono::NaiveDate> {
+        chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").map_err(|_| {
+            rusqlite::Error::InvalidParameterName("Invalid date format. Expected YYYY-MM-DD".to_string())
+        })
+    }
+
+    fn normalize_transaction_date(date: Option<String>) -> Result<String> {
+        match date {
+            Some(value) if !value.trim().is_empty() => {
+                let parsed = chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+                    .map_err(|_| rusqlite::Error::InvalidParameterName("Invalid date format. Expected YYYY-MM-DD".to_string()))?;
+                let now_time = chrono::Local::now().naive_local().time();
+                Ok(parsed.and_time(now_time).format("%Y-%m-%d %H:%M:%S").to_string())
+            }
+            _ => Ok(chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub success_count: usize,
+    pub error_count: usize,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CsvImportMapping {
+    pub container_id: i64,
+    pub amount_column: usize,
+    pub description_column: usize,
+    pub category_column: usize,
+    pub date_column: usize,
+    pub skip_header: bool,
+    /// Decimal places to keep when storing amounts; `Some(0)` snaps to whole
+    /// rupiah. Values at or above the configured minor units change nothing.
+    pub round_decimals: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportSession {
+    pub id: i64,
+    pub path: String,
+    pub columns: Vec<String>,
+    pub row_count: i64,
+    pub mapping: Option<CsvImportMapping>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportPreviewRow {
+    pub row: usize,
+    pub amount: i64,
+    pub description: String,
+    pub category: String,
+    pub date: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub rows: Vec<ImportPreviewRow>,
+    pub valid_count: usize,
+    pub error_count: usize,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatementMapping {
+    pub date_column: usize,
+    pub amount_column: usize,
+    pub description_column: usize,
+    pub skip_header: bool,
+    pub date_tolerance_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatementLine {
+    pub id: i64,
+    pub statement_id: i64,
+    pub account_id: i64,
+    pub date: String,
+    pub amount: i64,
+    pub description: String,
+    pub transaction_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatementMatch {
+    pub line_id: i64,
+    pub transaction_id: i64,
+    pub amount: i64,
+    pub date_difference_days: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatementReconciliation {
+    pub statement_id: i64,
+    pub start_date: String,
+    pub end_date: String,
+    pub matched: Vec<StatementMatch>,
+    pub unmatched_lines: Vec<StatementLine>,
+    pub unmatched_transactions: Vec<Transaction>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AmountMismatch {
+    pub line: StatementLine,
+    pub transaction: Transaction,
+    pub difference: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiscrepancyExplanation {
+    pub account_id: i64,
+    pub statement_id: i64,
+    pub start_date: String,
+    pub end_date: String,
+    pub statement_total: i64,
+    pub book_total: i64,
+    pub difference: i64,
+    pub future_dated: Vec<Transaction>,
+    pub missing_from_statement: Vec<Transaction>,
+    pub missing_from_books: Vec<StatementLine>,
+    pub amount_mismatches: Vec<AmountMismatch>,
+}
+
+impl Database {
+    pub fn import_transactions_from_csv(&self, csv_content: String, mapping: CsvImportMapping) -> Result<ImportResult> {
+        let container_id = mapping.container_id;
+        let skip_header = mapping.skip_header;
+        Self::ensure_csv_limits(&csv_content, skip_header)?;
+        let (minor_units, locale) = {
+            let conn = self.conn.lock().unwrap();
+            Self::ensure_bank_categories(&conn)?;
+            (Self::read_minor_units(&conn, container_id)?, Self::read_locale(&conn)?)
+        };
+        let imported_description = i18n::translate(&locale, "default.imported");
+        let mut reader = ReaderBuilder::new()
+            .has_headers(skip_header)
+            .from_reader(csv_content.as_bytes());
+
+        let mut success_count = 0;
+        let mut error_count = 0;
+        let mut errors = Vec::new();
+
+        for (index, result) in reader.records().enumerate() {
+            let row_num = if skip_header { index + 2 } else { index + 1 };
+            let parsed = result
+                .map_err(|e| format!("Failed to parse CSV - {}", e))
+                .and_then(|record| {
+                    Self::parse_import_record(&record, &mapping, minor_units, &imported_description)
+                });
+            let (amount_cents, description, category, parsed_date) = match parsed {
+                Ok(row) => row,
+                Err(e) => {
+                    errors.push(format!("Row {}: {}", row_num, e));
+                    error_count += 1;
+                    continue;
+                }
+            };
+
+            match self.insert_imported_transaction(
+                container_id,
+                amount_cents,
+                description,
+                category,
+                parsed_date,
+            ) {
+                Ok(_) => success_count += 1,
+                Err(e) => {
+                    errors.push(format!("Row {}: Failed to insert - {}", row_num, e));
+                    error_count += 1;
+                }
+            }
+        }
+
+        Ok(ImportResult {
+            success_count,
+            error_count,
+            errors,
+        })
+    }
+
+    /// Imports a CSV file straight from disk. Rows are streamed and written in
+    /// chunks, each in its own transaction, so very large statements neither
+    /// cross IPC nor hold the connection for the whole import.
+    pub fn import_csv_from_path(&self, path: &Path, mapping: CsvImportMapping) -> Result<ImportResult> {
+        let (minor_units, locale) = {
+            let conn = self.conn.lock().unwrap();
+            Self::ensure_bank_categories(&conn)?;
+            (Self::read_minor_units(&conn, mapping.container_id)?, Self::read_locale(&conn)?)
+        };
+        let imported_description = i18n::translate(&locale, "default.imported");
+        let mut reader = Self::open_import_reader(path, mapping.skip_header)?;
+
+        let mut success_count = 0;
+        let mut error_count = 0;
+        let mut errors = Vec::new();
+        let mut chunk = Vec::with_capacity(Self::IMPORT_CHUNK_ROWS);
+        for (index, result) in reader.records().enumerate() {
+            let row_num = if mapping.skip_header { index + 2 } else { index + 1 };
+            let parsed = result
+                .map_err(|e| format!("Failed to parse CSV - {}", e))
+                .and_then(|record| {
+                    Self::parse_import_record(&record, &mapping, minor_units, &imported_description)
+                });
+            match parsed {
+                Ok(row) => chunk.push(row),
+                Err(e) => {
+                    error_count += 1;
+                    if errors.len() < Self::MAX_IMPORT_ERRORS {
+                        errors.push(format!("Row {}: {}", row_num, e));
+                    }
+                }
+            }
+
+            if chunk.len() >= Self::IMPORT_CHUNK_ROWS {
+                success_count += self.insert_imported_chunk(mapping.container_id, &mut chunk)?;
+            }
+        }
+        success_count += self.insert_imported_chunk(mapping.container_id, &mut chunk)?;
+
+        Ok(ImportResult {
+            success_count,
+            error_count,
+            errors,
+        })
+    }
+
+    /// Imports a manual cash book ("buku kas") kept in Excel and saved as CSV:
+    /// date, description, money in (masuk), money out (keluar) and a running
+    /// balance (saldo). The header row is found by its column names, title
+    /// rows above it are skipped, and masuk/keluar become signed amounts on
+    /// `account_id`. Rows without either amount, like "Saldo awal", are
+    /// ignored.
+    pub fn import_cash_book_csv(&self, account_id: i64, csv_content: String) -> Result<ImportResult> {
+        Self::ensure_csv_limits(&csv_content, false)?;
+        let (container_id, minor_units) = {
+            let conn = self.conn.lock().unwrap();
+            Self::ensure_bank_categories(&conn)?;
+            let container_id: i64 = conn.query_row(
+                "SELECT container_id FROM accounts WHERE id = ?1",
+                [account_id],
+                |row| row.get(0),
+            )?;
+            (container_id, Self::read_minor_units(&conn, container_id)?)
+        };
+        let first_line = csv_content.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+        let delimiter = if first_line.matches(';').count() > first_line.matches(',').count() {
+            b';'
+        } else {
+            b','
+        };
+        let records: Vec<csv::StringRecord> = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(delimiter)
+            .from_reader(csv_content.as_bytes())
+            .records()
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| rusqlite::Error::InvalidParameterName(format!("Failed to parse CSV - {}", e)))?;
+
+        let find_column = |record: &csv::StringRecord, names: &[&str]| {
+            record
+                .iter()
+                .position(|cell| {
+                    let cell = cell.trim().to_lowercase();
+                    names.iter().any(|name| cell.contains(name))
+                })
+        };
+        let mut layout = None;
+        for (index, record) in records.iter().enumerate().take(Self::CASH_BOOK_HEADER_SCAN_ROWS) {
+            let date = find_column(record, &["tanggal", "tgl", "date"]);
+            let money_in = find_column(record, &["masuk", "penerimaan", "debit", "debet"]);
+            let money_out = find_column(record, &["keluar", "pengeluaran", "kredit", "credit"]);
+            if let (Some(date), Some(money_in), Some(money_out)) = (date, money_in, money_out) {
+                if date == money_in || date == money_out || money_in == money_out {
+                    continue;
+                }
+                let description = find_column(record, &["keterangan", "uraian", "deskripsi", "description"]);
+                let category = find_column(record, &["kategori", "category"]);
+                layout = Some((index, date, description, money_in, money_out, category));
+                break;
+            }
+        }
+        let (header_index, date_column, description_column, in_column, out_column, category_column) =
+            layout.ok_or_else(|| {
+                rusqlite::Error::InvalidParameterName(
+                    "No header row with date, masuk and keluar columns was found".to_string(),
+                )
+            })?;
+
+        let mut rows = Vec::new();
+        let mut errors = Vec::new();
+        let mut error_count = 0;
+        for (index, record) in records.iter().enumerate().skip(header_index + 1) {
+            let cell = |column: Option<usize>| column.and_then(|c| record.get(c)).unwrap_or("").trim();
+            let (money_in, money_out) = (cell(Some(in_column)), cell(Some(out_column)));
+            if money_in.is_empty() && money_out.is_empty() {
+                continue;
+            }
+            let parsed = Self::parse_cash_book_row(
+                cell(Some(date_column)),
+                money_in,
+                money_out,
+                minor_units,
+            );
+            match parsed {
+                Ok((date, amount)) if amount != 0 => {
+                    let description = cell(description_column).to_string();
+                    let category = match cell(category_column) {
+                        "" => Self::detect_bank_category(&description, amount)
+                            .unwrap_or(if amount > 0 {
+                                Self::DEFAULT_INCOME_CATEGORY
+                            } else {
+                                Self::DEFAULT_FALLBACK_CATEGORY
+                            })
+                            .to_string(),
+                        value => value.to_string(),
+                    };
+                    rows.push((amount, description, category, date));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    error_count += 1;
+                    if errors.len() < Self::MAX_IMPORT_ERRORS {
+                        errors.push(format!("Row {}: {}", index + 1, e));
+                    }
+                }
+            }
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO transactions (amount, description, category, date, container_id, account_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for (amount, description, category, date) in &rows {
+                stmt.execute(params![amount, description, category, date, container_id, account_id])?;
+            }
+        }
+        tx.commit()?;
+
+        Ok(ImportResult {
+            success_count: rows.len(),
+            error_count,
+            errors,
+        })
+    }
 
-        let start_date = format!("{} 00:00:00", start.format("%Y-%m-%d"));
-        let end_date = format!("{} 23:59:59", end.format("%Y-%m-%d"));
+    fn parse_cash_book_row(
+        date: &str,
+        money_in: &str,
+        money_out: &str,
+        minor_units: u32,
+    ) -> Result<(String, i64), String> {
+        let amount = |value: &str| -> Result<i64, String> {
+            if value.is_empty() || value == "-" {
+                return Ok(0);
+            }
+            let cleaned = value.to_lowercase().replace("rp", "").replace(' ', "");
+            let units = Self::parse_grouped_number(&cleaned)
+                .ok_or_else(|| format!("Invalid amount '{}'", value))?;
+            Ok((units.abs() * 10_f64.powi(minor_units as i32)).round() as i64)
+        };
+        let net = amount(money_in)? - amount(money_out)?;
+
+        // Day-first formats come first: these ledgers are written the Indonesian way.
+        let day = ["%d/%m/%Y", "%d-%m-%Y", "%d.%m.%Y", "%Y-%m-%d", "%d/%m/%y"]
+            .iter()
+            .find_map(|format| chrono::NaiveDate::parse_from_str(date, format).ok())
+            .or_else(|| {
+                // Excel stores dates as days since 1899-12-30 when a cell is
+                // exported without date formatting.
+                let serial: i64 = date.parse().ok()?;
+                chrono::NaiveDate::from_ymd_opt(1899, 12, 30)?
+                    .checked_add_signed(chrono::Duration::days(serial))
+            })
+            .ok_or_else(|| format!("Invalid date '{}'", date))?;
+        Ok((format!("{} 00:00:00", day.format("%Y-%m-%d")), net))
+    }
 
-        Ok((start_date, end_date))
+    /// Reads `15000`, `15.000`, `15,000`, `15.000,50` and `1,5`: with both
+    /// separators the last one is decimal, with one kind it is a thousands
+    /// separator only when every group after the first has three digits.
+    fn parse_grouped_number(value: &str) -> Option<f64> {
+        let normalized = match (value.rfind('.'), value.rfind(',')) {
+            (Some(dot), Some(comma)) if dot > comma => value.replace(',', ""),
+            (Some(_), Some(_)) => value.replace('.', "").replace(',', "."),
+            _ => {
+                let groups: Vec<&str> = value.split(['.', ',']).collect();
+                if groups.len() > 1 && groups[1..].iter().all(|group| group.len() == 3) {
+                    groups.concat()
+                } else {
+                    value.replace(',', ".")
+                }
+            }
+        };
+        normalized.parse::<f64>().ok().filter(|number| number.is_finite())
     }
 
-    fn year_range(year: &str) -> Result<(String, String)> {
-        let year_num: i32 = year.parse().map_err(|_| {
-            rusqlite::Error::InvalidParameterName("Invalid year".to_string())
-        })?;
-        let start = chrono::NaiveDate::from_ymd_opt(year_num, 1, 1).ok_or_else(|| {
-            rusqlite::Error::InvalidParameterName("Invalid year".to_string())
-        })?;
-        let end = chrono::NaiveDate::from_ymd_opt(year_num, 12, 31).ok_or_else(|| {
-            rusqlite::Error::InvalidParameterName("Invalid year".to_string())
+    /// First step of the guided import: registers the file and reports its
+    /// columns (taken from the first row) and row count. The session is kept
+    /// in the database so the remaining steps can run in separate calls.
+    pub fn start_import_session(&self, path: &Path) -> Result<ImportSession> {
+        let mut reader = Self::open_import_reader(path, false)?;
+        let mut columns: Option<Vec<String>> = None;
+        let mut row_count: i64 = 0;
+        for result in reader.records() {
+            if columns.is_none() {
+                let record = result.map_err(|e| {
+                    rusqlite::Error::InvalidParameterName(format!("Failed to parse CSV - {}", e))
+                })?;
+                columns = Some(record.iter().map(|value| value.trim().to_string()).collect());
+            }
+            row_count += 1;
+        }
+        let columns = columns.ok_or_else(|| {
+            rusqlite::Error::InvalidParameterName("The file is empty".to_string())
         })?;
+        let columns_json = serde_json::to_string(&columns)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
 
-        let start_date = format!("{} 00:00:00", start.format("%Y-%m-%d"));
-        let end_date = format!("{} 23:59:59", end.format("%Y-%m-%d"));
-        Ok((start_date, end_date))
+        let conn = self.conn.lock().unwrap();
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute(
+            "INSERT INTO import_sessions (path, columns_json, row_count, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![path.to_string_lossy(), &columns_json, row_count, &now],
+        )?;
+        Self::fetch_import_session(&conn, conn.last_insert_rowid())
     }
 
-    fn year_range_last_known(conn: &Connection, container_id: i64, year: &str) -> Result<(String, String)> {
-        let (start_date, year_end) = Self::year_range(year)?;
-        let last_known: Option<String> = conn.query_row(
-            "SELECT MAX(date)
-             FROM transactions
-             WHERE container_id = ?1 AND date >= ?2 AND date <= ?3",
-            params![container_id, &start_date, &year_end],
+    /// Second step: stores the column mapping after checking that the
+    /// amount and date columns exist in the file and the container exists.
+    pub fn set_import_session_mapping(&self, session_id: i64, mapping: CsvImportMapping) -> Result<ImportSession> {
+        let conn = self.conn.lock().unwrap();
+        let session = Self::fetch_import_session(&conn, session_id)?;
+        let column_count = session.columns.len();
+        for (label, column) in [("Amount", mapping.amount_column), ("Date", mapping.date_column)] {
+            if column >= column_count {
+                return Err(rusqlite::Error::InvalidParameterName(format!(
+                    "{} column {} is out of range (the file has {} columns)",
+                    label, column, column_count
+                )));
+            }
+        }
+        if mapping.amount_column == mapping.date_column {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Amount and date must come from different columns".to_string(),
+            ));
+        }
+        let container_exists: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM containers WHERE id = ?1",
+            [mapping.container_id],
             |row| row.get(0),
         )?;
-        let end_date = last_known.unwrap_or(year_end);
-        Ok((start_date, end_date))
+        if container_exists == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+
+        let mapping_json = serde_json::to_string(&mapping)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        conn.execute(
+            "UPDATE import_sessions SET mapping_json = ?1 WHERE id = ?2",
+            params![&mapping_json, session_id],
+        )?;
+        Self::fetch_import_session(&conn, session_id)
     }
 
-    fn normalize_transaction_date(date: Option<String>) -> Result<String> {
-        match date {
-            Some(value) if !value.trim().is_empty() => {
-                let parsed = chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
-                    .map_err(|_| rusqlite::Error::InvalidParameterName("Invalid date format. Expected YYYY-MM-DD".to_string()))?;
-                let now_time = chrono::Local::now().naive_local().time();
-                Ok(parsed.and_time(now_time).format("%Y-%m-%d %H:%M:%S").to_string())
+    /// Third step: parses the whole file with the stored mapping without
+    /// writing anything, returning the first `limit` rows and every error
+    /// count so problems can be fixed before committing.
+    pub fn preview_import_session(&self, session_id: i64, limit: Option<usize>) -> Result<ImportPreview> {
+        let limit = limit.unwrap_or(Self::DEFAULT_IMPORT_PREVIEW_ROWS).clamp(1, Self::MAX_IMPORT_PREVIEW_ROWS);
+        let (session, locale) = {
+            let conn = self.conn.lock().unwrap();
+            (Self::fetch_import_session(&conn, session_id)?, Self::read_locale(&conn)?)
+        };
+        let mapping = session.mapping.as_ref().ok_or_else(Self::missing_import_mapping)?;
+        let minor_units = {
+            let conn = self.conn.lock().unwrap();
+            Self::read_minor_units(&conn, mapping.container_id)?
+        };
+        let imported_description = i18n::translate(&locale, "default.imported");
+        let mut reader = Self::open_import_reader(Path::new(&session.path), mapping.skip_header)?;
+
+        let mut preview = ImportPreview {
+            rows: Vec::new(),
+            valid_count: 0,
+            error_count: 0,
+            errors: Vec::new(),
+        };
+        for (index, result) in reader.records().enumerate() {
+            let row = if mapping.skip_header { index + 2 } else { index + 1 };
+            let parsed = result
+                .map_err(|e| format!("Failed to parse CSV - {}", e))
+                .and_then(|record| Self::parse_import_record(&record, mapping, minor_units, &imported_description));
+            match parsed {
+                Ok((amount, description, category, date)) => {
+                    preview.valid_count += 1;
+                    if preview.rows.len() < limit {
+                        preview.rows.push(ImportPreviewRow {
+                            row,
+                            amount,
+                            description,
+                            category,
+                            date,
+                        });
+                    }
+                }
+                Err(e) => {
+                    preview.error_count += 1;
+                    if preview.errors.len() < Self::MAX_IMPORT_ERRORS {
+                        preview.errors.push(format!("Row {}: {}", row, e));
+                    }
+                }
             }
-            _ => Ok(chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()),
         }
+        Ok(preview)
     }
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ImportResult {
-    pub success_count: usize,
-    pub error_count: usize,
-    pub errors: Vec<String>,
-}
+    /// Last step: imports the file with the stored mapping and closes the
+    /// session.
+    pub fn commit_import_session(&self, session_id: i64) -> Result<ImportResult> {
+        let session = {
+            let conn = self.conn.lock().unwrap();
+            Self::fetch_import_session(&conn, session_id)?
+        };
+        let mapping = session.mapping.ok_or_else(Self::missing_import_mapping)?;
+        let result = self.import_csv_from_path(Path::new(&session.path), mapping)?;
+        self.cancel_import_session(session_id)?;
+        Ok(result)
+    }
 
-impl Database {
-    pub fn import_transactions_from_csv(
-        &self,
-        csv_content: String,
-        container_id: i64,
-        amount_column: usize,
-        description_column: usize,
-        category_column: usize,
-        date_column: usize,
-        skip_header: bool,
-    ) -> Result<ImportResult> {
-        let mut reader = ReaderBuilder::new()
-            .has_headers(skip_header)
-            .from_reader(csv_content.as_bytes());
+    pub fn cancel_import_session(&self, session_id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM import_sessions WHERE id = ?1", [session_id])?;
+        Ok(())
+    }
 
-        let mut success_count = 0;
-        let mut error_count = 0;
-        let mut errors = Vec::new();
+    fn fetch_import_session(conn: &Connection, session_id: i64) -> Result<ImportSession> {
+        let (path, columns_json, row_count, mapping_json, created_at): (String, String, i64, Option<String>, String) =
+            conn.query_row(
+                "SELECT path, columns_json, row_count, mapping_json, created_at FROM import_sessions WHERE id = ?1",
+                [session_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+            )?;
+        let decode_error =
+            |e: serde_json::Error| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e));
+        let columns = serde_json::from_str(&columns_json).map_err(decode_error)?;
+        let mapping = match mapping_json {
+            Some(value) => Some(serde_json::from_str(&value).map_err(decode_error)?),
+            None => None,
+        };
+        Ok(ImportSession {
+            id: session_id,
+            path,
+            columns,
+            row_count,
+            mapping,
+            created_at,
+        })
+    }
 
-        for (index, result) in reader.records().enumerate() {
-            let row_num = if skip_header { index + 2 } else { index + 1 };
-            
-            match result {
-                Ok(record) => {
-                    let amount_str = record.get(amount_column).unwrap_or("").trim();
-                    let description = record.get(description_column).unwrap_or("Imported").trim().to_string();
-                    let category = record
-                        .get(category_column)
-                        .unwrap_or(Self::DEFAULT_FALLBACK_CATEGORY)
-                        .trim()
-                        .to_string();
-                    let date_str = record.get(date_column).unwrap_or("").trim();
-
-                    let amount_cents = match Self::parse_amount(amount_str) {
-                        Ok(amt) => amt,
-                        Err(e) => {
-                            errors.push(format!("Row {}: Invalid amount '{}' - {}", row_num, amount_str, e));
-                            error_count += 1;
-                            continue;
-                        }
-                    };
+    fn missing_import_mapping() -> rusqlite::Error {
+        rusqlite::Error::InvalidParameterName("Set a column mapping first".to_string())
+    }
 
-                    let parsed_date = match Self::parse_date(date_str) {
-                        Ok(date) => date,
-                        Err(e) => {
-                            errors.push(format!("Row {}: Invalid date '{}' - {}", row_num, date_str, e));
-                            error_count += 1;
-                            continue;
-                        }
-                    };
+    fn open_import_reader(path: &Path, has_headers: bool) -> Result<csv::Reader<std::io::BufReader<std::fs::File>>> {
+        let file = std::fs::File::open(path).map_err(|e| {
+            rusqlite::Error::InvalidParameterName(format!("Failed to open {}: {}", path.display(), e))
+        })?;
+        Ok(ReaderBuilder::new()
+            .has_headers(has_headers)
+            .from_reader(std::io::BufReader::new(file)))
+    }
 
-                    match self.insert_imported_transaction(
-                        container_id,
-                        amount_cents,
-                        description,
-                        category,
-                        parsed_date,
-                    ) {
-                        Ok(_) => success_count += 1,
-                        Err(e) => {
-                            errors.push(format!("Row {}: Failed to insert - {}", row_num, e));
-                            error_count += 1;
-                        }
-                    }
-                }
-                Err(e) => {
-                    errors.push(format!("Row {}: Failed to parse CSV - {}", row_num, e));
-                    error_count += 1;
-                }
+    fn insert_imported_chunk(
+        &self,
+        container_id: i64,
+        rows: &mut Vec<(i64, String, String, String)>,
+    ) -> Result<usize> {
+        if rows.is_empty() {
+            return Ok(0);
+        }
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let count = rows.len();
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO transactions (amount, description, category, date, container_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for (amount, description, category, date) in rows.drain(..) {
+                stmt.execute(params![amount, description, category, date, container_id])?;
             }
         }
+        tx.commit()?;
+        Ok(count)
+    }
 
-        Ok(ImportResult {
-            success_count,
-            error_count,
-            errors,
-        })
+    fn parse_import_record(
+        record: &csv::StringRecord,
+        mapping: &CsvImportMapping,
+        minor_units: u32,
+        imported_description: &str,
+    ) -> Result<(i64, String, String, String), String> {
+        let amount_str = record.get(mapping.amount_column).unwrap_or("").trim();
+        let description = record
+            .get(mapping.description_column)
+            .unwrap_or(imported_description)
+            .trim()
+            .to_string();
+        let date_str = record.get(mapping.date_column).unwrap_or("").trim();
+
+        let mut amount_cents = Self::parse_amount(amount_str, minor_units)
+            .map_err(|e| format!("Invalid amount '{}' - {}", amount_str, e))?;
+        if let Some(decimals) = mapping.round_decimals {
+            amount_cents = Self::round_minor_units(amount_cents, minor_units, decimals);
+        }
+        let category = match record.get(mapping.category_column).map(str::trim) {
+            Some(value) if !value.is_empty() => value.to_string(),
+            _ => Self::detect_bank_category(&description, amount_cents)
+                .unwrap_or(Self::DEFAULT_FALLBACK_CATEGORY)
+                .to_string(),
+        };
+        let parsed_date = Self::parse_date(date_str)
+            .map_err(|e| format!("Invalid date '{}' - {}", date_str, e))?;
+        Ok((amount_cents, description, category, parsed_date))
+    }
+
+    /// Patterns match whole words only, so "bungalow" is not interest.
+    /// Interest paid out (a negative amount) is an expense, not income.
+    fn detect_bank_category(description: &str, amount: i64) -> Option<&'static str> {
+        let description = description.to_lowercase();
+        let words: Vec<&str> = description
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        let matches = |pattern: &&str| {
+            let pattern: Vec<&str> = pattern.split(' ').collect();
+            words.windows(pattern.len()).any(|window| window == pattern.as_slice())
+        };
+        if Self::BANK_FEE_PATTERNS.iter().any(matches) {
+            Some(Self::BANK_FEE_CATEGORY)
+        } else if Self::BANK_INTEREST_PATTERNS.iter().any(matches) {
+            Some(if amount > 0 {
+                Self::BANK_INTEREST_CATEGORY
+            } else {
+                Self::BANK_INTEREST_EXPENSE_CATEGORY
+            })
+        } else {
+            None
+        }
+    }
+
+    fn ensure_bank_categories(conn: &Connection) -> Result<()> {
+        conn.execute(
+            "INSERT OR IGNORE INTO categories (name, category_type, is_default) VALUES (?1, 'income', 0)",
+            [Self::BANK_INTEREST_CATEGORY],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO categories (name, category_type, is_default) VALUES (?1, 'expense', 0)",
+            [Self::BANK_INTEREST_EXPENSE_CATEGORY],
+        )?;
+        Ok(())
     }
 
-    fn parse_amount(amount_str: &str) -> Result<i64, String> {
+    fn parse_amount(amount_str: &str, minor_units: u32) -> Result<i64, String> {
         let cleaned = amount_str
             .replace("$", "")
             .replace("€", "")
@@ -1788,11 +12326,23 @@ impl Database {
             .to_string();
 
         match cleaned.parse::<f64>() {
-            Ok(amount) => Ok((amount * 100.0).round() as i64),
+            Ok(amount) => Ok((amount * 10_i64.pow(minor_units) as f64).round() as i64),
             Err(_) => Err(format!("Cannot parse as number")),
         }
     }
 
+    /// Rounds an amount in minor units to `decimals` places, half away from
+    /// zero, so 10000.50 kept at zero decimals becomes 10001.
+    fn round_minor_units(amount: i64, minor_units: u32, decimals: u32) -> i64 {
+        if decimals >= minor_units {
+            return amount;
+        }
+        let step = 10_i64.pow(minor_units - decimals);
+        let half = step / 2;
+        let rounded = if amount >= 0 { (amount + half) / step } else { (amount - half) / step };
+        rounded * step
+    }
+
     fn parse_date(date_str: &str) -> Result<String, String> {
         let formats = vec![
             "%Y-%m-%d",
@@ -1844,4 +12394,377 @@ impl Database {
 
         Ok(())
     }
+
+    pub fn reconcile_with_statement(
+        &self,
+        account_id: i64,
+        csv_content: String,
+        mapping: StatementMapping,
+    ) -> Result<StatementReconciliation> {
+        Self::ensure_csv_limits(&csv_content, mapping.skip_header)?;
+        let tolerance = mapping.date_tolerance_days.unwrap_or(3).clamp(0, 31);
+        let minor_units = {
+            let conn = self.conn.lock().unwrap();
+            let container_id: i64 =
+                conn.query_row("SELECT container_id FROM accounts WHERE id = ?1", [account_id], |row| row.get(0))?;
+            Self::read_minor_units(&conn, container_id)?
+        };
+        let mut reader = ReaderBuilder::new()
+            .has_headers(mapping.skip_header)
+            .flexible(true)
+            .from_reader(csv_content.as_bytes());
+
+        let mut parsed: Vec<(String, i64, String)> = Vec::new();
+        let mut errors = Vec::new();
+        for (index, result) in reader.records().enumerate() {
+            let row_num = if mapping.skip_header { index + 2 } else { index + 1 };
+            let record = match result {
+                Ok(record) => record,
+                Err(e) => {
+                    errors.push(format!("Row {}: Failed to parse CSV - {}", row_num, e));
+                    continue;
+                }
+            };
+            let amount_str = record.get(mapping.amount_column).unwrap_or("").trim();
+            let date_str = record.get(mapping.date_column).unwrap_or("").trim();
+            let description = record
+                .get(mapping.description_column)
+                .unwrap_or("")
+                .trim()
+                .to_string();
+
+            let amount = match Self::parse_amount(amount_str, minor_units) {
+                Ok(amount) => amount,
+                Err(e) => {
+                    errors.push(format!("Row {}: Invalid amount '{}' - {}", row_num, amount_str, e));
+                    continue;
+                }
+            };
+            let date = match Self::parse_date(date_str) {
+                Ok(date) => date,
+                Err(e) => {
+                    errors.push(format!("Row {}: Invalid date '{}' - {}", row_num, date_str, e));
+                    continue;
+                }
+            };
+            parsed.push((date, amount, description));
+        }
+
+        if parsed.is_empty() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "Statement contains no valid lines".to_string(),
+            ));
+        }
+        parsed.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let start_day = Self::parse_day(&Self::date_only(&parsed[0].0))?;
+        let end_day = Self::parse_day(&Self::date_only(&parsed[parsed.len() - 1].0))?;
+        let start_date = format!("{} 00:00:00", start_day.format("%Y-%m-%d"));
+        let end_date = format!("{} 23:59:59", end_day.format("%Y-%m-%d"));
+        let window_start = format!(
+            "{} 00:00:00",
+            (start_day - chrono::Duration::days(tolerance)).format("%Y-%m-%d")
+        );
+        let window_end = format!(
+            "{} 23:59:59",
+            (end_day + chrono::Duration::days(tolerance)).format("%Y-%m-%d")
+        );
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        tx.execute(
+            "INSERT INTO statement_imports (account_id, start_date, end_date, imported_at) VALUES (?1, ?2, ?3, ?4)",
+            params![account_id, &start_date, &end_date, &now],
+        )?;
+        let statement_id = tx.last_insert_rowid();
+
+        let book = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT {}
+                 FROM transactions
+                 WHERE account_id = ?1 AND date >= ?2 AND date <= ?3 AND deleted_at IS NULL
+                 ORDER BY date ASC, id ASC",
+                Self::TRANSACTION_COLUMNS
+            ))?;
+            let rows = stmt.query_map(
+                params![account_id, &window_start, &window_end],
+                Self::transaction_from_row,
+            )?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+        let mut book_matched = vec![false; book.len()];
+
+        let mut matched = Vec::new();
+        let mut unmatched_lines = Vec::new();
+        for (date, amount, description) in parsed {
+            let line_day = Self::parse_day(&Self::date_only(&date))?;
+            let mut best: Option<(usize, i64)> = None;
+            for (index, transaction) in book.iter().enumerate() {
+                if book_matched[index] || transaction.amount != amount {
+                    continue;
+                }
+                let book_day = Self::parse_day(&Self::date_only(&transaction.date))?;
+                let difference = (book_day - line_day).num_days().abs();
+                if difference <= tolerance && best.is_none_or(|(_, d)| difference < d) {
+                    best = Some((index, difference));
+                }
+            }
+
+            let transaction_id = best.map(|(index, _)| book[index].id);
+            tx.execute(
+                "INSERT INTO statement_lines (statement_id, account_id, date, amount, description, transaction_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![statement_id, account_id, &date, amount, &description, transaction_id],
+            )?;
+            let line_id = tx.last_insert_rowid();
+
+            match best {
+                Some((index, difference)) => {
+                    book_matched[index] = true;
+                    matched.push(StatementMatch {
+                        line_id,
+                        transaction_id: book[index].id,
+                        amount,
+                        date_difference_days: difference,
+                    });
+                }
+                None => unmatched_lines.push(StatementLine {
+                    id: line_id,
+                    statement_id,
+                    account_id,
+                    date,
+                    amount,
+                    description,
+                    transaction_id: None,
+                }),
+            }
+        }
+        tx.commit()?;
+
+        let unmatched_transactions = book
+            .into_iter()
+            .zip(book_matched)
+            .filter(|(transaction, was_matched)| {
+                !was_matched && transaction.date >= start_date && transaction.date <= end_date
+            })
+            .map(|(transaction, _)| transaction)
+            .collect();
+
+        Ok(StatementReconciliation {
+            statement_id,
+            start_date,
+            end_date,
+            matched,
+            unmatched_lines,
+            unmatched_transactions,
+            errors,
+        })
+    }
+
+    /// Breaks the gap between the latest imported statement and the books
+    /// into its likely causes. Amount mismatch candidates are unmatched pairs
+    /// on the same side within a few days whose amounts differ by at most 10%.
+    pub fn explain_discrepancy(&self, account_id: i64) -> Result<DiscrepancyExplanation> {
+        let conn = self.conn.lock().unwrap();
+        let (statement_id, start_date, end_date): (i64, String, String) = conn.query_row(
+            "SELECT id, start_date, end_date FROM statement_imports
+             WHERE account_id = ?1
+             ORDER BY id DESC
+             LIMIT 1",
+            [account_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+
+        let statement_total: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(amount), 0) FROM statement_lines WHERE statement_id = ?1",
+            [statement_id],
+            |row| row.get(0),
+        )?;
+        let book_total: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(amount), 0) FROM transactions
+             WHERE account_id = ?1 AND date >= ?2 AND date <= ?3 AND deleted_at IS NULL",
+            params![account_id, &start_date, &end_date],
+            |row| row.get(0),
+        )?;
+
+        let mut missing_lines: Vec<StatementLine> = {
+            let mut stmt = conn.prepare(
+                "SELECT id, statement_id, account_id, date, amount, description, transaction_id
+                 FROM statement_lines
+                 WHERE statement_id = ?1 AND transaction_id IS NULL
+                 ORDER BY date ASC, id ASC",
+            )?;
+            let rows = stmt.query_map([statement_id], |row| {
+                Ok(StatementLine {
+                    id: row.get(0)?,
+                    statement_id: row.get(1)?,
+                    account_id: row.get(2)?,
+                    date: row.get(3)?,
+                    amount: row.get(4)?,
+                    description: row.get(5)?,
+                    transaction_id: row.get(6)?,
+                })
+            })?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+
+        let unmatched: Vec<Transaction> = {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {}
+                 FROM transactions
+                 WHERE account_id = ?1 AND date >= ?2 AND deleted_at IS NULL
+                   AND id NOT IN (SELECT transaction_id FROM statement_lines WHERE transaction_id IS NOT NULL)
+                 ORDER BY date ASC, id ASC",
+                Self::TRANSACTION_COLUMNS
+            ))?;
+            let rows = stmt.query_map(params![account_id, &start_date], Self::transaction_from_row)?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+        let (future_dated, mut missing_from_statement): (Vec<Transaction>, Vec<Transaction>) =
+            unmatched.into_iter().partition(|t| t.date > end_date);
+
+        let mut amount_mismatches = Vec::new();
+        let mut remaining_lines = Vec::new();
+        for line in missing_lines.drain(..) {
+            let line_day = Self::parse_day(&Self::date_only(&line.date))?;
+            let mut best: Option<(usize, i64)> = None;
+            for (index, transaction) in missing_from_statement.iter().enumerate() {
+                if (transaction.amount < 0) != (line.amount < 0) {
+                    continue;
+                }
+                let book_day = Self::parse_day(&Self::date_only(&transaction.date))?;
+                if (book_day - line_day).num_days().abs() > 3 {
+                    continue;
+                }
+                let difference = (transaction.amount - line.amount).abs();
+                if difference * 10 <= line.amount.abs() && best.is_none_or(|(_, d)| difference < d) {
+                    best = Some((index, difference));
+                }
+            }
+            match best {
+                Some((index, _)) => {
+                    let transaction = missing_from_statement.remove(index);
+                    amount_mismatches.push(AmountMismatch {
+                        difference: transaction.amount - line.amount,
+                        line,
+                        transaction,
+                    });
+                }
+                None => remaining_lines.push(line),
+            }
+        }
+
+        Ok(DiscrepancyExplanation {
+            account_id,
+            statement_id,
+            start_date,
+            end_date,
+            statement_total,
+            book_total,
+            difference: book_total - statement_total,
+            future_dated,
+            missing_from_statement,
+            missing_from_books: remaining_lines,
+            amount_mismatches,
+        })
+    }
+
+    pub fn create_missing_statement_transactions(
+        &self,
+        statement_id: i64,
+        category: Option<String>,
+        net_fees: bool,
+    ) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let category = category
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| Self::DEFAULT_FALLBACK_CATEGORY.to_string());
+        let locale = Self::read_locale(&conn)?;
+        let tx = conn.transaction()?;
+        Self::ensure_bank_categories(&tx)?;
+
+        let lines: Vec<(i64, i64, String, i64, String, i64)> = {
+            let mut stmt = tx.prepare(
+                "SELECT l.id, l.account_id, l.date, l.amount, l.description, a.container_id
+                 FROM statement_lines l
+                 JOIN accounts a ON a.id = l.account_id
+                 WHERE l.statement_id = ?1 AND l.transaction_id IS NULL
+                 ORDER BY l.date ASC, l.id ASC",
+            )?;
+            let rows = stmt.query_map([statement_id], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                ))
+            })?;
+            rows.collect::<Result<Vec<_>>>()?
+        };
+
+        // Netted fees are grouped per account and month, dated on the last fee of that month.
+        let mut fee_lines: HashMap<(i64, String), Vec<i64>> = HashMap::new();
+        let mut fee_totals: HashMap<(i64, String), (i64, String, i64)> = HashMap::new();
+        let mut created = 0;
+        for (line_id, account_id, date, amount, description, container_id) in &lines {
+            let detected = Self::detect_bank_category(description, *amount);
+            if net_fees && detected == Some(Self::BANK_FEE_CATEGORY) {
+                let key = (*account_id, date.chars().take(7).collect::<String>());
+                fee_lines.entry(key.clone()).or_default().push(*line_id);
+                let total = fee_totals.entry(key).or_insert((0, date.clone(), *container_id));
+                total.0 += amount;
+                total.1 = date.clone();
+                continue;
+            }
+
+            let description = if description.is_empty() {
+                i18n::translate(&locale, "default.imported")
+            } else {
+                description.clone()
+            };
+            let line_category = detected.map(str::to_string).unwrap_or_else(|| category.clone());
+            tx.execute(
+                "INSERT INTO transactions (amount, description, category, date, container_id, account_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![amount, &description, &line_category, date, container_id, account_id],
+            )?;
+            let transaction_id = tx.last_insert_rowid();
+            tx.execute(
+                "UPDATE statement_lines SET transaction_id = ?1 WHERE id = ?2",
+                params![transaction_id, line_id],
+            )?;
+            created += 1;
+        }
+
+        let fee_description = i18n::translate(&locale, "default.bank_fees");
+        for ((account_id, month), (total, date, container_id)) in fee_totals {
+            tx.execute(
+                "INSERT INTO transactions (amount, description, category, date, container_id, account_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    total,
+                    format!("{} {}", fee_description, month),
+                    Self::BANK_FEE_CATEGORY,
+                    &date,
+                    container_id,
+                    account_id
+                ],
+            )?;
+            let transaction_id = tx.last_insert_rowid();
+            for line_id in &fee_lines[&(account_id, month)] {
+                tx.execute(
+                    "UPDATE statement_lines SET transaction_id = ?1 WHERE id = ?2",
+                    params![transaction_id, line_id],
+                )?;
+            }
+            created += 1;
+        }
+        tx.commit()?;
+
+        Ok(created)
+    }
 }
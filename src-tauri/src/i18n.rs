@@ -0,0 +1,141 @@
+pub const DEFAULT_LOCALE: &str = "id";
+pub const SUPPORTED_LOCALES: [&str; 2] = ["id", "en"];
+
+/// Used while no locale has been chosen. It keeps the text from before the
+/// setting existed: Indonesian reports, but English generated descriptions,
+/// transaction types and transaction export headers.
+pub const UNSET_LOCALE: &str = "";
+const UNSET_LOCALE_ENGLISH_KEYS: [&str; 7] = [
+    "default.untitled",
+    "default.transfer",
+    "default.imported",
+    "type.transfer",
+    "type.income",
+    "type.expense",
+    "export.transactions.header",
+];
+
+const TRANSLATIONS: &[(&str, &str, &str)] = &[
+    ("default.untitled", "Untitled", "Tanpa Judul"),
+    ("default.transfer", "Transfer", "Pemindahan Dana"),
+    ("default.imported", "Imported", "Hasil Impor"),
+    ("default.bank_fees", "Bank fees", "Biaya bank"),
+    ("default.petty_cash_replenishment", "Petty cash replenishment", "Pengisian kas kecil"),
+    ("default.settlement", "Payout", "Pencairan dana"),
+    ("default.settlement_fee", "Payout fees", "Potongan pencairan"),
+    ("default.other_channel", "Other", "Lainnya"),
+    ("default.year_end_close", "Year-end close", "Penutupan buku"),
+    ("type.transfer", "Transfer", "Transfer"),
+    ("type.income", "Income", "Pemasukan"),
+    ("type.expense", "Expense", "Pengeluaran"),
+    ("export.transactions.header", "ID,Amount,Description,Category,Date", "ID,Jumlah,Deskripsi,Kategori,Tanggal"),
+    ("report.profit_loss.header", "Section,Category,Amount", "Bagian,Kategori,Nilai"),
+    ("report.balance_sheet.header", "Section,Account,Balance", "Bagian,Akun,Saldo"),
+    ("report.transactions.header", "Date,Description,Account,Category,Type,Debit,Credit,Balance,Container", "Tanggal,Deskripsi,Akun,Kategori,Tipe,Debit,Kredit,Saldo,Container"),
+    ("report.income", "Income", "Pendapatan"),
+    ("report.total_income", "Total Income", "Total Pendapatan"),
+    ("report.expense", "Expenses", "Beban"),
+    ("report.total_expense", "Total Expenses", "Total Beban"),
+    ("report.net_income", "Net Income", "Laba Bersih"),
+    ("report.assets", "Assets", "Aset"),
+    ("report.total_assets", "Total Assets", "Total Aset"),
+    ("report.liabilities", "Liabilities", "Liabilitas"),
+    ("report.total_liabilities", "Total Liabilities", "Total Liabilitas"),
+    ("report.equity", "Equity", "Ekuitas"),
+    ("report.total_equity", "Total Equity", "Total Ekuitas"),
+    ("report.total_liabilities_equity", "Total Liabilities & Equity", "Total Liabilitas & Ekuitas"),
+    ("render.item", "Item", "Pos"),
+    ("render.amount", "Amount", "Jumlah"),
+    ("palette.matches", "matching transactions", "transaksi cocok"),
+    ("summary.this_month", "This month", "Bulan ini"),
+    ("summary.in", "In", "Pada"),
+    ("summary.income", "income", "pemasukan"),
+    ("summary.expense", "expenses", "pengeluaran"),
+    ("summary.profit", "profit", "untung"),
+    ("summary.loss", "loss", "rugi"),
+    ("summary.up", "up", "naik"),
+    ("summary.down", "down", "turun"),
+    ("summary.from_last_month", "from last month", "dari bulan lalu"),
+    ("summary.unchanged", "same as last month", "sama dengan bulan lalu"),
+    ("summary.thousand", "K", " rb"),
+    ("summary.million", "M", " jt"),
+    ("summary.billion", "B", " M"),
+    ("loan_pack.title", "Loan Application Pack", "Paket Pengajuan Pinjaman"),
+    ("loan_pack.period", "Period", "Periode"),
+    ("loan_pack.profit_loss", "Profit and Loss", "Laporan Laba Rugi"),
+    ("loan_pack.balance_sheet", "Balance Sheet", "Laporan Posisi Keuangan"),
+    ("loan_pack.cash_flow", "Cash Flow", "Laporan Arus Kas"),
+    ("loan_pack.cash_flow.header", "Month,Cash In,Cash Out,Net Cash Flow", "Bulan,Kas Masuk,Kas Keluar,Arus Kas Bersih"),
+    ("loan_pack.trend", "Monthly Trend", "Tren Bulanan"),
+    ("loan_pack.trend.header", "Month,Income,Expenses,Net Income", "Bulan,Pendapatan,Beban,Laba Bersih"),
+    ("sak.position.title", "Statement of Financial Position", "Laporan Posisi Keuangan"),
+    ("sak.profit_loss.title", "Income Statement", "Laporan Laba Rugi"),
+    ("sak.notes.title", "Notes to the Financial Statements", "Catatan atas Laporan Keuangan"),
+    ("sak.as_of", "As of", "Per"),
+    ("sak.period", "For the period", "Untuk periode"),
+    ("sak.to", "to", "sampai dengan"),
+    ("sak.assets", "ASSETS", "ASET"),
+    ("sak.total_assets", "Total Assets", "Jumlah Aset"),
+    ("sak.liabilities", "LIABILITIES", "LIABILITAS"),
+    ("sak.total_liabilities", "Total Liabilities", "Jumlah Liabilitas"),
+    ("sak.equity", "EQUITY", "EKUITAS"),
+    ("sak.total_equity", "Total Equity", "Jumlah Ekuitas"),
+    ("sak.total_liabilities_equity", "TOTAL LIABILITIES AND EQUITY", "JUMLAH LIABILITAS DAN EKUITAS"),
+    ("sak.revenue", "REVENUE", "PENDAPATAN"),
+    ("sak.total_revenue", "Total Revenue", "Jumlah Pendapatan"),
+    ("sak.expenses", "EXPENSES", "BEBAN"),
+    ("sak.total_expenses", "Total Expenses", "Jumlah Beban"),
+    ("sak.profit_before_tax", "PROFIT (LOSS) BEFORE INCOME TAX", "LABA (RUGI) SEBELUM PAJAK PENGHASILAN"),
+    ("sak.income_tax", "Income Tax Expense", "Beban Pajak Penghasilan"),
+    ("sak.profit_after_tax", "PROFIT (LOSS) AFTER INCOME TAX", "LABA (RUGI) SETELAH PAJAK PENGHASILAN"),
+    ("sak.notes.general", "1. General", "1. Umum"),
+    ("sak.notes.entity", "The financial statements are prepared for", "Laporan keuangan ini disusun untuk entitas"),
+    ("sak.notes.policies", "2. Summary of Significant Accounting Policies", "2. Ikhtisar Kebijakan Akuntansi Penting"),
+    ("sak.notes.compliance", "a. Statement of compliance: the financial statements are prepared in accordance with SAK EMKM.", "a. Pernyataan kepatuhan: laporan keuangan disusun menggunakan Standar Akuntansi Keuangan Entitas Mikro, Kecil, dan Menengah (SAK EMKM)."),
+    ("sak.notes.basis", "b. Basis of preparation: historical cost under the accrual basis.", "b. Dasar penyusunan: biaya historis dengan menggunakan asumsi dasar akrual."),
+    ("sak.notes.currency", "c. The presentation currency is Rupiah.", "c. Mata uang yang digunakan dalam penyusunan laporan keuangan adalah Rupiah."),
+    ("month.01", "January", "Januari"),
+    ("month.02", "February", "Februari"),
+    ("month.03", "March", "Maret"),
+    ("month.04", "April", "April"),
+    ("month.05", "May", "Mei"),
+    ("month.06", "June", "Juni"),
+    ("month.07", "July", "Juli"),
+    ("month.08", "August", "Agustus"),
+    ("month.09", "September", "September"),
+    ("month.10", "October", "Oktober"),
+    ("month.11", "November", "November"),
+    ("month.12", "December", "Desember"),
+];
+
+pub fn is_supported(locale: &str) -> bool {
+    SUPPORTED_LOCALES.contains(&locale)
+}
+
+pub fn translate(locale: &str, key: &str) -> String {
+    let entry = TRANSLATIONS.iter().find(|(k, _, _)| *k == key);
+    match entry {
+        Some((_, en, id)) => {
+            if locale == "en" || (locale == UNSET_LOCALE && UNSET_LOCALE_ENGLISH_KEYS.contains(&key)) {
+                en.to_string()
+            } else {
+                id.to_string()
+            }
+        }
+        None => key.to_string(),
+    }
+}
+
+pub fn long_date(locale: &str, date: &str) -> String {
+    let parts: Vec<&str> = date.split(' ').next().unwrap_or(date).split('-').collect();
+    if parts.len() != 3 {
+        return date.to_string();
+    }
+    let month = translate(locale, &format!("month.{}", parts[1]));
+    let day = parts[2].trim_start_matches('0');
+    if locale == "en" {
+        format!("{} {}, {}", month, day, parts[0])
+    } else {
+        format!("{} {} {}", day, month, parts[0])
+    }
+}
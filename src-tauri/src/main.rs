@@ -1,58 +1,102 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod database;
+mod i18n;
+mod storage;
 
 use database::{
-    Account, AccountBalance, BalanceSheetReport, Category, CategoryBalance, Container, Database,
-    NewTransaction, ProfitLossReport, ReportsCsvExport, Transaction,
+    Account, AccountBalance, ActionResult, AppState, ArchivedItem, AuditEntry, BackupManifest,
+    BackupVerification, BalanceSheetReport, BalanceSnapshot, BulkDeleteResult, CashCountRecord,
+    CashCountSession, Category, CategoryBalance, CategoryEdit, CategoryForecast, CategorySuggestion,
+    ChangeFeed, ClosedYear, Container, ContainerStats, CsvImportMapping, CustomField,
+    CustomFieldValue, Database, DifferentialBackupSummary, DiscrepancyExplanation, Envelope,
+    EnvelopeReconciliation, FeesReport, Fund, FundReport, Giro, HousekeepingSuggestion,
+    ImportPreview, ImportResult, ImportSession, InstanceStatus, LocationSummary, NewGiro,
+    NewRecurringRule, NewRenewal, NewTransaction, NewTransfer, PackImportResult, PaymentMethodTotal,
+    PendingSale, PettyCash, PettyCashReplenishment, PointInTimeRestore, ProfitLossReport,
+    PurchaseRequest, QueryResult, Reconciliation, ReconciliationCandidate, RecoveryReport,
+    RecurringRule, Renewal, ReportsCsvExport, RestorePreview, RiskAlert, SakEmkmExport, SavedFilter,
+    Settlement, SettlementBatch, StatementMapping, StatementReconciliation, SubscriptionsReport,
+    Tag, Transaction, TransactionComment, TransactionFilter, TransactionLocation, TransactionPage,
+    TransactionVersion, TransferSuggestion, TrashedTransaction, UndoEntry, Voucher, VoucherReport,
+    WeeklyTotal, YearEndClosing, YearRollover, ZakatReport,
 };
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use storage::{DatabaseLocation, RecentProfile};
 use tauri::Manager;
 
+struct ActiveDatabase(RwLock<Arc<Database>>);
+
+impl ActiveDatabase {
+    fn current(&self) -> Arc<Database> {
+        self.0.read().unwrap().clone()
+    }
+
+    fn replace(&self, database: Database) {
+        *self.0.write().unwrap() = Arc::new(database);
+    }
+}
+
+/// Writes against a read-only profile surface as a permission error instead
+/// of SQLite's "attempt to write a readonly database".
+fn command_error(error: rusqlite::Error) -> String {
+    if Database::is_read_only_error(&error) {
+        "PermissionDenied: this profile is open read-only".to_string()
+    } else if let Some(conflict) = Database::name_conflict(&error) {
+        format!("Conflict: {}", serde_json::to_string(conflict).unwrap_or_default())
+    } else {
+        error.to_string()
+    }
+}
+
 #[tauri::command]
-fn add_transaction(
-    amount: i64,
-    description: Option<String>,
-    category: Option<String>,
-    container_id: i64,
-    account_id: i64,
-    date: Option<String>,
-    db: tauri::State<Arc<Database>>,
-) -> Result<Transaction, String> {
-    let new_transaction = NewTransaction {
-        amount,
-        description,
-        category,
-        container_id,
-        account_id,
-        date,
-    };
-    
-    db.add_transaction(new_transaction)
-        .map_err(|e| e.to_string())
+fn add_transaction(transaction: NewTransaction, db: tauri::State<ActiveDatabase>) -> Result<Transaction, String> {
+    db.current().add_transaction(transaction).map_err(command_error)
 }
 
 #[tauri::command]
-fn add_transfer(
-    amount: i64,
-    description: Option<String>,
-    container_id: i64,
-    from_account_id: i64,
-    to_account_id: i64,
-    date: Option<String>,
-    db: tauri::State<Arc<Database>>,
-) -> Result<i64, String> {
-    db.add_transfer(container_id, from_account_id, to_account_id, amount, description, date)
-        .map_err(|e| e.to_string())
+fn add_transfer(transfer: NewTransfer, db: tauri::State<ActiveDatabase>) -> Result<i64, String> {
+    db.current().add_transfer(transfer).map_err(command_error)
+}
+
+#[tauri::command]
+fn suggest_transfer_links(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<TransferSuggestion>, String> {
+    db.current()
+        .suggest_transfer_links(container_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn link_transfer(outgoing_id: i64, incoming_id: i64, db: tauri::State<ActiveDatabase>) -> Result<i64, String> {
+    db.current()
+        .link_transfer(outgoing_id, incoming_id)
+        .map_err(command_error)
 }
 
 #[tauri::command]
 fn get_transactions(
     container_id: i64,
     limit: Option<i64>,
-    db: tauri::State<Arc<Database>>,
+    offset: Option<i64>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<Transaction>, String> {
+    db.current().get_transactions(container_id, limit, offset).map_err(command_error)
+}
+
+#[tauri::command]
+fn search_transactions(
+    container_id: i64,
+    query: String,
+    limit: Option<i64>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<Vec<Transaction>, String> {
-    db.get_transactions(container_id, limit).map_err(|e| e.to_string())
+    db.current().search_transactions(container_id, query, limit).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_transaction_count(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<i64, String> {
+    db.current().get_transaction_count(container_id).map_err(command_error)
 }
 
 #[tauri::command]
@@ -60,10 +104,10 @@ fn get_transactions_by_account(
     container_id: i64,
     account_id: i64,
     limit: Option<i64>,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<Vec<Transaction>, String> {
-    db.get_transactions_by_account(container_id, account_id, limit)
-        .map_err(|e| e.to_string())
+    db.current().get_transactions_by_account(container_id, account_id, limit)
+        .map_err(command_error)
 }
 
 #[tauri::command]
@@ -71,69 +115,268 @@ fn get_transactions_by_category(
     container_id: i64,
     category: String,
     limit: Option<i64>,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<Vec<Transaction>, String> {
-    db.get_transactions_by_category(container_id, category, limit)
-        .map_err(|e| e.to_string())
+    db.current().get_transactions_by_category(container_id, category, limit)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_monthly_balance(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<i64, String> {
+    db.current().get_monthly_balance(container_id).map_err(command_error)
 }
 
 #[tauri::command]
-fn get_monthly_balance(container_id: i64, db: tauri::State<Arc<Database>>) -> Result<i64, String> {
-    db.get_monthly_balance(container_id).map_err(|e| e.to_string())
+fn get_weekly_totals(
+    container_id: i64,
+    start_date: String,
+    end_date: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<WeeklyTotal>, String> {
+    db.current().get_weekly_totals(container_id, start_date, end_date)
+        .map_err(command_error)
 }
 
 #[tauri::command]
-fn get_all_time_balance(container_id: i64, db: tauri::State<Arc<Database>>) -> Result<i64, String> {
-    db.get_all_time_balance(container_id).map_err(|e| e.to_string())
+fn get_all_time_balance(
+    container_id: i64,
+    exclude_pending: Option<bool>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<i64, String> {
+    db.current()
+        .get_all_time_balance(container_id, exclude_pending.unwrap_or(false))
+        .map_err(command_error)
 }
 
 #[tauri::command]
-fn export_csv(container_id: i64, db: tauri::State<Arc<Database>>) -> Result<String, String> {
-    db.export_transactions_csv(container_id).map_err(|e| e.to_string())
+fn export_csv(
+    container_id: i64,
+    redact: Option<bool>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<String, String> {
+    db.current().export_transactions_csv(container_id, redact.unwrap_or(false))
+        .map_err(command_error)
 }
 
 #[tauri::command]
 fn export_reports_csv(
     container_id: i64,
     year: String,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<ReportsCsvExport, String> {
-    db.export_reports_csv(container_id, year)
-        .map_err(|e| e.to_string())
+    db.current().export_reports_csv(container_id, year)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn export_sak_emkm_reports(
+    container_id: i64,
+    year: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<SakEmkmExport, String> {
+    db.current().export_sak_emkm_csv(container_id, year)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn render_report(
+    container_id: i64,
+    report_type: String,
+    period: String,
+    format: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<String, String> {
+    db.current().render_report(container_id, report_type, period, format)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_plain_summary(
+    container_id: i64,
+    month: String,
+    locale: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<String, String> {
+    db.current().get_plain_summary(container_id, month, locale)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn execute_action(
+    container_id: i64,
+    action: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<ActionResult, String> {
+    db.current().execute_action(container_id, action)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn generate_loan_pack(
+    container_id: i64,
+    months: u32,
+    path: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<String, String> {
+    let pack = db
+        .current()
+        .export_loan_pack_csv(container_id, months)
+        .map_err(command_error)?;
+    std::fs::write(&path, pack).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+#[tauri::command]
+fn export_container_sqlite(container_id: i64, path: String, db: tauri::State<ActiveDatabase>) -> Result<String, String> {
+    db.current().export_container_sqlite(container_id, &path)
+        .map_err(command_error)?;
+    Ok(path)
+}
+
+#[tauri::command]
+fn delete_transaction(id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_transaction(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn delete_transactions(ids: Vec<i64>, db: tauri::State<ActiveDatabase>) -> Result<Vec<BulkDeleteResult>, String> {
+    db.current().delete_transactions(ids).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_trashed_transactions(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<TrashedTransaction>, String> {
+    db.current().get_trashed_transactions(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn restore_transaction(id: i64, db: tauri::State<ActiveDatabase>) -> Result<Transaction, String> {
+    db.current().restore_transaction(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn purge_trash(container_id: i64, older_than_days: Option<i64>, db: tauri::State<ActiveDatabase>) -> Result<usize, String> {
+    db.current().purge_trash(container_id, older_than_days).map_err(command_error)
 }
 
 #[tauri::command]
-fn delete_transaction(id: i64, db: tauri::State<Arc<Database>>) -> Result<(), String> {
-    db.delete_transaction(id).map_err(|e| e.to_string())
+fn undo_last(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Option<UndoEntry>, String> {
+    db.current().undo_last(container_id).map_err(command_error)
 }
 
 #[tauri::command]
-fn get_category_totals(container_id: i64, db: tauri::State<Arc<Database>>) -> Result<Vec<(String, i64)>, String> {
-    db.get_category_totals(container_id).map_err(|e| e.to_string())
+fn redo_last(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Option<UndoEntry>, String> {
+    db.current().redo_last(container_id).map_err(command_error)
 }
 
 #[tauri::command]
-fn get_categories(db: tauri::State<Arc<Database>>) -> Result<Vec<Category>, String> {
-    db.get_categories().map_err(|e| e.to_string())
+fn get_category_totals(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<(String, i64)>, String> {
+    db.current().get_category_totals(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_categories(db: tauri::State<ActiveDatabase>) -> Result<Vec<Category>, String> {
+    db.current().get_categories().map_err(command_error)
 }
 
 #[tauri::command]
 fn get_category_balances(
     container_id: i64,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<Vec<CategoryBalance>, String> {
-    db.get_category_balances(container_id)
-        .map_err(|e| e.to_string())
+    db.current().get_category_balances(container_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn suggest_category(
+    container_id: i64,
+    description: String,
+    amount: i64,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<CategorySuggestion>, String> {
+    db.current()
+        .suggest_category(container_id, description, amount)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn record_category_correction(
+    container_id: i64,
+    description: String,
+    amount: i64,
+    category: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<(), String> {
+    db.current()
+        .record_category_correction(container_id, description, amount, category)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn export_configuration_pack(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<String, String> {
+    db.current().export_configuration_pack(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn import_configuration_pack(
+    container_id: i64,
+    json: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<PackImportResult, String> {
+    db.current()
+        .import_configuration_pack(container_id, json)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn load_setup_pack(container_id: i64, path: String, db: tauri::State<ActiveDatabase>) -> Result<PackImportResult, String> {
+    db.current()
+        .load_setup_pack(container_id, Path::new(path.trim()))
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn forecast_category(
+    container_id: i64,
+    category: String,
+    months_ahead: u32,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<CategoryForecast>, String> {
+    db.current()
+        .forecast_category(container_id, category, months_ahead)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_accounts(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<Account>, String> {
+    db.current().get_accounts(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_account_balances(
+    container_id: i64,
+    exclude_pending: Option<bool>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<AccountBalance>, String> {
+    db.current()
+        .get_account_balances(container_id, exclude_pending.unwrap_or(false))
+        .map_err(command_error)
 }
 
 #[tauri::command]
-fn get_accounts(container_id: i64, db: tauri::State<Arc<Database>>) -> Result<Vec<Account>, String> {
-    db.get_accounts(container_id).map_err(|e| e.to_string())
+fn get_balance_history(
+    account_id: i64,
+    start_date: String,
+    end_date: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<BalanceSnapshot>, String> {
+    db.current().get_balance_history(account_id, start_date, end_date)
+        .map_err(command_error)
 }
 
 #[tauri::command]
-fn get_account_balances(container_id: i64, db: tauri::State<Arc<Database>>) -> Result<Vec<AccountBalance>, String> {
-    db.get_account_balances(container_id).map_err(|e| e.to_string())
+fn refresh_balance_snapshots(db: tauri::State<ActiveDatabase>) -> Result<usize, String> {
+    db.current().refresh_balance_snapshots().map_err(command_error)
 }
 
 #[tauri::command]
@@ -142,10 +385,10 @@ fn add_account(
     name: String,
     account_type: String,
     opening_balance: i64,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<Account, String> {
-    db.add_account(container_id, name, account_type, opening_balance)
-        .map_err(|e| e.to_string())
+    db.current().add_account(container_id, name, account_type, opening_balance)
+        .map_err(command_error)
 }
 
 #[tauri::command]
@@ -153,35 +396,53 @@ fn update_account(
     id: i64,
     name: String,
     opening_balance: i64,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<Account, String> {
-    db.update_account(id, name, opening_balance)
-        .map_err(|e| e.to_string())
+    db.current().update_account(id, name, opening_balance)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn delete_account(id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_account(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn reassign_account(
+    from_account_id: i64,
+    to_account_id: i64,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<usize, String> {
+    db.current().reassign_account(from_account_id, to_account_id, start_date, end_date)
+        .map_err(command_error)
 }
 
 #[tauri::command]
-fn delete_account(id: i64, db: tauri::State<Arc<Database>>) -> Result<(), String> {
-    db.delete_account(id).map_err(|e| e.to_string())
+fn merge_accounts(source_id: i64, target_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Account, String> {
+    db.current().merge_accounts(source_id, target_id)
+        .map_err(command_error)
 }
 
 #[tauri::command]
-fn add_category(name: String, db: tauri::State<Arc<Database>>) -> Result<(), String> {
-    db.add_category(name, "expense".to_string())
-        .map_err(|e| e.to_string())
+fn add_category(name: String, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().add_category(name, "expense".to_string())
+        .map_err(command_error)
 }
 
 #[tauri::command]
 fn add_category_with_type(
     name: String,
     category_type: String,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<(), String> {
-    db.add_category(name, category_type).map_err(|e| e.to_string())
+    db.current().add_category(name, category_type).map_err(command_error)
 }
 
 #[tauri::command]
-fn delete_category(name: String, db: tauri::State<Arc<Database>>) -> Result<(), String> {
-    db.delete_category(name).map_err(|e| e.to_string())
+fn delete_category(name: String, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_category(name).map_err(command_error)
 }
 
 #[tauri::command]
@@ -189,20 +450,56 @@ fn update_category(
     old_name: String,
     new_name: String,
     category_type: String,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<(), String> {
-    db.update_category(old_name, new_name, category_type)
-        .map_err(|e| e.to_string())
+    db.current().update_category(old_name, new_name, category_type)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn update_categories(edits: Vec<CategoryEdit>, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().update_categories(edits).map_err(command_error)
+}
+
+#[tauri::command]
+fn reorder_categories(ordered_names: Vec<String>, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().reorder_categories(ordered_names).map_err(command_error)
+}
+
+#[tauri::command]
+fn bulk_update_category(
+    container_id: i64,
+    from_category: String,
+    to_category: String,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<usize, String> {
+    db.current()
+        .bulk_update_category(container_id, from_category, to_category, start_date, end_date)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_available_months(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<String>, String> {
+    db.current().get_available_months(container_id).map_err(command_error)
 }
 
 #[tauri::command]
-fn get_available_months(container_id: i64, db: tauri::State<Arc<Database>>) -> Result<Vec<String>, String> {
-    db.get_available_months(container_id).map_err(|e| e.to_string())
+fn get_balance_for_month(container_id: i64, month: String, db: tauri::State<ActiveDatabase>) -> Result<i64, String> {
+    db.current().get_balance_for_month(container_id, month).map_err(command_error)
 }
 
 #[tauri::command]
-fn get_balance_for_month(container_id: i64, month: String, db: tauri::State<Arc<Database>>) -> Result<i64, String> {
-    db.get_balance_for_month(container_id, month).map_err(|e| e.to_string())
+fn get_transactions_for_range(
+    container_id: i64,
+    start_date: String,
+    end_date: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<Transaction>, String> {
+    db.current()
+        .get_transactions_for_range(container_id, start_date, end_date)
+        .map_err(command_error)
 }
 
 #[tauri::command]
@@ -210,54 +507,80 @@ fn get_transactions_for_month(
     container_id: i64,
     month: String,
     limit: Option<i64>,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<Vec<Transaction>, String> {
-    db.get_transactions_for_month(container_id, month, limit).map_err(|e| e.to_string())
+    db.current().get_transactions_for_month(container_id, month, limit).map_err(command_error)
 }
 
 #[tauri::command]
-fn get_category_totals_for_month(container_id: i64, month: String, db: tauri::State<Arc<Database>>) -> Result<Vec<(String, i64)>, String> {
-    db.get_category_totals_for_month(container_id, month).map_err(|e| e.to_string())
+fn get_category_totals_for_month(container_id: i64, month: String, db: tauri::State<ActiveDatabase>) -> Result<Vec<(String, i64)>, String> {
+    db.current().get_category_totals_for_month(container_id, month).map_err(command_error)
 }
 
 #[tauri::command]
 fn get_profit_and_loss_for_month(
     container_id: i64,
     month: String,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<ProfitLossReport, String> {
-    db.get_profit_and_loss_for_month(container_id, month)
-        .map_err(|e| e.to_string())
+    db.current().get_profit_and_loss_for_month(container_id, month)
+        .map_err(command_error)
 }
 
 #[tauri::command]
 fn get_profit_and_loss_for_year(
     container_id: i64,
     year: String,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<ProfitLossReport, String> {
-    db.get_profit_and_loss_for_year(container_id, year)
-        .map_err(|e| e.to_string())
+    db.current().get_profit_and_loss_for_year(container_id, year)
+        .map_err(command_error)
 }
 
 #[tauri::command]
 fn get_balance_sheet_for_month(
     container_id: i64,
     month: String,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<BalanceSheetReport, String> {
-    db.get_balance_sheet_for_month(container_id, month)
-        .map_err(|e| e.to_string())
+    db.current().get_balance_sheet_for_month(container_id, month)
+        .map_err(command_error)
 }
 
 #[tauri::command]
 fn get_balance_sheet_for_year(
     container_id: i64,
     year: String,
-    db: tauri::State<Arc<Database>>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<BalanceSheetReport, String> {
-    db.get_balance_sheet_for_year(container_id, year)
-        .map_err(|e| e.to_string())
+    db.current().get_balance_sheet_for_year(container_id, year)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn run_year_end_close(
+    container_id: i64,
+    fiscal_year: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<YearEndClosing, String> {
+    db.current().run_year_end_close(container_id, fiscal_year)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_closed_years(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<ClosedYear>, String> {
+    db.current().get_closed_years(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn roll_over_year(
+    container_id: i64,
+    fiscal_year: String,
+    new_container_name: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<YearRollover, String> {
+    db.current().roll_over_year(container_id, fiscal_year, new_container_name)
+        .map_err(command_error)
 }
 
 #[tauri::command]
@@ -267,58 +590,1171 @@ fn update_transaction(
     description: String,
     category: String,
     account_id: i64,
-    db: tauri::State<Arc<Database>>,
+    notes: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Transaction, String> {
+    db.current().update_transaction(id, amount, description, category, account_id, notes)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn set_transaction_payment_method(
+    id: i64,
+    payment_method: Option<String>,
+    db: tauri::State<ActiveDatabase>,
 ) -> Result<Transaction, String> {
-    db.update_transaction(id, amount, description, category, account_id)
-        .map_err(|e| e.to_string())
+    db.current()
+        .set_transaction_payment_method(id, payment_method)
+        .map_err(command_error)
 }
 
 #[tauri::command]
-fn get_containers(db: tauri::State<Arc<Database>>) -> Result<Vec<Container>, String> {
-    db.get_containers().map_err(|e| e.to_string())
+fn set_transaction_status(id: i64, status: String, db: tauri::State<ActiveDatabase>) -> Result<Transaction, String> {
+    db.current().set_transaction_status(id, status).map_err(command_error)
 }
 
 #[tauri::command]
-fn add_container(name: String, db: tauri::State<Arc<Database>>) -> Result<Container, String> {
-    db.add_container(name).map_err(|e| e.to_string())
+fn set_transactions_status(
+    ids: Vec<i64>,
+    status: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<usize, String> {
+    db.current().set_transactions_status(ids, status).map_err(command_error)
 }
 
 #[tauri::command]
-fn delete_container(id: i64, db: tauri::State<Arc<Database>>) -> Result<(), String> {
-    db.delete_container(id).map_err(|e| e.to_string())
+fn get_payment_method_totals(
+    container_id: i64,
+    period: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<PaymentMethodTotal>, String> {
+    db.current()
+        .get_payment_method_totals(container_id, period)
+        .map_err(command_error)
 }
 
 #[tauri::command]
-fn update_container(id: i64, name: String, db: tauri::State<Arc<Database>>) -> Result<Container, String> {
-    db.update_container(id, name).map_err(|e| e.to_string())
+fn set_transaction_review(id: i64, needs_review: bool, db: tauri::State<ActiveDatabase>) -> Result<Transaction, String> {
+    db.current().set_transaction_review(id, needs_review)
+        .map_err(command_error)
 }
 
 #[tauri::command]
-fn import_csv(
-    csv_content: String,
+fn get_transactions_needing_review(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<Transaction>, String> {
+    db.current().get_transactions_needing_review(container_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_review_count(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<i64, String> {
+    db.current().get_review_count(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_saved_filters(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<SavedFilter>, String> {
+    db.current().get_saved_filters(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn save_filter(
     container_id: i64,
-    amount_column: usize,
-    description_column: usize,
-    category_column: usize,
-    date_column: usize,
-    skip_header: bool,
-    db: tauri::State<Arc<Database>>,
-) -> Result<serde_json::Value, String> {
-    let result = db.import_transactions_from_csv(
-        csv_content,
-        container_id,
-        amount_column,
-        description_column,
-        category_column,
-        date_column,
-        skip_header,
-    ).map_err(|e| e.to_string())?;
-    
-    Ok(serde_json::json!({
-        "success_count": result.success_count,
-        "error_count": result.error_count,
-        "errors": result.errors,
-    }))
+    name: String,
+    filter: TransactionFilter,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<SavedFilter, String> {
+    db.current().save_filter(container_id, name, filter)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn update_saved_filter(
+    id: i64,
+    name: String,
+    filter: TransactionFilter,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<(), String> {
+    db.current().update_saved_filter(id, name, filter)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn delete_saved_filter(id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_saved_filter(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn run_saved_filter(
+    id: i64,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<TransactionPage, String> {
+    db.current().run_saved_filter(id, limit, offset)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_custom_fields(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<CustomField>, String> {
+    db.current().get_custom_fields(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn add_custom_field(
+    container_id: i64,
+    name: String,
+    field_type: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<CustomField, String> {
+    db.current().add_custom_field(container_id, name, field_type)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn rename_custom_field(id: i64, name: String, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().rename_custom_field(id, name).map_err(command_error)
+}
+
+#[tauri::command]
+fn delete_custom_field(id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_custom_field(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn set_transaction_custom_value(
+    transaction_id: i64,
+    field_id: i64,
+    value: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<(), String> {
+    db.current().set_transaction_custom_value(transaction_id, field_id, value)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_transaction_custom_values(
+    transaction_id: i64,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<CustomFieldValue>, String> {
+    db.current().get_transaction_custom_values(transaction_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn set_transaction_location(
+    transaction_id: i64,
+    latitude: f64,
+    longitude: f64,
+    label: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<TransactionLocation, String> {
+    db.current()
+        .set_transaction_location(transaction_id, latitude, longitude, label)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_transaction_location(
+    transaction_id: i64,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Option<TransactionLocation>, String> {
+    db.current()
+        .get_transaction_location(transaction_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn clear_transaction_location(transaction_id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current()
+        .clear_transaction_location(transaction_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_spending_by_location(
+    container_id: i64,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<LocationSummary>, String> {
+    db.current()
+        .get_spending_by_location(container_id, start_date, end_date)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_transaction_history(transaction_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<TransactionVersion>, String> {
+    db.current()
+        .get_transaction_history(transaction_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_risk_alerts(
+    container_id: i64,
+    period: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<RiskAlert>, String> {
+    db.current().get_risk_alerts(container_id, period)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_transaction_comments(
+    transaction_id: i64,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<TransactionComment>, String> {
+    db.current().get_transaction_comments(transaction_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn add_transaction_comment(
+    transaction_id: i64,
+    author: String,
+    body: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<TransactionComment, String> {
+    db.current().add_transaction_comment(transaction_id, author, body)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn delete_transaction_comment(id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_transaction_comment(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn issue_voucher(
+    container_id: i64,
+    code: String,
+    amount: i64,
+    account_id: i64,
+    expires_at: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Voucher, String> {
+    db.current()
+        .issue_voucher(container_id, code, amount, account_id, expires_at)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn redeem_voucher(
+    container_id: i64,
+    code: String,
+    amount: i64,
+    category: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Voucher, String> {
+    db.current()
+        .redeem_voucher(container_id, code, amount, category)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_outstanding_vouchers(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<VoucherReport, String> {
+    db.current()
+        .get_outstanding_vouchers(container_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn record_pending_sale(
+    container_id: i64,
+    channel: String,
+    amount: i64,
+    category: Option<String>,
+    description: Option<String>,
+    date: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<PendingSale, String> {
+    db.current()
+        .record_pending_sale(container_id, channel, amount, category, description, date)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_pending_sales(
+    container_id: i64,
+    channel: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<PendingSale>, String> {
+    db.current()
+        .get_pending_sales(container_id, channel)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn record_settlement(batch: SettlementBatch, db: tauri::State<ActiveDatabase>) -> Result<Settlement, String> {
+    db.current().record_settlement(batch).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_housekeeping_suggestions(db: tauri::State<ActiveDatabase>) -> Result<Vec<HousekeepingSuggestion>, String> {
+    db.current()
+        .get_housekeeping_suggestions()
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn set_archived(kind: String, id: i64, archived: bool, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().set_archived(kind, id, archived).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_archived_items(db: tauri::State<ActiveDatabase>) -> Result<Vec<ArchivedItem>, String> {
+    db.current().get_archived_items().map_err(command_error)
+}
+
+#[tauri::command]
+fn set_category_fee(name: String, is_fee: bool, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().set_category_fee(name, is_fee).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_fee_categories(db: tauri::State<ActiveDatabase>) -> Result<Vec<String>, String> {
+    db.current().get_fee_categories().map_err(command_error)
+}
+
+#[tauri::command]
+fn get_fees_report(container_id: i64, period: String, db: tauri::State<ActiveDatabase>) -> Result<FeesReport, String> {
+    db.current()
+        .get_fees_report(container_id, period)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn add_purchase_request(
+    container_id: i64,
+    item: String,
+    quantity: i64,
+    notes: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<PurchaseRequest, String> {
+    db.current()
+        .add_purchase_request(container_id, item, quantity, notes)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn add_purchase_quote(
+    request_id: i64,
+    supplier: String,
+    unit_price: i64,
+    delivery_days: Option<i64>,
+    notes: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<PurchaseRequest, String> {
+    db.current()
+        .add_purchase_quote(request_id, supplier, unit_price, delivery_days, notes)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn delete_purchase_quote(id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_purchase_quote(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_purchase_requests(
+    container_id: i64,
+    status: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<PurchaseRequest>, String> {
+    db.current()
+        .get_purchase_requests(container_id, status)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn accept_purchase_quote(
+    quote_id: i64,
+    account_id: i64,
+    category: Option<String>,
+    date: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<PurchaseRequest, String> {
+    db.current()
+        .accept_purchase_quote(quote_id, account_id, category, date)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn cancel_purchase_request(id: i64, db: tauri::State<ActiveDatabase>) -> Result<PurchaseRequest, String> {
+    db.current().cancel_purchase_request(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn add_fund(container_id: i64, name: String, grantor: String, db: tauri::State<ActiveDatabase>) -> Result<Fund, String> {
+    db.current()
+        .add_fund(container_id, name, grantor)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_funds(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<Fund>, String> {
+    db.current().get_funds(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn delete_fund(id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_fund(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn set_transaction_fund(transaction_id: i64, fund_id: Option<i64>, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current()
+        .set_transaction_fund(transaction_id, fund_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_fund_report(fund_id: i64, db: tauri::State<ActiveDatabase>) -> Result<FundReport, String> {
+    db.current().get_fund_report(fund_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn add_envelope(account_id: i64, name: String, db: tauri::State<ActiveDatabase>) -> Result<Envelope, String> {
+    db.current().add_envelope(account_id, name).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_envelopes(account_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<Envelope>, String> {
+    db.current().get_envelopes(account_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn delete_envelope(id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_envelope(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn allocate_to_envelope(envelope_id: i64, amount: i64, db: tauri::State<ActiveDatabase>) -> Result<Envelope, String> {
+    db.current()
+        .allocate_to_envelope(envelope_id, amount)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn move_between_envelopes(
+    from_id: i64,
+    to_id: i64,
+    amount: i64,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<Envelope>, String> {
+    db.current()
+        .move_between_envelopes(from_id, to_id, amount)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn spend_from_envelope(envelope_id: i64, transaction_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Envelope, String> {
+    db.current()
+        .spend_from_envelope(envelope_id, transaction_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_envelope_reconciliation(account_id: i64, db: tauri::State<ActiveDatabase>) -> Result<EnvelopeReconciliation, String> {
+    db.current()
+        .get_envelope_reconciliation(account_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn add_transaction_tag(transaction_id: i64, tag: String, db: tauri::State<ActiveDatabase>) -> Result<Vec<String>, String> {
+    db.current()
+        .add_transaction_tag(transaction_id, tag)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn remove_transaction_tag(transaction_id: i64, tag: String, db: tauri::State<ActiveDatabase>) -> Result<Vec<String>, String> {
+    db.current()
+        .remove_transaction_tag(transaction_id, tag)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_transaction_tags(transaction_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<String>, String> {
+    db.current().get_transaction_tags(transaction_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_tags(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<Tag>, String> {
+    db.current().get_tags(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn delete_tag(id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_tag(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_profit_and_loss_for_tag(
+    container_id: i64,
+    tag: String,
+    start_date: String,
+    end_date: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<ProfitLossReport, String> {
+    db.current()
+        .get_profit_and_loss_for_tag(container_id, tag, start_date, end_date)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn set_account_zakat(account_id: i64, included: bool, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current()
+        .set_account_zakat(account_id, included)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_zakat_account_ids(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<i64>, String> {
+    db.current()
+        .get_zakat_account_ids(container_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn set_zakat_settings(rate_bps: i64, nisab: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current()
+        .set_zakat_settings(rate_bps, nisab)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_zakat_report(container_id: i64, period: String, db: tauri::State<ActiveDatabase>) -> Result<ZakatReport, String> {
+    db.current()
+        .get_zakat_report(container_id, period)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn add_renewal(renewal: NewRenewal, db: tauri::State<ActiveDatabase>) -> Result<Renewal, String> {
+    db.current().add_renewal(renewal).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_renewals(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<Renewal>, String> {
+    db.current().get_renewals(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_upcoming_renewals(container_id: i64, days: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<Renewal>, String> {
+    db.current()
+        .get_upcoming_renewals(container_id, days)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn pay_renewal(id: i64, date: Option<String>, db: tauri::State<ActiveDatabase>) -> Result<Renewal, String> {
+    db.current().pay_renewal(id, date).map_err(command_error)
+}
+
+#[tauri::command]
+fn delete_renewal(id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_renewal(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn add_recurring_rule(rule: NewRecurringRule, db: tauri::State<ActiveDatabase>) -> Result<RecurringRule, String> {
+    db.current().add_recurring_rule(rule).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_recurring_rules(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<RecurringRule>, String> {
+    db.current().get_recurring_rules(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn set_recurring_rule_active(id: i64, active: bool, db: tauri::State<ActiveDatabase>) -> Result<RecurringRule, String> {
+    db.current().set_recurring_rule_active(id, active).map_err(command_error)
+}
+
+#[tauri::command]
+fn delete_recurring_rule(id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_recurring_rule(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn run_recurring_rules(db: tauri::State<ActiveDatabase>) -> Result<Vec<Transaction>, String> {
+    db.current().run_recurring_rules().map_err(command_error)
+}
+
+#[tauri::command]
+fn get_subscriptions_report(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<SubscriptionsReport, String> {
+    db.current()
+        .get_subscriptions_report(container_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn set_petty_cash(
+    account_id: i64,
+    float_amount: i64,
+    funding_account_id: i64,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<PettyCash, String> {
+    db.current()
+        .set_petty_cash(account_id, float_amount, funding_account_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn remove_petty_cash(account_id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().remove_petty_cash(account_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_petty_cash_accounts(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<PettyCash>, String> {
+    db.current()
+        .get_petty_cash_accounts(container_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn replenish_petty_cash(account_id: i64, db: tauri::State<ActiveDatabase>) -> Result<PettyCashReplenishment, String> {
+    db.current()
+        .replenish_petty_cash(account_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn start_cash_count(account_id: i64, db: tauri::State<ActiveDatabase>) -> Result<CashCountSession, String> {
+    db.current().start_cash_count(account_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn record_denomination_count(
+    session_id: i64,
+    denominations: std::collections::BTreeMap<i64, i64>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<CashCountRecord, String> {
+    db.current()
+        .record_denomination_count(session_id, denominations)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_cash_count_history(session_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<CashCountRecord>, String> {
+    db.current().get_cash_count_history(session_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_cash_count_sessions(account_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<CashCountSession>, String> {
+    db.current().get_cash_count_sessions(account_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn start_reconciliation(
+    account_id: i64,
+    statement_date: String,
+    statement_balance: i64,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Reconciliation, String> {
+    db.current()
+        .start_reconciliation(account_id, statement_date, statement_balance)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn mark_reconciliation_transactions(
+    reconciliation_id: i64,
+    transaction_ids: Vec<i64>,
+    marked: bool,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Reconciliation, String> {
+    db.current()
+        .mark_reconciliation_transactions(reconciliation_id, transaction_ids, marked)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_reconciliation(reconciliation_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Reconciliation, String> {
+    db.current().get_reconciliation(reconciliation_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_reconciliations(account_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Vec<Reconciliation>, String> {
+    db.current().get_reconciliations(account_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_reconciliation_candidates(
+    reconciliation_id: i64,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<ReconciliationCandidate>, String> {
+    db.current()
+        .get_reconciliation_candidates(reconciliation_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn finish_reconciliation(reconciliation_id: i64, db: tauri::State<ActiveDatabase>) -> Result<Reconciliation, String> {
+    db.current().finish_reconciliation(reconciliation_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn cancel_reconciliation(reconciliation_id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().cancel_reconciliation(reconciliation_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn add_giro(giro: NewGiro, db: tauri::State<ActiveDatabase>) -> Result<Giro, String> {
+    db.current().add_giro(giro).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_giros(container_id: i64, status: Option<String>, db: tauri::State<ActiveDatabase>) -> Result<Vec<Giro>, String> {
+    db.current()
+        .get_giros(container_id, status)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn clear_giro(id: i64, date: Option<String>, db: tauri::State<ActiveDatabase>) -> Result<Giro, String> {
+    db.current().clear_giro(id, date).map_err(command_error)
+}
+
+#[tauri::command]
+fn cancel_giro(id: i64, db: tauri::State<ActiveDatabase>) -> Result<Giro, String> {
+    db.current().cancel_giro(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_changes_since(
+    container_id: i64,
+    cursor: i64,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<ChangeFeed, String> {
+    db.current().get_changes_since(container_id, cursor)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_audit_log(
+    entity: Option<String>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<AuditEntry>, String> {
+    db.current()
+        .get_audit_log(entity, start_date, end_date, limit, offset)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_minor_units(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<u32, String> {
+    db.current().get_minor_units(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn set_minor_units(container_id: i64, minor_units: u32, db: tauri::State<ActiveDatabase>) -> Result<u32, String> {
+    db.current().set_minor_units(container_id, minor_units).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_locale(db: tauri::State<ActiveDatabase>) -> Result<String, String> {
+    db.current().get_locale().map_err(command_error)
+}
+
+#[tauri::command]
+fn set_locale(locale: String, db: tauri::State<ActiveDatabase>) -> Result<String, String> {
+    db.current().set_locale(locale).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_week_start(db: tauri::State<ActiveDatabase>) -> Result<String, String> {
+    db.current().get_week_start().map_err(command_error)
+}
+
+#[tauri::command]
+fn set_week_start(week_start: String, db: tauri::State<ActiveDatabase>) -> Result<String, String> {
+    db.current().set_week_start(week_start).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_date_format(db: tauri::State<ActiveDatabase>) -> Result<String, String> {
+    db.current().get_date_format().map_err(command_error)
+}
+
+#[tauri::command]
+fn set_date_format(date_format: String, db: tauri::State<ActiveDatabase>) -> Result<String, String> {
+    db.current().set_date_format(date_format).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_app_state(db: tauri::State<ActiveDatabase>) -> Result<AppState, String> {
+    db.current().get_app_state().map_err(command_error)
+}
+
+#[tauri::command]
+fn set_last_container(container_id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().set_last_container(container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn set_onboarding_completed(completed: bool, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().set_onboarding_completed(completed).map_err(command_error)
+}
+
+#[tauri::command]
+fn set_readonly_queries_enabled(enabled: bool, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().set_readonly_queries_enabled(enabled)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn run_readonly_query(sql: String, db: tauri::State<ActiveDatabase>) -> Result<QueryResult, String> {
+    db.current().run_readonly_query(sql).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_containers(db: tauri::State<ActiveDatabase>) -> Result<Vec<Container>, String> {
+    db.current().get_containers().map_err(command_error)
+}
+
+#[tauri::command]
+fn get_container_stats(db: tauri::State<ActiveDatabase>) -> Result<Vec<ContainerStats>, String> {
+    db.current().get_container_stats().map_err(command_error)
+}
+
+#[tauri::command]
+fn add_container(name: String, db: tauri::State<ActiveDatabase>) -> Result<Container, String> {
+    db.current().add_container(name).map_err(command_error)
+}
+
+#[tauri::command]
+fn find_similar_names(
+    entity: String,
+    name: String,
+    container_id: Option<i64>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<Vec<String>, String> {
+    db.current().find_similar_names(entity, name, container_id).map_err(command_error)
+}
+
+#[tauri::command]
+fn delete_container(id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current().delete_container(id).map_err(command_error)
+}
+
+#[tauri::command]
+fn update_container(id: i64, name: String, db: tauri::State<ActiveDatabase>) -> Result<Container, String> {
+    db.current().update_container(id, name).map_err(command_error)
+}
+
+#[tauri::command]
+fn import_csv(
+    csv_content: String,
+    mapping: CsvImportMapping,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<serde_json::Value, String> {
+    let result = db.current().import_transactions_from_csv(csv_content, mapping)
+        .map_err(command_error)?;
+    
+    Ok(serde_json::json!({
+        "success_count": result.success_count,
+        "error_count": result.error_count,
+        "errors": result.errors,
+    }))
+}
+
+#[tauri::command]
+fn import_csv_from_path(path: String, mapping: CsvImportMapping, db: tauri::State<ActiveDatabase>) -> Result<ImportResult, String> {
+    db.current()
+        .import_csv_from_path(Path::new(&path), mapping)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn import_cash_book_csv(
+    account_id: i64,
+    csv_content: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<ImportResult, String> {
+    db.current()
+        .import_cash_book_csv(account_id, csv_content)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn start_import_session(path: String, db: tauri::State<ActiveDatabase>) -> Result<ImportSession, String> {
+    db.current()
+        .start_import_session(Path::new(&path))
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn set_import_session_mapping(
+    session_id: i64,
+    mapping: CsvImportMapping,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<ImportSession, String> {
+    db.current()
+        .set_import_session_mapping(session_id, mapping)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn preview_import_session(
+    session_id: i64,
+    limit: Option<usize>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<ImportPreview, String> {
+    db.current()
+        .preview_import_session(session_id, limit)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn commit_import_session(session_id: i64, db: tauri::State<ActiveDatabase>) -> Result<ImportResult, String> {
+    db.current()
+        .commit_import_session(session_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn cancel_import_session(session_id: i64, db: tauri::State<ActiveDatabase>) -> Result<(), String> {
+    db.current()
+        .cancel_import_session(session_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn reconcile_with_statement(
+    account_id: i64,
+    csv_content: String,
+    mapping: StatementMapping,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<StatementReconciliation, String> {
+    db.current().reconcile_with_statement(account_id, csv_content, mapping)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn explain_discrepancy(account_id: i64, db: tauri::State<ActiveDatabase>) -> Result<DiscrepancyExplanation, String> {
+    db.current()
+        .explain_discrepancy(account_id)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn create_missing_statement_transactions(
+    statement_id: i64,
+    category: Option<String>,
+    net_fees: Option<bool>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<usize, String> {
+    db.current().create_missing_statement_transactions(statement_id, category, net_fees.unwrap_or(false))
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn get_recovery_report(recovery: tauri::State<Mutex<Option<RecoveryReport>>>) -> Option<RecoveryReport> {
+    recovery.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn get_instance_status(db: tauri::State<ActiveDatabase>) -> Result<InstanceStatus, String> {
+    db.current().get_instance_status().map_err(command_error)
+}
+
+fn app_data_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path().app_data_dir().map_err(|e| e.to_string())
+}
+
+fn database_location(app_dir: &Path, db: &Database) -> DatabaseLocation {
+    let path = storage::resolve_database_path(app_dir);
+    DatabaseLocation {
+        restart_required: path.as_path() != db.path(),
+        path: path.to_string_lossy().to_string(),
+        active_path: db.path().to_string_lossy().to_string(),
+        portable: storage::is_portable(),
+        read_only: db.is_read_only().unwrap_or(false),
+    }
+}
+
+fn copy_database(db: &Database, target: &Path) -> Result<(), String> {
+    if target == db.path() {
+        return Ok(());
+    }
+    if target.exists() {
+        return Err(format!("A database already exists at {}", target.display()));
+    }
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    db.copy_to(target).map_err(command_error)
+}
+
+#[tauri::command]
+fn get_database_path(app: tauri::AppHandle, db: tauri::State<ActiveDatabase>) -> Result<DatabaseLocation, String> {
+    let app_dir = app_data_dir(&app)?;
+    Ok(database_location(&app_dir, &db.current()))
+}
+
+#[tauri::command]
+fn set_database_path(
+    new_path: String,
+    move_data: bool,
+    app: tauri::AppHandle,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<DatabaseLocation, String> {
+    let app_dir = app_data_dir(&app)?;
+    let target = storage::normalize_database_path(&new_path)?;
+    if move_data {
+        copy_database(&db.current(), &target)?;
+    }
+    storage::set_configured_path(&app_dir, target)?;
+    storage::set_portable(false)?;
+    Ok(database_location(&app_dir, &db.current()))
+}
+
+#[tauri::command]
+fn set_portable_mode(
+    enabled: bool,
+    move_data: bool,
+    app: tauri::AppHandle,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<DatabaseLocation, String> {
+    let app_dir = app_data_dir(&app)?;
+    let target = if enabled {
+        storage::portable_database_path()?
+    } else {
+        storage::configured_database_path(&app_dir)
+    };
+    if move_data {
+        copy_database(&db.current(), &target)?;
+    }
+    storage::set_portable(enabled)?;
+    Ok(database_location(&app_dir, &db.current()))
+}
+
+#[tauri::command]
+fn create_backup(path: String, db: tauri::State<ActiveDatabase>) -> Result<BackupManifest, String> {
+    let target = storage::normalize_database_path(&path)?;
+    if target.exists() {
+        return Err(format!("A file already exists at {}", target.display()));
+    }
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    db.current().create_backup(&target).map_err(command_error)
+}
+
+#[tauri::command]
+fn verify_backup(path: String) -> Result<BackupVerification, String> {
+    let target = storage::normalize_database_path(&path)?;
+    Database::verify_backup(&target).map_err(command_error)
+}
+
+#[tauri::command]
+fn create_differential_backup(
+    base_path: String,
+    path: String,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<DifferentialBackupSummary, String> {
+    let base = storage::normalize_database_path(&base_path)?;
+    let target = PathBuf::from(path.trim());
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    db.current().create_differential_backup(&base, &target).map_err(command_error)
+}
+
+#[tauri::command]
+fn restore_point_in_time(
+    base_path: String,
+    differential_path: Option<String>,
+    until: Option<String>,
+    target_path: String,
+) -> Result<PointInTimeRestore, String> {
+    let base = storage::normalize_database_path(&base_path)?;
+    let differential = differential_path
+        .map(|path| PathBuf::from(path.trim()))
+        .filter(|path| !path.as_os_str().is_empty());
+    let target = storage::normalize_database_path(&target_path)?;
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    Database::restore_point_in_time(&base, differential.as_deref(), until.as_deref(), &target)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn preview_restore(
+    backup_path: String,
+    container_id: Option<i64>,
+    table: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<RestorePreview, String> {
+    let backup = storage::normalize_database_path(&backup_path)?;
+    db.current()
+        .preview_restore(&backup, container_id, table)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn restore_from_backup(
+    backup_path: String,
+    container_id: Option<i64>,
+    table: Option<String>,
+    db: tauri::State<ActiveDatabase>,
+) -> Result<RestorePreview, String> {
+    let backup = storage::normalize_database_path(&backup_path)?;
+    db.current()
+        .restore_from_backup(&backup, container_id, table)
+        .map_err(command_error)
+}
+
+#[tauri::command]
+fn open_profile(
+    path: String,
+    read_only: Option<bool>,
+    app: tauri::AppHandle,
+    db: tauri::State<ActiveDatabase>,
+    recovery: tauri::State<Mutex<Option<RecoveryReport>>>,
+) -> Result<DatabaseLocation, String> {
+    let app_dir = app_data_dir(&app)?;
+    let target = storage::normalize_database_path(&path)?;
+    let read_only = storage::is_viewer() || read_only.unwrap_or(false);
+    let placeholder = recovery.lock().unwrap().as_ref().is_some_and(|report| !report.opened);
+    if placeholder || target.as_path() != db.current().path() {
+        if read_only {
+            if !target.exists() {
+                return Err(format!("No database found at {}", target.display()));
+            }
+            let database = Database::open_read_only(target.clone()).map_err(command_error)?;
+            db.replace(database);
+            *recovery.lock().unwrap() = None;
+        } else {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            let (database, report) = Database::open_with_recovery(target.clone()).map_err(command_error)?;
+            db.replace(database);
+            *recovery.lock().unwrap() = report;
+        }
+    } else if !read_only && db.current().is_opened_read_only().map_err(command_error)? {
+        // A file opened for viewing has to be reopened to become writable.
+        let (database, report) = Database::open_with_recovery(target.clone()).map_err(command_error)?;
+        db.replace(database);
+        *recovery.lock().unwrap() = report;
+    } else {
+        db.current().set_read_only(read_only).map_err(command_error)?;
+    }
+    storage::record_recent_profile(&app_dir, &target, read_only)?;
+    Ok(database_location(&app_dir, &db.current()))
+}
+
+#[tauri::command]
+fn list_recent_profiles(app: tauri::AppHandle) -> Result<Vec<RecentProfile>, String> {
+    let app_dir = app_data_dir(&app)?;
+    Ok(storage::recent_profiles(&app_dir))
 }
 
 fn main() {
@@ -330,21 +1766,68 @@ fn main() {
             let app_dir = app.path().app_data_dir().expect("Failed to get app data dir");
             std::fs::create_dir_all(&app_dir).expect("Failed to create app data dir");
             
-            let db_path = app_dir.join("spent.db");
-            let database = Arc::new(Database::new(db_path).expect("Failed to initialize database"));
+            let db_path = storage::resolve_database_path(&app_dir);
+            if let Some(parent) = db_path.parent() {
+                std::fs::create_dir_all(parent).expect("Failed to create database dir");
+            }
+            let viewer = storage::is_viewer();
+            let opened = if viewer {
+                Database::open_read_only(db_path.clone()).map(|database| (database, None))
+            } else {
+                Database::open_with_recovery(db_path.clone())
+            };
+            // Starting without the profile still lets the user read the
+            // failure from get_recovery_report and open another one.
+            let (database, recovery) = opened.unwrap_or_else(|e| {
+                let database = Database::open_placeholder(db_path.clone()).expect("Failed to initialize database");
+                (database, Some(RecoveryReport::open_failed(e.to_string())))
+            });
+            let _ = storage::record_recent_profile(&app_dir, &db_path, viewer);
+            if !viewer {
+                // A failing rule (e.g. one dated into a closed year) must not
+                // keep the app from starting; it is retried on demand.
+                let _ = database.run_recurring_rules();
+                let _ = database.refresh_balance_snapshots();
+                let _ = database.prune_change_log();
+            }
             
-            app.manage(database);
+            app.manage(ActiveDatabase(RwLock::new(Arc::new(database))));
+            app.manage(Mutex::new(recovery));
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
+            get_recovery_report,
+            get_instance_status,
+            get_database_path,
+            set_database_path,
+            set_portable_mode,
+            create_backup,
+            verify_backup,
+            create_differential_backup,
+            restore_point_in_time,
+            preview_restore,
+            restore_from_backup,
+            open_profile,
+            list_recent_profiles,
             add_transaction,
             add_transfer,
+            suggest_transfer_links,
+            link_transfer,
             get_transactions,
+            get_transaction_count,
+            search_transactions,
             get_transactions_by_account,
             get_transactions_by_category,
             get_monthly_balance,
+            get_weekly_totals,
             get_all_time_balance,
             delete_transaction,
+            delete_transactions,
+            get_trashed_transactions,
+            restore_transaction,
+            purge_trash,
+            undo_last,
+            redo_last,
             get_category_totals,
             get_categories,
             get_category_balances,
@@ -352,27 +1835,174 @@ fn main() {
             add_category_with_type,
             delete_category,
             update_category,
+            update_categories,
+            reorder_categories,
+            bulk_update_category,
+            suggest_category,
+            record_category_correction,
+            export_configuration_pack,
+            import_configuration_pack,
+            load_setup_pack,
+            forecast_category,
             get_accounts,
             get_account_balances,
+            get_balance_history,
+            refresh_balance_snapshots,
             add_account,
             update_account,
             delete_account,
+            reassign_account,
+            merge_accounts,
             export_csv,
             export_reports_csv,
+            export_sak_emkm_reports,
+            render_report,
+            get_plain_summary,
+            execute_action,
+            generate_loan_pack,
+            export_container_sqlite,
             get_available_months,
             get_balance_for_month,
             get_transactions_for_month,
+            get_transactions_for_range,
             get_category_totals_for_month,
             get_profit_and_loss_for_month,
             get_profit_and_loss_for_year,
             get_balance_sheet_for_month,
             get_balance_sheet_for_year,
+            run_year_end_close,
+            get_closed_years,
+            roll_over_year,
             update_transaction,
+            set_transaction_payment_method,
+            set_transaction_status,
+            set_transactions_status,
+            get_payment_method_totals,
+            set_transaction_review,
+            get_transactions_needing_review,
+            get_review_count,
+            get_saved_filters,
+            save_filter,
+            update_saved_filter,
+            delete_saved_filter,
+            run_saved_filter,
+            get_custom_fields,
+            add_custom_field,
+            rename_custom_field,
+            delete_custom_field,
+            set_transaction_custom_value,
+            get_transaction_custom_values,
+            set_transaction_location,
+            get_transaction_location,
+            clear_transaction_location,
+            get_spending_by_location,
+            get_transaction_history,
+            get_risk_alerts,
+            get_transaction_comments,
+            add_transaction_comment,
+            delete_transaction_comment,
+            issue_voucher,
+            redeem_voucher,
+            get_outstanding_vouchers,
+            record_pending_sale,
+            get_pending_sales,
+            record_settlement,
+            get_housekeeping_suggestions,
+            set_archived,
+            get_archived_items,
+            set_category_fee,
+            get_fee_categories,
+            get_fees_report,
+            add_purchase_request,
+            add_purchase_quote,
+            delete_purchase_quote,
+            get_purchase_requests,
+            accept_purchase_quote,
+            cancel_purchase_request,
+            add_fund,
+            get_funds,
+            delete_fund,
+            set_transaction_fund,
+            get_fund_report,
+            add_envelope,
+            get_envelopes,
+            delete_envelope,
+            allocate_to_envelope,
+            move_between_envelopes,
+            spend_from_envelope,
+            get_envelope_reconciliation,
+            add_transaction_tag,
+            remove_transaction_tag,
+            get_transaction_tags,
+            get_tags,
+            delete_tag,
+            get_profit_and_loss_for_tag,
+            set_account_zakat,
+            get_zakat_account_ids,
+            set_zakat_settings,
+            get_zakat_report,
+            add_renewal,
+            get_renewals,
+            get_upcoming_renewals,
+            pay_renewal,
+            delete_renewal,
+            add_recurring_rule,
+            get_recurring_rules,
+            set_recurring_rule_active,
+            delete_recurring_rule,
+            run_recurring_rules,
+            get_subscriptions_report,
+            set_petty_cash,
+            remove_petty_cash,
+            get_petty_cash_accounts,
+            replenish_petty_cash,
+            start_cash_count,
+            record_denomination_count,
+            get_cash_count_history,
+            get_cash_count_sessions,
+            start_reconciliation,
+            mark_reconciliation_transactions,
+            get_reconciliation,
+            get_reconciliations,
+            get_reconciliation_candidates,
+            finish_reconciliation,
+            cancel_reconciliation,
+            add_giro,
+            get_giros,
+            clear_giro,
+            cancel_giro,
+            get_changes_since,
+            get_audit_log,
+            get_minor_units,
+            set_minor_units,
+            get_locale,
+            set_locale,
+            get_week_start,
+            set_week_start,
+            get_date_format,
+            set_date_format,
+            get_app_state,
+            set_last_container,
+            set_onboarding_completed,
+            set_readonly_queries_enabled,
+            run_readonly_query,
             get_containers,
+            get_container_stats,
             add_container,
+            find_similar_names,
             delete_container,
             update_container,
-            import_csv
+            import_csv,
+            import_csv_from_path,
+            import_cash_book_csv,
+            start_import_session,
+            set_import_session_mapping,
+            preview_import_session,
+            commit_import_session,
+            cancel_import_session,
+            reconcile_with_statement,
+            explain_discrepancy,
+            create_missing_statement_transactions
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const DATABASE_FILE: &str = "spent.db";
+const CONFIG_FILE: &str = "storage.json";
+const PORTABLE_MARKER: &str = "spent.portable";
+const VIEWER_MARKER: &str = "spent.viewer";
+const MAX_RECENT_PROFILES: usize = 10;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StorageConfig {
+    database_path: Option<PathBuf>,
+    #[serde(default)]
+    recent_profiles: Vec<RecentProfile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentProfile {
+    pub path: String,
+    pub name: String,
+    pub opened_at: String,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseLocation {
+    pub path: String,
+    pub active_path: String,
+    pub portable: bool,
+    pub restart_required: bool,
+    pub read_only: bool,
+}
+
+pub fn executable_dir() -> Option<PathBuf> {
+    std::env::current_exe().ok()?.parent().map(Path::to_path_buf)
+}
+
+pub fn is_portable() -> bool {
+    executable_dir()
+        .map(|dir| dir.join(PORTABLE_MARKER).exists())
+        .unwrap_or(false)
+}
+
+/// Viewer builds ship with a `spent.viewer` marker next to the executable
+/// and only ever open profiles read-only.
+pub fn is_viewer() -> bool {
+    executable_dir()
+        .map(|dir| dir.join(VIEWER_MARKER).exists())
+        .unwrap_or(false)
+}
+
+pub fn resolve_database_path(app_dir: &Path) -> PathBuf {
+    if is_portable() {
+        if let Some(dir) = executable_dir() {
+            return dir.join(DATABASE_FILE);
+        }
+    }
+    configured_database_path(app_dir)
+}
+
+pub fn configured_database_path(app_dir: &Path) -> PathBuf {
+    read_config(app_dir)
+        .database_path
+        .unwrap_or_else(|| app_dir.join(DATABASE_FILE))
+}
+
+pub fn normalize_database_path(path: &str) -> Result<PathBuf, String> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err("Database path cannot be empty".to_string());
+    }
+    let path = PathBuf::from(path);
+    if path.is_dir() {
+        Ok(path.join(DATABASE_FILE))
+    } else {
+        Ok(path)
+    }
+}
+
+pub fn set_configured_path(app_dir: &Path, path: PathBuf) -> Result<(), String> {
+    let mut config = read_config(app_dir);
+    config.database_path = Some(path);
+    write_config(app_dir, &config)
+}
+
+pub fn recent_profiles(app_dir: &Path) -> Vec<RecentProfile> {
+    read_config(app_dir).recent_profiles
+}
+
+pub fn record_recent_profile(app_dir: &Path, path: &Path, read_only: bool) -> Result<(), String> {
+    let mut config = read_config(app_dir);
+    let path_text = path.to_string_lossy().to_string();
+    config.recent_profiles.retain(|profile| profile.path != path_text);
+    config.recent_profiles.insert(
+        0,
+        RecentProfile {
+            name: path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| path_text.clone()),
+            path: path_text,
+            opened_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            read_only,
+        },
+    );
+    config.recent_profiles.truncate(MAX_RECENT_PROFILES);
+    write_config(app_dir, &config)
+}
+
+pub fn set_portable(enabled: bool) -> Result<(), String> {
+    let dir = executable_dir().ok_or_else(|| "Cannot locate the application folder".to_string())?;
+    let marker = dir.join(PORTABLE_MARKER);
+    if enabled {
+        std::fs::write(&marker, "").map_err(|e| e.to_string())
+    } else if marker.exists() {
+        std::fs::remove_file(&marker).map_err(|e| e.to_string())
+    } else {
+        Ok(())
+    }
+}
+
+pub fn portable_database_path() -> Result<PathBuf, String> {
+    executable_dir()
+        .map(|dir| dir.join(DATABASE_FILE))
+        .ok_or_else(|| "Cannot locate the application folder".to_string())
+}
+
+fn write_config(app_dir: &Path, config: &StorageConfig) -> Result<(), String> {
+    let contents = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    std::fs::write(app_dir.join(CONFIG_FILE), contents).map_err(|e| e.to_string())
+}
+
+fn read_config(app_dir: &Path) -> StorageConfig {
+    std::fs::read_to_string(app_dir.join(CONFIG_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
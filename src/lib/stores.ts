@@ -1,4 +1,4 @@
-import { writable } from 'svelte/store';
+import { get, writable } from 'svelte/store';
 
 export interface CurrencySettings {
   code: string;
@@ -36,17 +36,30 @@ currencySettings.subscribe(value => {
   }
 });
 
+// Decimal places of the selected container's amounts, loaded from the backend.
+// Stored amounts are integers in these units (e.g. 0 for rupiah, 2 for cents).
+export const minorUnits = writable<number>(2);
+
+export function toMinorUnits(amount: number, units: number = get(minorUnits)): number {
+  return Math.round(amount * 10 ** units);
+}
+
+export function fromMinorUnits(value: number, units: number = get(minorUnits)): number {
+  return value / 10 ** units;
+}
+
 export const currencyOptions = [
   { code: 'IDR', symbol: 'Rp', name: 'Indonesian Rupiah', position: 'before' as const, locale: 'id-ID' },
   { code: 'USD', symbol: '$', name: 'US Dollar', position: 'before' as const, locale: 'en-US' },
 ];
 
 export function formatCurrency(cents: number, settings: CurrencySettings): string {
-  const dollars = Math.abs(cents) / 100;
+  const units = get(minorUnits);
+  const dollars = fromMinorUnits(Math.abs(cents), units);
   
   const formatted = new Intl.NumberFormat(settings.locale, {
-    minimumFractionDigits: 2,
-    maximumFractionDigits: 2,
+    minimumFractionDigits: units,
+    maximumFractionDigits: units,
   }).format(dollars);
   
   if (settings.position === 'before') {